@@ -3,12 +3,18 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     tonic_build::configure()
         .build_client(false)
         .build_server(true)
+        // the rest gateway returns the messages as json
+        .type_attribute(".", "#[derive(serde::Serialize)]")
+        // the maps are encoded in the same order every time, so that the idempotent requests hash the same
+        .btree_map(["."])
         .compile(
             &[
                 "./proto/notes.proto",
                 "./proto/tags.proto",
                 "./proto/files.proto",
                 "./proto/shelves.proto",
+                "./proto/templates.proto",
+                "./proto/reminders.proto",
             ],
             &["proto"],
         )?;
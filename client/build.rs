@@ -0,0 +1,19 @@
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::configure()
+        .build_client(true)
+        .build_server(false)
+        .compile(
+            &[
+                "../proto/notes.proto",
+                "../proto/tags.proto",
+                "../proto/files.proto",
+                "../proto/shelves.proto",
+                "../proto/templates.proto",
+                "../proto/reminders.proto",
+            ],
+            &["../proto"],
+        )?;
+
+    Ok(())
+}
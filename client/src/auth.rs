@@ -0,0 +1,32 @@
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::service::{interceptor::InterceptedService, Interceptor};
+use tonic::transport::Channel;
+use tonic::{Request, Status};
+
+use crate::error::{Error, Result};
+
+/// the channel that the generated clients of a [`crate::Client`] use
+pub type AuthChannel = InterceptedService<Channel, BearerAuth>;
+
+/// an interceptor that puts the service token into the authorization metadata of every call
+#[derive(Clone, Debug)]
+pub struct BearerAuth {
+    authorization: MetadataValue<Ascii>,
+}
+
+impl BearerAuth {
+    pub fn new(token: &str) -> Result<Self> {
+        let authorization = format!("Bearer {token}")
+            .parse()
+            .map_err(|_| Error::InvalidToken)?;
+
+        Ok(Self { authorization })
+    }
+}
+
+impl Interceptor for BearerAuth {
+    fn call(&mut self, mut request: Request<()>) -> std::result::Result<Request<()>, Status> {
+        request.metadata_mut().insert("authorization", self.authorization.clone());
+        Ok(request)
+    }
+}
@@ -0,0 +1,493 @@
+use std::future::Future;
+use std::time::Duration;
+
+use tonic::metadata::{Ascii, MetadataValue};
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Request, Response, Status, Streaming};
+
+use crate::auth::{AuthChannel, BearerAuth};
+use crate::error::{Error, Result};
+use crate::proto::files::{files_client::FilesClient, DeleteFileReq};
+use crate::proto::notes::{notes_client::NotesClient, AttachTagReq, BatchDeleteNotesReq, BatchMoveNotesReq, BatchResult, BatchSetNoteStateReq, BatchTagsReq, CreateNoteReq, DeleteNoteReq, DetachTagReq, Filters, Note, NoteList, NoteState, Pagination, ReadNotesReq, SetNoteStateReq, Sort, UpdateNoteReq, GetBacklinksReq, GetNoteGraphReq, NoteGraph, NoteRef, ListTasksReq, TaskList, ToggleTaskReq};
+use crate::proto::shelves::{shelves_client::ShelvesClient, ClearShelfReq, ConvertToNoteReq, ReadShelfReq, Shelf, UpdateShelfReq};
+use crate::proto::tags::{tags_client::TagsClient, CreateTagReq, DeleteTagReq, MergeTagsReq, ReadTagsReq, ReorderTagsReq, Tag, TagSort, UpdateTagReq};
+use crate::proto::reminders::{reminders_client::RemindersClient, CreateReminderReq, DeleteReminderReq, DueReminder, ReadRemindersReq, Reminder, UpdateReminderReq, WatchDueRemindersReq};
+use crate::proto::templates::{templates_client::TemplatesClient, CreateNoteFromTemplateReq, CreateTemplateReq, DeleteTemplateReq, ReadTemplatesReq, Template, UpdateTemplateReq};
+
+/// the default size of the uploaded file chunks. the service accepts up to 8mb by default
+const DEFAULT_CHUNK_SIZE: usize = 1024 * 1024;
+
+/// how the calls that failed with UNAVAILABLE are retried. only the reads, the calls that set the same values
+/// when they are repeated and the calls with an idempotency key are retried, since a call that failed
+/// with UNAVAILABLE might still have gone through
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// 0 disables the retries
+    pub max_retries: u32,
+    /// the delay before the first retry. it doubles with each retry
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self { max_retries: 0, ..Default::default() }
+    }
+
+    fn backoff(&self, retry: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(self.max_backoff)
+    }
+}
+
+/// a client for all of the services. it is cheap to clone, and the clones share the connection
+#[derive(Clone)]
+pub struct Client {
+    pub(crate) notes: NotesClient<AuthChannel>,
+    pub(crate) tags: TagsClient<AuthChannel>,
+    pub(crate) files: FilesClient<AuthChannel>,
+    pub(crate) shelves: ShelvesClient<AuthChannel>,
+    pub(crate) templates: TemplatesClient<AuthChannel>,
+    pub(crate) reminders: RemindersClient<AuthChannel>,
+    pub(crate) retry_policy: RetryPolicy,
+    pub(crate) chunk_size: usize,
+}
+
+/// calls the `method` of the generated `client` with the retries, cloning the request for each attempt
+macro_rules! call {
+    ($self:ident.$client:ident.$method:ident($req:expr)) => {{
+        let req = $req;
+        $self.retry(|| {
+            let mut client = $self.$client.clone();
+            let req = req.clone();
+            async move { client.$method(req).await }
+        }).await
+    }};
+}
+
+pub(crate) use call;
+
+/// the same as `call!` without the retries, for the calls that would change something again when repeated
+macro_rules! call_once {
+    ($self:ident.$client:ident.$method:ident($req:expr)) => {{
+        let mut client = $self.$client.clone();
+        client.$method($req).await.map(Response::into_inner).map_err(Error::from)
+    }};
+}
+
+/// the same as `call!`, with the `key` sent as the idempotency-key metadata of every attempt,
+/// so that a retry of a call that reached the service doesn't create a duplicate
+macro_rules! call_with_key {
+    ($self:ident.$client:ident.$method:ident($req:expr, $key:expr)) => {{
+        let key: MetadataValue<Ascii> = $key.parse().map_err(|_| Error::InvalidIdempotencyKey)?;
+        let req = $req;
+        $self.retry(|| {
+            let mut client = $self.$client.clone();
+            let mut request = Request::new(req.clone());
+            request.metadata_mut().insert(IDEMPOTENCY_KEY, key.clone());
+            async move { client.$method(request).await }
+        }).await
+    }};
+}
+
+/// the metadata key of the idempotency keys
+const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+impl Client {
+    /// connects to the service at the `endpoint`, like "http://localhost:5050", with the service `token`
+    pub async fn connect(endpoint: impl Into<String>, token: &str) -> Result<Self> {
+        let channel = Endpoint::try_from(endpoint.into())?.connect().await?;
+        Self::new(channel, token)
+    }
+
+    /// uses an existing channel, for example one with tls or timeouts configured
+    pub fn new(channel: Channel, token: &str) -> Result<Self> {
+        let auth = BearerAuth::new(token)?;
+
+        Ok(Self {
+            notes: NotesClient::with_interceptor(channel.clone(), auth.clone()),
+            tags: TagsClient::with_interceptor(channel.clone(), auth.clone()),
+            files: FilesClient::with_interceptor(channel.clone(), auth.clone()),
+            shelves: ShelvesClient::with_interceptor(channel.clone(), auth.clone()),
+            templates: TemplatesClient::with_interceptor(channel.clone(), auth.clone()),
+            reminders: RemindersClient::with_interceptor(channel, auth),
+            retry_policy: RetryPolicy::default(),
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        })
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// the size of the uploaded file chunks in bytes. it must not be larger than the service's max_file_chunk_size
+    pub fn with_chunk_size(mut self, chunk_size: usize) -> Self {
+        self.chunk_size = chunk_size;
+        self
+    }
+
+    // the generated clients, for making the calls directly
+
+    pub fn notes(&self) -> NotesClient<AuthChannel> {
+        self.notes.clone()
+    }
+
+    pub fn tags(&self) -> TagsClient<AuthChannel> {
+        self.tags.clone()
+    }
+
+    pub fn files(&self) -> FilesClient<AuthChannel> {
+        self.files.clone()
+    }
+
+    pub fn shelves(&self) -> ShelvesClient<AuthChannel> {
+        self.shelves.clone()
+    }
+
+    pub fn templates(&self) -> TemplatesClient<AuthChannel> {
+        self.templates.clone()
+    }
+
+    pub fn reminders(&self) -> RemindersClient<AuthChannel> {
+        self.reminders.clone()
+    }
+
+    /// makes the `call`, and makes it again with a backoff while it fails with UNAVAILABLE
+    pub(crate) async fn retry<T, F, Fut>(&self, mut call: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = std::result::Result<Response<T>, Status>>,
+    {
+        let mut retry = 0;
+
+        loop {
+            match call().await {
+                Ok(response) => return Ok(response.into_inner()),
+                Err(status) if status.code() == Code::Unavailable && retry < self.retry_policy.max_retries => {
+                    tokio::time::sleep(self.retry_policy.backoff(retry)).await;
+                    retry += 1;
+                },
+                Err(status) => return Err(status.into()),
+            }
+        }
+    }
+
+    // notes
+
+    pub async fn create_note(&self, user_id: i32, title: &str, text: &str) -> Result<Note> {
+        call_once!(self.notes.create_note(CreateNoteReq { user_id, title: title.into(), text: text.into() }))
+    }
+
+    /// creates the note only once for each idempotency `key`. a repeated call returns the same note
+    pub async fn create_note_with_key(&self, key: &str, user_id: i32, title: &str, text: &str) -> Result<Note> {
+        call_with_key!(self.notes.create_note(CreateNoteReq { user_id, title: title.into(), text: text.into() }, key))
+    }
+
+    pub async fn read_notes(&self, user_id: i32, pagination: Pagination, sort: Sort, filters: Filters) -> Result<NoteList> {
+        call!(self.notes.read_notes(ReadNotesReq { user_id, pagination: Some(pagination), sort: Some(sort), filters: Some(filters) }))
+    }
+
+    pub async fn update_note(&self, user_id: i32, id: i32, title: &str, text: &str) -> Result<Note> {
+        call_once!(self.notes.update_note(UpdateNoteReq { id, user_id, title: title.into(), text: text.into() }))
+    }
+
+    pub async fn delete_note(&self, user_id: i32, id: i32) -> Result<()> {
+        call_once!(self.notes.delete_note(DeleteNoteReq { id, user_id })).map(|_| ())
+    }
+
+    pub async fn attach_tag(&self, user_id: i32, note_id: i32, tag_id: i32) -> Result<()> {
+        call_once!(self.notes.attach_tag(AttachTagReq { user_id, note_id, tag_id })).map(|_| ())
+    }
+
+    pub async fn detach_tag(&self, user_id: i32, note_id: i32, tag_id: i32) -> Result<()> {
+        call_once!(self.notes.detach_tag(DetachTagReq { user_id, note_id, tag_id })).map(|_| ())
+    }
+
+    /// pins, archives or stars the note. the flags that aren't set in the `state` are kept
+    pub async fn set_note_state(&self, user_id: i32, id: i32, state: NoteState) -> Result<Note> {
+        call!(self.notes.set_note_state(SetNoteStateReq { id, user_id, state: Some(state) }))
+    }
+
+    /// the notes that link to the note with `[[Title]]` or `[[#id]]`
+    pub async fn get_backlinks(&self, user_id: i32, note_id: i32) -> Result<Vec<NoteRef>> {
+        call!(self.notes.get_backlinks(GetBacklinksReq { user_id, note_id })).map(|list| list.notes)
+    }
+
+    /// the user's notes and the links between them
+    pub async fn get_note_graph(&self, user_id: i32, include_archived: bool) -> Result<NoteGraph> {
+        call!(self.notes.get_note_graph(GetNoteGraphReq { user_id, include_archived }))
+    }
+
+    /// a page of the `- [ ]` and `- [x]` tasks of the user's notes, only the done or the open ones with `done`,
+    /// and only the ones of the notes with the `tag_id`
+    pub async fn list_tasks(&self, user_id: i32, pagination: Pagination, done: Option<bool>, tag_id: Option<i32>, include_archived: bool) -> Result<TaskList> {
+        call!(self.notes.list_tasks(ListTasksReq { user_id, done, tag_id, include_archived, pagination: Some(pagination) }))
+    }
+
+    /// flips the checkbox of the task at the `position` in the note text. `times_edited` is the note's one from
+    /// the last read, and the call fails with NOTE_EDITED if it has changed, so a retry never flips the task back
+    pub async fn toggle_task(&self, user_id: i32, note_id: i32, position: i32, times_edited: i32) -> Result<Note> {
+        call!(self.notes.toggle_task(ToggleTaskReq { user_id, note_id, position, times_edited }))
+    }
+
+    // batches. with `partial`, the items that fail are skipped and reported in the results,
+    // otherwise the whole batch fails with the error of the first one
+
+    /// attaches every tag to every note
+    pub async fn batch_attach_tags(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32], partial: bool) -> Result<BatchResult> {
+        call_once!(self.notes.batch_attach_tags(BatchTagsReq { user_id, note_ids: note_ids.to_vec(), tag_ids: tag_ids.to_vec(), partial }))
+    }
+
+    /// detaches every tag from every note
+    pub async fn batch_detach_tags(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32], partial: bool) -> Result<BatchResult> {
+        call_once!(self.notes.batch_detach_tags(BatchTagsReq { user_id, note_ids: note_ids.to_vec(), tag_ids: tag_ids.to_vec(), partial }))
+    }
+
+    pub async fn batch_delete_notes(&self, user_id: i32, note_ids: &[i32], partial: bool) -> Result<BatchResult> {
+        call_once!(self.notes.batch_delete_notes(BatchDeleteNotesReq { user_id, note_ids: note_ids.to_vec(), partial }))
+    }
+
+    /// replaces the `from_tag_id` of the notes with the `to_tag_id`
+    pub async fn batch_move_notes(&self, user_id: i32, note_ids: &[i32], from_tag_id: i32, to_tag_id: i32, partial: bool) -> Result<BatchResult> {
+        call_once!(self.notes.batch_move_notes(BatchMoveNotesReq { user_id, note_ids: note_ids.to_vec(), from_tag_id, to_tag_id, partial }))
+    }
+
+    /// sets the state of every note like `set_note_state`
+    pub async fn batch_set_note_state(&self, user_id: i32, note_ids: &[i32], state: NoteState, partial: bool) -> Result<BatchResult> {
+        call!(self.notes.batch_set_note_state(BatchSetNoteStateReq { user_id, note_ids: note_ids.to_vec(), state: Some(state), partial }))
+    }
+
+    // tags
+
+    pub async fn create_tag(&self, user_id: i32, name: &str) -> Result<Tag> {
+        call_once!(self.tags.create_tag(CreateTagReq { user_id, name: name.into(), ..Default::default() }))
+    }
+
+    /// creates a tag with a color, an icon and a description
+    pub async fn create_styled_tag(&self, user_id: i32, name: &str, color: Option<&str>, icon: Option<&str>, description: &str) -> Result<Tag> {
+        call_once!(self.tags.create_tag(CreateTagReq {
+            user_id,
+            name: name.into(),
+            color: color.map(Into::into),
+            icon: icon.map(Into::into),
+            description: description.into(),
+        }))
+    }
+
+    /// creates the tag only once for each idempotency `key`. a repeated call returns the same tag
+    pub async fn create_tag_with_key(&self, key: &str, user_id: i32, name: &str) -> Result<Tag> {
+        call_with_key!(self.tags.create_tag(CreateTagReq { user_id, name: name.into(), ..Default::default() }, key))
+    }
+
+    /// the tags are sorted by id, and have their usage counts
+    pub async fn read_tags(&self, user_id: i32) -> Result<Vec<Tag>> {
+        call!(self.tags.read_tags(ReadTagsReq { user_id, sort: None })).map(|list| list.tags)
+    }
+
+    pub async fn read_tags_sorted(&self, user_id: i32, sort: TagSort) -> Result<Vec<Tag>> {
+        call!(self.tags.read_tags(ReadTagsReq { user_id, sort: Some(sort) })).map(|list| list.tags)
+    }
+
+    pub async fn update_tag(&self, user_id: i32, id: i32, name: &str) -> Result<Tag> {
+        call!(self.tags.update_tag(UpdateTagReq { id, user_id, name: name.into(), ..Default::default() }))
+    }
+
+    /// sends the whole request, so the color, the icon and the description can be changed too
+    pub async fn update_tag_with(&self, request: UpdateTagReq) -> Result<Tag> {
+        call!(self.tags.update_tag(request))
+    }
+
+    pub async fn delete_tag(&self, user_id: i32, id: i32) -> Result<()> {
+        call_once!(self.tags.delete_tag(DeleteTagReq { id, user_id })).map(|_| ())
+    }
+
+    /// moves the notes of the source tags to the target tag and deletes the source tags.
+    /// returns the target tag with its new usage count
+    pub async fn merge_tags(&self, user_id: i32, source_tag_ids: &[i32], target_tag_id: i32) -> Result<Tag> {
+        call_once!(self.tags.merge_tags(MergeTagsReq { user_id, source_tag_ids: source_tag_ids.to_vec(), target_tag_id }))
+    }
+
+    /// moves the tags to the start of the manual order and returns all of the user's tags in the new order
+    pub async fn reorder_tags(&self, user_id: i32, tag_ids: &[i32]) -> Result<Vec<Tag>> {
+        call!(self.tags.reorder_tags(ReorderTagsReq { user_id, tag_ids: tag_ids.to_vec() })).map(|list| list.tags)
+    }
+
+    // shelves
+
+    /// creates an empty shelf if the user doesn't have one yet
+    pub async fn read_shelf(&self, user_id: i32) -> Result<Shelf> {
+        call!(self.shelves.read_shelf(ReadShelfReq { user_id }))
+    }
+
+    pub async fn update_shelf(&self, user_id: i32, text: &str) -> Result<Shelf> {
+        call_once!(self.shelves.update_shelf(UpdateShelfReq { user_id, text: text.into() }))
+    }
+
+    /// deletes the shelf's text and files
+    pub async fn clear_shelf(&self, user_id: i32) -> Result<Shelf> {
+        call_once!(self.shelves.clear_shelf(ClearShelfReq { user_id }))
+    }
+
+    /// creates a note with the shelf's files, and clears the shelf
+    pub async fn convert_to_note(&self, user_id: i32, note_title: &str, note_text: &str) -> Result<Shelf> {
+        call_once!(self.shelves.convert_to_note(ConvertToNoteReq { user_id, note_title: note_title.into(), note_text: note_text.into() }))
+    }
+
+    /// converts the shelf only once for each idempotency `key`. a repeated call returns the same response
+    pub async fn convert_to_note_with_key(&self, key: &str, user_id: i32, note_title: &str, note_text: &str) -> Result<Shelf> {
+        call_with_key!(self.shelves.convert_to_note(ConvertToNoteReq { user_id, note_title: note_title.into(), note_text: note_text.into() }, key))
+    }
+
+    // templates
+
+    /// the tags get attached to the notes created from the template
+    pub async fn create_template(&self, user_id: i32, name: &str, title: &str, text: &str, tag_ids: &[i32]) -> Result<Template> {
+        call_once!(self.templates.create_template(CreateTemplateReq { user_id, name: name.into(), title: title.into(), text: text.into(), tag_ids: tag_ids.to_vec() }))
+    }
+
+    /// the user's templates, sorted by name
+    pub async fn read_templates(&self, user_id: i32) -> Result<Vec<Template>> {
+        call!(self.templates.read_templates(ReadTemplatesReq { user_id })).map(|list| list.templates)
+    }
+
+    /// replaces all of the template's fields, including the tags
+    pub async fn update_template(&self, user_id: i32, id: i32, name: &str, title: &str, text: &str, tag_ids: &[i32]) -> Result<Template> {
+        call!(self.templates.update_template(UpdateTemplateReq { id, user_id, name: name.into(), title: title.into(), text: text.into(), tag_ids: tag_ids.to_vec() }))
+    }
+
+    pub async fn delete_template(&self, user_id: i32, id: i32) -> Result<()> {
+        call_once!(self.templates.delete_template(DeleteTemplateReq { id, user_id })).map(|_| ())
+    }
+
+    /// creates a note from the template with the (name, value) `variables` substituted.
+    /// {{date}}, {{time}} and {{weekday}} are in the IANA `timezone`, or in UTC if it's empty
+    pub async fn create_note_from_template(&self, user_id: i32, template_id: i32, variables: &[(&str, &str)], timezone: &str) -> Result<Note> {
+        call_once!(self.templates.create_note_from_template(note_from_template(user_id, template_id, variables, timezone)))
+    }
+
+    /// creates the note only once for each idempotency `key`. a repeated call returns the same note
+    pub async fn create_note_from_template_with_key(&self, key: &str, user_id: i32, template_id: i32, variables: &[(&str, &str)], timezone: &str) -> Result<Note> {
+        call_with_key!(self.templates.create_note_from_template(note_from_template(user_id, template_id, variables, timezone), key))
+    }
+
+    // reminders
+
+    /// reminds of the note at the `local_time`, like "2024-10-18T09:00", in the IANA `timezone`.
+    /// the `recurrence` is an RRULE like "FREQ=WEEKLY;BYDAY=MO,FR", or empty for a single reminder
+    pub async fn create_reminder(&self, user_id: i32, note_id: i32, local_time: &str, timezone: &str, recurrence: &str) -> Result<Reminder> {
+        call_once!(self.reminders.create_reminder(CreateReminderReq { user_id, note_id, local_time: local_time.into(), timezone: timezone.into(), recurrence: recurrence.into() }))
+    }
+
+    /// creates the reminder only once for each idempotency `key`. a repeated call returns the same reminder
+    pub async fn create_reminder_with_key(&self, key: &str, user_id: i32, note_id: i32, local_time: &str, timezone: &str, recurrence: &str) -> Result<Reminder> {
+        call_with_key!(self.reminders.create_reminder(CreateReminderReq { user_id, note_id, local_time: local_time.into(), timezone: timezone.into(), recurrence: recurrence.into() }, key))
+    }
+
+    /// the user's reminders, or only the note's ones, sorted by the fire time
+    pub async fn read_reminders(&self, user_id: i32, note_id: Option<i32>) -> Result<Vec<Reminder>> {
+        call!(self.reminders.read_reminders(ReadRemindersReq { user_id, note_id })).map(|list| list.reminders)
+    }
+
+    /// replaces the time and the recurrence, and schedules the reminder again
+    pub async fn update_reminder(&self, user_id: i32, id: i32, local_time: &str, timezone: &str, recurrence: &str) -> Result<Reminder> {
+        call!(self.reminders.update_reminder(UpdateReminderReq { id, user_id, local_time: local_time.into(), timezone: timezone.into(), recurrence: recurrence.into() }))
+    }
+
+    pub async fn delete_reminder(&self, user_id: i32, id: i32) -> Result<()> {
+        call_once!(self.reminders.delete_reminder(DeleteReminderReq { id, user_id })).map(|_| ())
+    }
+
+    /// the stream of the user's reminders as they fire, or of every user's ones with the `user_id` 0.
+    /// the call is retried only until the stream starts
+    pub async fn watch_due_reminders(&self, user_id: i32) -> Result<Streaming<DueReminder>> {
+        call!(self.reminders.watch_due_reminders(WatchDueRemindersReq { user_id }))
+    }
+
+    // files, other than the upload and the download
+
+    pub async fn delete_file(&self, user_id: i32, id: i32) -> Result<()> {
+        call_once!(self.files.delete_file(DeleteFileReq { id, user_id })).map(|_| ())
+    }
+}
+
+fn note_from_template(user_id: i32, template_id: i32, variables: &[(&str, &str)], timezone: &str) -> CreateNoteFromTemplateReq {
+    CreateNoteFromTemplateReq {
+        user_id,
+        template_id,
+        variables: variables.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        timezone: timezone.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::error::ErrorKind;
+    use crate::proto::tags::ReadTagsReq;
+
+    #[test]
+    fn backoff_doubles_up_to_the_max() {
+        let policy = RetryPolicy { max_retries: 10, initial_backoff: Duration::from_millis(100), max_backoff: Duration::from_millis(500) };
+        let backoffs: Vec<_> = (0..5).map(|retry| policy.backoff(retry).as_millis()).collect();
+        assert_eq!(backoffs, [100, 200, 400, 500, 500]);
+    }
+
+    #[tokio::test]
+    async fn unavailable_calls_are_retried() {
+        // nothing listens on the port, so every attempt fails with UNAVAILABLE
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let channel = Endpoint::from_shared(format!("http://127.0.0.1:{port}")).unwrap().connect_lazy();
+
+        let policy = RetryPolicy { max_retries: 2, initial_backoff: Duration::from_millis(50), max_backoff: Duration::from_secs(1) };
+        let client = Client::new(channel, "token").unwrap().with_retry_policy(policy);
+
+        let started = Instant::now();
+        let mut attempts = 0;
+
+        let error = client.retry(|| {
+            attempts += 1;
+            let mut tags = client.tags();
+            async move { tags.read_tags(ReadTagsReq { user_id: 1, sort: None }).await }
+        }).await.unwrap_err();
+
+        assert_eq!(error.kind(), Some(ErrorKind::Unavailable));
+        assert_eq!(attempts, 3);
+        assert!(started.elapsed() >= Duration::from_millis(150));
+    }
+
+    #[tokio::test]
+    async fn unkeyed_creations_are_not_retried() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let channel = Endpoint::from_shared(format!("http://127.0.0.1:{port}")).unwrap().connect_lazy();
+
+        let policy = RetryPolicy { max_retries: 2, initial_backoff: Duration::from_secs(1), max_backoff: Duration::from_secs(1) };
+        let client = Client::new(channel, "token").unwrap().with_retry_policy(policy);
+
+        // a retry would wait for the backoff first
+        let started = Instant::now();
+        let error = client.create_note(1, "title", "text").await.unwrap_err();
+
+        assert_eq!(error.kind(), Some(ErrorKind::Unavailable));
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        let started = Instant::now();
+        let error = client.create_note_with_key("key", 1, "title", "text").await.unwrap_err();
+
+        assert_eq!(error.kind(), Some(ErrorKind::Unavailable));
+        assert!(started.elapsed() >= Duration::from_secs(2));
+    }
+}
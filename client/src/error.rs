@@ -0,0 +1,179 @@
+use std::fmt;
+use std::time::Duration;
+
+use tonic::{Code, Status};
+use tonic_types::StatusExt;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// errors of the client's calls
+#[derive(Debug)]
+pub enum Error {
+    /// the service returned an error
+    Service(Box<ServiceError>),
+    /// could not connect to the service
+    Transport(tonic::transport::Error),
+    /// the token has characters that can't be sent in the metadata
+    InvalidToken,
+    /// the idempotency key has characters that can't be sent in the metadata
+    InvalidIdempotencyKey,
+    /// reading the uploaded data or writing the downloaded data failed
+    Io(std::io::Error),
+    /// the service responded with something that it shouldn't have
+    InvalidResponse(&'static str),
+}
+
+/// what kind of error the service returned, based on the gRPC code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// the request had invalid values. the field violations say which ones
+    InvalidArgument,
+    /// the note, tag, file or shelf doesn't exist or belongs to another user
+    NotFound,
+    AlreadyExists,
+    /// a reference to a note or a tag that doesn't exist
+    FailedPrecondition,
+    /// the call conflicted with another one and can be retried
+    Aborted,
+    Unauthenticated,
+    PermissionDenied,
+    /// the call was rate limited. the retry after says for how long
+    ResourceExhausted,
+    DeadlineExceeded,
+    Unavailable,
+    Internal,
+    Other(Code),
+}
+
+/// an error returned by the service, with its google.rpc details
+#[derive(Debug)]
+pub struct ServiceError {
+    pub kind: ErrorKind,
+    /// the UPPER_SNAKE_CASE reason of the google.rpc.ErrorInfo, like TAG_ALREADY_ATTACHED
+    pub reason: Option<String>,
+    /// the google.rpc.BadRequest field violations
+    pub field_violations: Vec<FieldViolation>,
+    /// the retry-after metadata of rate limited calls
+    pub retry_after: Option<Duration>,
+    pub status: Status,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FieldViolation {
+    pub field: String,
+    pub description: String,
+}
+
+impl Error {
+    /// the kind of the service's error, if it is one
+    pub fn kind(&self) -> Option<ErrorKind> {
+        match self {
+            Self::Service(e) => Some(e.kind),
+            _ => None,
+        }
+    }
+
+    /// the google.rpc.ErrorInfo reason of the service's error, if there is one
+    pub fn reason(&self) -> Option<&str> {
+        match self {
+            Self::Service(e) => e.reason.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+impl From<Code> for ErrorKind {
+    fn from(code: Code) -> Self {
+        match code {
+            Code::InvalidArgument => Self::InvalidArgument,
+            Code::NotFound => Self::NotFound,
+            Code::AlreadyExists => Self::AlreadyExists,
+            Code::FailedPrecondition => Self::FailedPrecondition,
+            Code::Aborted => Self::Aborted,
+            Code::Unauthenticated => Self::Unauthenticated,
+            Code::PermissionDenied => Self::PermissionDenied,
+            Code::ResourceExhausted => Self::ResourceExhausted,
+            Code::DeadlineExceeded => Self::DeadlineExceeded,
+            Code::Unavailable => Self::Unavailable,
+            Code::Internal => Self::Internal,
+            code => Self::Other(code),
+        }
+    }
+}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        let details = status.get_error_details();
+
+        let reason = details.error_info().map(|info| info.reason.clone());
+
+        let field_violations = details.bad_request()
+            .map(|bad_request| bad_request.field_violations
+                .iter()
+                .map(|v| FieldViolation { field: v.field.clone(), description: v.description.clone() })
+                .collect())
+            .unwrap_or_default();
+
+        let retry_after = status.metadata()
+            .get("retry-after")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs);
+
+        Self::Service(Box::new(ServiceError {
+            kind: status.code().into(),
+            reason,
+            field_violations,
+            retry_after,
+            status,
+        }))
+    }
+}
+
+impl From<tonic::transport::Error> for Error {
+    fn from(error: tonic::transport::Error) -> Self {
+        Self::Transport(error)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Service(e) => write!(f, "{e}"),
+            Self::Transport(e) => write!(f, "could not connect to the data service: {e}"),
+            Self::InvalidToken => write!(f, "the token can't be sent in the metadata"),
+            Self::InvalidIdempotencyKey => write!(f, "the idempotency key can't be sent in the metadata"),
+            Self::Io(e) => write!(f, "{e}"),
+            Self::InvalidResponse(what) => write!(f, "invalid response from the data service: {what}"),
+        }
+    }
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}: {}", self.status.code(), self.status.message())?;
+
+        if let Some(reason) = &self.reason {
+            write!(f, " ({reason})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Service(e) => Some(&e.status),
+            Self::Transport(e) => Some(e),
+            Self::Io(e) => Some(e),
+            Self::InvalidToken | Self::InvalidIdempotencyKey | Self::InvalidResponse(_) => None,
+        }
+    }
+}
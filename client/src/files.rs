@@ -0,0 +1,90 @@
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio_stream::StreamExt;
+use tokio_util::io::ReaderStream;
+
+use crate::client::{call, Client};
+use crate::error::{Error, Result};
+use crate::proto::files::{create_file_metadata::AttachId, CreateFileMetadata, CreateFileReq, DownloadFileMetadata, DownloadFileReq, File};
+
+impl Client {
+    /// uploads `size` bytes from the `reader` in chunks, and attaches the file to a note or a shelf.
+    /// the upload is not retried, since the reader can't be read again
+    pub async fn upload_file<R>(&self, user_id: i32, attach_id: AttachId, name: &str, size: u64, reader: R) -> Result<File>
+    where
+        R: AsyncRead + Send + 'static,
+    {
+        let metadata = CreateFileMetadata {
+            user_id,
+            attach_id: Some(attach_id),
+            name: name.into(),
+            file_size: size,
+        };
+
+        let first_part = CreateFileReq { metadata: Some(metadata), data: Vec::new() };
+
+        // a read error ends the stream early, and gets returned instead of the service's error about the size
+
+        let read_error = Arc::new(Mutex::new(None));
+        let stream_read_error = read_error.clone();
+
+        let data_parts = ReaderStream::with_capacity(reader, self.chunk_size)
+            .map_while(move |chunk| match chunk {
+                Ok(chunk) => Some(CreateFileReq { metadata: None, data: chunk.to_vec() }),
+                Err(e) => {
+                    *stream_read_error.lock().unwrap() = Some(e);
+                    None
+                },
+            });
+
+        let result = self.files.clone()
+            .create_file(tokio_stream::once(first_part).chain(data_parts))
+            .await;
+
+        if let Some(e) = read_error.lock().unwrap().take() {
+            return Err(e.into());
+        }
+
+        Ok(result?.into_inner())
+    }
+
+    /// uploads the file at the `path` under its own name
+    pub async fn upload_path(&self, user_id: i32, attach_id: AttachId, path: impl AsRef<Path>) -> Result<File> {
+        let path = path.as_ref();
+        let name = path.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        let file = tokio::fs::File::open(path).await?;
+        let size = file.metadata().await?.len();
+
+        self.upload_file(user_id, attach_id, &name, size, file).await
+    }
+
+    /// writes the file into the `writer`, and returns its name and size.
+    /// the call is retried only until the service starts sending the file
+    pub async fn download_file<W>(&self, user_id: i32, file_hash: &str, writer: &mut W) -> Result<DownloadFileMetadata>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let mut stream = call!(self.files.download_file(DownloadFileReq { user_id, file_hash: file_hash.into() }))?;
+
+        let metadata = stream.message().await?
+            .and_then(|part| part.metadata)
+            .ok_or(Error::InvalidResponse("the download didn't start with the file metadata"))?;
+
+        let mut written = 0;
+        while let Some(part) = stream.message().await? {
+            writer.write_all(&part.data).await?;
+            written += part.data.len() as i64;
+        }
+
+        writer.flush().await?;
+
+        if written != metadata.size {
+            return Err(Error::InvalidResponse("the downloaded data doesn't match the file size"));
+        }
+
+        Ok(metadata)
+    }
+}
@@ -0,0 +1,41 @@
+//! a client for the data service of Miku Notes.
+//!
+//! [`Client`] wraps the generated clients with bearer token auth, retries of the calls
+//! that failed with UNAVAILABLE and are safe to repeat, chunked file uploads and downloads, and typed [`Error`]s.
+//! the generated clients and messages are in [`proto`], for anything that the wrappers don't cover
+
+mod auth;
+mod client;
+mod error;
+mod files;
+
+pub mod proto {
+    pub mod files {
+        tonic::include_proto!("files");
+    }
+
+    pub mod tags {
+        tonic::include_proto!("tags");
+    }
+
+    pub mod notes {
+        tonic::include_proto!("notes");
+    }
+
+    pub mod shelves {
+        tonic::include_proto!("shelves");
+    }
+
+    pub mod templates {
+        tonic::include_proto!("templates");
+    }
+
+    pub mod reminders {
+        tonic::include_proto!("reminders");
+    }
+}
+
+pub use auth::{AuthChannel, BearerAuth};
+pub use client::{Client, RetryPolicy};
+pub use error::{Error, ErrorKind, FieldViolation, Result, ServiceError};
+pub use proto::files::create_file_metadata::AttachId;
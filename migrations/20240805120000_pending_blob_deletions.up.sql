@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS pending_blob_deletions (
+    id SERIAL PRIMARY KEY,
+    hash VARCHAR(50) NOT NULL,
+    attempts INT DEFAULT 0 NOT NULL,
+    last_error TEXT,
+    next_attempt TIMESTAMP DEFAULT NOW() NOT NULL,
+    created TIMESTAMP DEFAULT NOW() NOT NULL,
+    done TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS pending_blob_deletions_due ON pending_blob_deletions(next_attempt) WHERE done IS NULL;
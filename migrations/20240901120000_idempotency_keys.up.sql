@@ -0,0 +1,18 @@
+-- Add up migration script here
+
+-- the responses of the calls that were made with an idempotency key.
+-- a row without a response is a call that is still in progress, which holds its key only for a lease from reserved_at,
+-- so that the key of a call that never finished, after a crash for example, can be taken over.
+-- the token tells the calls apart, so that a call whose key was taken over can't store a response or free it
+CREATE TABLE IF NOT EXISTS idempotency_keys (
+    user_id INT NOT NULL,
+    key VARCHAR(100) NOT NULL,
+    request_hash VARCHAR(64) NOT NULL,
+    token VARCHAR(36) NOT NULL,
+    response BYTEA,
+    created TIMESTAMP DEFAULT NOW() NOT NULL,
+    reserved_at TIMESTAMP DEFAULT NOW() NOT NULL,
+    PRIMARY KEY (user_id, key)
+);
+
+CREATE INDEX IF NOT EXISTS idempotency_keys_created ON idempotency_keys(created);
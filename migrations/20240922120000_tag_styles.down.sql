@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+ALTER TABLE tags
+    DROP COLUMN IF EXISTS color,
+    DROP COLUMN IF EXISTS icon,
+    DROP COLUMN IF EXISTS description,
+    DROP COLUMN IF EXISTS position;
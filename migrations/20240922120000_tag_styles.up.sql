@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+ALTER TABLE tags
+    ADD COLUMN IF NOT EXISTS color VARCHAR(7) CHECK (color ~ '^#[0-9a-f]{6}$'),
+    ADD COLUMN IF NOT EXISTS icon VARCHAR(32),
+    ADD COLUMN IF NOT EXISTS description VARCHAR(500) DEFAULT '' NOT NULL,
+    ADD COLUMN IF NOT EXISTS position INT DEFAULT 0 NOT NULL;
+
+-- the existing tags keep the order of their ids
+UPDATE tags SET position = (SELECT COUNT(*) FROM tags AS t WHERE t.user_id = tags.user_id AND t.id < tags.id);
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE notes DROP COLUMN IF EXISTS pinned;
+ALTER TABLE notes DROP COLUMN IF EXISTS archived;
+ALTER TABLE notes DROP COLUMN IF EXISTS favorite;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS pinned BOOLEAN DEFAULT FALSE NOT NULL;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS archived BOOLEAN DEFAULT FALSE NOT NULL;
+ALTER TABLE notes ADD COLUMN IF NOT EXISTS favorite BOOLEAN DEFAULT FALSE NOT NULL;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+-- the links in the texts of the notes. the links by title keep their title, so that they can be
+-- resolved when a note with that title appears, and the links by id have an empty one
+CREATE TABLE IF NOT EXISTS note_links (
+    source_id INT NOT NULL,
+    target_id INT,
+    target_title VARCHAR(250) NOT NULL,
+    FOREIGN KEY (source_id) REFERENCES notes(id),
+    FOREIGN KEY (target_id) REFERENCES notes(id)
+);
+
+CREATE INDEX IF NOT EXISTS note_links_source_id ON note_links(source_id);
+CREATE INDEX IF NOT EXISTS note_links_target_id ON note_links(target_id);
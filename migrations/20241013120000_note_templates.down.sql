@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS template_tags;
+DROP TABLE IF EXISTS note_templates;
@@ -0,0 +1,22 @@
+-- Add up migration script here
+
+-- the local_time is the first occurrence in the timezone, and the fire_at is the next one in utc.
+-- a reminder is fired once it has no occurrences left, and it keeps its last fire_at
+CREATE TABLE IF NOT EXISTS note_reminders (
+    id SERIAL PRIMARY KEY,
+    user_id INT NOT NULL,
+    note_id INT NOT NULL,
+    local_time TIMESTAMP NOT NULL,
+    timezone VARCHAR(64) NOT NULL,
+    recurrence VARCHAR(250) NOT NULL,
+    fire_at TIMESTAMP NOT NULL,
+    fired BOOLEAN DEFAULT FALSE NOT NULL,
+    created TIMESTAMP DEFAULT NOW() NOT NULL,
+    last_edited TIMESTAMP DEFAULT NOW() NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (note_id) REFERENCES notes(id)
+);
+
+CREATE INDEX IF NOT EXISTS note_reminders_user_id ON note_reminders(user_id);
+CREATE INDEX IF NOT EXISTS note_reminders_note_id ON note_reminders(note_id);
+CREATE INDEX IF NOT EXISTS note_reminders_due ON note_reminders(fire_at) WHERE NOT fired;
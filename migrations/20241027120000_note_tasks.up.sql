@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+-- the `- [ ]` and `- [x]` tasks in the texts of the notes, numbered by their position in the text.
+-- they are saved whenever a note text is written, and the older notes get theirs from the reindex-tasks admin command
+CREATE TABLE IF NOT EXISTS note_tasks (
+    note_id INT NOT NULL,
+    position INT NOT NULL,
+    text TEXT NOT NULL,
+    done BOOLEAN NOT NULL,
+    PRIMARY KEY (note_id, position),
+    FOREIGN KEY (note_id) REFERENCES notes(id)
+);
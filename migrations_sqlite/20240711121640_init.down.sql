@@ -0,0 +1,9 @@
+-- Add down migration script here
+
+DROP TABLE IF EXISTS shelf_files;
+DROP TABLE IF EXISTS shelves;
+DROP TABLE IF EXISTS note_tags;
+DROP TABLE IF EXISTS note_files;
+DROP TABLE IF EXISTS notes;
+DROP TABLE IF EXISTS tags;
+DROP TABLE IF EXISTS files;
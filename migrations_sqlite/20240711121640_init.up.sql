@@ -0,0 +1,64 @@
+-- the same schema as in ../migrations, except that there is no users table to reference,
+-- since there is no Auth service in the deployments that use sqlite.
+-- sqlite doesn't enforce the VARCHAR sizes, so they are checked explicitly
+
+CREATE TABLE IF NOT EXISTS files (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INT NOT NULL,
+    hash VARCHAR(50) UNIQUE NOT NULL CHECK (length(hash) <= 50),
+    name VARCHAR(250) NOT NULL CHECK (length(name) <= 250),
+    size BIGINT NOT NULL,
+    created TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS files_hash ON files(hash);
+
+CREATE TABLE IF NOT EXISTS tags (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INT NOT NULL,
+    name VARCHAR(50) NOT NULL CHECK (length(name) <= 50),
+    created TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS notes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INT NOT NULL,
+    title VARCHAR(250) NOT NULL CHECK (length(title) <= 250),
+    text VARCHAR(50000) NOT NULL CHECK (length(text) <= 50000),
+    created TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_edited TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    times_edited INT DEFAULT 0 NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS note_files (
+    note_id INT NOT NULL,
+    file_id INT NOT NULL,
+    FOREIGN KEY (note_id) REFERENCES notes(id),
+    FOREIGN KEY (file_id) REFERENCES files(id),
+    PRIMARY KEY (note_id, file_id)
+);
+
+CREATE TABLE IF NOT EXISTS note_tags (
+    note_id INT NOT NULL,
+    tag_id INT NOT NULL,
+    FOREIGN KEY (note_id) REFERENCES notes(id),
+    FOREIGN KEY (tag_id) REFERENCES tags(id),
+    PRIMARY KEY (note_id, tag_id)
+);
+
+CREATE TABLE IF NOT EXISTS shelves (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INT UNIQUE NOT NULL,
+    text VARCHAR(2500) NOT NULL CHECK (length(text) <= 2500),
+    created TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_edited TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    times_edited INT DEFAULT 0 NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS shelf_files (
+    shelf_id INT NOT NULL,
+    file_id INT NOT NULL,
+    FOREIGN KEY (shelf_id) REFERENCES shelves(id),
+    FOREIGN KEY (file_id) REFERENCES files(id),
+    PRIMARY KEY (shelf_id, file_id)
+);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+-- the merged duplicates can't be restored
+DROP INDEX IF EXISTS tags_user_id_name_key;
@@ -0,0 +1,25 @@
+-- Add up migration script here
+
+-- the tags with the same name, ignoring the case, get merged into the oldest one of them.
+-- sqlite's LOWER only folds the ascii letters
+CREATE TEMPORARY TABLE duplicate_tags AS
+SELECT t.id, (
+    SELECT MIN(k.id) FROM tags AS k WHERE k.user_id = t.user_id AND LOWER(k.name) = LOWER(t.name)
+) AS keep_id
+FROM tags AS t;
+
+DELETE FROM duplicate_tags WHERE id = keep_id;
+
+INSERT INTO note_tags (note_id, tag_id)
+SELECT nt.note_id, d.keep_id FROM note_tags AS nt
+INNER JOIN duplicate_tags AS d ON d.id = nt.tag_id
+-- the WHERE keeps sqlite from parsing the ON CONFLICT as a join constraint
+WHERE true
+ON CONFLICT DO NOTHING;
+
+DELETE FROM note_tags WHERE tag_id IN (SELECT id FROM duplicate_tags);
+DELETE FROM tags WHERE id IN (SELECT id FROM duplicate_tags);
+
+DROP TABLE duplicate_tags;
+
+CREATE UNIQUE INDEX IF NOT EXISTS tags_user_id_name_key ON tags(user_id, LOWER(name));
@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+ALTER TABLE tags DROP COLUMN color;
+ALTER TABLE tags DROP COLUMN icon;
+ALTER TABLE tags DROP COLUMN description;
+ALTER TABLE tags DROP COLUMN position;
@@ -0,0 +1,9 @@
+-- Add up migration script here
+
+ALTER TABLE tags ADD COLUMN color VARCHAR(7) CHECK (color GLOB '#[0-9a-f][0-9a-f][0-9a-f][0-9a-f][0-9a-f][0-9a-f]');
+ALTER TABLE tags ADD COLUMN icon VARCHAR(32) CHECK (length(icon) <= 32);
+ALTER TABLE tags ADD COLUMN description VARCHAR(500) DEFAULT '' NOT NULL CHECK (length(description) <= 500);
+ALTER TABLE tags ADD COLUMN position INT DEFAULT 0 NOT NULL;
+
+-- the existing tags keep the order of their ids
+UPDATE tags SET position = (SELECT COUNT(*) FROM tags AS t WHERE t.user_id = tags.user_id AND t.id < tags.id);
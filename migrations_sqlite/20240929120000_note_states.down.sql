@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+ALTER TABLE notes DROP COLUMN pinned;
+ALTER TABLE notes DROP COLUMN archived;
+ALTER TABLE notes DROP COLUMN favorite;
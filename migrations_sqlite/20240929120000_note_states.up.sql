@@ -0,0 +1,5 @@
+-- Add up migration script here
+
+ALTER TABLE notes ADD COLUMN pinned BOOLEAN DEFAULT FALSE NOT NULL;
+ALTER TABLE notes ADD COLUMN archived BOOLEAN DEFAULT FALSE NOT NULL;
+ALTER TABLE notes ADD COLUMN favorite BOOLEAN DEFAULT FALSE NOT NULL;
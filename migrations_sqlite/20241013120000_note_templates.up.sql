@@ -0,0 +1,22 @@
+-- Add up migration script here
+
+CREATE TABLE IF NOT EXISTS note_templates (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    user_id INT NOT NULL,
+    name VARCHAR(100) NOT NULL CHECK (length(name) <= 100),
+    title VARCHAR(250) NOT NULL CHECK (length(title) <= 250),
+    text VARCHAR(50000) NOT NULL CHECK (length(text) <= 50000),
+    created TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL,
+    last_edited TIMESTAMP DEFAULT CURRENT_TIMESTAMP NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS note_templates_user_id ON note_templates(user_id);
+
+-- the default tags of the templates, which get attached to the notes created from them
+CREATE TABLE IF NOT EXISTS template_tags (
+    template_id INT NOT NULL,
+    tag_id INT NOT NULL,
+    FOREIGN KEY (template_id) REFERENCES note_templates(id),
+    FOREIGN KEY (tag_id) REFERENCES tags(id),
+    PRIMARY KEY (template_id, tag_id)
+);
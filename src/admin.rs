@@ -0,0 +1,289 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+use sqlx::PgPool;
+
+use crate::{config::{Command, Config}, db, gc, outbox, repo::sql::PgRepo};
+
+/// runs any of the subcommands other than serve
+pub async fn run(command: Command, config: &Config, pool: &PgPool) -> Result<()> {
+    let storage_path = &config.storage.path;
+
+    match command {
+        Command::Serve => unreachable!("serve is not an admin command"),
+        Command::Migrate => {
+            db::migrate(pool).await?;
+            println!("Applied all of the pending migrations");
+        },
+        Command::Reset { yes } => {
+            if !yes {
+                bail!("reset deletes all of the data in the database and the storage directory, pass --yes to confirm");
+            }
+
+            db::reset(pool).await?;
+            clear_dir(storage_path).await?;
+            println!("Reset the database and cleared {}", storage_path.display());
+        },
+        Command::Seed { user_id } => {
+            seed(pool, storage_path, user_id).await?;
+            println!("Seeded the data for user {user_id}");
+        },
+        Command::Gc { dry_run, min_age } => {
+            let mut gc_config = config.gc.clone();
+            if let Some(min_age) = min_age {
+                gc_config.min_age = min_age;
+            }
+
+            let report = gc::collect(pool, storage_path, &gc_config, dry_run).await?;
+            for line in report.lines() {
+                println!("{line}");
+            }
+
+            if report.is_empty() {
+                println!("Found nothing to collect");
+            }
+        },
+        Command::ExportUser { user_id, output } => {
+            let file_count = export_user(pool, storage_path, user_id, &output).await?;
+            println!("Exported the data and {file_count} file(s) of user {user_id} into {}", output.display());
+        },
+        Command::DeleteUser { user_id, yes } => {
+            if !yes {
+                bail!("delete-user deletes all of the user's notes, tags, files, templates, reminders and shelf, pass --yes to confirm");
+            }
+
+            let file_count = delete_user(pool, user_id).await?;
+            println!("Deleted the data of user {user_id}, {file_count} file(s) are queued for deletion by the service");
+        },
+        Command::ReindexTasks => {
+            let note_count = PgRepo::new(pool.clone()).reindex_tasks().await.map_err(|e| anyhow!("could not save the tasks: {e:?}"))?;
+            println!("Saved the tasks of {note_count} note(s)");
+        },
+    }
+
+    Ok(())
+}
+
+/// removes every file in the `dir`, but keeps the dir itself
+async fn clear_dir(dir: &Path) -> Result<()> {
+    let mut entries = tokio::fs::read_dir(dir).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.metadata().await?.is_file() {
+            tokio::fs::remove_file(entry.path()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn check_user_exists(pool: &PgPool, user_id: i32) -> Result<()> {
+    let exists: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE id = $1);")
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+    match exists {
+        true => Ok(()),
+        false => Err(anyhow!("user {user_id} does not exist, users are created by the Auth service")),
+    }
+}
+
+/// (name, color)
+const SEED_TAGS: [(&str, &str); 3] = [("work", "#1e90ff"), ("ideas", "#ffa500"), ("personal", "#3cb371")];
+
+/// (title, text, indexes of the SEED_TAGS to attach)
+const SEED_NOTES: [(&str, &str, &[usize]); 4] = [
+    ("Welcome", "This is a seeded note. It has a file attached to it.", &[2]),
+    ("Meeting notes", "- discuss the roadmap\n- review the open issues", &[0]),
+    ("Project ideas", "A note taking app, but with more Miku", &[1, 2]),
+    ("Untagged note", "This note has no tags", &[]),
+];
+
+/// (name, title, text, index of the SEED_TAGS to attach)
+const SEED_TEMPLATE: (&str, &str, &str, usize) = ("Daily journal", "Journal {{date}}", "# {{weekday}}\n\n- ", 2);
+
+const SEED_FILE: (&str, &[u8]) = ("hello.txt", b"Hello from the seed command\n");
+
+/// inserts a few tags, notes, a template, a file and a shelf for the user
+async fn seed(pool: &PgPool, storage_path: &Path, user_id: i32) -> Result<()> {
+    check_user_exists(pool, user_id).await?;
+
+    let mut transaction = pool.begin().await?;
+
+    let mut tag_ids = Vec::with_capacity(SEED_TAGS.len());
+    for (name, color) in SEED_TAGS {
+        // the seeded tags go after the user's other tags, like the created ones
+        let tag_id: i32 = sqlx::query_scalar(r"
+            INSERT INTO tags (user_id, name, color, position)
+            VALUES ($1, $2, $3, (SELECT COALESCE(MAX(position) + 1, 0) FROM tags WHERE user_id = $1))
+            RETURNING id;
+        ")
+            .bind(user_id).bind(name).bind(color)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        tag_ids.push(tag_id);
+    }
+
+    let mut note_ids = Vec::with_capacity(SEED_NOTES.len());
+    for (title, text, tag_indexes) in SEED_NOTES {
+        let note_id: i32 = sqlx::query_scalar("INSERT INTO notes (user_id, title, text) VALUES ($1, $2, $3) RETURNING id;")
+            .bind(user_id).bind(title).bind(text)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        for &i in tag_indexes {
+            sqlx::query("INSERT INTO note_tags (note_id, tag_id) VALUES ($1, $2);")
+                .bind(note_id).bind(tag_ids[i])
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        note_ids.push(note_id);
+    }
+
+    let (name, title, text, tag_index) = SEED_TEMPLATE;
+    let template_id: i32 = sqlx::query_scalar("INSERT INTO note_templates (user_id, name, title, text) VALUES ($1, $2, $3, $4) RETURNING id;")
+        .bind(user_id).bind(name).bind(title).bind(text)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+    sqlx::query("INSERT INTO template_tags (template_id, tag_id) VALUES ($1, $2);")
+        .bind(template_id).bind(tag_ids[tag_index])
+        .execute(&mut *transaction)
+        .await?;
+
+    sqlx::query("INSERT INTO shelves (user_id, text) VALUES ($1, $2) ON CONFLICT (user_id) DO NOTHING;")
+        .bind(user_id).bind("Seeded shelf text")
+        .execute(&mut *transaction)
+        .await?;
+
+    // the file is written before the commit, so if anything fails after this point,
+    // the file stays in the storage dir as an orphan that the gc command can remove
+
+    let (file_name, file_data) = SEED_FILE;
+    let file_hash = uuid::Uuid::new_v4().to_string();
+    tokio::fs::write(storage_path.join(&file_hash), file_data).await?;
+
+    let file_id: i32 = sqlx::query_scalar("INSERT INTO files (user_id, hash, name, size) VALUES ($1, $2, $3, $4) RETURNING id;")
+        .bind(user_id).bind(&file_hash).bind(file_name).bind(file_data.len() as i64)
+        .fetch_one(&mut *transaction)
+        .await?;
+
+    sqlx::query("INSERT INTO note_files (note_id, file_id) VALUES ($1, $2);")
+        .bind(note_ids[0]).bind(file_id)
+        .execute(&mut *transaction)
+        .await?;
+
+    transaction.commit().await?;
+
+    Ok(())
+}
+
+/// all of the user's rows, with the relations between them
+const EXPORT_QUERY: &str = "
+SELECT json_build_object(
+    'user_id', $1::int,
+    'exported', NOW(),
+    'tags', COALESCE((SELECT json_agg(t ORDER BY t.id) FROM tags t WHERE t.user_id = $1), '[]'),
+    'notes', COALESCE((SELECT json_agg(n ORDER BY n.id) FROM notes n WHERE n.user_id = $1), '[]'),
+    'files', COALESCE((SELECT json_agg(f ORDER BY f.id) FROM files f WHERE f.user_id = $1), '[]'),
+    'shelf', (SELECT row_to_json(s) FROM shelves s WHERE s.user_id = $1),
+    'note_tags', COALESCE((
+        SELECT json_agg(nt) FROM note_tags nt
+        WHERE nt.note_id IN (SELECT id FROM notes WHERE user_id = $1)
+    ), '[]'),
+    'note_files', COALESCE((
+        SELECT json_agg(nf) FROM note_files nf
+        WHERE nf.note_id IN (SELECT id FROM notes WHERE user_id = $1)
+    ), '[]'),
+    'shelf_files', COALESCE((
+        SELECT json_agg(sf) FROM shelf_files sf
+        WHERE sf.shelf_id IN (SELECT id FROM shelves WHERE user_id = $1)
+    ), '[]'),
+    'note_links', COALESCE((
+        SELECT json_agg(nl) FROM note_links nl
+        WHERE nl.source_id IN (SELECT id FROM notes WHERE user_id = $1)
+    ), '[]'),
+    'templates', COALESCE((SELECT json_agg(t ORDER BY t.id) FROM note_templates t WHERE t.user_id = $1), '[]'),
+    'template_tags', COALESCE((
+        SELECT json_agg(tt) FROM template_tags tt
+        WHERE tt.template_id IN (SELECT id FROM note_templates WHERE user_id = $1)
+    ), '[]'),
+    'reminders', COALESCE((SELECT json_agg(r ORDER BY r.id) FROM note_reminders r WHERE r.user_id = $1), '[]'),
+    'note_tasks', COALESCE((
+        SELECT json_agg(t ORDER BY t.note_id, t.position) FROM note_tasks t
+        WHERE t.note_id IN (SELECT id FROM notes WHERE user_id = $1)
+    ), '[]')
+)::text;
+";
+
+/// writes the user's rows into `output`/data.json and copies their files into `output`/files.
+/// returns the amount of copied files
+async fn export_user(pool: &PgPool, storage_path: &Path, user_id: i32, output: &Path) -> Result<usize> {
+    check_user_exists(pool, user_id).await?;
+
+    let data: String = sqlx::query_scalar(EXPORT_QUERY)
+        .bind(user_id)
+        .fetch_one(pool)
+        .await?;
+
+    let data: serde_json::Value = serde_json::from_str(&data)?;
+
+    let files_dir = output.join("files");
+    tokio::fs::create_dir_all(&files_dir).await?;
+    tokio::fs::write(output.join("data.json"), serde_json::to_string_pretty(&data)?).await?;
+
+    let hashes: Vec<String> = sqlx::query_scalar("SELECT hash FROM files WHERE user_id = $1;")
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+    for hash in &hashes {
+        tokio::fs::copy(storage_path.join(hash), files_dir.join(hash))
+            .await
+            .map_err(|e| anyhow!("could not copy the file {hash}: {e}"))?;
+    }
+
+    Ok(hashes.len())
+}
+
+/// deletes all of the user's rows in a single transaction, and queues their files for deletion.
+/// the row in the users table is left alone, since it belongs to the Auth service.
+/// returns the amount of queued files
+async fn delete_user(pool: &PgPool, user_id: i32) -> Result<usize> {
+    let mut transaction = pool.begin().await?;
+
+    let queries = [
+        "DELETE FROM note_tags WHERE note_id IN (SELECT id FROM notes WHERE user_id = $1);",
+        "DELETE FROM note_files WHERE note_id IN (SELECT id FROM notes WHERE user_id = $1) OR file_id IN (SELECT id FROM files WHERE user_id = $1);",
+        "DELETE FROM shelf_files WHERE shelf_id IN (SELECT id FROM shelves WHERE user_id = $1) OR file_id IN (SELECT id FROM files WHERE user_id = $1);",
+        "DELETE FROM note_links WHERE source_id IN (SELECT id FROM notes WHERE user_id = $1) OR target_id IN (SELECT id FROM notes WHERE user_id = $1);",
+        "DELETE FROM template_tags WHERE template_id IN (SELECT id FROM note_templates WHERE user_id = $1) OR tag_id IN (SELECT id FROM tags WHERE user_id = $1);",
+        "DELETE FROM note_templates WHERE user_id = $1;",
+        "DELETE FROM note_reminders WHERE user_id = $1;",
+        "DELETE FROM note_tasks WHERE note_id IN (SELECT id FROM notes WHERE user_id = $1);",
+        "DELETE FROM notes WHERE user_id = $1;",
+        "DELETE FROM tags WHERE user_id = $1;",
+        "DELETE FROM shelves WHERE user_id = $1;",
+        "DELETE FROM idempotency_keys WHERE user_id = $1;",
+    ];
+
+    for query in queries {
+        sqlx::query(query)
+            .bind(user_id)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    let hashes: Vec<String> = sqlx::query_scalar("DELETE FROM files WHERE user_id = $1 RETURNING hash;")
+        .bind(user_id)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    outbox::enqueue(&mut *transaction, &hashes).await?;
+    transaction.commit().await?;
+
+    Ok(hashes.len())
+}
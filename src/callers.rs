@@ -0,0 +1,122 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use subtle::{Choice, ConstantTimeEq};
+
+use crate::config::AuthConfig;
+
+/// a service that is allowed to call this one, identified by its own token(s)
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Caller {
+    pub name: String,
+    pub tokens: Vec<CallerToken>,
+    /// either "*", a whole service like "notes.Notes", or a single rpc like "notes.Notes/ReadNotes"
+    pub scopes: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CallerToken {
+    pub value: String,
+    /// after this moment the token is rejected. used to rotate tokens with an overlap window
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl Caller {
+    /// the tokens are compared in constant time and all of them are checked,
+    /// so that the time of a check doesn't tell how much of a token was right
+    pub fn has_token(&self, token: &str, now: DateTime<Utc>) -> bool {
+        let found = self.tokens.iter().fold(Choice::from(0), |found, t| {
+            let expired = matches!(t.expires, Some(e) if e <= now);
+            found | (t.value.as_bytes().ct_eq(token.as_bytes()) & Choice::from(u8::from(!expired)))
+        });
+
+        found.into()
+    }
+
+    /// checks if the caller can call the `path`, which looks like "/notes.Notes/ReadNotes"
+    pub fn can_call(&self, path: &str) -> bool {
+        let path = path.trim_start_matches('/');
+        let service = path.split('/').next().unwrap_or_default();
+
+        self.scopes
+            .iter()
+            .any(|s| s == "*" || s == service || s == path)
+    }
+}
+
+/// finds the caller that the bearer `auth_header` belongs to. every caller is checked,
+/// so that the time it takes doesn't tell which of them the token belongs to
+pub fn find_caller<'a>(callers: &'a [Caller], auth_header: &str) -> Option<&'a Caller> {
+    let token = auth_header.strip_prefix("Bearer ")?;
+    let now = Utc::now();
+
+    callers
+        .iter()
+        .fold(None, |found, caller| {
+            let matches = caller.has_token(token, now);
+            found.or(matches.then_some(caller))
+        })
+}
+
+/// builds the caller list from the config. the service token becomes
+/// a caller called "default" that can call everything
+pub fn from_config(auth: &AuthConfig) -> Vec<Caller> {
+    let mut callers = auth.callers.clone();
+
+    if let Some(service_token) = &auth.service_token {
+        callers.push(Caller {
+            name: "default".into(),
+            tokens: vec![CallerToken { value: service_token.clone(), expires: None }],
+            scopes: vec!["*".into()],
+        });
+    }
+
+    callers
+}
+
+/// parses a string like "gateway:new_token,old_token@1793491200:notes.Notes,tags.Tags;admin:admin_token:*",
+/// where each caller is "name:tokens:scopes", and a token can have an expiration unix timestamp after the "@"
+pub fn parse_callers(value: &str) -> Result<Vec<Caller>> {
+    let mut callers = Vec::new();
+
+    for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let [name, tokens, scopes] = entry.split(':').collect::<Vec<_>>()[..] else {
+            return Err(anyhow!("invalid caller entry, expected name:tokens:scopes, got {entry:?}"));
+        };
+
+        // the rest of the checks are done when the whole config gets validated
+
+        let name = name.trim().to_owned();
+
+        let tokens = tokens
+            .split(',')
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+            .map(parse_token)
+            .collect::<Result<Vec<_>>>()?;
+
+        let scopes = scopes
+            .split(',')
+            .map(|s| s.trim().trim_start_matches('/').to_owned())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        callers.push(Caller { name, tokens, scopes });
+    }
+
+    Ok(callers)
+}
+
+fn parse_token(token: &str) -> Result<CallerToken> {
+    let Some((value, expires)) = token.split_once('@') else {
+        return Ok(CallerToken { value: token.into(), expires: None });
+    };
+
+    let expires = DateTime::from_timestamp(expires.parse()?, 0)
+        .ok_or(anyhow!("invalid token expiration timestamp: {expires:?}"))?;
+
+    Ok(CallerToken { value: value.into(), expires: Some(expires) })
+}
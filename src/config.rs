@@ -0,0 +1,661 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use clap::{Parser, Subcommand};
+use log::LevelFilter;
+use serde::Deserialize;
+
+use crate::{callers::{self, Caller}, db, gc::GcAction, rate_limit::{Budget, Limits}, tls};
+
+/// the whole service configuration. values are taken from the toml config file first,
+/// then overridden by the env variables, and then overridden by the cli flags
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub log_level: LogLevel,
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub storage: StorageConfig,
+    pub auth: AuthConfig,
+    pub tls: Option<TlsConfig>,
+    pub rate_limit: RateLimitConfig,
+    pub gc: GcConfig,
+    pub blob_deletion: BlobDeletionConfig,
+    pub metrics: MetricsConfig,
+    pub web: WebConfig,
+    pub idempotency: IdempotencyConfig,
+    pub reminders: RemindersConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub port: u16,
+    /// in seconds. applies to the whole handling of a call, including receiving a file upload
+    pub request_timeout: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub min_connections: u32,
+    /// in seconds
+    pub acquire_timeout: u64,
+    /// in seconds
+    pub idle_timeout: Option<u64>,
+    /// apply the pending migrations before starting the server
+    pub run_migrations: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageConfig {
+    /// the directory that the uploaded files are stored in
+    pub path: PathBuf,
+    /// the maximum size of received file chunks in megabytes
+    pub max_file_chunk_size: usize,
+    /// how many file chunks can be read ahead of the client during a download
+    pub download_channel_depth: usize,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    /// a token for a caller called "default" that can call everything
+    pub service_token: Option<String>,
+    pub callers: Vec<Caller>,
+    pub user_jwt_secret: Option<String>,
+    pub user_jwt_jwks_path: Option<PathBuf>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    pub client_ca_path: Option<PathBuf>,
+    /// in seconds
+    #[serde(default = "default_tls_reload_interval")]
+    pub reload_interval: u64,
+    /// in seconds. the connections that don't finish the handshake in time are dropped
+    #[serde(default = "default_tls_handshake_timeout")]
+    pub handshake_timeout: u64,
+    /// client certificate common name -> services it is allowed to call
+    pub client_services: Option<HashMap<String, Vec<String>>>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub caller: Limits,
+    pub user: Limits,
+}
+
+/// the reconciliation job between the storage dir and the files table
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GcConfig {
+    /// in seconds. the job is not run periodically if this is not set
+    pub interval: Option<u64>,
+    /// in seconds. blobs and rows younger than this are left alone
+    pub min_age: u64,
+    /// blobs in the storage dir without a files row
+    pub orphan_blobs: GcAction,
+    /// files rows without a blob in the storage dir
+    pub missing_blobs: GcAction,
+    /// files that are attached to neither a note nor a shelf
+    pub unattached_files: GcAction,
+}
+
+/// the worker that deletes the blobs queued in the pending_blob_deletions table
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BlobDeletionConfig {
+    /// in seconds
+    pub poll_interval: u64,
+    /// how many entries are processed in a single transaction
+    pub batch_size: u32,
+    /// in seconds. failed deletions are retried with an exponential backoff of up to this long
+    pub max_backoff: u64,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MetricsConfig {
+    /// the port to serve the prometheus metrics on. they are not served if this is not set
+    pub port: Option<u16>,
+}
+
+/// serving the browsers and the plain http clients
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebConfig {
+    /// accept gRPC-Web calls on the gRPC port
+    pub grpc_web: bool,
+    /// the port to serve the JSON/HTTP gateway on. it is not served if this is not set
+    pub rest_port: Option<u16>,
+    /// the origins that are allowed to make cross-origin gRPC-Web and REST calls. any origin is allowed if this is empty
+    pub cors_origins: Vec<String>,
+}
+
+/// the responses that are stored for the calls with an idempotency key
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct IdempotencyConfig {
+    /// in seconds. a key can't be reused for this long, and its response is returned on the replays
+    pub ttl: u64,
+    /// in seconds. how long a call that hasn't finished holds its key without renewing it, so that the key
+    /// of a crashed call can be used again. the running calls renew it every third of the lease
+    pub lease: u64,
+}
+
+/// the scheduler that fires the due reminders of the notes
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RemindersConfig {
+    /// in seconds. how often the due reminders are looked for, and how late they can fire
+    pub poll_interval: u64,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct LogLevel(pub LevelFilter);
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self { port: 5050, request_timeout: None }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            max_connections: 5,
+            min_connections: 0,
+            acquire_timeout: 30,
+            idle_timeout: Some(600),
+            run_migrations: false,
+        }
+    }
+}
+
+impl Default for GcConfig {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            min_age: 3600,
+            orphan_blobs: GcAction::Delete,
+            missing_blobs: GcAction::Report,
+            unattached_files: GcAction::Report,
+        }
+    }
+}
+
+impl Default for BlobDeletionConfig {
+    fn default() -> Self {
+        Self {
+            poll_interval: 5,
+            batch_size: 100,
+            max_backoff: 3600,
+        }
+    }
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self { ttl: 24 * 60 * 60, lease: 5 * 60 }
+    }
+}
+
+impl Default for RemindersConfig {
+    fn default() -> Self {
+        Self { poll_interval: 10 }
+    }
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: "./files".into(),
+            max_file_chunk_size: 8,
+            download_channel_depth: 4,
+        }
+    }
+}
+
+impl Default for LogLevel {
+    fn default() -> Self {
+        Self(LevelFilter::Info)
+    }
+}
+
+impl TryFrom<String> for LogLevel {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        LevelFilter::from_str(&value)
+            .map(Self)
+            .map_err(|_| format!("invalid log level {value:?}, expected one of off, error, warn, info, debug, trace"))
+    }
+}
+
+fn default_tls_reload_interval() -> u64 {
+    60
+}
+
+fn default_tls_handshake_timeout() -> u64 {
+    10
+}
+
+#[derive(Parser, Debug)]
+#[command(version, about = "The data service of Miku Notes")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    /// path to the toml config file
+    #[arg(long, short, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// off, error, warn, info, debug or trace
+    #[arg(long, global = true)]
+    pub log_level: Option<String>,
+
+    /// the port that the gRPC server listens on
+    #[arg(long)]
+    pub port: Option<u16>,
+
+    /// timeout for handling a single call, in seconds
+    #[arg(long)]
+    pub request_timeout: Option<u64>,
+
+    /// postgres or sqlite connection url
+    #[arg(long, global = true)]
+    pub database_url: Option<String>,
+
+    /// maximum amount of connections in the pool
+    #[arg(long)]
+    pub db_max_connections: Option<u32>,
+
+    /// minimum amount of idle connections kept in the pool
+    #[arg(long)]
+    pub db_min_connections: Option<u32>,
+
+    /// how long to wait for a pool connection, in seconds
+    #[arg(long)]
+    pub db_acquire_timeout: Option<u64>,
+
+    /// how long a connection can stay idle before getting closed, in seconds
+    #[arg(long)]
+    pub db_idle_timeout: Option<u64>,
+
+    /// apply the pending migrations before starting the server
+    #[arg(long)]
+    pub run_migrations: bool,
+
+    /// the directory that the uploaded files are stored in
+    #[arg(long, global = true)]
+    pub storage_path: Option<PathBuf>,
+
+    /// maximum size of received file chunks, in megabytes
+    #[arg(long)]
+    pub max_file_chunk_size: Option<usize>,
+
+    /// how many file chunks can be read ahead of the client during a download
+    #[arg(long)]
+    pub download_channel_depth: Option<usize>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// run the gRPC server (the default)
+    Serve,
+    /// apply the pending database migrations
+    Migrate,
+    /// revert all of the migrations, apply them again and clear the storage directory. for development only
+    Reset {
+        /// confirm that all of the data should be deleted
+        #[arg(long)]
+        yes: bool,
+    },
+    /// insert fixture notes, tags, files and a shelf for a user
+    Seed {
+        #[arg(long)]
+        user_id: i32,
+    },
+    /// find and fix the inconsistencies between the storage dir and the files table, according to the gc config
+    Gc {
+        /// only report what would be deleted
+        #[arg(long)]
+        dry_run: bool,
+        /// blobs and rows younger than this many seconds are skipped, since they might still be uploading. overrides the config value
+        #[arg(long)]
+        min_age: Option<u64>,
+    },
+    /// export all of a user's data into a directory
+    ExportUser {
+        #[arg(long)]
+        user_id: i32,
+        /// the directory to write data.json and the user's files into
+        #[arg(long)]
+        output: PathBuf,
+    },
+    /// delete all of a user's data and files
+    DeleteUser {
+        #[arg(long)]
+        user_id: i32,
+        /// confirm that the user's data should be deleted
+        #[arg(long)]
+        yes: bool,
+    },
+    /// save the tasks of every note again, for the notes that were written before the tasks were saved
+    ReindexTasks,
+}
+
+impl Config {
+    /// loads the config from the file, the env and the cli flags, and validates it
+    pub fn load(cli: &Cli) -> Result<Self> {
+        let config_path = match &cli.config {
+            Some(path) => Some(path.clone()),
+            None => dotenvy::var("CONFIG_FILE").ok().map(PathBuf::from),
+        };
+
+        let mut config = match config_path {
+            Some(path) => {
+                let contents = std::fs::read_to_string(&path)
+                    .map_err(|e| anyhow!("could not read the config file {}: {e}", path.display()))?;
+
+                toml::from_str(&contents)
+                    .map_err(|e| anyhow!("could not parse the config file {}: {e}", path.display()))?
+            },
+            None => Config::default(),
+        };
+
+        config.apply_env()?;
+        config.apply_cli(cli)?;
+        config.validate(matches!(cli.command, None | Some(Command::Serve)))?;
+
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        set_from_env(&mut self.log_level, "LOG_LEVEL", |v| LogLevel::try_from(v.to_owned()).map_err(|e| anyhow!(e)))?;
+
+        set_from_env(&mut self.server.port, "SERVICE_PORT", parse)?;
+        set_from_env(&mut self.server.request_timeout, "REQUEST_TIMEOUT", |v| parse(v).map(Some))?;
+
+        set_from_env(&mut self.database.url, "DATABASE_URL", |v| Ok(v.to_owned()))?;
+        set_from_env(&mut self.database.max_connections, "DB_MAX_CONNECTIONS", parse)?;
+        set_from_env(&mut self.database.min_connections, "DB_MIN_CONNECTIONS", parse)?;
+        set_from_env(&mut self.database.acquire_timeout, "DB_ACQUIRE_TIMEOUT", parse)?;
+        set_from_env(&mut self.database.idle_timeout, "DB_IDLE_TIMEOUT", |v| parse(v).map(Some))?;
+        set_from_env(&mut self.database.run_migrations, "RUN_MIGRATIONS", parse)?;
+
+        set_from_env(&mut self.storage.path, "STORAGE_PATH", |v| Ok(v.into()))?;
+        set_from_env(&mut self.storage.max_file_chunk_size, "MAX_FILE_CHUNK_SIZE", parse)?;
+        set_from_env(&mut self.storage.download_channel_depth, "DOWNLOAD_CHANNEL_DEPTH", parse)?;
+
+        set_from_env(&mut self.auth.service_token, "SERVICE_TOKEN", |v| Ok(Some(v.to_owned())))?;
+        set_from_env(&mut self.auth.callers, "SERVICE_CALLERS", callers::parse_callers)?;
+        set_from_env(&mut self.auth.user_jwt_secret, "USER_JWT_SECRET", |v| Ok(Some(v.to_owned())))?;
+        set_from_env(&mut self.auth.user_jwt_jwks_path, "USER_JWT_JWKS_PATH", |v| Ok(Some(v.into())))?;
+
+        // the tls section is only created from the env if the cert path is there
+
+        if let Ok(cert_path) = dotenvy::var("TLS_CERT_PATH") {
+            let tls = self.tls.get_or_insert_with(|| TlsConfig {
+                cert_path: PathBuf::new(),
+                key_path: PathBuf::new(),
+                client_ca_path: None,
+                reload_interval: default_tls_reload_interval(),
+                handshake_timeout: default_tls_handshake_timeout(),
+                client_services: None,
+            });
+
+            tls.cert_path = cert_path.into();
+        }
+
+        if let Some(tls) = &mut self.tls {
+            set_from_env(&mut tls.key_path, "TLS_KEY_PATH", |v| Ok(v.into()))?;
+            set_from_env(&mut tls.client_ca_path, "TLS_CLIENT_CA_PATH", |v| Ok(Some(v.into())))?;
+            set_from_env(&mut tls.reload_interval, "TLS_RELOAD_INTERVAL", parse)?;
+            set_from_env(&mut tls.handshake_timeout, "TLS_HANDSHAKE_TIMEOUT", parse)?;
+            set_from_env(&mut tls.client_services, "TLS_CLIENT_SERVICES", |v| tls::parse_client_services(v).map(Some))?;
+        }
+
+        for (limits, prefix) in [(&mut self.rate_limit.caller, "RATE_LIMIT_CALLER"), (&mut self.rate_limit.user, "RATE_LIMIT_USER")] {
+            set_from_env(&mut limits.calls, &format!("{prefix}_CALLS"), |v| Budget::parse(v).map(Some))?;
+            set_from_env(&mut limits.upload_bytes, &format!("{prefix}_UPLOAD_BYTES"), |v| Budget::parse(v).map(Some))?;
+            set_from_env(&mut limits.downloads, &format!("{prefix}_DOWNLOADS"), |v| parse(v).map(Some))?;
+        }
+
+        set_from_env(&mut self.gc.interval, "GC_INTERVAL", |v| parse(v).map(Some))?;
+        set_from_env(&mut self.gc.min_age, "GC_MIN_AGE", parse)?;
+        set_from_env(&mut self.gc.orphan_blobs, "GC_ORPHAN_BLOBS", GcAction::parse)?;
+        set_from_env(&mut self.gc.missing_blobs, "GC_MISSING_BLOBS", GcAction::parse)?;
+        set_from_env(&mut self.gc.unattached_files, "GC_UNATTACHED_FILES", GcAction::parse)?;
+
+        set_from_env(&mut self.blob_deletion.poll_interval, "BLOB_DELETION_POLL_INTERVAL", parse)?;
+        set_from_env(&mut self.blob_deletion.batch_size, "BLOB_DELETION_BATCH_SIZE", parse)?;
+        set_from_env(&mut self.blob_deletion.max_backoff, "BLOB_DELETION_MAX_BACKOFF", parse)?;
+
+        set_from_env(&mut self.metrics.port, "METRICS_PORT", |v| parse(v).map(Some))?;
+
+        set_from_env(&mut self.idempotency.ttl, "IDEMPOTENCY_TTL", parse)?;
+        set_from_env(&mut self.idempotency.lease, "IDEMPOTENCY_LEASE", parse)?;
+
+        set_from_env(&mut self.reminders.poll_interval, "REMINDERS_POLL_INTERVAL", parse)?;
+
+        set_from_env(&mut self.web.grpc_web, "GRPC_WEB", parse)?;
+        set_from_env(&mut self.web.rest_port, "REST_PORT", |v| parse(v).map(Some))?;
+        set_from_env(&mut self.web.cors_origins, "CORS_ORIGINS", |v| Ok(v.split(',').map(|o| o.trim().to_owned()).filter(|o| !o.is_empty()).collect()))?;
+
+        Ok(())
+    }
+
+    fn apply_cli(&mut self, cli: &Cli) -> Result<()> {
+        if let Some(log_level) = &cli.log_level {
+            self.log_level = LogLevel::try_from(log_level.clone()).map_err(|e| anyhow!(e))?;
+        }
+
+        set_from_cli(&mut self.server.port, cli.port);
+        set_from_cli(&mut self.server.request_timeout, cli.request_timeout.map(Some));
+
+        set_from_cli(&mut self.database.url, cli.database_url.clone());
+        set_from_cli(&mut self.database.max_connections, cli.db_max_connections);
+        set_from_cli(&mut self.database.min_connections, cli.db_min_connections);
+        set_from_cli(&mut self.database.acquire_timeout, cli.db_acquire_timeout);
+        set_from_cli(&mut self.database.idle_timeout, cli.db_idle_timeout.map(Some));
+        set_from_cli(&mut self.database.run_migrations, cli.run_migrations.then_some(true));
+
+        set_from_cli(&mut self.storage.path, cli.storage_path.clone());
+        set_from_cli(&mut self.storage.max_file_chunk_size, cli.max_file_chunk_size);
+        set_from_cli(&mut self.storage.download_channel_depth, cli.download_channel_depth);
+
+        Ok(())
+    }
+
+    /// checks all of the values and returns every problem at once.
+    /// the auth values are only required when running the server
+    fn validate(&self, serving: bool) -> Result<()> {
+        let mut errors = Vec::new();
+
+        if self.database.url.is_empty() {
+            errors.push("database.url is not set (set it in the config file, DATABASE_URL or --database-url)".to_owned());
+        } else if db::is_sqlite(&self.database.url) {
+            if !cfg!(feature = "sqlite") {
+                errors.push("database.url is a sqlite: url, but the service was built without the sqlite feature".to_owned());
+            }
+
+            if self.gc.interval.is_some() {
+                errors.push("gc is only supported with postgres, gc.interval can't be set with a sqlite database".to_owned());
+            }
+        } else if !self.database.url.starts_with("postgres://") && !self.database.url.starts_with("postgresql://") {
+            errors.push("database.url must be a postgres:// or a sqlite: url".to_owned());
+        }
+
+        if self.database.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_owned());
+        }
+
+        if self.database.min_connections > self.database.max_connections {
+            errors.push(format!(
+                "database.min_connections ({}) can't be bigger than database.max_connections ({})",
+                self.database.min_connections, self.database.max_connections,
+            ));
+        }
+
+        if self.database.acquire_timeout == 0 {
+            errors.push("database.acquire_timeout must be at least 1 second".to_owned());
+        }
+
+        if self.server.request_timeout == Some(0) {
+            errors.push("server.request_timeout must be at least 1 second".to_owned());
+        }
+
+        if self.storage.path.as_os_str().is_empty() {
+            errors.push("storage.path can't be empty".to_owned());
+        }
+
+        if !(1..=1024).contains(&self.storage.max_file_chunk_size) {
+            errors.push(format!("storage.max_file_chunk_size must be between 1 and 1024 megabytes, got {}", self.storage.max_file_chunk_size));
+        }
+
+        // the uploads are only slowed down, but a chunk that is bigger than the burst could never be taken
+        for (limits, name) in [(&self.rate_limit.caller, "caller"), (&self.rate_limit.user, "user")] {
+            if limits.upload_bytes.is_some_and(|b| b.burst < (self.storage.max_file_chunk_size * 1024 * 1024) as f64) {
+                errors.push(format!("the burst of rate_limit.{name}.upload_bytes can't be smaller than storage.max_file_chunk_size"));
+            }
+        }
+
+        if self.storage.download_channel_depth == 0 {
+            errors.push("storage.download_channel_depth must be at least 1".to_owned());
+        }
+
+        if serving && self.auth.service_token.is_none() && self.auth.callers.is_empty() {
+            errors.push("either auth.service_token (SERVICE_TOKEN) or auth.callers (SERVICE_CALLERS) must be set".to_owned());
+        }
+
+        if self.auth.service_token.as_ref().is_some_and(|t| t.is_empty()) {
+            errors.push("auth.service_token can't be empty".to_owned());
+        }
+
+        for (i, caller) in self.auth.callers.iter().enumerate() {
+            if caller.name.is_empty() {
+                errors.push(format!("auth.callers[{i}] has an empty name"));
+            }
+
+            if self.auth.callers[..i].iter().any(|c| c.name == caller.name) {
+                errors.push(format!("auth.callers has a duplicate caller {:?}", caller.name));
+            }
+
+            if caller.tokens.is_empty() || caller.tokens.iter().any(|t| t.value.is_empty()) {
+                errors.push(format!("caller {:?} must have at least one token, and its tokens can't be empty", caller.name));
+            }
+
+            if caller.scopes.is_empty() {
+                errors.push(format!("caller {:?} has no scopes, so it can't call anything", caller.name));
+            }
+        }
+
+        if self.auth.user_jwt_secret.is_some() && self.auth.user_jwt_jwks_path.is_some() {
+            errors.push("only one of auth.user_jwt_secret and auth.user_jwt_jwks_path can be set".to_owned());
+        }
+
+        if let Some(tls) = &self.tls {
+            if tls.key_path.as_os_str().is_empty() {
+                errors.push("tls.key_path (TLS_KEY_PATH) must be set when tls is enabled".to_owned());
+            }
+
+            if tls.reload_interval == 0 {
+                errors.push("tls.reload_interval must be at least 1 second".to_owned());
+            }
+
+            if tls.handshake_timeout == 0 {
+                errors.push("tls.handshake_timeout must be at least 1 second".to_owned());
+            }
+
+            if tls.client_services.is_some() && tls.client_ca_path.is_none() {
+                errors.push("tls.client_services requires tls.client_ca_path to be set".to_owned());
+            }
+        }
+
+        if self.idempotency.ttl == 0 {
+            errors.push("idempotency.ttl must be at least 1 second".to_owned());
+        }
+
+        if self.idempotency.lease == 0 {
+            errors.push("idempotency.lease must be at least 1 second".to_owned());
+        }
+
+        if self.reminders.poll_interval == 0 {
+            errors.push("reminders.poll_interval must be at least 1 second".to_owned());
+        }
+
+        if self.web.rest_port.is_some() {
+            if self.web.rest_port == Some(self.server.port) || self.web.rest_port == self.metrics.port {
+                errors.push("web.rest_port must be different from server.port and metrics.port".to_owned());
+            }
+
+            // the client certificates of the gateway's connections are not passed to the interceptor
+            if self.tls.as_ref().is_some_and(|t| t.client_services.is_some()) {
+                errors.push("web.rest_port can't be set together with tls.client_services".to_owned());
+            }
+        }
+
+        for origin in &self.web.cors_origins {
+            if !origin.starts_with("http://") && !origin.starts_with("https://") || origin.parse::<hyper::header::HeaderValue>().is_err() {
+                errors.push(format!("web.cors_origins has an invalid origin {origin:?}, expected something like https://notes.example.com"));
+            }
+        }
+
+        if self.gc.interval == Some(0) {
+            errors.push("gc.interval must be at least 1 second".to_owned());
+        }
+
+        if self.blob_deletion.poll_interval == 0 {
+            errors.push("blob_deletion.poll_interval must be at least 1 second".to_owned());
+        }
+
+        if self.blob_deletion.batch_size == 0 {
+            errors.push("blob_deletion.batch_size must be at least 1".to_owned());
+        }
+
+        if self.blob_deletion.max_backoff == 0 {
+            errors.push("blob_deletion.max_backoff must be at least 1 second".to_owned());
+        }
+
+        match errors.is_empty() {
+            true => Ok(()),
+            false => Err(anyhow!("invalid configuration:\n- {}", errors.join("\n- "))),
+        }
+    }
+}
+
+fn parse<T>(value: &str) -> Result<T>
+where
+    T: FromStr,
+    T::Err: std::error::Error + Send + Sync + 'static,
+{
+    Ok(value.trim().parse()?)
+}
+
+/// overrides the `field` if the env variable `name` is set
+fn set_from_env<T>(field: &mut T, name: &str, parse: impl FnOnce(&str) -> Result<T>) -> Result<()> {
+    if let Ok(value) = dotenvy::var(name) {
+        *field = parse(&value).map_err(|e| anyhow!("invalid value for {name}: {e}"))?;
+    }
+
+    Ok(())
+}
+
+fn set_from_cli<T>(field: &mut T, value: Option<T>) {
+    if let Some(value) = value {
+        *field = value;
+    }
+}
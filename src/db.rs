@@ -1,39 +1,79 @@
-use sqlx::{postgres::PgPoolOptions, PgPool};
-use anyhow::{Result, anyhow};
-use tokio::fs;
+use std::time::Duration;
+#[cfg(feature = "sqlite")]
+use std::str::FromStr;
 
-pub async fn get_pool(db_url: &str) -> Result<PgPool> {
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, PgPool};
+#[cfg(feature = "sqlite")]
+use sqlx::{sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePoolOptions}, SqlitePool};
+use anyhow::{Result, anyhow, bail};
+
+use crate::config::DatabaseConfig;
+
+/// the migrations from the ./migrations directory, embedded into the binary
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+pub async fn get_pool(config: &DatabaseConfig) -> Result<PgPool> {
     PgPoolOptions::new()
-        .max_connections(5)
-        .connect(db_url)
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout))
+        .idle_timeout(config.idle_timeout.map(Duration::from_secs))
+        .connect(&config.url)
         .await
         .map_err(|e| anyhow!(e))
 }
 
-async fn _run_script(pool: &PgPool, script_path: &str) -> Result<()> {
-    let file = fs::read_to_string(script_path).await?;
-    let mut transaction = pool.begin().await?;
+/// applies the pending migrations. the migrator holds a postgres advisory lock while doing that,
+/// so if multiple replicas start at once, one of them applies the migrations and the others wait for it
+pub async fn migrate(pool: &PgPool) -> Result<()> {
+    // all of the tables reference the users table, which is created by the Auth service's migrations
+    let users_exist: bool = sqlx::query_scalar("SELECT to_regclass('users') IS NOT NULL;")
+        .fetch_one(pool)
+        .await?;
 
-    for query in file.split(';') {
-        sqlx::query(query).execute(&mut *transaction).await?;
+    if !users_exist {
+        bail!("the users table does not exist in the database. it is created by the Auth service's migrations, which have to be applied before the migrations of this service");
     }
 
-    transaction.commit().await?;
+    MIGRATOR.run(pool).await?;
     Ok(())
 }
 
-pub async fn _reset(pool: &PgPool) -> Result<()> {
-    println!("Resetting the DB");
-    _run_script(pool, "./migrations/reset.sql").await?;
+/// reverts all of the migrations and then applies them again
+pub async fn reset(pool: &PgPool) -> Result<()> {
+    MIGRATOR.undo(pool, 0).await?;
+    migrate(pool).await
+}
 
-    println!("Clearing the files directory");
-    tokio::fs::remove_dir_all("./files").await?;
-    tokio::fs::create_dir("./files").await?;
+/// whether the `url` points at a sqlite database instead of postgres
+pub fn is_sqlite(url: &str) -> bool {
+    url.starts_with("sqlite:")
+}
 
-    Ok(())
+/// the migrations from the ./migrations_sqlite directory, embedded into the binary
+#[cfg(feature = "sqlite")]
+pub static SQLITE_MIGRATOR: Migrator = sqlx::migrate!("./migrations_sqlite");
+
+/// creates the database file if it doesn't exist yet. the foreign keys are enforced by sqlx by default
+#[cfg(feature = "sqlite")]
+pub async fn get_sqlite_pool(config: &DatabaseConfig) -> Result<SqlitePool> {
+    let options = SqliteConnectOptions::from_str(&config.url)?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .busy_timeout(Duration::from_secs(config.acquire_timeout));
+
+    SqlitePoolOptions::new()
+        .max_connections(config.max_connections)
+        .min_connections(config.min_connections)
+        .acquire_timeout(Duration::from_secs(config.acquire_timeout))
+        .idle_timeout(config.idle_timeout.map(Duration::from_secs))
+        .connect_with(options)
+        .await
+        .map_err(|e| anyhow!(e))
 }
 
-pub async fn _test_insert(pool: &PgPool) -> Result<()> {
-    println!("Inserting test data into the DB");
-    _run_script(pool, "./migrations/test_insert.sql").await
+#[cfg(feature = "sqlite")]
+pub async fn migrate_sqlite(pool: &SqlitePool) -> Result<()> {
+    SQLITE_MIGRATOR.run(pool).await?;
+    Ok(())
 }
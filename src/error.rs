@@ -0,0 +1,259 @@
+use std::collections::HashMap;
+
+use sqlx::postgres::PgDatabaseError;
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+
+/// the domain of the google.rpc.ErrorInfo details
+pub const ERROR_DOMAIN: &str = "data.miku-notes";
+
+/// what went wrong, in a form that can be shown to the client
+#[derive(Debug)]
+pub struct Violation {
+    /// the request field that caused the error, if it's known.
+    /// gets sent as a google.rpc.BadRequest field violation
+    pub field: Option<String>,
+    /// an UPPER_SNAKE_CASE google.rpc.ErrorInfo reason that the clients can match on
+    pub reason: &'static str,
+    pub description: String,
+}
+
+/// errors that the handlers return. each variant becomes a gRPC code
+/// with ErrorInfo details, and BadRequest details if the field is known
+#[derive(Debug)]
+pub enum ServiceError {
+    InvalidArgument(Violation),
+    /// every invalid field of a request, found by the validation
+    InvalidFields(Vec<Violation>),
+    NotFound(Violation),
+    AlreadyExists(Violation),
+    FailedPrecondition(Violation),
+    /// the transaction conflicted with another one and can be retried
+    Aborted(&'static str),
+    Unavailable(&'static str),
+    Internal(&'static str),
+}
+
+impl ServiceError {
+    pub fn invalid_field(field: &str, reason: &'static str, description: impl Into<String>) -> Self {
+        Self::InvalidArgument(Violation { field: Some(field.to_owned()), reason, description: description.into() })
+    }
+
+    pub fn missing_field(field: &str) -> Self {
+        Self::invalid_field(field, "MISSING_FIELD", format!("{field} is required"))
+    }
+
+    pub fn not_found(reason: &'static str, description: impl Into<String>) -> Self {
+        Self::NotFound(Violation { field: None, reason, description: description.into() })
+    }
+
+    /// maps the error of a statement that deletes rows which other rows can reference.
+    /// the `From` impl is for the statements that insert or update the referencing rows
+    pub fn from_delete(e: sqlx::Error) -> Self {
+        from_sqlx_error(e, Operation::Delete)
+    }
+
+    fn code(&self) -> Code {
+        match self {
+            Self::InvalidArgument(_) | Self::InvalidFields(_) => Code::InvalidArgument,
+            Self::NotFound(_) => Code::NotFound,
+            Self::AlreadyExists(_) => Code::AlreadyExists,
+            Self::FailedPrecondition(_) => Code::FailedPrecondition,
+            Self::Aborted(_) => Code::Aborted,
+            Self::Unavailable(_) => Code::Unavailable,
+            Self::Internal(_) => Code::Internal,
+        }
+    }
+}
+
+impl From<ServiceError> for Status {
+    fn from(error: ServiceError) -> Self {
+        let code = error.code();
+
+        let violation = match error {
+            ServiceError::InvalidFields(violations) if violations.len() != 1 => {
+                let description = violations.iter().map(|v| v.description.as_str()).collect::<Vec<_>>().join("; ");
+
+                let mut details = ErrorDetails::new();
+                details.set_error_info("INVALID_FIELDS", ERROR_DOMAIN, HashMap::new());
+
+                for v in violations {
+                    details.add_bad_request_violation(v.field.unwrap_or_default(), v.description);
+                }
+
+                return Status::with_error_details(code, description, details);
+            },
+            ServiceError::InvalidFields(mut violations) => violations.remove(0),
+            ServiceError::InvalidArgument(v)
+            | ServiceError::NotFound(v)
+            | ServiceError::AlreadyExists(v)
+            | ServiceError::FailedPrecondition(v) => v,
+            ServiceError::Aborted(reason) => Violation { field: None, reason, description: "the transaction conflicted with another one, try again".into() },
+            ServiceError::Unavailable(reason) => Violation { field: None, reason, description: "the service is temporarily unavailable, try again".into() },
+            ServiceError::Internal(reason) => Violation { field: None, reason, description: "internal error".into() },
+        };
+
+        let mut details = ErrorDetails::new();
+        details.set_error_info(violation.reason, ERROR_DOMAIN, HashMap::new());
+
+        if let Some(field) = violation.field {
+            details.add_bad_request_violation(field, &violation.description);
+        }
+
+        Status::with_error_details(code, violation.description, details)
+    }
+}
+
+/// (constraint, field, reason, description) for the constraints that the clients can violate.
+/// unique constraints become ALREADY_EXISTS, and foreign keys become FAILED_PRECONDITION
+const CONSTRAINTS: &[(&str, &str, &str, &str)] = &[
+    ("files_hash_key", "hash", "FILE_ALREADY_EXISTS", "a file with this hash already exists"),
+    ("shelves_user_id_key", "user_id", "SHELF_ALREADY_EXISTS", "the user already has a shelf"),
+    ("tags_user_id_name_key", "name", "TAG_ALREADY_EXISTS", "the user already has a tag with this name"),
+    ("note_tags_pkey", "tag_id", "TAG_ALREADY_ATTACHED", "the tag is already attached to the note"),
+    ("note_files_pkey", "file_id", "FILE_ALREADY_ATTACHED", "the file is already attached to the note"),
+    ("shelf_files_pkey", "file_id", "FILE_ALREADY_ATTACHED", "the file is already attached to the shelf"),
+    ("files_user_id_fkey", "user_id", "USER_NOT_FOUND", "the user does not exist"),
+    ("tags_user_id_fkey", "user_id", "USER_NOT_FOUND", "the user does not exist"),
+    ("notes_user_id_fkey", "user_id", "USER_NOT_FOUND", "the user does not exist"),
+    ("shelves_user_id_fkey", "user_id", "USER_NOT_FOUND", "the user does not exist"),
+    ("note_templates_user_id_fkey", "user_id", "USER_NOT_FOUND", "the user does not exist"),
+    ("note_reminders_user_id_fkey", "user_id", "USER_NOT_FOUND", "the user does not exist"),
+    ("note_tags_note_id_fkey", "note_id", "NOTE_NOT_FOUND", "the note does not exist"),
+    ("note_tags_tag_id_fkey", "tag_id", "TAG_NOT_FOUND", "the tag does not exist"),
+    ("note_files_note_id_fkey", "note_id", "NOTE_NOT_FOUND", "the note does not exist"),
+    ("note_files_file_id_fkey", "file_id", "FILE_NOT_FOUND", "the file does not exist"),
+    ("note_reminders_note_id_fkey", "note_id", "NOTE_NOT_FOUND", "the note does not exist"),
+    ("shelf_files_shelf_id_fkey", "shelf_id", "SHELF_NOT_FOUND", "the shelf does not exist"),
+    ("shelf_files_file_id_fkey", "file_id", "FILE_NOT_FOUND", "the file does not exist"),
+];
+
+/// what the statement that failed did. both directions of a foreign key violation have the same code
+/// and the same constraint, so the repos tell them apart by the statements they make
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Operation {
+    /// inserts or updates the rows that reference others
+    Write,
+    /// deletes the rows that others reference
+    Delete,
+}
+
+fn still_referenced() -> ServiceError {
+    ServiceError::FailedPrecondition(violation(None, "STILL_REFERENCED", "the row is still referenced by other rows"))
+}
+
+fn violation(field: Option<&str>, reason: &'static str, description: impl Into<String>) -> Violation {
+    Violation { field: field.map(str::to_owned), reason, description: description.into() }
+}
+
+/// maps a postgres error by its SQLSTATE code and constraint name
+fn from_database_error(e: &PgDatabaseError, operation: Operation) -> ServiceError {
+    let known = e.constraint().and_then(|c| CONSTRAINTS.iter().find(|(name, ..)| *name == c));
+
+    match e.code() {
+        // unique_violation
+        "23505" => ServiceError::AlreadyExists(match known {
+            Some((_, field, reason, description)) => violation(Some(field), reason, *description),
+            None => violation(None, "ALREADY_EXISTS", "the value already exists"),
+        }),
+        // foreign_key_violation
+        "23503" if operation == Operation::Delete => still_referenced(),
+        "23503" => ServiceError::FailedPrecondition(match known {
+            Some((_, field, reason, description)) => violation(Some(field), reason, *description),
+            None => violation(None, "REFERENCE_NOT_FOUND", "a referenced row does not exist"),
+        }),
+        // not_null_violation
+        "23502" => ServiceError::InvalidArgument(violation(e.column(), "MISSING_FIELD", "a required value is missing")),
+        // check_violation
+        "23514" => ServiceError::InvalidArgument(violation(e.column(), "CHECK_VIOLATION", "a value is out of its allowed range")),
+        // string_data_right_truncation
+        "22001" => ServiceError::InvalidArgument(violation(e.column(), "VALUE_TOO_LONG", "a value is too long")),
+        // numeric_value_out_of_range, invalid_text_representation, datetime_field_overflow, invalid_datetime_format
+        "22003" | "22P02" | "22008" | "22007" => ServiceError::InvalidArgument(violation(e.column(), "INVALID_VALUE", "a value is invalid")),
+        // serialization_failure, deadlock_detected
+        "40001" | "40P01" => ServiceError::Aborted("TRANSACTION_CONFLICT"),
+        // too_many_connections, cannot_connect_now
+        "53300" | "57P03" => ServiceError::Unavailable("DATABASE_UNAVAILABLE"),
+        _ => ServiceError::Internal("DATABASE_ERROR"),
+    }
+}
+
+/// maps a sqlite error by its extended result code. sqlite doesn't report the constraint names,
+/// only the "table.column" pairs in the message, so the names of the equivalent postgres constraints
+/// are rebuilt from those to look up the known constraints
+#[cfg(feature = "sqlite")]
+fn from_sqlite_error(e: &sqlx::sqlite::SqliteError, operation: Operation) -> ServiceError {
+    use sqlx::error::DatabaseError;
+
+    // e.g. "UNIQUE constraint failed: note_tags.note_id, note_tags.tag_id".
+    // the unique indexes on expressions are reported by their name instead, like "... failed: index 'tags_user_id_name_key'"
+    let index = e.message()
+        .split_once("index '")
+        .and_then(|(_, rest)| rest.split_once('\''))
+        .map(|(name, _)| name.to_owned());
+
+    let columns: Vec<_> = e.message()
+        .split_once(": ")
+        .map(|(_, columns)| columns.split(", ").filter_map(|c| c.split_once('.')).collect())
+        .unwrap_or_default();
+
+    let find = |constraint: String| CONSTRAINTS.iter().find(|(name, ..)| *name == constraint);
+
+    match e.code().as_deref() {
+        // SQLITE_CONSTRAINT_UNIQUE, SQLITE_CONSTRAINT_PRIMARYKEY
+        Some("2067") | Some("1555") => {
+            let known = match columns.as_slice() {
+                _ if index.is_some() => index.and_then(find),
+                [(table, column)] => find(format!("{table}_{column}_key")),
+                [(table, _), ..] => find(format!("{table}_pkey")),
+                [] => None,
+            };
+
+            ServiceError::AlreadyExists(match known {
+                Some((_, field, reason, description)) => violation(Some(field), reason, *description),
+                None => violation(None, "ALREADY_EXISTS", "the value already exists"),
+            })
+        },
+        // SQLITE_CONSTRAINT_FOREIGNKEY. the message doesn't say which key
+        Some("787") if operation == Operation::Delete => still_referenced(),
+        Some("787") => ServiceError::FailedPrecondition(violation(None, "REFERENCE_NOT_FOUND", "a referenced row does not exist")),
+        // SQLITE_CONSTRAINT_NOTNULL
+        Some("1299") => ServiceError::InvalidArgument(violation(columns.first().map(|(_, c)| *c), "MISSING_FIELD", "a required value is missing")),
+        // SQLITE_CONSTRAINT_CHECK. all of the checks in the migrations are length checks
+        Some("275") => ServiceError::InvalidArgument(violation(None, "VALUE_TOO_LONG", "a value is too long")),
+        // SQLITE_BUSY, SQLITE_BUSY_SNAPSHOT, SQLITE_LOCKED
+        Some("5") | Some("517") | Some("6") => ServiceError::Aborted("TRANSACTION_CONFLICT"),
+        _ => ServiceError::Internal("DATABASE_ERROR"),
+    }
+}
+
+fn from_sqlx_error(e: sqlx::Error, operation: Operation) -> ServiceError {
+    let error = match &e {
+        sqlx::Error::RowNotFound => ServiceError::not_found("NOT_FOUND", "not found"),
+        sqlx::Error::Database(db_error) => match db_error.try_downcast_ref::<PgDatabaseError>() {
+            Some(pg_error) => from_database_error(pg_error, operation),
+            #[cfg(feature = "sqlite")]
+            None => match db_error.try_downcast_ref::<sqlx::sqlite::SqliteError>() {
+                Some(sqlite_error) => from_sqlite_error(sqlite_error, operation),
+                None => ServiceError::Internal("DATABASE_ERROR"),
+            },
+            #[cfg(not(feature = "sqlite"))]
+            None => ServiceError::Internal("DATABASE_ERROR"),
+        },
+        sqlx::Error::PoolTimedOut | sqlx::Error::PoolClosed | sqlx::Error::Io(_) => ServiceError::Unavailable("DATABASE_UNAVAILABLE"),
+        _ => ServiceError::Internal("DATABASE_ERROR"),
+    };
+
+    match error {
+        ServiceError::Internal(_) | ServiceError::Unavailable(_) => log::error!("DB ERR: {:#?}", e),
+        _ => log::debug!("DB ERR: {:?}", e),
+    }
+
+    error
+}
+
+impl From<sqlx::Error> for ServiceError {
+    fn from(e: sqlx::Error) -> Self {
+        from_sqlx_error(e, Operation::Write)
+    }
+}
@@ -0,0 +1,265 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use sqlx::{FromRow, PgPool};
+
+use crate::{config::GcConfig, outbox};
+
+/// what to do with each of the inconsistencies that the gc finds
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum GcAction {
+    /// only log it
+    Report,
+    /// fix it by deleting the blob or the row
+    Delete,
+}
+
+impl GcAction {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim() {
+            "report" => Ok(Self::Report),
+            "delete" => Ok(Self::Delete),
+            _ => Err(anyhow!("invalid gc action {value:?}, expected report or delete")),
+        }
+    }
+}
+
+impl TryFrom<String> for GcAction {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+#[derive(Debug, FromRow)]
+pub struct FileRow {
+    pub id: i32,
+    pub user_id: i32,
+    pub hash: String,
+}
+
+/// the things that were found, and whether they were deleted or only reported
+#[derive(Debug)]
+pub struct Found<T> {
+    pub action: GcAction,
+    pub items: Vec<T>,
+}
+
+#[derive(Debug)]
+pub struct GcReport {
+    /// names of the blobs in the storage dir that don't have a files row
+    pub orphan_blobs: Found<String>,
+    /// files rows that don't have a blob
+    pub missing_blobs: Found<FileRow>,
+    /// files rows that are attached to neither a note nor a shelf
+    pub unattached_files: Found<FileRow>,
+}
+
+impl GcReport {
+    pub fn is_empty(&self) -> bool {
+        self.orphan_blobs.items.is_empty()
+            && self.missing_blobs.items.is_empty()
+            && self.unattached_files.items.is_empty()
+    }
+
+    /// a human readable line for each of the found things
+    pub fn lines(&self) -> Vec<String> {
+        let verb = |action| match action {
+            GcAction::Report => "Found",
+            GcAction::Delete => "Deleted",
+        };
+
+        let mut lines = Vec::new();
+
+        for name in &self.orphan_blobs.items {
+            lines.push(format!("{} a blob without a files row: {name}", verb(self.orphan_blobs.action)));
+        }
+
+        for file in &self.missing_blobs.items {
+            lines.push(format!(
+                "{} a files row without a blob: id {}, user {}, hash {}",
+                verb(self.missing_blobs.action), file.id, file.user_id, file.hash,
+            ));
+        }
+
+        for file in &self.unattached_files.items {
+            lines.push(format!(
+                "{} a file that is not attached to anything: id {}, user {}, hash {}",
+                verb(self.unattached_files.action), file.id, file.user_id, file.hash,
+            ));
+        }
+
+        lines
+    }
+}
+
+/// finds blobs without rows, rows without blobs and files that aren't attached to anything,
+/// and deletes them according to the `config`'s policy. with `dry_run`, everything is only reported.
+/// blobs and rows that are younger than the `config`'s min_age are skipped,
+/// since they might belong to an upload that is still in progress
+pub async fn collect(pool: &PgPool, storage_path: &Path, config: &GcConfig, dry_run: bool) -> Result<GcReport> {
+    let policy = |action| if dry_run { GcAction::Report } else { action };
+    let min_age = Duration::from_secs(config.min_age);
+
+    // the dir is listed before the rows are fetched, so that a blob
+    // that gets its row in between isn't mistaken for an orphan
+    let blobs = list_blobs(storage_path).await?;
+
+    let files = sqlx::query_as::<_, FileRow>("SELECT id, user_id, hash FROM files;")
+        .fetch_all(pool)
+        .await?;
+
+    let known_hashes: HashSet<&str> = files.iter().map(|f| f.hash.as_str()).collect();
+
+    let orphan_blobs: Vec<String> = blobs
+        .iter()
+        .filter(|(name, age)| !known_hashes.contains(name.as_str()) && **age >= min_age)
+        .map(|(name, _)| name.clone())
+        .collect();
+
+    let orphan_blobs = Found { action: policy(config.orphan_blobs), items: orphan_blobs };
+    if orphan_blobs.action == GcAction::Delete {
+        for name in &orphan_blobs.items {
+            remove_blob(storage_path, name).await;
+        }
+    }
+
+    // a row is inserted right after its blob is written, so an upload that finishes while the dir is listed
+    // has a row without a listed blob. such rows are young, and their blob is there when it is looked up again
+    let settled_ids: HashSet<i32> = sqlx::query_scalar("
+        SELECT id FROM files
+        WHERE created < NOW() - make_interval(secs => $1);
+    ")
+        .bind(config.min_age as f64)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+
+    let (candidates, mut files): (Vec<FileRow>, Vec<FileRow>) = files
+        .into_iter()
+        .partition(|f| !blobs.contains_key(&f.hash) && settled_ids.contains(&f.id));
+
+    let mut missing_blobs = Vec::new();
+    for file in candidates {
+        if tokio::fs::try_exists(storage_path.join(&file.hash)).await? {
+            files.push(file);
+        } else {
+            missing_blobs.push(file);
+        }
+    }
+
+    let missing_blobs = Found { action: policy(config.missing_blobs), items: missing_blobs };
+    if missing_blobs.action == GcAction::Delete {
+        delete_rows(pool, &missing_blobs.items, false).await?;
+    }
+
+    let unattached_ids: HashSet<i32> = sqlx::query_scalar("
+        SELECT id FROM files
+        WHERE id NOT IN (SELECT file_id FROM note_files)
+        AND id NOT IN (SELECT file_id FROM shelf_files)
+        AND created < NOW() - make_interval(secs => $1);
+    ")
+        .bind(config.min_age as f64)
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .collect();
+
+    let unattached_files: Vec<FileRow> = files
+        .into_iter()
+        .filter(|f| unattached_ids.contains(&f.id))
+        .collect();
+
+    let unattached_files = Found { action: policy(config.unattached_files), items: unattached_files };
+    if unattached_files.action == GcAction::Delete {
+        delete_rows(pool, &unattached_files.items, true).await?;
+    }
+
+    Ok(GcReport { orphan_blobs, missing_blobs, unattached_files })
+}
+
+/// the names of the files in the `storage_path` and how long ago they were modified
+async fn list_blobs(storage_path: &Path) -> Result<HashMap<String, Duration>> {
+    let now = SystemTime::now();
+    let mut blobs = HashMap::new();
+    let mut entries = tokio::fs::read_dir(storage_path).await?;
+
+    while let Some(entry) = entries.next_entry().await? {
+        let metadata = entry.metadata().await?;
+        if !metadata.is_file() {
+            continue;
+        }
+
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+
+        let age = now.duration_since(metadata.modified()?).unwrap_or_default();
+        blobs.insert(name, age);
+    }
+
+    Ok(blobs)
+}
+
+/// a blob that can't be removed gets found again on the next run, so the error is only logged
+async fn remove_blob(storage_path: &Path, name: &str) {
+    if let Err(e) = tokio::fs::remove_file(storage_path.join(name)).await {
+        log::error!("Could not delete a file: {name}; {:?}", e);
+    }
+}
+
+/// deletes the files rows along with their attachments, and queues their blobs for deletion if `with_blobs`
+async fn delete_rows(pool: &PgPool, files: &[FileRow], with_blobs: bool) -> Result<()> {
+    if files.is_empty() {
+        return Ok(());
+    }
+
+    let ids: Vec<i32> = files.iter().map(|f| f.id).collect();
+    let mut transaction = pool.begin().await?;
+
+    for query in [
+        "DELETE FROM note_files WHERE file_id = ANY($1);",
+        "DELETE FROM shelf_files WHERE file_id = ANY($1);",
+        "DELETE FROM files WHERE id = ANY($1);",
+    ] {
+        sqlx::query(query)
+            .bind(&ids)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    if with_blobs {
+        let hashes: Vec<String> = files.iter().map(|f| f.hash.clone()).collect();
+        outbox::enqueue(&mut *transaction, &hashes).await?;
+    }
+
+    transaction.commit().await?;
+    Ok(())
+}
+
+/// spawns a task that runs the gc every `interval` seconds, starting right away
+pub fn spawn(pool: PgPool, storage_path: PathBuf, config: GcConfig, interval: u64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(interval));
+
+        loop {
+            interval.tick().await;
+
+            match collect(&pool, &storage_path, &config, false).await {
+                Ok(report) if report.is_empty() => log::debug!("GC found nothing to collect"),
+                Ok(report) => {
+                    for line in report.lines() {
+                        log::warn!("GC: {line}");
+                    }
+                },
+                Err(e) => log::error!("GC ERR: {:?}", e),
+            }
+        }
+    });
+}
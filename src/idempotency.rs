@@ -0,0 +1,148 @@
+//! idempotency keys of the calls that create something. a call that is retried with the same
+//! `idempotency-key` metadata gets the stored response of the first call instead of creating a duplicate
+
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use prost::Message;
+use sha2::{Digest, Sha256};
+use tokio::time::MissedTickBehavior;
+use tonic::{Request, Status};
+
+use crate::error::{ServiceError, Violation};
+use crate::repo::{IdempotencyRecord, IdempotencyRepo};
+use crate::types::AppState;
+
+/// the metadata key of the idempotency keys
+pub const IDEMPOTENCY_KEY: &str = "idempotency-key";
+
+const KEY_MAX_LEN: usize = 100;
+
+/// how often the expired keys are deleted
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// how many times a running call renews its lease within the lease, so that a slow renewal doesn't lose the key
+const RENEWALS_PER_LEASE: f32 = 3.0;
+
+fn invalid_key(description: &str) -> ServiceError {
+    ServiceError::InvalidArgument(Violation { field: None, reason: "INVALID_IDEMPOTENCY_KEY", description: description.into() })
+}
+
+/// returns the idempotency key of the `request`, if it has one
+pub fn key<T>(request: &Request<T>) -> Result<Option<String>, ServiceError> {
+    let Some(key) = request.metadata().get(IDEMPOTENCY_KEY) else {
+        return Ok(None);
+    };
+
+    let key = key.to_str().map_err(|_| invalid_key("the idempotency key must be printable ascii"))?;
+
+    if key.is_empty() || key.len() > KEY_MAX_LEN {
+        return Err(invalid_key(&format!("the idempotency key must be between 1 and {KEY_MAX_LEN} characters long")));
+    }
+
+    Ok(Some(key.to_owned()))
+}
+
+/// the hex sha256 of the `method` and the encoded `request`
+fn request_hash(method: &str, request: &impl Message) -> String {
+    let hash = Sha256::new()
+        .chain_update(method)
+        .chain_update(request.encode_to_vec())
+        .finalize();
+
+    hash.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+impl AppState {
+    /// makes the `call` only once for each of the user's idempotency keys. a replay of the same `request`
+    /// gets the stored response, and a different request with the same key is rejected with ALREADY_EXISTS.
+    /// the key is freed if the call fails, so that it can be retried
+    pub async fn idempotent<Req, Res, F, Fut>(&self, key: Option<String>, method: &str, user_id: i32, request: &Req, call: F) -> Result<Res, Status>
+    where
+        Req: Message,
+        Res: Message + Default,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<Res, Status>>,
+    {
+        let Some(key) = key else {
+            return call().await;
+        };
+
+        let request_hash = request_hash(method, request);
+        let token = uuid::Uuid::new_v4().to_string();
+
+        match self.idempotency.reserve(user_id, &key, &request_hash, &token, self.idempotency_lease, self.idempotency_ttl).await? {
+            None => (),
+            Some(record) if record.request_hash != request_hash => {
+                return Err(ServiceError::AlreadyExists(Violation {
+                    field: None,
+                    reason: "IDEMPOTENCY_KEY_REUSED",
+                    description: "the idempotency key was already used for a different request".into(),
+                }).into());
+            },
+            Some(IdempotencyRecord { response: Some(response), .. }) => {
+                log::debug!("Replaying the response of {method} for the idempotency key {key}");
+
+                return Res::decode(response.as_slice()).map_err(|e| {
+                    log::error!("Could not decode a stored response of {method}: {:?}", e);
+                    ServiceError::Internal("STORED_RESPONSE_INVALID").into()
+                });
+            },
+            Some(_) => return Err(ServiceError::Aborted("IDEMPOTENT_CALL_IN_PROGRESS").into()),
+        }
+
+        // the lease is renewed while the call runs, so that a slow call, like a throttled upload,
+        // keeps its key however long it takes
+
+        let call = call();
+        tokio::pin!(call);
+
+        let mut renewal = tokio::time::interval(Duration::from_secs(self.idempotency_lease).div_f32(RENEWALS_PER_LEASE));
+        renewal.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        renewal.tick().await;
+
+        let result = loop {
+            tokio::select! {
+                result = &mut call => break result,
+                _ = renewal.tick() => {
+                    if let Err(e) = self.idempotency.renew(user_id, &key, &token).await {
+                        log::error!("Could not renew the idempotency key {key} of {method}: {:?}", e);
+                    }
+                },
+            }
+        };
+
+        // the call already happened, so a failure to store its response is only logged.
+        // the key then stays in progress until its lease ends, and a retry after that makes the call again.
+        // a call whose key was taken over in the meantime doesn't touch it, since the token doesn't match anymore
+
+        let stored = match &result {
+            Ok(response) => self.idempotency.complete(user_id, &key, &token, &response.encode_to_vec()).await,
+            Err(_) => self.idempotency.release(user_id, &key, &token).await,
+        };
+
+        if let Err(e) = stored {
+            log::error!("Could not update the idempotency key {key} of {method}: {:?}", e);
+        }
+
+        result
+    }
+}
+
+/// deletes the expired keys periodically in a separate task
+pub fn spawn_purge(repo: Arc<dyn IdempotencyRepo>, ttl: u64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+        loop {
+            interval.tick().await;
+
+            match repo.purge_expired(ttl).await {
+                Ok(0) => (),
+                Ok(purged) => log::debug!("Purged {purged} expired idempotency keys"),
+                Err(e) => log::error!("Could not purge the expired idempotency keys: {:?}", e),
+            }
+        }
+    });
+}
@@ -1,26 +1,141 @@
+// handlers and their helpers return tonic::Status as the error everywhere
+#![allow(clippy::result_large_err)]
+
+use clap::Parser;
+use std::{path::PathBuf, sync::Arc};
+
+use config::{Cli, Command, Config};
+use repo::{sql::PgRepo, FileRepo, IdempotencyRepo, NoteRepo, ReminderRepo, ShelfRepo, TagRepo, TemplateRepo};
 use types::AppState;
 
+mod admin;
+mod callers;
+mod config;
 mod db;
+mod error;
+mod gc;
+mod idempotency;
+mod metrics;
+mod outbox;
 mod proto;
+mod rate_limit;
+mod reminders;
+mod repo;
+mod rest;
 mod types;
 mod server;
+mod templates;
+mod tls;
+mod user_auth;
+mod validation;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
 
-    if !tokio::fs::try_exists("./files").await? {
-        tokio::fs::create_dir("./files").await?;
+    let cli = Cli::parse();
+    let config = Config::load(&cli)?;
+
+    env_logger::Builder::new()
+        .filter_level(config.log_level.0)
+        .init();
+
+    let storage_path = config.storage.path.clone();
+    if !tokio::fs::try_exists(&storage_path).await? {
+        tokio::fs::create_dir_all(&storage_path).await?;
     }
 
-    let db_url = dotenvy::var("DATABASE_URL")?;
-    let service_port = dotenvy::var("SERVICE_PORT")?.parse()?;
-    let service_token = dotenvy::var("SERVICE_TOKEN")?;
-    let chunk_size = dotenvy::var("MAX_FILE_CHUNK_SIZE")?.parse()?;
+    #[cfg(feature = "sqlite")]
+    if db::is_sqlite(&config.database.url) {
+        return serve_sqlite(cli.command, &config, storage_path).await;
+    }
+
+    let pool = db::get_pool(&config.database).await?;
+
+    match cli.command {
+        None | Some(Command::Serve) => (),
+        Some(command) => return admin::run(command, &config, &pool).await,
+    }
+
+    if config.database.run_migrations {
+        db::migrate(&pool).await?;
+        log::info!("Applied the pending migrations");
+    }
+
+    outbox::spawn_worker(pool.clone(), storage_path.clone(), config.blob_deletion.clone());
+
+    if let Some(port) = config.metrics.port {
+        metrics::spawn_server(port)?;
+    }
+
+    if let Some(interval) = config.gc.interval {
+        gc::spawn(pool.clone(), storage_path.clone(), config.gc.clone(), interval);
+    }
 
-    let pool = db::get_pool(&db_url).await?;
-    let state = AppState { pool, chunk_size };
+    let state = app_state(&config, storage_path, Arc::new(PgRepo::new(pool)));
+    idempotency::spawn_purge(state.idempotency.clone(), config.idempotency.ttl);
+    reminders::spawn_scheduler(state.reminders.clone(), state.due_reminders.clone(), config.reminders.poll_interval);
 
-    server::start(&state, service_port, &service_token).await?;
+    server::start(&state, &config).await?;
 
     Ok(())
 }
+
+/// the state of the handlers, with all of the repositories implemented by the `repo`
+fn app_state<R>(config: &Config, storage_path: PathBuf, repo: Arc<R>) -> AppState
+where
+    R: NoteRepo + TagRepo + FileRepo + ShelfRepo + TemplateRepo + ReminderRepo + IdempotencyRepo + 'static,
+{
+    AppState {
+        notes: repo.clone(),
+        tags: repo.clone(),
+        files: repo.clone(),
+        shelves: repo.clone(),
+        templates: repo.clone(),
+        reminders: repo.clone(),
+        idempotency: repo,
+        idempotency_ttl: config.idempotency.ttl,
+        idempotency_lease: config.idempotency.lease,
+        chunk_size: config.storage.max_file_chunk_size,
+        storage_path,
+        download_channel_depth: config.storage.download_channel_depth,
+        due_reminders: tokio::sync::broadcast::channel(reminders::CHANNEL_CAPACITY).0,
+    }
+}
+
+/// the same as the postgres part of main, but without the gc and the admin commands other than migrate and reindex-tasks
+#[cfg(feature = "sqlite")]
+async fn serve_sqlite(command: Option<Command>, config: &Config, storage_path: PathBuf) -> anyhow::Result<()> {
+    let pool = db::get_sqlite_pool(&config.database).await?;
+
+    match command {
+        None | Some(Command::Serve) => (),
+        Some(Command::Migrate) => {
+            db::migrate_sqlite(&pool).await?;
+            println!("Applied all of the pending migrations");
+            return Ok(());
+        },
+        Some(Command::ReindexTasks) => {
+            let note_count = repo::sql::SqliteRepo::new(pool).reindex_tasks().await.map_err(|e| anyhow::anyhow!("could not save the tasks: {e:?}"))?;
+            println!("Saved the tasks of {note_count} note(s)");
+            return Ok(());
+        },
+        Some(_) => anyhow::bail!("the gc and the admin commands other than migrate and reindex-tasks are only supported with postgres"),
+    }
+
+    if config.database.run_migrations {
+        db::migrate_sqlite(&pool).await?;
+        log::info!("Applied the pending migrations");
+    }
+
+    outbox::spawn_worker(pool.clone(), storage_path.clone(), config.blob_deletion.clone());
+
+    if let Some(port) = config.metrics.port {
+        metrics::spawn_server(port)?;
+    }
+
+    let state = app_state(config, storage_path, Arc::new(repo::sql::SqliteRepo::new(pool)));
+    idempotency::spawn_purge(state.idempotency.clone(), config.idempotency.ttl);
+    reminders::spawn_scheduler(state.reminders.clone(), state.due_reminders.clone(), config.reminders.poll_interval);
+
+    server::start(&state, config).await
+}
@@ -0,0 +1,64 @@
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use hyper::{service::{make_service_fn, service_fn}, Body, Method, Request, Response, Server, StatusCode};
+
+/// the amount of blobs that are queued for deletion but not deleted yet
+pub static BLOB_DELETION_BACKLOG: AtomicI64 = AtomicI64::new(0);
+/// the amount of failed blob deletion attempts
+pub static BLOB_DELETION_FAILURES: AtomicU64 = AtomicU64::new(0);
+
+/// the metrics in the prometheus text format
+fn render() -> String {
+    let metrics = [
+        (
+            "miku_notes_blob_deletion_backlog", "gauge",
+            "Blobs that are queued for deletion but not deleted yet",
+            BLOB_DELETION_BACKLOG.load(Ordering::Relaxed).to_string(),
+        ),
+        (
+            "miku_notes_blob_deletion_failures_total", "counter",
+            "Failed blob deletion attempts",
+            BLOB_DELETION_FAILURES.load(Ordering::Relaxed).to_string(),
+        ),
+    ];
+
+    let mut output = String::new();
+    for (name, kind, help, value) in metrics {
+        let _ = write!(output, "# HELP {name} {help}\n# TYPE {name} {kind}\n{name} {value}\n");
+    }
+
+    output
+}
+
+async fn handle(req: Request<Body>) -> Result<Response<Body>, Infallible> {
+    let response = match (req.method(), req.uri().path()) {
+        (&Method::GET, "/metrics") => Response::builder()
+            .header("content-type", "text/plain; version=0.0.4")
+            .body(Body::from(render())),
+        _ => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Body::empty()),
+    };
+
+    Ok(response.unwrap())
+}
+
+/// serves the metrics on GET /metrics in a separate task
+pub fn spawn_server(port: u16) -> anyhow::Result<()> {
+    let addr = SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], port));
+    let builder = Server::try_bind(&addr)?;
+
+    tokio::spawn(async move {
+        let make_service = make_service_fn(|_| async { Ok::<_, Infallible>(service_fn(handle)) });
+
+        if let Err(e) = builder.serve(make_service).await {
+            log::error!("METRICS SERVER ERR: {:?}", e);
+        }
+    });
+
+    log::info!("Metrics listening on {addr}");
+    Ok(())
+}
@@ -0,0 +1,172 @@
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+use anyhow::Result;
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Pool, Type};
+
+use crate::repo::query::SqlDatabase;
+use crate::types::{fill_tuple_placeholder, fill_values_placeholder, BindIter, CountWrapper};
+use crate::{config::BlobDeletionConfig, metrics};
+
+/// how long the done entries are kept around before getting pruned
+const DONE_RETENTION_DAYS: i64 = 7;
+
+/// queues the blobs with the `hashes` for deletion. should be called in the same
+/// transaction that deletes their files rows, so that a blob can't outlive its row
+/// without a record of it, even if the process dies right after the commit
+pub async fn enqueue<'c, DB>(executor: impl Executor<'c, Database = DB>, hashes: &[String]) -> sqlx::Result<()>
+where
+    DB: SqlDatabase,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> &'q String: Encode<'q, DB> + Type<DB>,
+{
+    if hashes.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(&fill_values_placeholder("INSERT INTO pending_blob_deletions (hash) VALUES ();", hashes.len(), 1, 0))
+        .bind_iter(hashes)
+        .execute(executor)
+        .await?;
+
+    Ok(())
+}
+
+/// an entry of the queue
+#[derive(FromRow)]
+pub struct PendingDeletion {
+    id: i32,
+    hash: String,
+    attempts: i32,
+}
+
+/// deletes the blob of the `entry`. if that fails, returns the error and
+/// how many seconds to wait before the next attempt
+async fn delete_blob(storage_path: &Path, entry: &PendingDeletion, config: &BlobDeletionConfig) -> Option<(String, u64)> {
+    match tokio::fs::remove_file(storage_path.join(&entry.hash)).await {
+        Ok(()) => None,
+        // the blob might have already been removed by the gc or by a previous attempt
+        Err(e) if e.kind() == ErrorKind::NotFound => None,
+        Err(e) => {
+            let backoff = 2_u64.saturating_pow(entry.attempts as u32).min(config.max_backoff);
+            log::warn!("Could not delete a file: {} (attempt {}), retrying in {}s; {:?}", entry.hash, entry.attempts + 1, backoff, e);
+            metrics::BLOB_DELETION_FAILURES.fetch_add(1, Ordering::Relaxed);
+
+            Some((e.to_string(), backoff))
+        },
+    }
+}
+
+/// deletes the blobs of a single batch of due entries. on postgres the entries are locked with SKIP LOCKED,
+/// so several replicas can run the worker at once. returns the amount of processed entries
+async fn process_batch<DB>(pool: &Pool<DB>, storage_path: &Path, config: &BlobDeletionConfig) -> Result<usize>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    PendingDeletion: for<'r> FromRow<'r, DB::Row>,
+{
+    let mut transaction = pool.begin().await?;
+
+    let entries = sqlx::query_as::<_, PendingDeletion>(&format!("
+        SELECT id, hash, attempts FROM pending_blob_deletions
+        WHERE done IS NULL AND next_attempt <= {}
+        ORDER BY next_attempt
+        LIMIT $1
+        {};
+    ", DB::DIALECT.now(), DB::DIALECT.skip_locked()))
+        .bind(config.batch_size as i64)
+        .fetch_all(&mut *transaction)
+        .await?;
+
+    let mut done_ids = Vec::with_capacity(entries.len());
+
+    for entry in &entries {
+        match delete_blob(storage_path, entry, config).await {
+            None => done_ids.push(entry.id),
+            Some((error, backoff)) => {
+                sqlx::query(&format!("
+                    UPDATE pending_blob_deletions
+                    SET attempts = attempts + 1, last_error = $2, next_attempt = {}
+                    WHERE id = $1;
+                ", DB::DIALECT.seconds_later("$3")))
+                    .bind(entry.id).bind(error).bind(backoff as i64)
+                    .execute(&mut *transaction)
+                    .await?;
+            },
+        }
+    }
+
+    if !done_ids.is_empty() {
+        sqlx::query(&fill_tuple_placeholder(
+            &format!("UPDATE pending_blob_deletions SET done = {} WHERE id IN ();", DB::DIALECT.now()),
+            &done_ids, 0,
+        ))
+            .bind_iter(&done_ids)
+            .execute(&mut *transaction)
+            .await?;
+    }
+
+    transaction.commit().await?;
+
+    Ok(entries.len())
+}
+
+/// processes all of the due entries, prunes the old done ones and updates the backlog metric
+async fn process_due<DB>(pool: &Pool<DB>, storage_path: &Path, config: &BlobDeletionConfig) -> Result<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    PendingDeletion: for<'r> FromRow<'r, DB::Row>,
+    CountWrapper: for<'r> FromRow<'r, DB::Row>,
+{
+    while process_batch(pool, storage_path, config).await? == config.batch_size as usize {}
+
+    sqlx::query(&format!("DELETE FROM pending_blob_deletions WHERE done < {};", DB::DIALECT.seconds_ago("$1")))
+        .bind(DONE_RETENTION_DAYS * 24 * 60 * 60)
+        .execute(pool)
+        .await?;
+
+    let backlog = sqlx::query_as::<_, CountWrapper>("SELECT COUNT(*) AS count FROM pending_blob_deletions WHERE done IS NULL;")
+        .fetch_one(pool)
+        .await?
+        .count;
+
+    metrics::BLOB_DELETION_BACKLOG.store(backlog, Ordering::Relaxed);
+
+    Ok(())
+}
+
+/// spawns a task that deletes the queued blobs every poll_interval seconds of the `config`
+pub fn spawn_worker<DB>(pool: Pool<DB>, storage_path: PathBuf, config: BlobDeletionConfig)
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    PendingDeletion: for<'r> FromRow<'r, DB::Row>,
+    CountWrapper: for<'r> FromRow<'r, DB::Row>,
+{
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(config.poll_interval));
+
+        loop {
+            interval.tick().await;
+
+            if let Err(e) = process_due(&pool, &storage_path, &config).await {
+                log::error!("BLOB DELETION ERR: {:?}", e);
+            }
+        }
+    });
+}
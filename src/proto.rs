@@ -14,3 +14,11 @@ pub mod notes {
 pub mod shelves {
     tonic::include_proto!("shelves");
 }
+
+pub mod templates {
+    tonic::include_proto!("templates");
+}
+
+pub mod reminders {
+    tonic::include_proto!("reminders");
+}
@@ -0,0 +1,307 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use tonic::Status;
+
+use crate::config::RateLimitConfig;
+
+/// once a bucket map gets this big, buckets that are full again get dropped from it
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+/// a token bucket's refill rate per second and its capacity
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub struct Budget {
+    pub rate: f64,
+    pub burst: f64,
+}
+
+impl Budget {
+    /// parses a string like "20/40", where 20 is the rate and 40 is the burst.
+    /// the burst is optional and equals the rate by default
+    pub fn parse(value: &str) -> Result<Self> {
+        let (rate, burst) = match value.split_once('/') {
+            Some((rate, burst)) => (rate.trim().parse()?, burst.trim().parse()?),
+            None => {
+                let rate = value.trim().parse()?;
+                (rate, rate)
+            },
+        };
+
+        if !(rate > 0.0 && burst >= 1.0) {
+            return Err(anyhow!("invalid rate limit budget {value:?}, the rate must be positive and the burst must be at least 1"));
+        }
+
+        Ok(Self { rate, burst })
+    }
+}
+
+impl TryFrom<String> for Budget {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        Self::parse(&value)
+    }
+}
+
+/// budgets for either a single caller or a single user. None means unlimited
+#[derive(Clone, Copy, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// calls per second
+    pub calls: Option<Budget>,
+    /// uploaded file bytes per second
+    pub upload_bytes: Option<Budget>,
+    /// concurrent download streams
+    pub downloads: Option<usize>,
+}
+
+impl Limits {
+    fn is_unlimited(&self) -> bool {
+        self.calls.is_none() && self.upload_bytes.is_none() && self.downloads.is_none()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum LimitKey {
+    Caller(String),
+    User(i32),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, budget: Budget, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * budget.rate).min(budget.burst);
+        self.updated = now;
+    }
+
+    /// takes the `amount` of tokens, or returns how long to wait until it can be taken.
+    /// the amount can't be bigger than the burst
+    fn take(&mut self, amount: f64, budget: Budget, now: Instant) -> Result<(), Duration> {
+        self.refill(budget, now);
+
+        if self.tokens < amount {
+            return Err(Duration::from_secs_f64((amount - self.tokens) / budget.rate));
+        }
+
+        self.tokens -= amount;
+        Ok(())
+    }
+}
+
+/// token bucket rate limiter, with separate budgets for callers and users
+pub struct RateLimiter {
+    caller_limits: Limits,
+    user_limits: Limits,
+    calls: Mutex<HashMap<LimitKey, Bucket>>,
+    upload_bytes: Mutex<HashMap<LimitKey, Bucket>>,
+    downloads: Mutex<HashMap<LimitKey, usize>>,
+}
+
+impl RateLimiter {
+    pub fn new(caller_limits: Limits, user_limits: Limits) -> Self {
+        Self {
+            caller_limits,
+            user_limits,
+            calls: Mutex::default(),
+            upload_bytes: Mutex::default(),
+            downloads: Mutex::default(),
+        }
+    }
+
+    /// returns None if no limits are configured
+    pub fn from_config(config: &RateLimitConfig) -> Option<Self> {
+        if config.caller.is_unlimited() && config.user.is_unlimited() {
+            return None;
+        }
+
+        Some(Self::new(config.caller, config.user))
+    }
+
+    fn limits(&self, key: &LimitKey) -> &Limits {
+        match key {
+            LimitKey::Caller(_) => &self.caller_limits,
+            LimitKey::User(_) => &self.user_limits,
+        }
+    }
+
+    /// takes the `amount` from the `key`'s bucket, or returns how long to wait until it can be taken
+    fn take(&self, kind: BucketKind, key: &LimitKey, amount: f64) -> Result<(), Duration> {
+        let Some(budget) = self.budget_for(key, kind) else {
+            return Ok(());
+        };
+
+        let buckets = match kind {
+            BucketKind::Calls => &self.calls,
+            BucketKind::UploadBytes => &self.upload_bytes,
+        };
+
+        let now = Instant::now();
+        let mut buckets = buckets.lock().unwrap();
+
+        if buckets.len() > MAX_TRACKED_KEYS {
+            buckets.retain(|k, b| match self.budget_for(k, kind) {
+                Some(budget) => {
+                    b.refill(budget, now);
+                    b.tokens < budget.burst
+                },
+                None => false,
+            });
+        }
+
+        let bucket = buckets
+            .entry(key.clone())
+            .or_insert(Bucket { tokens: budget.burst, updated: now });
+
+        bucket.take(amount, budget, now)
+    }
+
+    /// charges a single call to the `key`'s budget
+    pub fn check_call(&self, key: &LimitKey) -> Result<(), Status> {
+        self.take(BucketKind::Calls, key, 1.0)
+            .map_err(|retry_after| exhausted(key, retry_after))
+    }
+
+    /// charges uploaded file bytes to the `key`'s budget, waiting until the bucket has enough of them.
+    /// only a chunk that is bigger than the whole bucket is rejected, since it would never fit
+    pub async fn throttle_upload(&self, key: &LimitKey, bytes: usize) -> Result<(), Status> {
+        if let Some(budget) = self.budget_for(key, BucketKind::UploadBytes) {
+            if bytes as f64 > budget.burst {
+                return Err(chunk_too_big(key, bytes, budget));
+            }
+        }
+
+        while let Err(retry_after) = self.take(BucketKind::UploadBytes, key, bytes as f64) {
+            tokio::time::sleep(retry_after).await;
+        }
+
+        Ok(())
+    }
+
+    /// registers a new download stream for the `key`. the returned guard
+    /// should be kept alive for as long as the stream is being sent
+    pub fn start_download(self: &Arc<Self>, key: &LimitKey) -> Result<DownloadGuard, Status> {
+        if let Some(max_downloads) = self.limits(key).downloads {
+            let mut downloads = self.downloads.lock().unwrap();
+            let count = downloads.entry(key.clone()).or_default();
+
+            if *count >= max_downloads {
+                return Err(exhausted(key, Duration::from_secs(1)));
+            }
+
+            *count += 1;
+        }
+
+        Ok(DownloadGuard { limiter: self.clone(), key: key.clone() })
+    }
+
+    fn budget_for(&self, key: &LimitKey, kind: BucketKind) -> Option<Budget> {
+        let limits = self.limits(key);
+        match kind {
+            BucketKind::Calls => limits.calls,
+            BucketKind::UploadBytes => limits.upload_bytes,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum BucketKind {
+    Calls,
+    UploadBytes,
+}
+
+/// decrements the download stream count of its key when dropped
+pub struct DownloadGuard {
+    limiter: Arc<RateLimiter>,
+    key: LimitKey,
+}
+
+impl Drop for DownloadGuard {
+    fn drop(&mut self) {
+        if self.limiter.limits(&self.key).downloads.is_none() {
+            return;
+        }
+
+        let mut downloads = self.limiter.downloads.lock().unwrap();
+        if let Some(count) = downloads.get_mut(&self.key) {
+            *count -= 1;
+            if *count == 0 {
+                downloads.remove(&self.key);
+            }
+        }
+    }
+}
+
+fn who(key: &LimitKey) -> String {
+    match key {
+        LimitKey::Caller(name) => format!("caller {name}"),
+        LimitKey::User(id) => format!("user {id}"),
+    }
+}
+
+fn exhausted(key: &LimitKey, retry_after: Duration) -> Status {
+    let retry_after_secs = retry_after.as_secs_f64().ceil().max(1.0) as u64;
+
+    let mut status = Status::resource_exhausted(format!("rate limit exceeded for {}, retry after {retry_after_secs}s", who(key)));
+    status.metadata_mut().insert("retry-after", retry_after_secs.into());
+    status
+}
+
+fn chunk_too_big(key: &LimitKey, bytes: usize, budget: Budget) -> Status {
+    Status::resource_exhausted(format!(
+        "a file chunk of {bytes} bytes is bigger than the upload burst of {} bytes for {}, send smaller chunks",
+        budget.burst, who(key),
+    ))
+}
+
+/// inserted into the request's extensions by the interceptor, so that the handlers
+/// can charge the budgets of the user that the request is made for
+#[derive(Clone)]
+pub struct RequestLimiter {
+    limiter: Arc<RateLimiter>,
+    caller: LimitKey,
+    user_charged: bool,
+}
+
+impl RequestLimiter {
+    /// charges the call to the caller's budget, and to the user's budget if the user is already known
+    pub fn charge(limiter: Arc<RateLimiter>, caller: &str, user_id: Option<i32>) -> Result<Self, Status> {
+        let caller = LimitKey::Caller(caller.to_owned());
+        limiter.check_call(&caller)?;
+
+        if let Some(user_id) = user_id {
+            limiter.check_call(&LimitKey::User(user_id))?;
+        }
+
+        Ok(Self { limiter, caller, user_charged: user_id.is_some() })
+    }
+
+    /// charges the call to the user's budget, unless that was already done by the interceptor
+    pub fn check_user_call(&self, user_id: i32) -> Result<(), Status> {
+        match self.user_charged {
+            true => Ok(()),
+            false => self.limiter.check_call(&LimitKey::User(user_id)),
+        }
+    }
+
+    /// slows the upload down to the caller's and the user's upload budgets
+    pub async fn throttle_upload(&self, user_id: i32, bytes: usize) -> Result<(), Status> {
+        self.limiter.throttle_upload(&self.caller, bytes).await?;
+        self.limiter.throttle_upload(&LimitKey::User(user_id), bytes).await
+    }
+
+    pub fn start_download(&self, user_id: i32) -> Result<[DownloadGuard; 2], Status> {
+        Ok([
+            self.limiter.start_download(&self.caller)?,
+            self.limiter.start_download(&LimitKey::User(user_id))?,
+        ])
+    }
+}
@@ -0,0 +1,314 @@
+//! the times of the note reminders, with their recurrence rules, and the scheduler that fires them.
+//! the occurrences are counted in the local time of the reminder, so that a reminder
+//! at 9:00 stays at 9:00 across the daylight saving changes
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, Days, LocalResult, Months, NaiveDate, NaiveDateTime, Offset, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use tokio::sync::broadcast;
+
+use crate::proto::reminders::DueReminder;
+use crate::repo::{ReminderRepo, RepoResult};
+use crate::templates::parse_timezone;
+
+/// the format of the local times, like 2024-10-18T09:00
+pub const LOCAL_TIME_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+/// how many due reminders can wait for the slowest watcher before it starts missing them
+pub const CHANNEL_CAPACITY: usize = 1024;
+
+/// how many due reminders are fetched at once
+const FIRE_BATCH_SIZE: i64 = 100;
+
+pub const MAX_INTERVAL: u32 = 1000;
+pub const MAX_COUNT: u32 = 1000;
+
+/// the rules that never have an occurrence, like the 30th of february, are cut off after this many periods
+const MAX_PERIODS: u32 = 100_000;
+
+pub fn parse_local_time(value: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(value, LOCAL_TIME_FORMAT).ok()
+}
+
+/// the utc time of the `local` time in the `timezone`. the times that happen twice
+/// are the earlier one, and the skipped ones are moved forward by the length of the gap
+pub fn to_utc(local: NaiveDateTime, timezone: Tz) -> DateTime<Utc> {
+    match timezone.from_local_datetime(&local) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.with_timezone(&Utc),
+        LocalResult::None => {
+            // the offset from before the gap, since the changes are never a day apart
+            let offset = timezone.offset_from_utc_datetime(&(local - Days::new(1))).fix();
+            (local - offset).and_utc()
+        },
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Until {
+    /// the last day of the occurrences, in the local time
+    Date(NaiveDate),
+    Time(DateTime<Utc>),
+}
+
+/// the supported subset of the iCalendar RRULE: FREQ, INTERVAL, COUNT, UNTIL,
+/// and BYDAY with plain weekdays for the weekly rules
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recurrence {
+    pub frequency: Frequency,
+    pub interval: u32,
+    /// the days of a weekly rule, sorted from monday. the day of the first occurrence if it's empty
+    pub by_day: Vec<Weekday>,
+    pub count: Option<u32>,
+    pub until: Option<Until>,
+}
+
+impl Recurrence {
+    /// parses a rule like "FREQ=WEEKLY;INTERVAL=2;BYDAY=MO,FR", with or without the "RRULE:" prefix.
+    /// the empty rule is None, and the error describes the invalid part
+    pub fn parse(rule: &str) -> Result<Option<Self>, String> {
+        let rule = rule.trim().to_ascii_uppercase();
+        let rule = rule.strip_prefix("RRULE:").unwrap_or(&rule);
+
+        if rule.is_empty() {
+            return Ok(None);
+        }
+
+        let (mut frequency, mut interval, mut by_day, mut count, mut until) = (None, None, None, None, None);
+
+        for part in rule.split(';').filter(|p| !p.is_empty()) {
+            let Some((name, value)) = part.split_once('=') else {
+                return Err(format!("{part:?} is not a NAME=VALUE pair"));
+            };
+
+            let duplicate = match name {
+                "FREQ" => frequency.replace(parse_frequency(value)?).is_some(),
+                "INTERVAL" => interval.replace(parse_number(name, value, MAX_INTERVAL)?).is_some(),
+                "BYDAY" => by_day.replace(parse_weekdays(value)?).is_some(),
+                "COUNT" => count.replace(parse_number(name, value, MAX_COUNT)?).is_some(),
+                "UNTIL" => until.replace(parse_until(value)?).is_some(),
+                _ => return Err(format!("{name} is not supported, only FREQ, INTERVAL, BYDAY, COUNT and UNTIL are")),
+            };
+
+            if duplicate {
+                return Err(format!("{name} is set more than once"));
+            }
+        }
+
+        let Some(frequency) = frequency else {
+            return Err("FREQ is required".into());
+        };
+
+        if by_day.is_some() && frequency != Frequency::Weekly {
+            return Err("BYDAY is only supported with FREQ=WEEKLY".into());
+        }
+
+        if count.is_some() && until.is_some() {
+            return Err("only one of COUNT and UNTIL can be set".into());
+        }
+
+        Ok(Some(Self { frequency, interval: interval.unwrap_or(1), by_day: by_day.unwrap_or_default(), count, until }))
+    }
+
+    /// the local dates of the occurrences in the `period`th period from the `start`, which might be before it
+    fn period_dates(&self, start: NaiveDate, period: u32) -> Vec<NaiveDate> {
+        let step = period * self.interval;
+
+        let date = match self.frequency {
+            Frequency::Daily => start.checked_add_days(Days::new(step.into())),
+            Frequency::Weekly if self.by_day.is_empty() => start.checked_add_days(Days::new(7 * u64::from(step))),
+            Frequency::Weekly => {
+                let week = start
+                    .checked_sub_days(Days::new(start.weekday().num_days_from_monday().into()))
+                    .and_then(|monday| monday.checked_add_days(Days::new(7 * u64::from(step))));
+
+                return week.into_iter()
+                    .flat_map(|monday| self.by_day.iter().filter_map(move |day| monday.checked_add_days(Days::new(day.num_days_from_monday().into()))))
+                    .collect();
+            },
+            // the months and the years that don't have the day are skipped
+            Frequency::Monthly => start
+                .with_day(1)
+                .and_then(|first| first.checked_add_months(Months::new(step)))
+                .and_then(|month| month.with_day(start.day())),
+            Frequency::Yearly => start
+                .with_day(1)
+                .and_then(|first| first.checked_add_months(Months::new(12 * step)))
+                .and_then(|month| month.with_day(start.day())),
+        };
+
+        date.into_iter().collect()
+    }
+
+    /// the local times of all of the occurrences from the `start`, in order
+    fn occurrences(&self, start: NaiveDateTime, timezone: Tz) -> impl Iterator<Item = NaiveDateTime> + '_ {
+        (0..MAX_PERIODS)
+            .flat_map(move |period| self.period_dates(start.date(), period))
+            .map(move |date| date.and_time(start.time()))
+            .filter(move |time| *time >= start)
+            .take(self.count.map_or(usize::MAX, |count| count as usize))
+            .take_while(move |time| match self.until {
+                None => true,
+                Some(Until::Date(date)) => time.date() <= date,
+                Some(Until::Time(until)) => to_utc(*time, timezone) <= until,
+            })
+    }
+}
+
+fn parse_frequency(value: &str) -> Result<Frequency, String> {
+    match value {
+        "DAILY" => Ok(Frequency::Daily),
+        "WEEKLY" => Ok(Frequency::Weekly),
+        "MONTHLY" => Ok(Frequency::Monthly),
+        "YEARLY" => Ok(Frequency::Yearly),
+        _ => Err(format!("FREQ must be one of DAILY, WEEKLY, MONTHLY or YEARLY, got {value:?}")),
+    }
+}
+
+fn parse_number(name: &str, value: &str, max: u32) -> Result<u32, String> {
+    match value.parse() {
+        Ok(number) if (1..=max).contains(&number) => Ok(number),
+        _ => Err(format!("{name} must be a number between 1 and {max}, got {value:?}")),
+    }
+}
+
+fn parse_weekdays(value: &str) -> Result<Vec<Weekday>, String> {
+    let mut days = Vec::new();
+
+    for day in value.split(',') {
+        let day = match day {
+            "MO" => Weekday::Mon,
+            "TU" => Weekday::Tue,
+            "WE" => Weekday::Wed,
+            "TH" => Weekday::Thu,
+            "FR" => Weekday::Fri,
+            "SA" => Weekday::Sat,
+            "SU" => Weekday::Sun,
+            _ => return Err(format!("BYDAY must be a list of MO, TU, WE, TH, FR, SA or SU, got {day:?}")),
+        };
+
+        if !days.contains(&day) {
+            days.push(day);
+        }
+    }
+
+    days.sort_by_key(|d| d.num_days_from_monday());
+    Ok(days)
+}
+
+/// a local date like 20241231, or a utc time like 20241231T090000Z
+fn parse_until(value: &str) -> Result<Until, String> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Ok(Until::Date(date));
+    }
+
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ")
+        .map(|time| Until::Time(time.and_utc()))
+        .map_err(|_| format!("UNTIL must be a date like 20241231 or a utc time like 20241231T090000Z, got {value:?}"))
+}
+
+/// the parsed time and recurrence of a reminder
+pub struct Schedule {
+    pub local_time: NaiveDateTime,
+    pub timezone: Tz,
+    pub recurrence: Option<Recurrence>,
+}
+
+impl Schedule {
+    /// None if any of the values is invalid
+    pub fn parse(local_time: &str, timezone: &str, recurrence: &str) -> Option<Self> {
+        Some(Self {
+            local_time: parse_local_time(local_time)?,
+            timezone: parse_timezone(timezone)?,
+            recurrence: Recurrence::parse(recurrence).ok()?,
+        })
+    }
+
+    /// the first occurrence that is later than `after`, or the very first one without it.
+    /// None if there are no occurrences left
+    pub fn next_fire(&self, after: Option<DateTime<Utc>>) -> Option<DateTime<Utc>> {
+        let later = |time: &DateTime<Utc>| after.is_none_or(|after| *time > after);
+
+        match &self.recurrence {
+            None => Some(to_utc(self.local_time, self.timezone)).filter(later),
+            Some(recurrence) => recurrence
+                .occurrences(self.local_time, self.timezone)
+                .map(|time| to_utc(time, self.timezone))
+                .find(later),
+        }
+    }
+}
+
+/// fires the reminders that are due at `now`, and moves them to their next occurrences after it,
+/// so that the ones that were missed while the service was down fire only once. returns the amount of fired reminders
+pub async fn fire_due(repo: &dyn ReminderRepo, sender: &broadcast::Sender<DueReminder>, now: DateTime<Utc>) -> RepoResult<usize> {
+    let mut fired = 0;
+
+    loop {
+        let due = repo.due(now.naive_utc(), FIRE_BATCH_SIZE).await?;
+        let (batch_len, fired_before) = (due.len(), fired);
+
+        for mut due_reminder in due {
+            let Some(reminder) = due_reminder.reminder.as_mut() else {
+                continue;
+            };
+
+            // the stored values are always valid, but a reminder that can't be scheduled is only fired once
+            let next = Schedule::parse(&reminder.local_time, &reminder.timezone, &reminder.recurrence)
+                .and_then(|schedule| schedule.next_fire(Some(now)));
+
+            let Some(due_at) = DateTime::from_timestamp(due_reminder.due_at, 0) else {
+                continue;
+            };
+
+            // another instance of the service might have fired it already
+            if !repo.reschedule(reminder.id, due_at.naive_utc(), next.map(|n| n.naive_utc())).await? {
+                continue;
+            }
+
+            match next {
+                Some(next) => reminder.fire_at = next.timestamp(),
+                None => reminder.fired = true,
+            }
+
+            // the watchers might have all left in the meantime
+            let _ = sender.send(due_reminder);
+            fired += 1;
+        }
+
+        if (batch_len as i64) < FIRE_BATCH_SIZE || fired == fired_before {
+            return Ok(fired);
+        }
+    }
+}
+
+/// fires the due reminders every `poll_interval` seconds in a separate task. nothing is fired while nobody
+/// watches, so that the reminders wait for the notification service instead of getting lost while it restarts
+pub fn spawn_scheduler(repo: Arc<dyn ReminderRepo>, sender: broadcast::Sender<DueReminder>, poll_interval: u64) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(poll_interval));
+
+        loop {
+            interval.tick().await;
+
+            if sender.receiver_count() == 0 {
+                continue;
+            }
+
+            match fire_due(repo.as_ref(), &sender, Utc::now()).await {
+                Ok(0) => (),
+                Ok(fired) => log::debug!("Fired {fired} due reminders"),
+                Err(e) => log::error!("Could not fire the due reminders: {:?}", e),
+            }
+        }
+    });
+}
@@ -0,0 +1,148 @@
+//! the per-item checks of the batch operations, shared by the repositories.
+//! a repository looks up which of the notes and tags belong to the user and which of them are attached,
+//! and these functions decide what happens to each item
+
+use std::collections::HashSet;
+
+use crate::error::{ServiceError, Violation};
+use crate::proto::notes::BatchItemResult;
+
+use super::RepoResult;
+
+const NOTE_NOT_FOUND: &str = "NOTE_NOT_FOUND";
+const TAG_NOT_FOUND: &str = "TAG_NOT_FOUND";
+const TAG_ALREADY_ATTACHED: &str = "TAG_ALREADY_ATTACHED";
+const TAG_NOT_ATTACHED: &str = "TAG_NOT_ATTACHED";
+
+/// what the repository found out about the items of a batch
+pub struct Lookup {
+    /// the notes of the batch that belong to the user
+    pub notes: HashSet<i32>,
+    /// the tags of the batch that belong to the user
+    pub tags: HashSet<i32>,
+    /// the (note_id, tag_id) pairs of the batch that are already attached
+    pub attached: HashSet<(i32, i32)>,
+}
+
+fn item(note_id: i32, tag_id: i32, failure: Option<&str>) -> BatchItemResult {
+    BatchItemResult { note_id, tag_id, ok: failure.is_none(), reason: failure.unwrap_or_default().to_owned() }
+}
+
+/// a result for each note-tag pair. a pair can be attached if both of them exist and it isn't attached yet,
+/// and it can be detached if it is attached
+pub fn tag_pairs(note_ids: &[i32], tag_ids: &[i32], lookup: &Lookup, attach: bool) -> Vec<BatchItemResult> {
+    note_ids.iter()
+        .flat_map(|&note_id| tag_ids.iter().map(move |&tag_id| (note_id, tag_id)))
+        .map(|(note_id, tag_id)| {
+            let failure = if !lookup.notes.contains(&note_id) {
+                Some(NOTE_NOT_FOUND)
+            } else if !lookup.tags.contains(&tag_id) {
+                Some(TAG_NOT_FOUND)
+            } else {
+                match (attach, lookup.attached.contains(&(note_id, tag_id))) {
+                    (true, true) => Some(TAG_ALREADY_ATTACHED),
+                    (false, false) => Some(TAG_NOT_ATTACHED),
+                    _ => None,
+                }
+            };
+
+            item(note_id, tag_id, failure)
+        })
+        .collect()
+}
+
+/// a result for each note that gets deleted or changed, which only needs the note to belong to the user
+pub fn found_notes(note_ids: &[i32], lookup: &Lookup) -> Vec<BatchItemResult> {
+    note_ids.iter()
+        .map(|&note_id| item(note_id, 0, (!lookup.notes.contains(&note_id)).then_some(NOTE_NOT_FOUND)))
+        .collect()
+}
+
+/// a result for each note that gets moved from the `from_tag_id`. a note can only be moved
+/// if it has that tag, and it's fine if it already has the other one
+pub fn moved_notes(note_ids: &[i32], from_tag_id: i32, lookup: &Lookup) -> Vec<BatchItemResult> {
+    note_ids.iter()
+        .map(|&note_id| {
+            let failure = if !lookup.notes.contains(&note_id) {
+                Some(NOTE_NOT_FOUND)
+            } else if !lookup.attached.contains(&(note_id, from_tag_id)) {
+                Some(TAG_NOT_ATTACHED)
+            } else {
+                None
+            };
+
+            item(note_id, 0, failure)
+        })
+        .collect()
+}
+
+/// both tags of a move must belong to the user, otherwise none of the notes can be moved
+pub fn check_move_tags(from_tag_id: i32, to_tag_id: i32, lookup: &Lookup) -> RepoResult<()> {
+    for (field, tag_id) in [("from_tag_id", from_tag_id), ("to_tag_id", to_tag_id)] {
+        if !lookup.tags.contains(&tag_id) {
+            return Err(ServiceError::NotFound(Violation {
+                field: Some(field.into()),
+                reason: TAG_NOT_FOUND,
+                description: format!("the tag {tag_id} does not exist"),
+            }));
+        }
+    }
+
+    Ok(())
+}
+
+/// the source and the target tags of a merge must all belong to the user
+pub fn check_merge_tags(source_ids: &[i32], target_id: i32, owned: &HashSet<i32>) -> RepoResult<()> {
+    check_owned_tags("target_tag_id", &[target_id], owned)?;
+    check_owned_tags("source_tag_ids", source_ids, owned)
+}
+
+/// fails with the first of the tags that doesn't belong to the user
+pub fn check_owned_tags(field: &str, tag_ids: &[i32], owned: &HashSet<i32>) -> RepoResult<()> {
+    match tag_ids.iter().find(|id| !owned.contains(id)) {
+        None => Ok(()),
+        Some(tag_id) => Err(ServiceError::NotFound(Violation {
+            field: Some(field.into()),
+            reason: TAG_NOT_FOUND,
+            description: format!("the tag {tag_id} does not exist"),
+        })),
+    }
+}
+
+/// the pairs of the items that succeeded
+pub fn succeeded(results: &[BatchItemResult]) -> Vec<(i32, i32)> {
+    results.iter().filter(|r| r.ok).map(|r| (r.note_id, r.tag_id)).collect()
+}
+
+/// fails with the first failed item, unless the batch is `partial`
+pub fn check_all(results: &[BatchItemResult], partial: bool) -> RepoResult<()> {
+    let mut failed = results.iter().filter(|r| !r.ok);
+
+    let Some(first) = failed.next().filter(|_| !partial) else {
+        return Ok(());
+    };
+
+    let BatchItemResult { note_id, tag_id, reason, .. } = first;
+
+    let (reason, field, mut description) = match reason.as_str() {
+        NOTE_NOT_FOUND => (NOTE_NOT_FOUND, "note_ids", format!("the note {note_id} does not exist")),
+        TAG_NOT_FOUND => (TAG_NOT_FOUND, "tag_ids", format!("the tag {tag_id} does not exist")),
+        TAG_ALREADY_ATTACHED => (TAG_ALREADY_ATTACHED, "tag_ids", format!("the tag {tag_id} is already attached to the note {note_id}")),
+        // the moves don't have a tag id in their results
+        _ if *tag_id == 0 => (TAG_NOT_ATTACHED, "from_tag_id", format!("the note {note_id} does not have the tag")),
+        _ => (TAG_NOT_ATTACHED, "tag_ids", format!("the tag {tag_id} is not attached to the note {note_id}")),
+    };
+
+    let others = failed.count();
+
+    if others > 0 {
+        description += &format!(", and {others} other items failed too");
+    }
+
+    let violation = Violation { field: Some(field.into()), reason, description };
+
+    Err(match reason {
+        TAG_ALREADY_ATTACHED => ServiceError::AlreadyExists(violation),
+        _ => ServiceError::NotFound(violation),
+    })
+}
@@ -0,0 +1,111 @@
+//! the wiki-style links of the note texts, shared by the repositories.
+//! a link is either `[[Title]]` or `[[#id]]`, optionally followed by a label like `[[Title|label]]`
+
+use std::collections::HashSet;
+use std::ops::Range;
+
+use crate::error::{ServiceError, Violation};
+use crate::validation::{NOTE_TEXT_MAX_LEN, NOTE_TITLE_MAX_LEN};
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Link {
+    /// resolved to the oldest of the user's notes with the title, ignoring the case
+    Title(String),
+    Id(i32),
+}
+
+/// the byte ranges of the targets of the links in the text, without the brackets and the labels
+fn targets(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    let mut offset = 0;
+
+    std::iter::from_fn(move || {
+        loop {
+            let start = offset + text[offset..].find("[[")? + 2;
+            let end = start + text[start..].find("]]")?;
+            offset = end + 2;
+
+            let inner = &text[start..end];
+
+            // a link is on a single line, and the brackets can't be nested
+            if inner.contains(['[', ']', '\n']) {
+                offset = start;
+                continue;
+            }
+
+            let target = inner.split('|').next().unwrap_or_default();
+            let trimmed = target.trim();
+
+            if trimmed.is_empty() {
+                continue;
+            }
+
+            let leading = target.len() - target.trim_start().len();
+            return Some(start + leading..start + leading + trimmed.len());
+        }
+    })
+}
+
+/// the links of the text, without the repeated ones
+pub fn parse(text: &str) -> Vec<Link> {
+    let mut seen = HashSet::new();
+
+    targets(text)
+        .filter_map(|range| {
+            let target = &text[range];
+
+            match target.strip_prefix('#').map(str::parse) {
+                Some(Ok(id)) => Some(Link::Id(id)),
+                _ if target.chars().count() <= NOTE_TITLE_MAX_LEN => Some(Link::Title(target.to_owned())),
+                _ => None,
+            }
+        })
+        .filter(|link| match link {
+            Link::Title(title) => seen.insert(Link::Title(title.to_lowercase())),
+            id => seen.insert(id.clone()),
+        })
+        .collect()
+}
+
+/// whether a title can be written in a link
+pub fn linkable(title: &str) -> bool {
+    !title.trim().is_empty() && !title.contains(['[', ']', '|', '\n'])
+}
+
+/// replaces the `old_title` of the title links with the `new_title`, keeping their labels.
+/// returns None if the text has no such links
+pub fn rename(text: &str, old_title: &str, new_title: &str) -> Option<String> {
+    let old_title = old_title.to_lowercase();
+
+    let ranges: Vec<_> = targets(text)
+        .filter(|range| text[range.clone()].to_lowercase() == old_title)
+        .collect();
+
+    if ranges.is_empty() {
+        return None;
+    }
+
+    let mut renamed = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for range in ranges {
+        renamed += &text[last..range.start];
+        renamed += new_title;
+        last = range.end;
+    }
+
+    renamed += &text[last..];
+    Some(renamed)
+}
+
+/// checks that the text that `rename` rewrote still fits in a note
+pub fn check_renamed(source_id: i32, renamed: &str) -> Result<(), ServiceError> {
+    if renamed.chars().count() <= NOTE_TEXT_MAX_LEN {
+        return Ok(());
+    }
+
+    Err(ServiceError::FailedPrecondition(Violation {
+        field: Some("title".into()),
+        reason: "LINKING_NOTE_TOO_LONG",
+        description: format!("the new title would make the text of the note {source_id}, which links to this one, longer than {NOTE_TEXT_MAX_LEN} characters"),
+    }))
+}
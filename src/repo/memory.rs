@@ -0,0 +1,1164 @@
+//! an in-memory implementation of the repositories, for testing the handlers without a database.
+//! it mirrors the behavior of the postgres implementation, including the errors it returns,
+//! except that every user is assumed to exist
+
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Mutex, MutexGuard};
+
+use chrono::NaiveDateTime;
+use tonic::async_trait;
+
+use crate::error::{ServiceError, Violation};
+use crate::proto::files::{create_file_metadata::AttachId, File};
+use crate::proto::notes::{filter_due, filter_state, sort, BatchItemResult, FilterDate, FilterDue, FilterState, Filters, GraphEdge, Note, NoteGraph, NoteRef, NoteState, Pagination, Sort, Task};
+use crate::proto::reminders::{DueReminder, Reminder};
+use crate::proto::shelves::Shelf;
+use crate::proto::tags::{tag_sort, Tag, TagSort};
+use crate::proto::templates::Template;
+use crate::reminders::LOCAL_TIME_FORMAT;
+
+use super::batch::{self, Lookup};
+use super::links::{self, Link};
+use super::tasks;
+use super::{FileRepo, IdempotencyRecord, IdempotencyRepo, NoteRepo, ReminderRepo, RepoResult, ShelfRepo, TagRepo, TemplateRepo};
+
+#[derive(Default)]
+struct Data {
+    last_id: i32,
+    notes: BTreeMap<i32, Note>,
+    tags: BTreeMap<i32, Tag>,
+    files: BTreeMap<i32, File>,
+    shelves: BTreeMap<i32, Shelf>,
+    // the tag ids of the templates are their template_tags
+    templates: BTreeMap<i32, Template>,
+    reminders: BTreeMap<i32, Reminder>,
+    // (note_id, tag_id)
+    note_tags: BTreeSet<(i32, i32)>,
+    // (note_id, file_id)
+    note_files: BTreeSet<(i32, i32)>,
+    // (shelf_id, file_id)
+    shelf_files: BTreeSet<(i32, i32)>,
+    /// the hashes of the blobs that would have been queued for deletion
+    pending_blob_deletions: Vec<String>,
+    // (user_id, key) -> key
+    idempotency_keys: BTreeMap<(i32, String), IdempotencyKey>,
+    note_links: Vec<NoteLink>,
+}
+
+/// a row of idempotency_keys
+struct IdempotencyKey {
+    record: IdempotencyRecord,
+    token: String,
+    created: i64,
+    reserved_at: i64,
+}
+
+/// a row of note_links, where the links by id have an empty title
+struct NoteLink {
+    source_id: i32,
+    target_id: Option<i32>,
+    target_title: String,
+}
+
+impl Data {
+    /// every table shares the same sequence, which is fine since the ids only have to be unique
+    fn next_id(&mut self) -> i32 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    fn owns_note(&self, id: i32, user_id: i32) -> bool {
+        self.notes.get(&id).is_some_and(|n| n.user_id == user_id)
+    }
+
+    fn owns_tag(&self, id: i32, user_id: i32) -> bool {
+        self.tags.get(&id).is_some_and(|t| t.user_id == user_id)
+    }
+
+    fn template_mut(&mut self, id: i32, user_id: i32) -> RepoResult<&mut Template> {
+        self.templates.get_mut(&id).filter(|t| t.user_id == user_id).ok_or_else(not_found)
+    }
+
+    /// the same check as `set_tags` of the postgres repository, returning the sorted tag ids
+    fn template_tags(&self, user_id: i32, tag_ids: &[i32]) -> RepoResult<Vec<i32>> {
+        let owned = tag_ids.iter().copied().filter(|id| self.owns_tag(*id, user_id)).collect();
+        batch::check_owned_tags("tag_ids", tag_ids, &owned)?;
+
+        let mut tag_ids = tag_ids.to_vec();
+        tag_ids.sort_unstable();
+        Ok(tag_ids)
+    }
+
+    /// the same lookup as the one that the postgres batches make
+    fn lookup(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32]) -> Lookup {
+        Lookup {
+            notes: note_ids.iter().copied().filter(|id| self.owns_note(*id, user_id)).collect(),
+            tags: tag_ids.iter().copied().filter(|id| self.owns_tag(*id, user_id)).collect(),
+            attached: self.note_tags.iter().copied().filter(|(n, t)| note_ids.contains(n) && tag_ids.contains(t)).collect(),
+        }
+    }
+
+    fn usage_count(&self, tag_id: i32) -> i32 {
+        self.note_tags.iter().filter(|(_, t)| *t == tag_id).count() as i32
+    }
+
+    /// the same check as the unique index on the user id and the lowercase name
+    fn check_tag_name(&self, user_id: i32, name: &str, updated_id: Option<i32>) -> RepoResult<()> {
+        let taken = self.tags.values()
+            .any(|t| t.user_id == user_id && Some(t.id) != updated_id && t.name.to_lowercase() == name.to_lowercase());
+
+        match taken {
+            true => Err(ServiceError::AlreadyExists(Violation {
+                field: Some("name".into()),
+                reason: "TAG_ALREADY_EXISTS",
+                description: "the user already has a tag with this name".into(),
+            })),
+            false => Ok(()),
+        }
+    }
+
+    fn note_mut(&mut self, id: i32, user_id: i32) -> RepoResult<&mut Note> {
+        self.notes.get_mut(&id).filter(|n| n.user_id == user_id).ok_or_else(not_found)
+    }
+
+    /// the oldest of the user's notes with the title, ignoring the case
+    fn note_by_title(&self, user_id: i32, title: &str) -> Option<i32> {
+        self.notes.values().find(|n| n.user_id == user_id && n.title.to_lowercase() == title.to_lowercase()).map(|n| n.id)
+    }
+
+    /// the same as `save_links` of the postgres repository
+    fn save_links(&mut self, user_id: i32, note_id: i32, text: &str) {
+        self.note_links.retain(|l| l.source_id != note_id);
+
+        for link in links::parse(text) {
+            let link = match link {
+                Link::Title(title) => NoteLink { source_id: note_id, target_id: self.note_by_title(user_id, &title), target_title: title },
+                Link::Id(id) if self.owns_note(id, user_id) => NoteLink { source_id: note_id, target_id: Some(id), target_title: String::new() },
+                Link::Id(_) => continue,
+            };
+
+            self.note_links.push(link);
+        }
+    }
+
+    fn resolve_links(&mut self, user_id: i32) {
+        let unresolved: Vec<_> = self.note_links.iter()
+            .enumerate()
+            .filter(|(_, l)| l.target_id.is_none() && !l.target_title.is_empty() && self.owns_note(l.source_id, user_id))
+            .map(|(i, l)| (i, self.note_by_title(user_id, &l.target_title)))
+            .collect();
+
+        for (i, target_id) in unresolved {
+            self.note_links[i].target_id = target_id;
+        }
+    }
+
+    /// the texts are all checked before any of them is rewritten, like in the rolled back transaction of the postgres repository
+    fn rename_links(&mut self, note_id: i32, old_title: &str, new_title: &str) -> RepoResult<()> {
+        if old_title == new_title || !links::linkable(new_title) {
+            return Ok(());
+        }
+
+        let renamed = |l: &NoteLink| l.target_id == Some(note_id) && !l.target_title.is_empty() && l.source_id != note_id;
+        let source_ids: BTreeSet<_> = self.note_links.iter().filter(|l| renamed(l)).map(|l| l.source_id).collect();
+
+        let mut texts = Vec::new();
+
+        for source_id in source_ids {
+            if let Some(text) = links::rename(&self.notes[&source_id].text, old_title, new_title) {
+                links::check_renamed(source_id, &text)?;
+                texts.push((source_id, text));
+            }
+        }
+
+        for (source_id, text) in texts {
+            let source = self.notes.get_mut(&source_id).unwrap();
+            source.text = text;
+            source.last_edited = now();
+            source.times_edited += 1;
+        }
+
+        for link in self.note_links.iter_mut().filter(|l| renamed(l)) {
+            link.target_title = new_title.to_owned();
+        }
+
+        Ok(())
+    }
+
+    /// the same as `delete_links` of the postgres repository, followed by the deletion of the note
+    fn delete_note_links(&mut self, note_id: i32) {
+        self.note_links.retain(|l| l.source_id != note_id && !(l.target_title.is_empty() && l.target_id == Some(note_id)));
+
+        for link in self.note_links.iter_mut().filter(|l| l.target_id == Some(note_id)) {
+            link.target_id = None;
+        }
+    }
+
+    fn reminder_mut(&mut self, id: i32, user_id: i32) -> RepoResult<&mut Reminder> {
+        self.reminders.get_mut(&id).filter(|r| r.user_id == user_id).ok_or_else(not_found)
+    }
+
+    /// the same as the due filter of the postgres query
+    fn has_due_reminder(&self, note_id: i32, filter: Option<&FilterDue>) -> bool {
+        let Some(filter) = filter else {
+            return true;
+        };
+
+        let now = now();
+
+        self.reminders.values()
+            .filter(|r| r.note_id == note_id && !r.fired)
+            .any(|r| match filter.due() {
+                filter_due::Due::Upcoming => r.fire_at > now && (filter.until == 0 || r.fire_at <= filter.until),
+                filter_due::Due::Overdue => r.fire_at <= now,
+            })
+    }
+
+    fn shelf_mut(&mut self, user_id: i32) -> RepoResult<&mut Shelf> {
+        self.shelves.values_mut().find(|s| s.user_id == user_id).ok_or_else(not_found)
+    }
+
+    /// deletes the user's files with the `file_ids` and queues their blobs for deletion
+    fn delete_files(&mut self, user_id: i32, file_ids: &[i32]) {
+        for file_id in file_ids {
+            if self.files.get(file_id).is_some_and(|f| f.user_id == user_id) {
+                let file = self.files.remove(file_id).unwrap();
+                self.pending_blob_deletions.push(file.hash);
+            }
+        }
+    }
+
+    /// the same fields as in `UPDATE shelves SET text = ..., last_edited = NOW(), times_edited = times_edited + 1`
+    fn edit_shelf(&mut self, user_id: i32, text: &str) -> RepoResult<Shelf> {
+        let shelf = self.shelf_mut(user_id)?;
+        shelf.text = text.to_owned();
+        shelf.last_edited = now();
+        shelf.times_edited += 1;
+
+        Ok(shelf.clone())
+    }
+
+    /// detaches all of the files from the shelf and returns their ids
+    fn take_shelf_files(&mut self, shelf_id: i32) -> Vec<i32> {
+        let file_ids: Vec<_> = self.shelf_files.iter().filter(|(s, _)| *s == shelf_id).map(|(_, f)| *f).collect();
+        self.shelf_files.retain(|(s, _)| *s != shelf_id);
+        file_ids
+    }
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+fn unix(time: NaiveDateTime) -> i64 {
+    time.and_utc().timestamp()
+}
+
+fn not_found() -> ServiceError {
+    ServiceError::not_found("NOT_FOUND", "not found")
+}
+
+/// the same range as `BETWEEN start AND end + 1` in the postgres query
+fn in_date_range(filter: &Option<FilterDate>, timestamp: i64) -> bool {
+    match filter {
+        None => true,
+        Some(f) => (f.start..=f.end + 1).contains(&timestamp),
+    }
+}
+
+/// the same conditions as the state filter of the postgres query, which leaves out the archived notes by default
+fn has_state(note: &Note, filter: Option<&FilterState>) -> bool {
+    let archived = match filter.map(|f| f.archive()).unwrap_or_default() {
+        filter_state::Archive::Active => !note.archived,
+        filter_state::Archive::Archived => note.archived,
+        filter_state::Archive::All => true,
+    };
+
+    archived
+        && filter.and_then(|f| f.pinned).is_none_or(|pinned| note.pinned == pinned)
+        && filter.and_then(|f| f.favorite).is_none_or(|favorite| note.favorite == favorite)
+}
+
+fn apply_state(note: &mut Note, state: &NoteState) {
+    note.pinned = state.pinned.unwrap_or(note.pinned);
+    note.archived = state.archived.unwrap_or(note.archived);
+    note.favorite = state.favorite.unwrap_or(note.favorite);
+}
+
+/// the error that postgres returns when a subquery of an insert doesn't find the row and NULL gets inserted instead
+fn null_value(field: &str) -> ServiceError {
+    ServiceError::InvalidArgument(Violation { field: Some(field.to_owned()), reason: "MISSING_FIELD", description: "a required value is missing".into() })
+}
+
+#[derive(Default)]
+pub struct MemoryRepo {
+    data: Mutex<Data>,
+}
+
+impl MemoryRepo {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn data(&self) -> MutexGuard<'_, Data> {
+        // a panic while holding the lock can't leave the data half-updated,
+        // since every method checks everything before changing anything
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// the hashes of the blobs that were queued for deletion so far
+    pub fn pending_blob_deletions(&self) -> Vec<String> {
+        self.data().pending_blob_deletions.clone()
+    }
+}
+
+#[async_trait]
+impl NoteRepo for MemoryRepo {
+    async fn create(&self, user_id: i32, title: &str, text: &str) -> RepoResult<Note> {
+        let mut data = self.data();
+        let id = data.next_id();
+
+        let note = Note {
+            id,
+            user_id,
+            title: title.to_owned(),
+            text: text.to_owned(),
+            created: now(),
+            last_edited: now(),
+            times_edited: 0,
+            tags: vec![],
+            files: vec![],
+            pinned: false,
+            archived: false,
+            favorite: false,
+        };
+
+        data.notes.insert(id, note.clone());
+        data.save_links(user_id, id, text);
+        data.resolve_links(user_id);
+
+        Ok(note)
+    }
+
+    async fn list(&self, user_id: i32, pagination: &Pagination, sort: &Sort, filters: &Filters) -> RepoResult<(Vec<Note>, i32)> {
+        let data = self.data();
+
+        // filtering
+
+        let mut notes: Vec<_> = data.notes.values()
+            .filter(|n| n.user_id == user_id)
+            .filter(|n| match &filters.filter_tags {
+                None => true,
+                // an empty list of tags filters the notes without any tags
+                Some(f) if f.tag_ids.is_empty() => !data.note_tags.iter().any(|(note_id, _)| *note_id == n.id),
+                Some(f) => data.note_tags.iter().any(|(note_id, tag_id)| *note_id == n.id && f.tag_ids.contains(tag_id)),
+            })
+            .filter(|n| in_date_range(&filters.filter_date, n.created))
+            .filter(|n| in_date_range(&filters.filter_date_modif, n.last_edited))
+            .filter(|n| match &filters.filter_search {
+                None => true,
+                Some(f) => n.title.to_lowercase().contains(&f.query.to_lowercase()),
+            })
+            .filter(|n| has_state(n, filters.filter_state.as_ref()))
+            .filter(|n| data.has_due_reminder(n.id, filters.filter_due.as_ref()))
+            .cloned()
+            .collect();
+
+        let total_count = notes.len() as i32;
+
+        // ordering
+
+        notes.sort_by(|a, b| {
+            let ordering = match sort.sort_field() {
+                sort::Field::Date => a.created.cmp(&b.created),
+                sort::Field::DateModif => a.last_edited.cmp(&b.last_edited),
+                sort::Field::Title => a.title.cmp(&b.title),
+            };
+
+            let ordering = match sort.sort_type() {
+                sort::Type::Asc => ordering,
+                sort::Type::Desc => ordering.reverse(),
+            };
+
+            let pinned = match sort.pinned_first {
+                true => b.pinned.cmp(&a.pinned),
+                false => Ordering::Equal,
+            };
+
+            pinned.then(ordering).then(b.id.cmp(&a.id))
+        });
+
+        // paginating
+
+        let offset = ((pagination.page - 1) * pagination.per_page).max(0) as usize;
+        let mut notes: Vec<_> = notes.into_iter().skip(offset).take(pagination.per_page.max(0) as usize).collect();
+
+        // attaching the tags and files
+
+        for note in &mut notes {
+            note.tags = data.note_tags.iter()
+                .filter(|(note_id, _)| *note_id == note.id)
+                .filter_map(|(_, tag_id)| data.tags.get(tag_id))
+                .map(|t| Tag { note_id: Some(note.id), ..t.clone() })
+                .collect();
+
+            note.files = data.note_files.iter()
+                .filter(|(note_id, _)| *note_id == note.id)
+                .filter_map(|(_, file_id)| data.files.get(file_id))
+                .map(|f| File { attach_id: Some(note.id), ..f.clone() })
+                .collect();
+        }
+
+        Ok((notes, total_count))
+    }
+
+    async fn update(&self, id: i32, user_id: i32, title: &str, text: &str) -> RepoResult<Note> {
+        let mut data = self.data();
+
+        // the other notes keep linking to the note by its new title
+
+        let old_title = data.note_mut(id, user_id)?.title.clone();
+        data.rename_links(id, &old_title, title)?;
+
+        let note = data.note_mut(id, user_id)?;
+        note.title = title.to_owned();
+        note.text = text.to_owned();
+        note.last_edited = now();
+        note.times_edited += 1;
+
+        let updated_note = note.clone();
+
+        data.save_links(user_id, id, text);
+        data.resolve_links(user_id);
+
+        Ok(updated_note)
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()> {
+        let mut data = self.data();
+        data.note_mut(id, user_id)?;
+
+        data.note_tags.retain(|(note_id, _)| *note_id != id);
+
+        let file_ids: Vec<_> = data.note_files.iter().filter(|(note_id, _)| *note_id == id).map(|(_, f)| *f).collect();
+        data.note_files.retain(|(note_id, _)| *note_id != id);
+        data.delete_files(user_id, &file_ids);
+
+        data.delete_note_links(id);
+        data.reminders.retain(|_, r| r.note_id != id);
+        data.notes.remove(&id);
+        data.resolve_links(user_id);
+
+        Ok(())
+    }
+
+    async fn attach_tag(&self, user_id: i32, note_id: i32, tag_id: i32) -> RepoResult<()> {
+        let mut data = self.data();
+
+        if !data.owns_note(note_id, user_id) {
+            return Err(null_value("note_id"));
+        }
+
+        if !data.owns_tag(tag_id, user_id) {
+            return Err(null_value("tag_id"));
+        }
+
+        if !data.note_tags.insert((note_id, tag_id)) {
+            return Err(ServiceError::AlreadyExists(Violation {
+                field: Some("tag_id".into()),
+                reason: "TAG_ALREADY_ATTACHED",
+                description: "the tag is already attached to the note".into(),
+            }));
+        }
+
+        Ok(())
+    }
+
+    async fn detach_tag(&self, user_id: i32, note_id: i32, tag_id: i32) -> RepoResult<()> {
+        let mut data = self.data();
+
+        let owned = data.owns_note(note_id, user_id) && data.owns_tag(tag_id, user_id);
+
+        match owned && data.note_tags.remove(&(note_id, tag_id)) {
+            true => Ok(()),
+            false => Err(not_found()),
+        }
+    }
+
+    async fn batch_attach_tags(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut data = self.data();
+
+        let results = batch::tag_pairs(note_ids, tag_ids, &data.lookup(user_id, note_ids, tag_ids), true);
+        batch::check_all(&results, partial)?;
+
+        data.note_tags.extend(batch::succeeded(&results));
+        Ok(results)
+    }
+
+    async fn batch_detach_tags(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut data = self.data();
+
+        let results = batch::tag_pairs(note_ids, tag_ids, &data.lookup(user_id, note_ids, tag_ids), false);
+        batch::check_all(&results, partial)?;
+
+        for pair in batch::succeeded(&results) {
+            data.note_tags.remove(&pair);
+        }
+
+        Ok(results)
+    }
+
+    async fn batch_delete(&self, user_id: i32, note_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut data = self.data();
+
+        let results = batch::found_notes(note_ids, &data.lookup(user_id, note_ids, &[]));
+        batch::check_all(&results, partial)?;
+
+        for (id, _) in batch::succeeded(&results) {
+            data.note_tags.retain(|(note_id, _)| *note_id != id);
+
+            let file_ids: Vec<_> = data.note_files.iter().filter(|(note_id, _)| *note_id == id).map(|(_, f)| *f).collect();
+            data.note_files.retain(|(note_id, _)| *note_id != id);
+            data.delete_files(user_id, &file_ids);
+
+            data.delete_note_links(id);
+            data.reminders.retain(|_, r| r.note_id != id);
+            data.notes.remove(&id);
+        }
+
+        data.resolve_links(user_id);
+
+        Ok(results)
+    }
+
+    async fn batch_move(&self, user_id: i32, note_ids: &[i32], from_tag_id: i32, to_tag_id: i32, partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut data = self.data();
+
+        let lookup = data.lookup(user_id, note_ids, &[from_tag_id, to_tag_id]);
+        batch::check_move_tags(from_tag_id, to_tag_id, &lookup)?;
+
+        let results = batch::moved_notes(note_ids, from_tag_id, &lookup);
+        batch::check_all(&results, partial)?;
+
+        for (note_id, _) in batch::succeeded(&results) {
+            data.note_tags.remove(&(note_id, from_tag_id));
+            data.note_tags.insert((note_id, to_tag_id));
+        }
+
+        Ok(results)
+    }
+
+    async fn set_state(&self, id: i32, user_id: i32, state: &NoteState) -> RepoResult<Note> {
+        let mut data = self.data();
+
+        let note = data.note_mut(id, user_id)?;
+        apply_state(note, state);
+
+        Ok(note.clone())
+    }
+
+    async fn batch_set_state(&self, user_id: i32, note_ids: &[i32], state: &NoteState, partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut data = self.data();
+
+        let results = batch::found_notes(note_ids, &data.lookup(user_id, note_ids, &[]));
+        batch::check_all(&results, partial)?;
+
+        for (id, _) in batch::succeeded(&results) {
+            apply_state(data.note_mut(id, user_id)?, state);
+        }
+
+        Ok(results)
+    }
+
+    async fn backlinks(&self, user_id: i32, note_id: i32) -> RepoResult<Vec<NoteRef>> {
+        let data = self.data();
+
+        if !data.owns_note(note_id, user_id) {
+            return Err(not_found());
+        }
+
+        let source_ids: BTreeSet<_> = data.note_links.iter().filter(|l| l.target_id == Some(note_id)).map(|l| l.source_id).collect();
+
+        Ok(source_ids.into_iter().map(|id| NoteRef { id, title: data.notes[&id].title.clone() }).collect())
+    }
+
+    async fn graph(&self, user_id: i32, include_archived: bool) -> RepoResult<NoteGraph> {
+        let data = self.data();
+
+        let included = |id: i32| data.notes.get(&id).is_some_and(|n| n.user_id == user_id && (include_archived || !n.archived));
+
+        let nodes = data.notes.values()
+            .filter(|n| included(n.id))
+            .map(|n| NoteRef { id: n.id, title: n.title.clone() })
+            .collect();
+
+        let edges: BTreeSet<_> = data.note_links.iter()
+            .filter_map(|l| Some((l.source_id, l.target_id?)))
+            .filter(|(source_id, target_id)| included(*source_id) && included(*target_id))
+            .collect();
+
+        Ok(NoteGraph {
+            nodes,
+            edges: edges.into_iter().map(|(source_id, target_id)| GraphEdge { source_id, target_id }).collect(),
+        })
+    }
+
+    /// the tasks are parsed from the texts when they're read, instead of being kept in a note_tasks table
+    async fn tasks(&self, user_id: i32, pagination: &Pagination, done: Option<bool>, tag_id: Option<i32>, include_archived: bool) -> RepoResult<(Vec<Task>, i32)> {
+        let data = self.data();
+
+        let tasks = data.notes.values()
+            .filter(|n| n.user_id == user_id && (include_archived || !n.archived))
+            .filter(|n| tag_id.is_none_or(|tag_id| data.note_tags.contains(&(n.id, tag_id))))
+            .flat_map(|n| tasks::parse(&n.text).into_iter().enumerate().map(move |(position, task)| Task {
+                note_id: n.id,
+                position: position as i32,
+                text: task.text,
+                done: task.done,
+                note_title: n.title.clone(),
+                note_times_edited: n.times_edited,
+            }))
+            .filter(|t| done.is_none_or(|done| t.done == done));
+
+        let tasks: Vec<_> = tasks.collect();
+        let total_count = tasks.len() as i32;
+
+        let offset = ((pagination.page - 1) * pagination.per_page).max(0) as usize;
+        let tasks = tasks.into_iter().skip(offset).take(pagination.per_page.max(0) as usize).collect();
+
+        Ok((tasks, total_count))
+    }
+
+    async fn toggle_task(&self, user_id: i32, note_id: i32, position: i32, times_edited: i32) -> RepoResult<Note> {
+        let mut data = self.data();
+
+        let note = data.note_mut(note_id, user_id)?;
+
+        if note.times_edited != times_edited {
+            return Err(tasks::note_edited());
+        }
+
+        note.text = tasks::toggle(&note.text, position as usize).ok_or_else(tasks::task_not_found)?;
+        note.last_edited = now();
+        note.times_edited += 1;
+
+        Ok(note.clone())
+    }
+}
+
+#[async_trait]
+impl TagRepo for MemoryRepo {
+    async fn create(&self, user_id: i32, name: &str, color: Option<&str>, icon: Option<&str>, description: &str) -> RepoResult<Tag> {
+        let mut data = self.data();
+        data.check_tag_name(user_id, name, None)?;
+
+        let id = data.next_id();
+        let position = data.tags.values().filter(|t| t.user_id == user_id).map(|t| t.position + 1).max().unwrap_or(0);
+
+        let tag = Tag {
+            id,
+            user_id,
+            name: name.to_owned(),
+            created: now(),
+            note_id: None,
+            usage_count: 0,
+            color: color.map(str::to_owned),
+            icon: icon.map(str::to_owned),
+            description: description.to_owned(),
+            position,
+        };
+
+        data.tags.insert(id, tag.clone());
+        Ok(tag)
+    }
+
+    async fn list(&self, user_id: i32, sort: &TagSort) -> RepoResult<Vec<Tag>> {
+        let data = self.data();
+
+        let mut tags: Vec<_> = data.tags.values()
+            .filter(|t| t.user_id == user_id)
+            .map(|t| Tag { usage_count: data.usage_count(t.id), ..t.clone() })
+            .collect();
+
+        tags.sort_by(|a, b| {
+            let ordering = match sort.sort_field() {
+                tag_sort::Field::Id => Ordering::Equal,
+                tag_sort::Field::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+                tag_sort::Field::Usage => a.usage_count.cmp(&b.usage_count),
+                tag_sort::Field::Created => a.created.cmp(&b.created),
+                tag_sort::Field::Position => a.position.cmp(&b.position),
+            }.then(a.id.cmp(&b.id));
+
+            match sort.sort_type() {
+                tag_sort::Type::Asc => ordering,
+                tag_sort::Type::Desc => ordering.reverse(),
+            }
+        });
+
+        Ok(tags)
+    }
+
+    async fn update(&self, id: i32, user_id: i32, name: &str, color: Option<&str>, icon: Option<&str>, description: Option<&str>) -> RepoResult<Tag> {
+        let mut data = self.data();
+        data.check_tag_name(user_id, name, Some(id))?;
+
+        let tag = data.tags.get_mut(&id).filter(|t| t.user_id == user_id).ok_or_else(not_found)?;
+        tag.name = name.to_owned();
+
+        if let Some(color) = color {
+            tag.color = Some(color.to_owned()).filter(|c| !c.is_empty());
+        }
+
+        if let Some(icon) = icon {
+            tag.icon = Some(icon.to_owned()).filter(|i| !i.is_empty());
+        }
+
+        if let Some(description) = description {
+            tag.description = description.to_owned();
+        }
+
+        Ok(tag.clone())
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()> {
+        let mut data = self.data();
+
+        if !data.owns_tag(id, user_id) {
+            return Err(not_found());
+        }
+
+        data.note_tags.retain(|(_, tag_id)| *tag_id != id);
+
+        for template in data.templates.values_mut() {
+            template.tag_ids.retain(|tag_id| *tag_id != id);
+        }
+
+        data.tags.remove(&id);
+        Ok(())
+    }
+
+    async fn merge(&self, user_id: i32, source_ids: &[i32], target_id: i32) -> RepoResult<Tag> {
+        let mut data = self.data();
+
+        let owned = source_ids.iter().chain([&target_id]).copied().filter(|id| data.owns_tag(*id, user_id)).collect();
+        batch::check_merge_tags(source_ids, target_id, &owned)?;
+
+        let merged: Vec<_> = data.note_tags.iter().filter(|(_, tag_id)| source_ids.contains(tag_id)).copied().collect();
+
+        for (note_id, tag_id) in merged {
+            data.note_tags.remove(&(note_id, tag_id));
+            data.note_tags.insert((note_id, target_id));
+        }
+
+        for template in data.templates.values_mut() {
+            if template.tag_ids.iter().any(|tag_id| source_ids.contains(tag_id)) {
+                template.tag_ids.retain(|tag_id| !source_ids.contains(tag_id) && *tag_id != target_id);
+                template.tag_ids.push(target_id);
+                template.tag_ids.sort_unstable();
+            }
+        }
+
+        for id in source_ids {
+            data.tags.remove(id);
+        }
+
+        Ok(Tag { usage_count: data.usage_count(target_id), ..data.tags[&target_id].clone() })
+    }
+
+    async fn reorder(&self, user_id: i32, tag_ids: &[i32]) -> RepoResult<Vec<Tag>> {
+        {
+            let mut data = self.data();
+
+            let mut current: Vec<_> = data.tags.values().filter(|t| t.user_id == user_id).map(|t| (t.position, t.id)).collect();
+            current.sort();
+
+            batch::check_owned_tags("tag_ids", tag_ids, &current.iter().map(|(_, id)| *id).collect())?;
+
+            let rest = current.into_iter().map(|(_, id)| id).filter(|id| !tag_ids.contains(id));
+
+            for (position, id) in tag_ids.iter().copied().chain(rest).enumerate() {
+                data.tags.get_mut(&id).unwrap().position = position as i32;
+            }
+        }
+
+        TagRepo::list(self, user_id, &TagSort { sort_field: tag_sort::Field::Position.into(), sort_type: tag_sort::Type::Asc.into() }).await
+    }
+}
+
+#[async_trait]
+impl FileRepo for MemoryRepo {
+    async fn check_attach_target(&self, user_id: i32, attach_id: &AttachId) -> RepoResult<()> {
+        let data = self.data();
+
+        let exists = match *attach_id {
+            AttachId::NoteId(note_id) => data.owns_note(note_id, user_id),
+            AttachId::ShelfId(shelf_id) => data.shelves.get(&shelf_id).is_some_and(|s| s.user_id == user_id),
+        };
+
+        match exists {
+            true => Ok(()),
+            false => Err(not_found()),
+        }
+    }
+
+    async fn create(&self, user_id: i32, hash: &str, name: &str, size: i64, attach_id: AttachId) -> RepoResult<File> {
+        let mut data = self.data();
+
+        if data.files.values().any(|f| f.hash == hash) {
+            return Err(ServiceError::AlreadyExists(Violation {
+                field: Some("hash".into()),
+                reason: "FILE_ALREADY_EXISTS",
+                description: "a file with this hash already exists".into(),
+            }));
+        }
+
+        // the same checks as the foreign keys of note_files and shelf_files
+        let (field, reason, description, attach_id_val, exists) = match attach_id {
+            AttachId::NoteId(note_id) => ("note_id", "NOTE_NOT_FOUND", "the note does not exist", note_id, data.notes.contains_key(&note_id)),
+            AttachId::ShelfId(shelf_id) => ("shelf_id", "SHELF_NOT_FOUND", "the shelf does not exist", shelf_id, data.shelves.contains_key(&shelf_id)),
+        };
+
+        if !exists {
+            return Err(ServiceError::FailedPrecondition(Violation { field: Some(field.into()), reason, description: description.into() }));
+        }
+
+        let id = data.next_id();
+
+        let file = File {
+            id,
+            user_id,
+            hash: hash.to_owned(),
+            name: name.to_owned(),
+            size,
+            created: now(),
+            attach_id: None,
+        };
+
+        data.files.insert(id, file.clone());
+
+        match attach_id {
+            AttachId::NoteId(note_id) => data.note_files.insert((note_id, id)),
+            AttachId::ShelfId(shelf_id) => data.shelf_files.insert((shelf_id, id)),
+        };
+
+        Ok(File { attach_id: Some(attach_id_val), ..file })
+    }
+
+    async fn find_by_hash(&self, user_id: i32, hash: &str) -> RepoResult<File> {
+        self.data().files.values()
+            .find(|f| f.hash == hash && f.user_id == user_id)
+            .cloned()
+            .ok_or_else(not_found)
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<File> {
+        let mut data = self.data();
+
+        let file = data.files.get(&id).filter(|f| f.user_id == user_id).cloned().ok_or_else(not_found)?;
+
+        data.note_files.retain(|(_, file_id)| *file_id != id);
+        data.shelf_files.retain(|(_, file_id)| *file_id != id);
+        data.delete_files(user_id, &[id]);
+
+        Ok(file)
+    }
+}
+
+#[async_trait]
+impl TemplateRepo for MemoryRepo {
+    async fn create(&self, user_id: i32, name: &str, title: &str, text: &str, tag_ids: &[i32]) -> RepoResult<Template> {
+        let mut data = self.data();
+        let tag_ids = data.template_tags(user_id, tag_ids)?;
+        let id = data.next_id();
+
+        let template = Template {
+            id,
+            user_id,
+            name: name.to_owned(),
+            title: title.to_owned(),
+            text: text.to_owned(),
+            tag_ids,
+            created: now(),
+            last_edited: now(),
+        };
+
+        data.templates.insert(id, template.clone());
+        Ok(template)
+    }
+
+    async fn list(&self, user_id: i32) -> RepoResult<Vec<Template>> {
+        let mut templates: Vec<_> = self.data().templates.values().filter(|t| t.user_id == user_id).cloned().collect();
+        templates.sort_by_key(|t| (t.name.to_lowercase(), t.id));
+        Ok(templates)
+    }
+
+    async fn get(&self, id: i32, user_id: i32) -> RepoResult<Template> {
+        Ok(self.data().template_mut(id, user_id)?.clone())
+    }
+
+    async fn update(&self, id: i32, user_id: i32, name: &str, title: &str, text: &str, tag_ids: &[i32]) -> RepoResult<Template> {
+        let mut data = self.data();
+        data.template_mut(id, user_id)?;
+        let tag_ids = data.template_tags(user_id, tag_ids)?;
+
+        let template = data.template_mut(id, user_id)?;
+        template.name = name.to_owned();
+        template.title = title.to_owned();
+        template.text = text.to_owned();
+        template.tag_ids = tag_ids;
+        template.last_edited = now();
+
+        Ok(template.clone())
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()> {
+        let mut data = self.data();
+        data.template_mut(id, user_id)?;
+        data.templates.remove(&id);
+        Ok(())
+    }
+
+    async fn create_note(&self, id: i32, user_id: i32, title: &str, text: &str) -> RepoResult<Note> {
+        let tag_ids = self.data().template_mut(id, user_id)?.tag_ids.clone();
+        let mut note = NoteRepo::create(self, user_id, title, text).await?;
+
+        let mut data = self.data();
+        data.note_tags.extend(tag_ids.iter().map(|tag_id| (note.id, *tag_id)));
+        note.tags = tag_ids.iter().map(|tag_id| Tag { note_id: Some(note.id), ..data.tags[tag_id].clone() }).collect();
+
+        Ok(note)
+    }
+}
+
+#[async_trait]
+impl ReminderRepo for MemoryRepo {
+    async fn create(&self, user_id: i32, note_id: i32, local_time: NaiveDateTime, timezone: &str, recurrence: &str, fire_at: NaiveDateTime) -> RepoResult<Reminder> {
+        let mut data = self.data();
+        data.note_mut(note_id, user_id)?;
+        let id = data.next_id();
+
+        let reminder = Reminder {
+            id,
+            user_id,
+            note_id,
+            local_time: local_time.format(LOCAL_TIME_FORMAT).to_string(),
+            timezone: timezone.to_owned(),
+            recurrence: recurrence.to_owned(),
+            fire_at: unix(fire_at),
+            fired: false,
+            created: now(),
+            last_edited: now(),
+        };
+
+        data.reminders.insert(id, reminder.clone());
+        Ok(reminder)
+    }
+
+    async fn list(&self, user_id: i32, note_id: Option<i32>) -> RepoResult<Vec<Reminder>> {
+        let mut data = self.data();
+
+        if let Some(note_id) = note_id {
+            data.note_mut(note_id, user_id)?;
+        }
+
+        let mut reminders: Vec<_> = data.reminders.values()
+            .filter(|r| r.user_id == user_id && note_id.is_none_or(|id| r.note_id == id))
+            .cloned()
+            .collect();
+
+        reminders.sort_by_key(|r| (r.fire_at, r.id));
+        Ok(reminders)
+    }
+
+    async fn update(&self, id: i32, user_id: i32, local_time: NaiveDateTime, timezone: &str, recurrence: &str, fire_at: NaiveDateTime) -> RepoResult<Reminder> {
+        let mut data = self.data();
+
+        let reminder = data.reminder_mut(id, user_id)?;
+        reminder.local_time = local_time.format(LOCAL_TIME_FORMAT).to_string();
+        reminder.timezone = timezone.to_owned();
+        reminder.recurrence = recurrence.to_owned();
+        reminder.fire_at = unix(fire_at);
+        reminder.fired = false;
+        reminder.last_edited = now();
+
+        Ok(reminder.clone())
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()> {
+        let mut data = self.data();
+        data.reminder_mut(id, user_id)?;
+        data.reminders.remove(&id);
+        Ok(())
+    }
+
+    async fn due(&self, now: NaiveDateTime, limit: i64) -> RepoResult<Vec<DueReminder>> {
+        let data = self.data();
+
+        let mut due: Vec<_> = data.reminders.values()
+            .filter(|r| !r.fired && r.fire_at <= unix(now))
+            .map(|r| DueReminder { reminder: Some(r.clone()), note_title: data.notes[&r.note_id].title.clone(), due_at: r.fire_at })
+            .collect();
+
+        due.sort_by_key(|d| (d.due_at, d.reminder.as_ref().map(|r| r.id)));
+        due.truncate(limit.max(0) as usize);
+        Ok(due)
+    }
+
+    async fn reschedule(&self, id: i32, due_at: NaiveDateTime, next: Option<NaiveDateTime>) -> RepoResult<bool> {
+        let mut data = self.data();
+
+        let Some(reminder) = data.reminders.get_mut(&id).filter(|r| !r.fired && r.fire_at == unix(due_at)) else {
+            return Ok(false);
+        };
+
+        match next {
+            Some(next) => reminder.fire_at = unix(next),
+            None => reminder.fired = true,
+        }
+
+        Ok(true)
+    }
+}
+
+#[async_trait]
+impl ShelfRepo for MemoryRepo {
+    async fn get_or_create(&self, user_id: i32) -> RepoResult<Shelf> {
+        let mut data = self.data();
+
+        let mut shelf = match data.shelf_mut(user_id) {
+            Ok(shelf) => shelf.clone(),
+            Err(_) => {
+                let id = data.next_id();
+
+                let shelf = Shelf {
+                    id,
+                    user_id,
+                    text: String::new(),
+                    created: now(),
+                    last_edited: now(),
+                    times_edited: 0,
+                    files: vec![],
+                };
+
+                data.shelves.insert(id, shelf.clone());
+                shelf
+            },
+        };
+
+        shelf.files = data.shelf_files.iter()
+            .filter(|(shelf_id, _)| *shelf_id == shelf.id)
+            .filter_map(|(_, file_id)| data.files.get(file_id))
+            .map(|f| File { attach_id: Some(shelf.id), ..f.clone() })
+            .collect();
+
+        Ok(shelf)
+    }
+
+    async fn update_text(&self, user_id: i32, text: &str) -> RepoResult<Shelf> {
+        self.data().edit_shelf(user_id, text)
+    }
+
+    async fn clear(&self, user_id: i32) -> RepoResult<Shelf> {
+        let mut data = self.data();
+
+        let shelf = data.edit_shelf(user_id, "")?;
+        let file_ids = data.take_shelf_files(shelf.id);
+        data.delete_files(user_id, &file_ids);
+
+        Ok(shelf)
+    }
+
+    async fn convert_to_note(&self, user_id: i32, title: &str, text: &str) -> RepoResult<Shelf> {
+        let mut data = self.data();
+
+        let shelf = data.edit_shelf(user_id, "")?;
+        let file_ids = data.take_shelf_files(shelf.id);
+
+        let note_id = data.next_id();
+
+        data.notes.insert(note_id, Note {
+            id: note_id,
+            user_id,
+            title: title.to_owned(),
+            text: text.to_owned(),
+            created: now(),
+            last_edited: now(),
+            times_edited: 0,
+            tags: vec![],
+            files: vec![],
+            pinned: false,
+            archived: false,
+            favorite: false,
+        });
+
+        data.save_links(user_id, note_id, text);
+        data.resolve_links(user_id);
+
+        data.note_files.extend(file_ids.into_iter().map(|file_id| (note_id, file_id)));
+
+        Ok(shelf)
+    }
+}
+
+#[async_trait]
+impl IdempotencyRepo for MemoryRepo {
+    async fn reserve(&self, user_id: i32, key: &str, request_hash: &str, token: &str, lease: u64, ttl: u64) -> RepoResult<Option<IdempotencyRecord>> {
+        let mut data = self.data();
+        let expired_before = now() - ttl as i64;
+        let lease_ended_before = now() - lease as i64;
+
+        match data.idempotency_keys.get(&(user_id, key.to_owned())) {
+            Some(held) if held.created >= expired_before && (held.record.response.is_some() || held.reserved_at >= lease_ended_before) => {
+                Ok(Some(held.record.clone()))
+            },
+            _ => {
+                let record = IdempotencyRecord { request_hash: request_hash.to_owned(), response: None };
+                let held = IdempotencyKey { record, token: token.to_owned(), created: now(), reserved_at: now() };
+                data.idempotency_keys.insert((user_id, key.to_owned()), held);
+                Ok(None)
+            },
+        }
+    }
+
+    async fn renew(&self, user_id: i32, key: &str, token: &str) -> RepoResult<()> {
+        if let Some(held) = self.data().idempotency_keys.get_mut(&(user_id, key.to_owned())) {
+            if held.token == token && held.record.response.is_none() {
+                held.reserved_at = now();
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn complete(&self, user_id: i32, key: &str, token: &str, response: &[u8]) -> RepoResult<()> {
+        if let Some(held) = self.data().idempotency_keys.get_mut(&(user_id, key.to_owned())) {
+            if held.token == token && held.record.response.is_none() {
+                held.record.response = Some(response.to_vec());
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn release(&self, user_id: i32, key: &str, token: &str) -> RepoResult<()> {
+        let mut data = self.data();
+        let id = (user_id, key.to_owned());
+
+        if data.idempotency_keys.get(&id).is_some_and(|held| held.token == token && held.record.response.is_none()) {
+            data.idempotency_keys.remove(&id);
+        }
+
+        Ok(())
+    }
+
+    async fn purge_expired(&self, ttl: u64) -> RepoResult<u64> {
+        let mut data = self.data();
+        let expired_before = now() - ttl as i64;
+
+        let count = data.idempotency_keys.len();
+        data.idempotency_keys.retain(|_, held| held.created >= expired_before);
+
+        Ok((count - data.idempotency_keys.len()) as u64)
+    }
+}
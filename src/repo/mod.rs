@@ -0,0 +1,193 @@
+//! storage of the notes and their tasks, tags, files, shelves, templates, reminders and idempotency keys, separated from the grpc handlers.
+//! the handlers only talk to these traits, so the same logic works on top of postgres
+//! and on top of the in-memory implementation
+
+use chrono::NaiveDateTime;
+use tonic::async_trait;
+
+use crate::error::ServiceError;
+use crate::proto::{files::{create_file_metadata::AttachId, File}, notes::{BatchItemResult, Filters, Note, NoteGraph, NoteRef, NoteState, Pagination, Sort, Task}, reminders::{DueReminder, Reminder}, shelves::Shelf, tags::{Tag, TagSort}, templates::Template};
+
+pub mod batch;
+pub mod links;
+#[cfg(test)]
+pub mod memory;
+pub mod query;
+pub mod sql;
+pub mod tasks;
+
+pub type RepoResult<T> = Result<T, ServiceError>;
+
+#[async_trait]
+pub trait NoteRepo: Send + Sync {
+    async fn create(&self, user_id: i32, title: &str, text: &str) -> RepoResult<Note>;
+
+    /// returns a page of the user's notes with their tags and files, and the total count of the matching notes
+    async fn list(&self, user_id: i32, pagination: &Pagination, sort: &Sort, filters: &Filters) -> RepoResult<(Vec<Note>, i32)>;
+
+    async fn update(&self, id: i32, user_id: i32, title: &str, text: &str) -> RepoResult<Note>;
+
+    /// deletes the note along with its files and reminders, and queues the files' blobs for deletion
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()>;
+
+    /// both the note and the tag must belong to the user
+    async fn attach_tag(&self, user_id: i32, note_id: i32, tag_id: i32) -> RepoResult<()>;
+
+    async fn detach_tag(&self, user_id: i32, note_id: i32, tag_id: i32) -> RepoResult<()>;
+
+    // the batches run in a single transaction, and return a result for each item.
+    // unless they're `partial`, they fail as a whole when any of the items fails
+
+    /// attaches every tag to every note
+    async fn batch_attach_tags(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>>;
+
+    /// detaches every tag from every note
+    async fn batch_detach_tags(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>>;
+
+    /// deletes the notes the same way as `delete`
+    async fn batch_delete(&self, user_id: i32, note_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>>;
+
+    /// replaces the `from_tag_id` of the notes with the `to_tag_id`
+    async fn batch_move(&self, user_id: i32, note_ids: &[i32], from_tag_id: i32, to_tag_id: i32, partial: bool) -> RepoResult<Vec<BatchItemResult>>;
+
+    /// sets the flags of the `state` that are set. it doesn't count as an edit of the note
+    async fn set_state(&self, id: i32, user_id: i32, state: &NoteState) -> RepoResult<Note>;
+
+    /// sets the flags of the notes the same way as `set_state`
+    async fn batch_set_state(&self, user_id: i32, note_ids: &[i32], state: &NoteState, partial: bool) -> RepoResult<Vec<BatchItemResult>>;
+
+    /// the notes that link to the note, which must belong to the user
+    async fn backlinks(&self, user_id: i32, note_id: i32) -> RepoResult<Vec<NoteRef>>;
+
+    /// the user's notes and the resolved links between them
+    async fn graph(&self, user_id: i32, include_archived: bool) -> RepoResult<NoteGraph>;
+
+    /// a page of the tasks of the user's notes, sorted by the note and the position, and the total amount of them
+    async fn tasks(&self, user_id: i32, pagination: &Pagination, done: Option<bool>, tag_id: Option<i32>, include_archived: bool) -> RepoResult<(Vec<Task>, i32)>;
+
+    /// flips the checkbox of the task in the note text as an edit of the note,
+    /// if the note's times_edited is still the `times_edited` that the caller has read
+    async fn toggle_task(&self, user_id: i32, note_id: i32, position: i32, times_edited: i32) -> RepoResult<Note>;
+}
+
+#[async_trait]
+pub trait TagRepo: Send + Sync {
+    /// puts the tag after the user's other tags in the manual order
+    async fn create(&self, user_id: i32, name: &str, color: Option<&str>, icon: Option<&str>, description: &str) -> RepoResult<Tag>;
+
+    /// returns the user's tags with their usage counts
+    async fn list(&self, user_id: i32, sort: &TagSort) -> RepoResult<Vec<Tag>>;
+
+    /// the `None`s keep the current values, and an empty color or icon removes it
+    async fn update(&self, id: i32, user_id: i32, name: &str, color: Option<&str>, icon: Option<&str>, description: Option<&str>) -> RepoResult<Tag>;
+
+    /// detaches the tag from all of its notes and deletes it
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()>;
+
+    /// attaches the target tag to the notes of the source tags, deletes the source tags,
+    /// and returns the target tag with its usage count
+    async fn merge(&self, user_id: i32, source_ids: &[i32], target_id: i32) -> RepoResult<Tag>;
+
+    /// moves the tags to the start of the manual order, keeping the order of the rest,
+    /// and returns all of the user's tags in the new order
+    async fn reorder(&self, user_id: i32, tag_ids: &[i32]) -> RepoResult<Vec<Tag>>;
+}
+
+#[async_trait]
+pub trait FileRepo: Send + Sync {
+    /// makes sure that the note or the shelf exists and belongs to the user
+    async fn check_attach_target(&self, user_id: i32, attach_id: &AttachId) -> RepoResult<()>;
+
+    /// saves the info of an already stored blob and attaches it to the note or the shelf
+    async fn create(&self, user_id: i32, hash: &str, name: &str, size: i64, attach_id: AttachId) -> RepoResult<File>;
+
+    async fn find_by_hash(&self, user_id: i32, hash: &str) -> RepoResult<File>;
+
+    /// detaches and deletes the file, and queues its blob for deletion
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<File>;
+}
+
+#[async_trait]
+pub trait ShelfRepo: Send + Sync {
+    /// returns the user's shelf with its files, creating an empty one if the user doesn't have it yet
+    async fn get_or_create(&self, user_id: i32) -> RepoResult<Shelf>;
+
+    async fn update_text(&self, user_id: i32, text: &str) -> RepoResult<Shelf>;
+
+    /// empties the text, deletes the files and queues their blobs for deletion
+    async fn clear(&self, user_id: i32) -> RepoResult<Shelf>;
+
+    /// creates a note with the `title` and `text` that takes over the shelf's files, and empties the shelf
+    async fn convert_to_note(&self, user_id: i32, title: &str, text: &str) -> RepoResult<Shelf>;
+}
+
+#[async_trait]
+pub trait TemplateRepo: Send + Sync {
+    /// the tags must belong to the user
+    async fn create(&self, user_id: i32, name: &str, title: &str, text: &str, tag_ids: &[i32]) -> RepoResult<Template>;
+
+    /// returns the user's templates sorted by name
+    async fn list(&self, user_id: i32) -> RepoResult<Vec<Template>>;
+
+    async fn get(&self, id: i32, user_id: i32) -> RepoResult<Template>;
+
+    /// replaces all of the template's fields, including the tags
+    async fn update(&self, id: i32, user_id: i32, name: &str, title: &str, text: &str, tag_ids: &[i32]) -> RepoResult<Template>;
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()>;
+
+    /// creates a note with the already substituted `title` and `text`, and attaches the template's tags to it
+    async fn create_note(&self, id: i32, user_id: i32, title: &str, text: &str) -> RepoResult<Note>;
+}
+
+// the times of the reminders are in utc, and the handlers compute the fire times from their schedules
+
+#[async_trait]
+pub trait ReminderRepo: Send + Sync {
+    /// the note must belong to the user
+    async fn create(&self, user_id: i32, note_id: i32, local_time: NaiveDateTime, timezone: &str, recurrence: &str, fire_at: NaiveDateTime) -> RepoResult<Reminder>;
+
+    /// returns the user's reminders, or only the note's if `note_id` is set, sorted by their fire times
+    async fn list(&self, user_id: i32, note_id: Option<i32>) -> RepoResult<Vec<Reminder>>;
+
+    /// replaces the time and the recurrence, and schedules a fired reminder again
+    async fn update(&self, id: i32, user_id: i32, local_time: NaiveDateTime, timezone: &str, recurrence: &str, fire_at: NaiveDateTime) -> RepoResult<Reminder>;
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()>;
+
+    /// up to `limit` of the reminders of every user that haven't fired yet and are due at `now`, the oldest first
+    async fn due(&self, now: NaiveDateTime, limit: i64) -> RepoResult<Vec<DueReminder>>;
+
+    /// moves the reminder that was due at `due_at` to its `next` occurrence, or marks it as fired if there is none.
+    /// returns false if it was already moved, by another instance of the service for example
+    async fn reschedule(&self, id: i32, due_at: NaiveDateTime, next: Option<NaiveDateTime>) -> RepoResult<bool>;
+}
+
+/// the record of a call that was made with an idempotency key
+#[derive(Clone, Debug, sqlx::FromRow)]
+pub struct IdempotencyRecord {
+    pub request_hash: String,
+    /// the encoded response message. None while the call is still in progress
+    pub response: Option<Vec<u8>>,
+}
+
+#[async_trait]
+pub trait IdempotencyRepo: Send + Sync {
+    /// claims the user's `key` for a call with the `request_hash` under the reservation `token`. returns None if
+    /// the key was claimed, or the existing record if the key was already used in the last `ttl` seconds. a call
+    /// that is still in progress holds the key only for `lease` seconds after it was reserved or last renewed,
+    /// so that the key of a crashed call gets freed
+    async fn reserve(&self, user_id: i32, key: &str, request_hash: &str, token: &str, lease: u64, ttl: u64) -> RepoResult<Option<IdempotencyRecord>>;
+
+    /// starts the lease of the call that holds the key under the `token` over
+    async fn renew(&self, user_id: i32, key: &str, token: &str) -> RepoResult<()>;
+
+    /// stores the response of the call that holds the key under the `token`
+    async fn complete(&self, user_id: i32, key: &str, token: &str, response: &[u8]) -> RepoResult<()>;
+
+    /// frees the key of a call that failed and still holds it under the `token`, so that it can be retried with the same key
+    async fn release(&self, user_id: i32, key: &str, token: &str) -> RepoResult<()>;
+
+    /// deletes the records that are older than `ttl` seconds, and returns their amount
+    async fn purge_expired(&self, ttl: u64) -> RepoResult<u64>;
+}
@@ -0,0 +1,434 @@
+use sqlx::{database::HasArguments, query::QueryAs, Database, Encode, FromRow, Postgres, Type};
+
+use crate::{proto::{files::File, notes::{filter_due, filter_state, sort, Filters, Note, Pagination, Sort}, tags::{tag_sort, Tag, TagSort}}, types::{fill_tuple_placeholder, BindIter, CountWrapper}};
+
+// creating constants for these strings so that i can have them type-checked
+pub struct SortField;
+impl SortField {
+    pub const CREATED: &'static str = "created";
+    pub const LAST_EDITED: &'static str = "last_edited";
+    pub const TITLE: &'static str = "title";
+}
+
+pub struct SortType;
+impl SortType {
+    pub const ASC: &'static str = "ASC";
+    pub const DESC: &'static str = "DESC";
+}
+
+/// the parts of the queries that differ between the databases.
+/// sqlx's sqlite driver accepts the same "$n" placeholders as postgres, so those are shared
+#[derive(Clone, Copy)]
+pub enum Dialect {
+    Postgres,
+    #[cfg(feature = "sqlite")]
+    Sqlite,
+}
+
+impl Dialect {
+    /// the unix timestamp of a TIMESTAMP column
+    fn epoch(self, column: &str) -> String {
+        match self {
+            Self::Postgres => format!("EXTRACT(EPOCH FROM {column})"),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => format!("unixepoch({column})"),
+        }
+    }
+
+    /// case-insensitive pattern matching. sqlite's LIKE only ignores the case of the ascii letters
+    fn ilike(self) -> &'static str {
+        match self {
+            Self::Postgres => "ILIKE",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => "LIKE",
+        }
+    }
+
+    /// the current time
+    pub fn now(self) -> &'static str {
+        match self {
+            Self::Postgres => "NOW()",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => "CURRENT_TIMESTAMP",
+        }
+    }
+
+    /// the time that was the given amount of seconds ago
+    pub fn seconds_ago(self, seconds: &str) -> String {
+        match self {
+            Self::Postgres => format!("NOW() - {seconds} * INTERVAL '1 second'"),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => format!("datetime('now', '-' || {seconds} || ' seconds')"),
+        }
+    }
+
+    /// the time that will be in the given amount of seconds
+    pub fn seconds_later(self, seconds: &str) -> String {
+        match self {
+            Self::Postgres => format!("NOW() + {seconds} * INTERVAL '1 second'"),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => format!("datetime('now', '+' || {seconds} || ' seconds')"),
+        }
+    }
+
+    /// locks the selected rows, waiting for the ones that are already locked. sqlite has nothing to lock, like in `skip_locked`
+    pub fn for_update(self) -> &'static str {
+        match self {
+            Self::Postgres => "FOR UPDATE",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => "",
+        }
+    }
+
+    /// locks the selected rows, skipping the ones that are already locked. sqlite has nothing to lock,
+    /// since its database is only used by a single instance of the service
+    pub fn skip_locked(self) -> &'static str {
+        match self {
+            Self::Postgres => "FOR UPDATE SKIP LOCKED",
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => "",
+        }
+    }
+
+    /// a parameter that may be NULL. postgres can't infer its type from "IS NULL" alone
+    pub fn nullable(self, param: &str, sql_type: &str) -> String {
+        match self {
+            Self::Postgres => format!("{param}::{sql_type}"),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => param.to_owned(),
+        }
+    }
+
+    /// a TIMESTAMP that compares by time. sqlite keeps them as text, which may be formatted differently
+    pub fn timestamp(self, value: &str) -> String {
+        match self {
+            Self::Postgres => value.to_owned(),
+            #[cfg(feature = "sqlite")]
+            Self::Sqlite => format!("unixepoch({value})"),
+        }
+    }
+}
+
+/// the databases that the sql repositories run on
+pub trait SqlDatabase: Database {
+    const DIALECT: Dialect;
+
+    /// the amount of rows that a query changed
+    fn rows_affected(result: &Self::QueryResult) -> u64;
+}
+
+impl SqlDatabase for Postgres {
+    const DIALECT: Dialect = Dialect::Postgres;
+
+    fn rows_affected(result: &Self::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl SqlDatabase for sqlx::Sqlite {
+    const DIALECT: Dialect = Dialect::Sqlite;
+
+    fn rows_affected(result: &Self::QueryResult) -> u64 {
+        result.rows_affected()
+    }
+}
+
+type Query<'q, DB, T> = QueryAs<'q, DB, T, <DB as HasArguments<'q>>::Arguments>;
+
+/// builds new db queries for both fetching Notes and fetching total count of Notes
+pub fn build_read_notes_queries<'q, DB>(
+    query_str: &'q str,
+    count_str: &'q str,
+    user_id: i32,
+    pagination: &Pagination,
+    filters: &'q Filters
+) -> (Query<'q, DB, Note>, Query<'q, DB, CountWrapper>)
+where
+    DB: Database,
+    Note: for<'r> FromRow<'r, DB::Row>,
+    CountWrapper: for<'r> FromRow<'r, DB::Row>,
+    i32: Encode<'q, DB> + Type<DB>,
+    i64: Encode<'q, DB> + Type<DB>,
+    bool: Encode<'q, DB> + Type<DB>,
+    String: Encode<'q, DB> + Type<DB>,
+{
+    let mut query = sqlx::query_as::<DB, Note>(query_str).bind(user_id);
+    let mut count_query = sqlx::query_as::<DB, CountWrapper>(count_str).bind(user_id);
+
+    // filters
+
+    if let Some(filter_tags) = &filters.filter_tags {
+        if !filter_tags.tag_ids.is_empty() {
+            query = query.bind_iter(&filter_tags.tag_ids);
+            count_query = count_query.bind_iter(&filter_tags.tag_ids);
+        }
+    }
+
+    if let Some(filter_date) = &filters.filter_date {
+        query = query.bind(filter_date.start).bind(filter_date.end + 1);
+        count_query = count_query.bind(filter_date.start).bind(filter_date.end + 1);
+    }
+
+    if let Some(filter_date_modif) = &filters.filter_date_modif {
+        query = query.bind(filter_date_modif.start).bind(filter_date_modif.end + 1);
+        count_query = count_query.bind(filter_date_modif.start).bind(filter_date_modif.end + 1);
+    }
+
+    if let Some(filter_search) = &filters.filter_search {
+        query = query.bind(format!("%{}%", filter_search.query));
+        count_query = count_query.bind(format!("%{}%", filter_search.query));
+    }
+
+    if let Some(filter_state) = &filters.filter_state {
+        for flag in [filter_state.pinned, filter_state.favorite].into_iter().flatten() {
+            query = query.bind(flag);
+            count_query = count_query.bind(flag);
+        }
+    }
+
+    if let Some(filter_due) = &filters.filter_due {
+        let now = chrono::Utc::now().timestamp();
+        query = query.bind(now);
+        count_query = count_query.bind(now);
+
+        if filter_due.due() == filter_due::Due::Upcoming && filter_due.until > 0 {
+            query = query.bind(filter_due.until);
+            count_query = count_query.bind(filter_due.until);
+        }
+    }
+
+    // pagination
+
+    query = query
+        .bind(pagination.per_page)
+        .bind((pagination.page - 1) * pagination.per_page);
+
+    (query, count_query)
+}
+
+/// builds strings for the db queries that both fetch Notes and fetch total count of Notes
+pub fn build_read_notes_query_strs(dialect: Dialect, sort: &Sort, filters: &Filters) -> (String, String) {
+    let mut query_str: String = "SELECT * FROM notes WHERE user_id = $1".into();
+    let mut param_num = 1;
+
+    // filtering
+
+    if let Some(filter_tags) = &filters.filter_tags {
+        if !filter_tags.tag_ids.is_empty() {
+            query_str += "\nAND id IN (SELECT note_id FROM note_tags WHERE tag_id IN ())";
+            query_str = fill_tuple_placeholder(&query_str, &filter_tags.tag_ids, param_num);
+            param_num += filter_tags.tag_ids.len();
+        } else {
+            query_str += "\nAND id NOT IN (SELECT note_id FROM note_tags)";
+        }
+    }
+
+    if filters.filter_date.is_some() {
+        query_str += &format!(
+            "\nAND {} BETWEEN ${} AND ${}",
+            dialect.epoch("created"), param_num + 1, param_num + 2,
+        );
+        param_num += 2;
+    }
+
+    if filters.filter_date_modif.is_some() {
+        query_str += &format!(
+            "\nAND {} BETWEEN ${} AND ${}",
+            dialect.epoch("last_edited"), param_num + 1, param_num + 2,
+        );
+        param_num += 2;
+    }
+
+    if filters.filter_search.is_some() {
+        query_str += &format!("\nAND title {} ${}", dialect.ilike(), param_num + 1);
+        param_num += 1;
+    }
+
+    // the archived notes are left out by default
+
+    let archive = filters.filter_state.as_ref().map(|f| f.archive()).unwrap_or_default();
+
+    match archive {
+        filter_state::Archive::Active => query_str += "\nAND NOT archived",
+        filter_state::Archive::Archived => query_str += "\nAND archived",
+        filter_state::Archive::All => (),
+    }
+
+    if let Some(filter_state) = &filters.filter_state {
+        for (column, flag) in [("pinned", filter_state.pinned), ("favorite", filter_state.favorite)] {
+            if flag.is_some() {
+                query_str += &format!("\nAND {column} = ${}", param_num + 1);
+                param_num += 1;
+            }
+        }
+    }
+
+    // the reminders that fire at the current time are already overdue,
+    // and the fired ones are neither overdue nor upcoming anymore
+
+    if let Some(filter_due) = &filters.filter_due {
+        let fire_at = dialect.epoch("fire_at");
+
+        let condition = match filter_due.due() {
+            filter_due::Due::Upcoming if filter_due.until > 0 => {
+                param_num += 2;
+                format!("{fire_at} > ${} AND {fire_at} <= ${}", param_num - 1, param_num)
+            },
+            filter_due::Due::Upcoming => {
+                param_num += 1;
+                format!("{fire_at} > ${param_num}")
+            },
+            filter_due::Due::Overdue => {
+                param_num += 1;
+                format!("{fire_at} <= ${param_num}")
+            },
+        };
+
+        query_str += &format!("\nAND id IN (SELECT note_id FROM note_reminders WHERE NOT fired AND {condition})");
+    }
+
+    // creating the count str
+
+    let count_str = query_str.replace('*', "COUNT(*) AS count") + ";";
+
+    // ordering
+
+    query_str += "\nORDER BY ";
+
+    if sort.pinned_first {
+        query_str += "pinned DESC, ";
+    }
+
+    query_str += match sort.sort_field() {
+        sort::Field::Date => SortField::CREATED,
+        sort::Field::DateModif => SortField::LAST_EDITED,
+        sort::Field::Title => SortField::TITLE,
+    };
+
+    query_str += " ";
+
+    query_str += match sort.sort_type() {
+        sort::Type::Asc => SortType::ASC,
+        sort::Type::Desc => SortType::DESC,
+    };
+
+    query_str += ", id DESC";
+
+    // paginating
+
+    query_str += &format!("\nLIMIT ${} OFFSET ${};", param_num + 1, param_num + 2);
+
+    (query_str, count_str)
+}
+
+/// builds strings for the db queries that fetch the tags and the files of the notes with the `note_ids`.
+/// they are sorted in a way that lets `assign_attachments` go through them only once
+pub fn build_attachment_query_strs(sort: &Sort, note_ids: &[i32]) -> (String, String) {
+    let attachment_sort_field = match sort.sort_field() {
+        sort::Field::Date => SortField::CREATED,
+        sort::Field::DateModif => SortField::LAST_EDITED,
+        sort::Field::Title => SortField::TITLE,
+    };
+
+    // the type here is reversed on purpose. specifically, it allows
+    // efficient assignment of attachments to their respective notes
+    let attachment_sort_type = match sort.sort_type() {
+        sort::Type::Asc => SortType::DESC,
+        sort::Type::Desc => SortType::ASC,
+    };
+
+    // the pinned notes come first, so their attachments come last
+    let pinned_order = match sort.pinned_first {
+        true => "n.pinned ASC, ",
+        false => "",
+    };
+
+    let tags_str = fill_tuple_placeholder(
+        &format!(r"
+            SELECT t.*, n.id AS note_id FROM tags AS t
+            INNER JOIN note_tags AS nt ON nt.tag_id = t.id
+            INNER JOIN notes AS n ON nt.note_id = n.id
+            WHERE n.id IN ()
+            ORDER BY {}n.{} {}, n.id ASC, t.id DESC;
+        ", pinned_order, attachment_sort_field, attachment_sort_type),
+        note_ids, 0,
+    );
+
+    let files_str = fill_tuple_placeholder(
+        &format!(r"
+            SELECT f.*, n.id AS attach_id FROM files AS f
+            INNER JOIN note_files AS nf ON nf.file_id = f.id
+            INNER JOIN notes AS n ON nf.note_id = n.id
+            WHERE n.id IN ()
+            ORDER BY {}n.{} {}, n.id ASC, f.id DESC;
+        ", pinned_order, attachment_sort_field, attachment_sort_type),
+        note_ids, 0,
+    );
+
+    (tags_str, files_str)
+}
+
+/// builds the string of the query that lists the user's tags with the amount of their notes
+pub fn build_read_tags_query_str(sort: &TagSort) -> String {
+    let sort_type = match sort.sort_type() {
+        tag_sort::Type::Asc => SortType::ASC,
+        tag_sort::Type::Desc => SortType::DESC,
+    };
+
+    // the ties are broken by the id, in the same direction
+    let order = match sort.sort_field() {
+        tag_sort::Field::Id => format!("t.id {sort_type}"),
+        tag_sort::Field::Name => format!("LOWER(t.name) {sort_type}, t.id {sort_type}"),
+        tag_sort::Field::Usage => format!("usage_count {sort_type}, t.id {sort_type}"),
+        tag_sort::Field::Created => format!("t.created {sort_type}, t.id {sort_type}"),
+        tag_sort::Field::Position => format!("t.position {sort_type}, t.id {sort_type}"),
+    };
+
+    format!(r"
+        SELECT t.*, CAST(COUNT(nt.note_id) AS INT) AS usage_count FROM tags AS t
+        LEFT JOIN note_tags AS nt ON nt.tag_id = t.id
+        WHERE t.user_id = $1
+        GROUP BY t.id
+        ORDER BY {order};
+    ")
+}
+
+/// assinging tags and files to their respective notes.
+/// since the arrays are properly sorted, this implementation
+/// "iterates" through each of these three arrays only once
+pub fn assign_attachments(notes: &mut [Note], mut tags: Vec<Tag>, mut files: Vec<File>) {
+    let mut tag_note_id = match tags.last() {
+        Some(t) => t.note_id.unwrap(),
+        None => 0,
+    };
+
+    let mut file_note_id = match files.last() {
+        Some(f) => f.attach_id.unwrap(),
+        None => 0,
+    };
+
+    for note in notes {
+
+        while !tags.is_empty() && tag_note_id == note.id {
+            note.tags.push(tags.pop().unwrap());
+
+            if let Some(t) = tags.last() {
+                tag_note_id = t.note_id.unwrap();
+            }
+        }
+
+        while !files.is_empty() && file_note_id == note.id {
+            note.files.push(files.pop().unwrap());
+
+            if let Some(f) = files.last() {
+                file_note_id = f.attach_id.unwrap();
+            }
+        }
+
+    }
+
+    // delete this later
+    assert_eq!(tags, Vec::new());
+    assert_eq!(files, Vec::new());
+}
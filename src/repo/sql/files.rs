@@ -0,0 +1,101 @@
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Type};
+use tonic::async_trait;
+
+use crate::error::ServiceError;
+use crate::outbox;
+use crate::proto::files::{create_file_metadata::AttachId, File};
+use crate::repo::{query::SqlDatabase, FileRepo, RepoResult};
+
+use super::SqlRepo;
+
+#[async_trait]
+impl<DB> FileRepo for SqlRepo<DB>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    File: for<'r> FromRow<'r, DB::Row>,
+{
+    async fn check_attach_target(&self, user_id: i32, attach_id: &AttachId) -> RepoResult<()> {
+        let (query, attach_id_val) = match *attach_id {
+            AttachId::NoteId(note_id) => (
+                sqlx::query("SELECT id FROM notes WHERE id = $1 AND user_id = $2;"),
+                note_id,
+            ),
+            AttachId::ShelfId(shelf_id) => (
+                sqlx::query("SELECT id FROM shelves WHERE id = $1 AND user_id = $2;"),
+                shelf_id,
+            ),
+        };
+
+        query
+            .bind(attach_id_val).bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn create(&self, user_id: i32, hash: &str, name: &str, size: i64, attach_id: AttachId) -> RepoResult<File> {
+        let mut transaction = self.pool.begin().await?;
+
+        let mut new_file_info = sqlx::query_as::<_, File>("INSERT INTO files (user_id, hash, name, size) VALUES ($1, $2, $3, $4) RETURNING *;")
+            .bind(user_id).bind(hash).bind(name).bind(size)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let (query, attach_id_val) = match attach_id {
+            AttachId::NoteId(note_id) => (sqlx::query("INSERT INTO note_files (note_id, file_id) VALUES ($1, $2);"), note_id),
+            AttachId::ShelfId(shelf_id) => (sqlx::query("INSERT INTO shelf_files (shelf_id, file_id) VALUES ($1, $2);"), shelf_id),
+        };
+
+        query
+            .bind(attach_id_val).bind(new_file_info.id)
+            .execute(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        new_file_info.attach_id = Some(attach_id_val);
+        Ok(new_file_info)
+    }
+
+    async fn find_by_hash(&self, user_id: i32, hash: &str) -> RepoResult<File> {
+        let file_info = sqlx::query_as::<_, File>("SELECT * FROM files WHERE hash = $1 AND user_id = $2;")
+            .bind(hash).bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(file_info)
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<File> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM note_files WHERE file_id = $1;")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM shelf_files WHERE file_id = $1;")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        let deleted_file = sqlx::query_as::<_, File>("DELETE FROM files WHERE id = $1 AND user_id = $2 RETURNING *;")
+            .bind(id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await.map_err(ServiceError::from_delete)?;
+
+        outbox::enqueue(&mut *transaction, std::slice::from_ref(&deleted_file.hash)).await?;
+
+        transaction.commit().await?;
+
+        Ok(deleted_file)
+    }
+}
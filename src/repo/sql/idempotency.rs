@@ -0,0 +1,84 @@
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Type};
+use tonic::async_trait;
+
+use crate::repo::{query::SqlDatabase, IdempotencyRecord, IdempotencyRepo, RepoResult};
+
+use super::SqlRepo;
+
+#[async_trait]
+impl<DB> IdempotencyRepo for SqlRepo<DB>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q [u8]: Encode<'q, DB> + Type<DB>,
+    IdempotencyRecord: for<'r> FromRow<'r, DB::Row>,
+{
+    async fn reserve(&self, user_id: i32, key: &str, request_hash: &str, token: &str, lease: u64, ttl: u64) -> RepoResult<Option<IdempotencyRecord>> {
+        // an expired record, or one whose call outlived its lease, gets taken over
+        // in the same statement, so that two calls can't both claim it
+
+        let now = DB::DIALECT.now();
+        let result = sqlx::query(&format!("
+            INSERT INTO idempotency_keys (user_id, key, request_hash, token, reserved_at) VALUES ($1, $2, $3, $4, {now})
+            ON CONFLICT (user_id, key) DO UPDATE SET request_hash = EXCLUDED.request_hash, token = EXCLUDED.token, response = NULL, created = {now}, reserved_at = {now}
+            WHERE idempotency_keys.created < {}
+            OR (idempotency_keys.response IS NULL AND idempotency_keys.reserved_at < {});
+        ", DB::DIALECT.seconds_ago("$6"), DB::DIALECT.seconds_ago("$5")))
+            .bind(user_id).bind(key).bind(request_hash).bind(token).bind(lease as i64).bind(ttl as i64)
+            .execute(&self.pool)
+            .await?;
+
+        if DB::rows_affected(&result) == 1 {
+            return Ok(None);
+        }
+
+        let record = sqlx::query_as::<_, IdempotencyRecord>("SELECT request_hash, response FROM idempotency_keys WHERE user_id = $1 AND key = $2;")
+            .bind(user_id).bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        // the record could have been released in the meantime, in which case
+        // the call is treated as still in progress and can be retried
+        Ok(Some(record.unwrap_or(IdempotencyRecord { request_hash: request_hash.to_owned(), response: None })))
+    }
+
+    async fn renew(&self, user_id: i32, key: &str, token: &str) -> RepoResult<()> {
+        sqlx::query(&format!("UPDATE idempotency_keys SET reserved_at = {} WHERE user_id = $1 AND key = $2 AND token = $3 AND response IS NULL;", DB::DIALECT.now()))
+            .bind(user_id).bind(key).bind(token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn complete(&self, user_id: i32, key: &str, token: &str, response: &[u8]) -> RepoResult<()> {
+        sqlx::query("UPDATE idempotency_keys SET response = $1 WHERE user_id = $2 AND key = $3 AND token = $4 AND response IS NULL;")
+            .bind(response).bind(user_id).bind(key).bind(token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn release(&self, user_id: i32, key: &str, token: &str) -> RepoResult<()> {
+        sqlx::query("DELETE FROM idempotency_keys WHERE user_id = $1 AND key = $2 AND token = $3 AND response IS NULL;")
+            .bind(user_id).bind(key).bind(token)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn purge_expired(&self, ttl: u64) -> RepoResult<u64> {
+        let result = sqlx::query(&format!("DELETE FROM idempotency_keys WHERE created < {};", DB::DIALECT.seconds_ago("$1")))
+            .bind(ttl as i64)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(DB::rows_affected(&result))
+    }
+}
@@ -0,0 +1,141 @@
+//! keeps the note_links table in sync with the texts and the titles of the notes
+
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Transaction, Type};
+
+use crate::repo::links::{self, Link};
+use crate::repo::{query::SqlDatabase, RepoResult};
+use crate::types::{fill_tuple_placeholder, BindIter};
+
+use super::tasks;
+
+/// replaces the links of the note with the ones in its text
+pub async fn save_links<DB>(transaction: &mut Transaction<'_, DB>, user_id: i32, note_id: i32, text: &str) -> RepoResult<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+{
+    sqlx::query("DELETE FROM note_links WHERE source_id = $1;")
+        .bind(note_id)
+        .execute(&mut **transaction)
+        .await?;
+
+    for link in links::parse(text) {
+        let query = match link {
+            Link::Title(title) => sqlx::query(r"
+                INSERT INTO note_links (source_id, target_id, target_title)
+                VALUES ($1, (SELECT MIN(id) FROM notes WHERE user_id = $2 AND LOWER(title) = LOWER($3)), $3);
+            ")
+                .bind(note_id).bind(user_id).bind(title),
+
+            // the links to the notes of other users are left out
+            Link::Id(id) => sqlx::query(r"
+                INSERT INTO note_links (source_id, target_id, target_title)
+                SELECT $1, id, '' FROM notes WHERE user_id = $2 AND id = $3;
+            ")
+                .bind(note_id).bind(user_id).bind(id),
+        };
+
+        query.execute(&mut **transaction).await?;
+    }
+
+    Ok(())
+}
+
+/// points the unresolved title links of the user's notes to the notes that have their titles now
+pub async fn resolve_links<DB>(transaction: &mut Transaction<'_, DB>, user_id: i32) -> RepoResult<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+{
+    sqlx::query(r"
+        UPDATE note_links SET target_id = (
+            SELECT MIN(n.id) FROM notes AS n
+            WHERE n.user_id = $1 AND LOWER(n.title) = LOWER(note_links.target_title)
+        )
+        WHERE target_id IS NULL AND target_title <> '' AND source_id IN (SELECT id FROM notes WHERE user_id = $1);
+    ")
+        .bind(user_id)
+        .execute(&mut **transaction)
+        .await?;
+
+    Ok(())
+}
+
+/// rewrites the title links to the renamed note in the texts of the other notes, which counts as an edit of them.
+/// a title that can't be written in a link leaves them with the old one, and one that would make a text too long fails
+pub async fn rename_links<DB>(transaction: &mut Transaction<'_, DB>, note_id: i32, old_title: &str, new_title: &str) -> RepoResult<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    (i32, String): for<'r> FromRow<'r, DB::Row>,
+{
+    if old_title == new_title || !links::linkable(new_title) {
+        return Ok(());
+    }
+
+    let sources = sqlx::query_as::<_, (i32, String)>(r"
+        SELECT id, text FROM notes
+        WHERE id <> $1 AND id IN (SELECT source_id FROM note_links WHERE target_id = $1 AND target_title <> '');
+    ")
+        .bind(note_id)
+        .fetch_all(&mut **transaction)
+        .await?;
+
+    for (source_id, text) in sources {
+        if let Some(text) = links::rename(&text, old_title, new_title) {
+            links::check_renamed(source_id, &text)?;
+
+            sqlx::query(&format!("UPDATE notes SET text = $1, last_edited = {}, times_edited = times_edited + 1 WHERE id = $2;", DB::DIALECT.now()))
+                .bind(&text).bind(source_id)
+                .execute(&mut **transaction)
+                .await?;
+
+            // the renamed links might be in the tasks too
+            tasks::save_tasks(transaction, source_id, &text).await?;
+        }
+    }
+
+    sqlx::query("UPDATE note_links SET target_title = $1 WHERE target_id = $2 AND target_title <> '' AND source_id <> $2;")
+        .bind(new_title).bind(note_id)
+        .execute(&mut **transaction)
+        .await?;
+
+    Ok(())
+}
+
+/// removes the links of the user's notes that are about to be deleted, and the links by id to them.
+/// the title links to them are unresolved, so `resolve_links` can point them to another note with the same title
+pub async fn delete_links<DB>(transaction: &mut Transaction<'_, DB>, user_id: i32, note_ids: &[i32]) -> RepoResult<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+{
+    let queries = [
+        "DELETE FROM note_links WHERE source_id IN (SELECT id FROM notes WHERE user_id = $1 AND id IN ());",
+        "DELETE FROM note_links WHERE target_title = '' AND target_id IN (SELECT id FROM notes WHERE user_id = $1 AND id IN ());",
+        "UPDATE note_links SET target_id = NULL WHERE target_id IN (SELECT id FROM notes WHERE user_id = $1 AND id IN ());",
+    ];
+
+    for query in queries {
+        sqlx::query(&fill_tuple_placeholder(query, note_ids, 1))
+            .bind(user_id).bind_iter(note_ids)
+            .execute(&mut **transaction)
+            .await?;
+    }
+
+    Ok(())
+}
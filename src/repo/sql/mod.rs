@@ -0,0 +1,29 @@
+use sqlx::{Database, Pool, Postgres};
+#[cfg(feature = "sqlite")]
+use sqlx::Sqlite;
+
+mod files;
+mod idempotency;
+mod links;
+mod notes;
+mod reminders;
+mod shelves;
+mod tags;
+mod tasks;
+mod templates;
+
+/// the sql implementation of all of the repositories. the same code runs on postgres and on sqlite,
+/// and the parts of the queries that differ between them come from the Dialect of the `DB`
+pub struct SqlRepo<DB: Database> {
+    pool: Pool<DB>,
+}
+
+impl<DB: Database> SqlRepo<DB> {
+    pub fn new(pool: Pool<DB>) -> Self {
+        Self { pool }
+    }
+}
+
+pub type PgRepo = SqlRepo<Postgres>;
+#[cfg(feature = "sqlite")]
+pub type SqliteRepo = SqlRepo<Sqlite>;
@@ -0,0 +1,566 @@
+use std::collections::BTreeSet;
+
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Transaction, Type};
+use tonic::async_trait;
+
+use crate::error::ServiceError;
+use crate::outbox;
+use crate::proto::notes::{BatchItemResult, Filters, GraphEdge, Note, NoteGraph, NoteRef, NoteState, Pagination, Sort, Task};
+use crate::proto::{files::File, tags::Tag};
+use crate::repo::batch::{self, Lookup};
+use crate::repo::tasks::{note_edited, task_not_found, toggle};
+use crate::repo::{query::*, NoteRepo, RepoResult};
+use crate::types::{fill_tuple_placeholder, fill_values_placeholder, BindIter, CountWrapper, IDWrapper};
+
+use super::{links, tasks, SqlRepo};
+
+#[async_trait]
+impl<DB> NoteRepo for SqlRepo<DB>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> Option<bool>: Encode<'q, DB> + Type<DB>,
+    for<'q> Option<i32>: Encode<'q, DB> + Type<DB>,
+    Note: for<'r> FromRow<'r, DB::Row>,
+    Tag: for<'r> FromRow<'r, DB::Row>,
+    File: for<'r> FromRow<'r, DB::Row>,
+    Task: for<'r> FromRow<'r, DB::Row>,
+    IDWrapper: for<'r> FromRow<'r, DB::Row>,
+    CountWrapper: for<'r> FromRow<'r, DB::Row>,
+    (i32, i32): for<'r> FromRow<'r, DB::Row>,
+    (i32, String): for<'r> FromRow<'r, DB::Row>,
+    (String, i32): for<'r> FromRow<'r, DB::Row>,
+    (String,): for<'r> FromRow<'r, DB::Row>,
+{
+    async fn create(&self, user_id: i32, title: &str, text: &str) -> RepoResult<Note> {
+        let mut transaction = self.pool.begin().await?;
+
+        let new_note = sqlx::query_as::<_, Note>("INSERT INTO notes (user_id, title, text) VALUES ($1, $2, $3) RETURNING *;")
+            .bind(user_id).bind(title).bind(text)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        links::save_links(&mut transaction, user_id, new_note.id, text).await?;
+        tasks::save_tasks(&mut transaction, new_note.id, text).await?;
+        links::resolve_links(&mut transaction, user_id).await?;
+
+        transaction.commit().await?;
+
+        Ok(new_note)
+    }
+
+    async fn list(&self, user_id: i32, pagination: &Pagination, sort: &Sort, filters: &Filters) -> RepoResult<(Vec<Note>, i32)> {
+        // building the query
+
+        let (query_str, count_str) = build_read_notes_query_strs(DB::DIALECT, sort, filters);
+        log::debug!("READ NOTES QUERIES: {}\n{}", query_str, count_str);
+        let (query, count_query) = build_read_notes_queries(&query_str, &count_str, user_id, pagination, filters);
+
+        // executing the two queries
+
+        let mut transaction = self.pool.begin().await?;
+
+        let mut notes = query
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        let total_count = count_query
+            .fetch_one(&mut *transaction)
+            .await?
+            .count as i32;
+
+        let note_ids: Vec<_> = notes.iter().map(|n| n.id).collect();
+
+        if note_ids.is_empty() {
+            return Ok((Vec::new(), total_count));
+        }
+
+        // fetching relevant tags and files
+
+        let (tags_str, files_str) = build_attachment_query_strs(sort, &note_ids);
+
+        let tags = sqlx::query_as::<_, Tag>(&tags_str)
+            .bind_iter(&note_ids)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        let files = sqlx::query_as::<_, File>(&files_str)
+            .bind_iter(&note_ids)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        assign_attachments(&mut notes, tags, files);
+
+        Ok((notes, total_count))
+    }
+
+    async fn update(&self, id: i32, user_id: i32, title: &str, text: &str) -> RepoResult<Note> {
+        let mut transaction = self.pool.begin().await?;
+
+        let old_title: String = sqlx::query_scalar("SELECT title FROM notes WHERE id = $1 AND user_id = $2;")
+            .bind(id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let updated_note = sqlx::query_as::<_, Note>(&format!(r"
+            UPDATE notes
+            SET title = $1, text = $2, last_edited = {}, times_edited = times_edited + 1
+            WHERE id = $3 AND user_id = $4
+            RETURNING *;
+        ", DB::DIALECT.now()))
+            .bind(title).bind(text).bind(id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        // the other notes keep linking to the note by its new title
+
+        links::rename_links(&mut transaction, id, &old_title, title).await?;
+        links::save_links(&mut transaction, user_id, id, text).await?;
+        tasks::save_tasks(&mut transaction, id, text).await?;
+        links::resolve_links(&mut transaction, user_id).await?;
+
+        transaction.commit().await?;
+
+        Ok(updated_note)
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        delete_notes(&mut transaction, user_id, &[id])
+            .await?
+            .eq(&1)
+            .then_some(())
+            .ok_or(ServiceError::from(sqlx::Error::RowNotFound))?;
+
+        // the files get deleted from the disk by the outbox worker after the commit
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn attach_tag(&self, user_id: i32, note_id: i32, tag_id: i32) -> RepoResult<()> {
+        // trying to insert a new note-tag relation while making sure
+        // that both the note and the tag belong to the user
+
+        sqlx::query(r"
+            INSERT INTO note_tags (note_id, tag_id)
+            SELECT (
+                SELECT id FROM notes WHERE id = $1 AND user_id = $3
+            ), (
+                SELECT id FROM tags WHERE id = $2 AND user_id = $3
+            );
+        ")
+            .bind(note_id).bind(tag_id).bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn detach_tag(&self, user_id: i32, note_id: i32, tag_id: i32) -> RepoResult<()> {
+        sqlx::query_as::<_, IDWrapper>(r"
+            DELETE FROM note_tags
+            WHERE note_id = (
+                SELECT id FROM notes WHERE id = $1 AND user_id = $3
+            ) AND tag_id = (
+                SELECT id FROM tags WHERE id = $2 AND user_id = $3
+            )
+            RETURNING note_id AS id;
+        ")
+            .bind(note_id).bind(tag_id).bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn batch_attach_tags(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let lookup = lookup(&mut transaction, user_id, note_ids, tag_ids).await?;
+        let results = batch::tag_pairs(note_ids, tag_ids, &lookup, true);
+        batch::check_all(&results, partial)?;
+
+        insert_note_tags(&mut transaction, batch::succeeded(&results)).await?;
+        transaction.commit().await?;
+
+        Ok(results)
+    }
+
+    async fn batch_detach_tags(&self, user_id: i32, note_ids: &[i32], tag_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let lookup = lookup(&mut transaction, user_id, note_ids, tag_ids).await?;
+        let results = batch::tag_pairs(note_ids, tag_ids, &lookup, false);
+        batch::check_all(&results, partial)?;
+
+        // every attached pair of these notes and tags is one of the detached ones
+
+        let pairs = batch::succeeded(&results);
+
+        if !pairs.is_empty() {
+            let note_ids: Vec<_> = pairs.iter().map(|(note_id, _)| *note_id).collect::<BTreeSet<_>>().into_iter().collect();
+            let tag_ids: Vec<_> = pairs.iter().map(|(_, tag_id)| *tag_id).collect::<BTreeSet<_>>().into_iter().collect();
+
+            let query_str = fill_tuple_placeholder("DELETE FROM note_tags WHERE note_id IN () AND tag_id IN ();", &note_ids, 0);
+            sqlx::query(&fill_tuple_placeholder(&query_str, &tag_ids, note_ids.len()))
+                .bind_iter(&note_ids).bind_iter(&tag_ids)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(results)
+    }
+
+    async fn batch_delete(&self, user_id: i32, note_ids: &[i32], partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let lookup = lookup(&mut transaction, user_id, note_ids, &[]).await?;
+        let results = batch::found_notes(note_ids, &lookup);
+        batch::check_all(&results, partial)?;
+
+        let deleted: Vec<_> = batch::succeeded(&results).into_iter().map(|(note_id, _)| note_id).collect();
+
+        if !deleted.is_empty() {
+            delete_notes(&mut transaction, user_id, &deleted).await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(results)
+    }
+
+    async fn batch_move(&self, user_id: i32, note_ids: &[i32], from_tag_id: i32, to_tag_id: i32, partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let lookup = lookup(&mut transaction, user_id, note_ids, &[from_tag_id, to_tag_id]).await?;
+        batch::check_move_tags(from_tag_id, to_tag_id, &lookup)?;
+
+        let results = batch::moved_notes(note_ids, from_tag_id, &lookup);
+        batch::check_all(&results, partial)?;
+
+        let moved: Vec<_> = batch::succeeded(&results).into_iter().map(|(note_id, _)| note_id).collect();
+
+        if !moved.is_empty() {
+            sqlx::query(&fill_tuple_placeholder("DELETE FROM note_tags WHERE tag_id = $1 AND note_id IN ();", &moved, 1))
+                .bind(from_tag_id).bind_iter(&moved)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        // the notes that already have the other tag keep it
+
+        let attached = moved.into_iter()
+            .filter(|note_id| !lookup.attached.contains(&(*note_id, to_tag_id)))
+            .map(|note_id| (note_id, to_tag_id))
+            .collect();
+
+        insert_note_tags(&mut transaction, attached).await?;
+        transaction.commit().await?;
+
+        Ok(results)
+    }
+
+    async fn set_state(&self, id: i32, user_id: i32, state: &NoteState) -> RepoResult<Note> {
+        let updated_note = sqlx::query_as::<_, Note>(&format!("{SET_STATE} WHERE id = $4 AND user_id = $5 RETURNING *;"))
+            .bind(state.pinned).bind(state.archived).bind(state.favorite).bind(id).bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(updated_note)
+    }
+
+    async fn batch_set_state(&self, user_id: i32, note_ids: &[i32], state: &NoteState, partial: bool) -> RepoResult<Vec<BatchItemResult>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let lookup = lookup(&mut transaction, user_id, note_ids, &[]).await?;
+        let results = batch::found_notes(note_ids, &lookup);
+        batch::check_all(&results, partial)?;
+
+        let changed: Vec<_> = batch::succeeded(&results).into_iter().map(|(note_id, _)| note_id).collect();
+
+        if !changed.is_empty() {
+            sqlx::query(&fill_tuple_placeholder(&format!("{SET_STATE} WHERE user_id = $4 AND id IN ();"), &changed, 4))
+                .bind(state.pinned).bind(state.archived).bind(state.favorite).bind(user_id).bind_iter(&changed)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(results)
+    }
+
+    async fn backlinks(&self, user_id: i32, note_id: i32) -> RepoResult<Vec<NoteRef>> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query_as::<_, IDWrapper>("SELECT id FROM notes WHERE id = $1 AND user_id = $2;")
+            .bind(note_id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let sources = sqlx::query_as::<_, (i32, String)>(r"
+            SELECT DISTINCT n.id, n.title FROM notes AS n
+            INNER JOIN note_links AS l ON l.source_id = n.id
+            WHERE l.target_id = $1 AND n.user_id = $2
+            ORDER BY n.id;
+        ")
+            .bind(note_id).bind(user_id)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(sources.into_iter().map(|(id, title)| NoteRef { id, title }).collect())
+    }
+
+    async fn graph(&self, user_id: i32, include_archived: bool) -> RepoResult<NoteGraph> {
+        let mut transaction = self.pool.begin().await?;
+
+        let nodes = sqlx::query_as::<_, (i32, String)>("SELECT id, title FROM notes WHERE user_id = $1 AND ($2 OR NOT archived) ORDER BY id;")
+            .bind(user_id).bind(include_archived)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        let edges = sqlx::query_as::<_, (i32, i32)>(r"
+            SELECT DISTINCT l.source_id, l.target_id FROM note_links AS l
+            INNER JOIN notes AS s ON s.id = l.source_id
+            INNER JOIN notes AS t ON t.id = l.target_id
+            WHERE s.user_id = $1 AND ($2 OR (NOT s.archived AND NOT t.archived))
+            ORDER BY l.source_id, l.target_id;
+        ")
+            .bind(user_id).bind(include_archived)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(NoteGraph {
+            nodes: nodes.into_iter().map(|(id, title)| NoteRef { id, title }).collect(),
+            edges: edges.into_iter().map(|(source_id, target_id)| GraphEdge { source_id, target_id }).collect(),
+        })
+    }
+
+    async fn tasks(&self, user_id: i32, pagination: &Pagination, done: Option<bool>, tag_id: Option<i32>, include_archived: bool) -> RepoResult<(Vec<Task>, i32)> {
+        let from = format!(r"
+            FROM note_tasks AS t
+            INNER JOIN notes AS n ON n.id = t.note_id
+            WHERE n.user_id = $1 AND ({} IS NULL OR t.done = $2) AND ($4 OR NOT n.archived)
+            AND ({} IS NULL OR t.note_id IN (SELECT note_id FROM note_tags WHERE tag_id = $3))
+        ", DB::DIALECT.nullable("$2", "BOOLEAN"), DB::DIALECT.nullable("$3", "INT"));
+
+        let mut transaction = self.pool.begin().await?;
+
+        let tasks = sqlx::query_as::<_, Task>(&format!("
+            SELECT t.*, n.title AS note_title, n.times_edited AS note_times_edited {from}
+            ORDER BY t.note_id, t.position
+            LIMIT $5 OFFSET $6;
+        "))
+            .bind(user_id).bind(done).bind(tag_id).bind(include_archived)
+            .bind(pagination.per_page).bind((pagination.page - 1) * pagination.per_page)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        let total_count = sqlx::query_as::<_, CountWrapper>(&format!("SELECT COUNT(*) AS count {from};"))
+            .bind(user_id).bind(done).bind(tag_id).bind(include_archived)
+            .fetch_one(&mut *transaction)
+            .await?
+            .count as i32;
+
+        transaction.commit().await?;
+
+        Ok((tasks, total_count))
+    }
+
+    async fn toggle_task(&self, user_id: i32, note_id: i32, position: i32, times_edited: i32) -> RepoResult<Note> {
+        let mut transaction = self.pool.begin().await?;
+
+        let (text, current) = sqlx::query_as::<_, (String, i32)>("SELECT text, times_edited FROM notes WHERE id = $1 AND user_id = $2;")
+            .bind(note_id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        if current != times_edited {
+            return Err(note_edited());
+        }
+
+        let text = toggle(&text, position as usize).ok_or_else(task_not_found)?;
+
+        // the note could have been edited since it was read, so the update checks the times_edited again
+
+        let updated_note = sqlx::query_as::<_, Note>(&format!(r"
+            UPDATE notes
+            SET text = $1, last_edited = {}, times_edited = times_edited + 1
+            WHERE id = $2 AND user_id = $3 AND times_edited = $4
+            RETURNING *;
+        ", DB::DIALECT.now()))
+            .bind(&text).bind(note_id).bind(user_id).bind(times_edited)
+            .fetch_optional(&mut *transaction)
+            .await?
+            .ok_or_else(note_edited)?;
+
+        tasks::save_tasks(&mut transaction, note_id, &text).await?;
+
+        transaction.commit().await?;
+
+        Ok(updated_note)
+    }
+}
+
+/// sets the flags of a NoteState that aren't null
+const SET_STATE: &str = "UPDATE notes SET pinned = COALESCE($1, pinned), archived = COALESCE($2, archived), favorite = COALESCE($3, favorite)";
+
+/// finds which of the notes and tags belong to the user, and which of them are attached to each other
+async fn lookup<DB>(transaction: &mut Transaction<'_, DB>, user_id: i32, note_ids: &[i32], tag_ids: &[i32]) -> RepoResult<Lookup>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    IDWrapper: for<'r> FromRow<'r, DB::Row>,
+    (i32, i32): for<'r> FromRow<'r, DB::Row>,
+{
+    let notes = sqlx::query_as::<_, IDWrapper>(&fill_tuple_placeholder("SELECT id FROM notes WHERE user_id = $1 AND id IN ();", note_ids, 1))
+        .bind(user_id).bind_iter(note_ids)
+        .fetch_all(&mut **transaction)
+        .await?;
+
+    let tags = match tag_ids.len() {
+        0 => Vec::new(),
+        _ => sqlx::query_as::<_, IDWrapper>(&fill_tuple_placeholder("SELECT id FROM tags WHERE user_id = $1 AND id IN ();", tag_ids, 1))
+            .bind(user_id).bind_iter(tag_ids)
+            .fetch_all(&mut **transaction)
+            .await?,
+    };
+
+    let attached = match tag_ids.len() {
+        0 => Vec::new(),
+        _ => sqlx::query_as::<_, (i32, i32)>(&fill_tuple_placeholder("SELECT note_id, tag_id FROM note_tags WHERE note_id IN ();", note_ids, 0))
+            .bind_iter(note_ids)
+            .fetch_all(&mut **transaction)
+            .await?,
+    };
+
+    Ok(Lookup {
+        notes: notes.into_iter().map(|w| w.id).collect(),
+        tags: tags.into_iter().map(|w| w.id).collect(),
+        attached: attached.into_iter().filter(|(_, tag_id)| tag_ids.contains(tag_id)).collect(),
+    })
+}
+
+/// inserts the (note_id, tag_id) pairs
+async fn insert_note_tags<DB>(transaction: &mut Transaction<'_, DB>, pairs: Vec<(i32, i32)>) -> RepoResult<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+{
+    if pairs.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query(&fill_values_placeholder("INSERT INTO note_tags (note_id, tag_id) VALUES ();", pairs.len(), 2, 0))
+        .bind_iter(pairs.into_iter().flat_map(|(note_id, tag_id)| [note_id, tag_id]))
+        .execute(&mut **transaction)
+        .await?;
+
+    Ok(())
+}
+
+/// deletes the user's notes along with their files, queues the files' blobs for deletion,
+/// and returns the amount of deleted notes
+async fn delete_notes<DB>(transaction: &mut Transaction<'_, DB>, user_id: i32, note_ids: &[i32]) -> RepoResult<u64>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q String: Encode<'q, DB> + Type<DB>,
+    File: for<'r> FromRow<'r, DB::Row>,
+    IDWrapper: for<'r> FromRow<'r, DB::Row>,
+{
+    // deleting tag and file relations. sqlite doesn't support DELETE ... USING
+
+    sqlx::query(&fill_tuple_placeholder(
+        r"
+            DELETE FROM note_tags
+            WHERE note_id IN (SELECT id FROM notes WHERE user_id = $1 AND id IN ());
+        ",
+        note_ids, 1,
+    ))
+        .bind(user_id).bind_iter(note_ids)
+        .execute(&mut **transaction)
+        .await?;
+
+    let file_ids: Vec<_> = sqlx::query_as::<_, IDWrapper>(&fill_tuple_placeholder(
+        r"
+            DELETE FROM note_files
+            WHERE note_id IN (SELECT id FROM notes WHERE user_id = $1 AND id IN ())
+            RETURNING file_id AS id;
+        ",
+        note_ids, 1,
+    ))
+        .bind(user_id).bind_iter(note_ids)
+        .fetch_all(&mut **transaction)
+        .await?
+        .into_iter()
+        .map(|w| w.id)
+        .collect();
+
+    // deleting related files from the db
+
+    let files = match file_ids.len() {
+        0 => Vec::new(),
+        _ => sqlx::query_as::<_, File>(&fill_tuple_placeholder(
+            r"
+                DELETE FROM files
+                WHERE user_id = $1 AND id IN ()
+                RETURNING *;
+            ",
+            &file_ids, 1,
+        ))
+            .bind(user_id).bind_iter(&file_ids)
+            .fetch_all(&mut **transaction)
+            .await.map_err(ServiceError::from_delete)?,
+    };
+
+    let hashes: Vec<_> = files.into_iter().map(|f| f.hash).collect();
+    outbox::enqueue(&mut **transaction, &hashes).await?;
+
+    // deleting the notes themselves with their tasks and reminders, and pointing the links to them to the other notes with their titles
+
+    links::delete_links(transaction, user_id, note_ids).await?;
+
+    sqlx::query(&fill_tuple_placeholder("DELETE FROM note_tasks WHERE note_id IN (SELECT id FROM notes WHERE user_id = $1 AND id IN ());", note_ids, 1))
+        .bind(user_id).bind_iter(note_ids)
+        .execute(&mut **transaction)
+        .await?;
+
+    sqlx::query(&fill_tuple_placeholder("DELETE FROM note_reminders WHERE user_id = $1 AND note_id IN ();", note_ids, 1))
+        .bind(user_id).bind_iter(note_ids)
+        .execute(&mut **transaction)
+        .await?;
+
+    let result = sqlx::query(&fill_tuple_placeholder("DELETE FROM notes WHERE user_id = $1 AND id IN ();", note_ids, 1))
+        .bind(user_id).bind_iter(note_ids)
+        .execute(&mut **transaction)
+        .await.map_err(ServiceError::from_delete)?;
+
+    links::resolve_links(transaction, user_id).await?;
+
+    Ok(DB::rows_affected(&result))
+}
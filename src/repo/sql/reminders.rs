@@ -0,0 +1,123 @@
+use chrono::NaiveDateTime;
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Type};
+use tonic::async_trait;
+
+use crate::error::ServiceError;
+use crate::proto::reminders::{DueReminder, Reminder};
+use crate::repo::{query::SqlDatabase, ReminderRepo, RepoResult};
+use crate::types::IDWrapper;
+
+use super::SqlRepo;
+
+#[async_trait]
+impl<DB> ReminderRepo for SqlRepo<DB>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q> Option<i32>: Encode<'q, DB> + Type<DB>,
+    for<'q> NaiveDateTime: Encode<'q, DB> + Type<DB>,
+    for<'q> Option<NaiveDateTime>: Encode<'q, DB> + Type<DB>,
+    Reminder: for<'r> FromRow<'r, DB::Row>,
+    DueReminder: for<'r> FromRow<'r, DB::Row>,
+    IDWrapper: for<'r> FromRow<'r, DB::Row>,
+{
+    async fn create(&self, user_id: i32, note_id: i32, local_time: NaiveDateTime, timezone: &str, recurrence: &str, fire_at: NaiveDateTime) -> RepoResult<Reminder> {
+        // the note is selected instead of inserted directly, so that the reminder can only go to the user's own note
+
+        let new_reminder = sqlx::query_as::<_, Reminder>(r"
+            INSERT INTO note_reminders (user_id, note_id, local_time, timezone, recurrence, fire_at)
+            SELECT user_id, id, $3, $4, $5, $6 FROM notes WHERE id = $1 AND user_id = $2
+            RETURNING *;
+        ")
+            .bind(note_id).bind(user_id).bind(local_time).bind(timezone).bind(recurrence).bind(fire_at)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(new_reminder)
+    }
+
+    async fn list(&self, user_id: i32, note_id: Option<i32>) -> RepoResult<Vec<Reminder>> {
+        let mut transaction = self.pool.begin().await?;
+
+        if let Some(note_id) = note_id {
+            sqlx::query_as::<_, IDWrapper>("SELECT id FROM notes WHERE id = $1 AND user_id = $2;")
+                .bind(note_id).bind(user_id)
+                .fetch_one(&mut *transaction)
+                .await?;
+        }
+
+        let reminders = sqlx::query_as::<_, Reminder>(&format!(r"
+            SELECT * FROM note_reminders
+            WHERE user_id = $1 AND ({} IS NULL OR note_id = $2)
+            ORDER BY fire_at, id;
+        ", DB::DIALECT.nullable("$2", "INT")))
+            .bind(user_id).bind(note_id)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(reminders)
+    }
+
+    async fn update(&self, id: i32, user_id: i32, local_time: NaiveDateTime, timezone: &str, recurrence: &str, fire_at: NaiveDateTime) -> RepoResult<Reminder> {
+        let updated_reminder = sqlx::query_as::<_, Reminder>(&format!(r"
+            UPDATE note_reminders
+            SET local_time = $1, timezone = $2, recurrence = $3, fire_at = $4, fired = FALSE, last_edited = {}
+            WHERE id = $5 AND user_id = $6
+            RETURNING *;
+        ", DB::DIALECT.now()))
+            .bind(local_time).bind(timezone).bind(recurrence).bind(fire_at).bind(id).bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(updated_reminder)
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()> {
+        let result = sqlx::query("DELETE FROM note_reminders WHERE id = $1 AND user_id = $2;")
+            .bind(id).bind(user_id)
+            .execute(&self.pool)
+            .await?;
+
+        DB::rows_affected(&result)
+            .eq(&1)
+            .then_some(())
+            .ok_or(ServiceError::from(sqlx::Error::RowNotFound))?;
+
+        Ok(())
+    }
+
+    async fn due(&self, now: NaiveDateTime, limit: i64) -> RepoResult<Vec<DueReminder>> {
+        let due = sqlx::query_as::<_, DueReminder>(&format!(r"
+            SELECT r.*, n.title AS note_title FROM note_reminders AS r
+            INNER JOIN notes AS n ON n.id = r.note_id
+            WHERE NOT r.fired AND {} <= {}
+            ORDER BY r.fire_at, r.id
+            LIMIT $2;
+        ", DB::DIALECT.timestamp("r.fire_at"), DB::DIALECT.timestamp("$1")))
+            .bind(now).bind(limit)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(due)
+    }
+
+    async fn reschedule(&self, id: i32, due_at: NaiveDateTime, next: Option<NaiveDateTime>) -> RepoResult<bool> {
+        let result = sqlx::query(r"
+            UPDATE note_reminders
+            SET fire_at = COALESCE($1, fire_at), fired = $2
+            WHERE id = $3 AND fire_at = $4 AND NOT fired;
+        ")
+            .bind(next).bind(next.is_none()).bind(id).bind(due_at)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(DB::rows_affected(&result) == 1)
+    }
+}
@@ -0,0 +1,151 @@
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Type};
+use tonic::async_trait;
+
+use crate::error::ServiceError;
+use crate::outbox;
+use crate::proto::{files::File, notes::Note, shelves::Shelf};
+use crate::repo::{query::SqlDatabase, RepoResult, ShelfRepo};
+use crate::types::{fill_tuple_placeholder, fill_values_placeholder, BindIter, IDWrapper};
+
+use super::{links, tasks, SqlRepo};
+
+#[async_trait]
+impl<DB> ShelfRepo for SqlRepo<DB>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    Note: for<'r> FromRow<'r, DB::Row>,
+    File: for<'r> FromRow<'r, DB::Row>,
+    Shelf: for<'r> FromRow<'r, DB::Row>,
+    IDWrapper: for<'r> FromRow<'r, DB::Row>,
+{
+    async fn get_or_create(&self, user_id: i32) -> RepoResult<Shelf> {
+        let db_res = sqlx::query_as::<_, Shelf>("SELECT * FROM shelves WHERE user_id = $1;")
+            .bind(user_id)
+            .fetch_one(&self.pool)
+            .await;
+
+        let mut shelf = match db_res {
+            Ok(shelf) => shelf,
+            Err(sqlx::Error::RowNotFound) => {
+                sqlx::query_as::<_, Shelf>("INSERT INTO shelves (user_id, text) VALUES ($1, '') RETURNING *;")
+                    .bind(user_id)
+                    .fetch_one(&self.pool)
+                    .await?
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        let files = sqlx::query_as::<_, File>(r"
+            SELECT f.*, sf.shelf_id AS attach_id FROM files AS f
+            INNER JOIN shelf_files AS sf ON sf.file_id = f.id
+            WHERE sf.shelf_id = $1
+            ORDER BY f.id ASC;
+        ")
+            .bind(shelf.id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        shelf.files = files;
+        Ok(shelf)
+    }
+
+    async fn update_text(&self, user_id: i32, text: &str) -> RepoResult<Shelf> {
+        let updated_shelf = sqlx::query_as::<_, Shelf>(&format!(r"
+            UPDATE shelves
+            SET text = $1, last_edited = {}, times_edited = times_edited + 1
+            WHERE user_id = $2 RETURNING *;
+        ", DB::DIALECT.now()))
+            .bind(text).bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(updated_shelf)
+    }
+
+    async fn clear(&self, user_id: i32) -> RepoResult<Shelf> {
+        let mut transaction = self.pool.begin().await?;
+
+        let shelf = sqlx::query_as::<_, Shelf>(&format!(r"
+            UPDATE shelves
+            SET text = '', last_edited = {}, times_edited = times_edited + 1
+            WHERE user_id = $1 RETURNING *;
+        ", DB::DIALECT.now()))
+            .bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let file_ids: Vec<_> = sqlx::query_as::<_, IDWrapper>("DELETE FROM shelf_files WHERE shelf_id = $1 RETURNING file_id AS id;")
+            .bind(shelf.id)
+            .fetch_all(&mut *transaction)
+            .await?
+            .iter()
+            .map(|v| v.id)
+            .collect();
+
+        let files = match file_ids.len() {
+            0 => Vec::new(),
+            _ => sqlx::query_as::<_, File>(&fill_tuple_placeholder(
+                "DELETE FROM files WHERE user_id = $1 AND id IN () RETURNING *;",
+                &file_ids, 1,
+            ))
+                .bind(user_id).bind_iter(file_ids)
+                .fetch_all(&mut *transaction)
+                .await.map_err(ServiceError::from_delete)?
+        };
+
+        let hashes: Vec<_> = files.into_iter().map(|f| f.hash).collect();
+        outbox::enqueue(&mut *transaction, &hashes).await?;
+
+        transaction.commit().await?;
+
+        Ok(shelf)
+    }
+
+    async fn convert_to_note(&self, user_id: i32, title: &str, text: &str) -> RepoResult<Shelf> {
+        let mut transaction = self.pool.begin().await?;
+
+        let shelf = sqlx::query_as::<_, Shelf>(&format!(r"
+            UPDATE shelves
+            SET text = '', last_edited = {}, times_edited = times_edited + 1
+            WHERE user_id = $1 RETURNING *;
+        ", DB::DIALECT.now()))
+            .bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let file_ids: Vec<_> = sqlx::query_as::<_, IDWrapper>("DELETE FROM shelf_files WHERE shelf_id = $1 RETURNING file_id AS id;")
+            .bind(shelf.id)
+            .fetch_all(&mut *transaction)
+            .await?
+            .iter()
+            .map(|v| v.id)
+            .collect();
+
+        let note = sqlx::query_as::<_, Note>("INSERT INTO notes (user_id, title, text) VALUES ($1, $2, $3) RETURNING *;")
+            .bind(user_id).bind(title).bind(text)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        links::save_links(&mut transaction, user_id, note.id, text).await?;
+        tasks::save_tasks(&mut transaction, note.id, text).await?;
+        links::resolve_links(&mut transaction, user_id).await?;
+
+        if !file_ids.is_empty() {
+            sqlx::query(&fill_values_placeholder("INSERT INTO note_files (note_id, file_id) VALUES ();", file_ids.len(), 2, 0))
+                .bind_iter(file_ids.iter().flat_map(|&file_id| [note.id, file_id]))
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        Ok(shelf)
+    }
+}
@@ -0,0 +1,181 @@
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Type};
+use tonic::async_trait;
+
+use crate::error::ServiceError;
+use crate::proto::tags::{tag_sort, Tag, TagSort};
+use crate::repo::{batch, query::{build_read_tags_query_str, SqlDatabase}, RepoResult, TagRepo};
+use crate::types::{fill_tuple_placeholder, BindIter, IDWrapper};
+
+use super::SqlRepo;
+
+#[async_trait]
+impl<DB> TagRepo for SqlRepo<DB>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    for<'q> Option<&'q str>: Encode<'q, DB> + Type<DB>,
+    Tag: for<'r> FromRow<'r, DB::Row>,
+    IDWrapper: for<'r> FromRow<'r, DB::Row>,
+{
+    async fn create(&self, user_id: i32, name: &str, color: Option<&str>, icon: Option<&str>, description: &str) -> RepoResult<Tag> {
+        let new_tag = sqlx::query_as::<_, Tag>(r"
+            INSERT INTO tags (user_id, name, color, icon, description, position)
+            VALUES ($1, $2, $3, $4, $5, (SELECT COALESCE(MAX(position) + 1, 0) FROM tags WHERE user_id = $1))
+            RETURNING *;
+        ")
+            .bind(user_id).bind(name).bind(color).bind(icon).bind(description)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(new_tag)
+    }
+
+    async fn list(&self, user_id: i32, sort: &TagSort) -> RepoResult<Vec<Tag>> {
+        let tags = sqlx::query_as::<_, Tag>(&build_read_tags_query_str(sort))
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(tags)
+    }
+
+    async fn update(&self, id: i32, user_id: i32, name: &str, color: Option<&str>, icon: Option<&str>, description: Option<&str>) -> RepoResult<Tag> {
+        let updated_tag = sqlx::query_as::<_, Tag>(r"
+            UPDATE tags SET
+                name = $1,
+                color = CASE WHEN $2 IS NULL THEN color ELSE NULLIF($2, '') END,
+                icon = CASE WHEN $3 IS NULL THEN icon ELSE NULLIF($3, '') END,
+                description = COALESCE($4, description)
+            WHERE id = $5 AND user_id = $6
+            RETURNING *;
+        ")
+            .bind(name).bind(color).bind(icon).bind(description).bind(id).bind(user_id)
+            .fetch_one(&self.pool)
+            .await?;
+
+        Ok(updated_tag)
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM note_tags WHERE tag_id = $1;")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query("DELETE FROM template_tags WHERE tag_id = $1;")
+            .bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query_as::<_, IDWrapper>("DELETE FROM tags WHERE id = $1 AND user_id = $2 RETURNING id;")
+            .bind(id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await.map_err(ServiceError::from_delete)?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn merge(&self, user_id: i32, source_ids: &[i32], target_id: i32) -> RepoResult<Tag> {
+        let mut transaction = self.pool.begin().await?;
+
+        let tag_ids: Vec<_> = source_ids.iter().copied().chain([target_id]).collect();
+
+        let owned = sqlx::query_as::<_, IDWrapper>(&fill_tuple_placeholder("SELECT id FROM tags WHERE user_id = $1 AND id IN ();", &tag_ids, 1))
+            .bind(user_id).bind_iter(&tag_ids)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        batch::check_merge_tags(source_ids, target_id, &owned.into_iter().map(|w| w.id).collect())?;
+
+        // re-pointing the notes of the source tags, except for the ones that already have the target tag.
+        // the WHERE also keeps sqlite from parsing the ON CONFLICT as a join constraint
+
+        sqlx::query(&fill_tuple_placeholder(
+            r"
+                INSERT INTO note_tags (note_id, tag_id)
+                SELECT DISTINCT note_id, $1 FROM note_tags
+                WHERE tag_id IN ()
+                ON CONFLICT DO NOTHING;
+            ",
+            source_ids, 1,
+        ))
+            .bind(target_id).bind_iter(source_ids)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query(&fill_tuple_placeholder("DELETE FROM note_tags WHERE tag_id IN ();", source_ids, 0))
+            .bind_iter(source_ids)
+            .execute(&mut *transaction)
+            .await?;
+
+        // the templates of the source tags get the target tag too
+
+        sqlx::query(&fill_tuple_placeholder(
+            r"
+                INSERT INTO template_tags (template_id, tag_id)
+                SELECT DISTINCT template_id, $1 FROM template_tags
+                WHERE tag_id IN ()
+                ON CONFLICT DO NOTHING;
+            ",
+            source_ids, 1,
+        ))
+            .bind(target_id).bind_iter(source_ids)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query(&fill_tuple_placeholder("DELETE FROM template_tags WHERE tag_id IN ();", source_ids, 0))
+            .bind_iter(source_ids)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query(&fill_tuple_placeholder("DELETE FROM tags WHERE user_id = $1 AND id IN ();", source_ids, 1))
+            .bind(user_id).bind_iter(source_ids)
+            .execute(&mut *transaction)
+            .await.map_err(ServiceError::from_delete)?;
+
+        let target = sqlx::query_as::<_, Tag>(r"
+            SELECT t.*, CAST(COUNT(nt.note_id) AS INT) AS usage_count FROM tags AS t
+            LEFT JOIN note_tags AS nt ON nt.tag_id = t.id
+            WHERE t.id = $1
+            GROUP BY t.id;
+        ")
+            .bind(target_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(target)
+    }
+
+    async fn reorder(&self, user_id: i32, tag_ids: &[i32]) -> RepoResult<Vec<Tag>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let current = sqlx::query_as::<_, IDWrapper>("SELECT id FROM tags WHERE user_id = $1 ORDER BY position, id;")
+            .bind(user_id)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        batch::check_owned_tags("tag_ids", tag_ids, &current.iter().map(|w| w.id).collect())?;
+
+        let rest = current.iter().map(|w| w.id).filter(|id| !tag_ids.contains(id));
+
+        for (position, id) in tag_ids.iter().copied().chain(rest).enumerate() {
+            sqlx::query("UPDATE tags SET position = $1 WHERE id = $2;")
+                .bind(position as i32).bind(id)
+                .execute(&mut *transaction)
+                .await?;
+        }
+
+        transaction.commit().await?;
+
+        TagRepo::list(self, user_id, &TagSort { sort_field: tag_sort::Field::Position.into(), sort_type: tag_sort::Type::Asc.into() }).await
+    }
+}
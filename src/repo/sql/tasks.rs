@@ -0,0 +1,88 @@
+//! keeps the note_tasks table in sync with the texts of the notes
+
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Transaction, Type};
+
+use crate::repo::tasks;
+use crate::repo::{query::SqlDatabase, RepoResult};
+use crate::types::fill_values_placeholder;
+
+use super::SqlRepo;
+
+/// how many notes get their tasks saved again in a single transaction
+const REINDEX_BATCH_SIZE: i64 = 500;
+
+/// replaces the tasks of the note with the ones in its text
+pub async fn save_tasks<DB>(transaction: &mut Transaction<'_, DB>, note_id: i32, text: &str) -> RepoResult<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+{
+    sqlx::query("DELETE FROM note_tasks WHERE note_id = $1;")
+        .bind(note_id)
+        .execute(&mut **transaction)
+        .await?;
+
+    let parsed = tasks::parse(text);
+
+    if parsed.is_empty() {
+        return Ok(());
+    }
+
+    let query_str = fill_values_placeholder("INSERT INTO note_tasks (note_id, position, text, done) VALUES ();", parsed.len(), 4, 0);
+    let mut query = sqlx::query(&query_str);
+
+    for (position, task) in parsed.into_iter().enumerate() {
+        query = query.bind(note_id).bind(position as i32).bind(task.text).bind(task.done);
+    }
+
+    query.execute(&mut **transaction).await?;
+
+    Ok(())
+}
+
+impl<DB> SqlRepo<DB>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> i64: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    (i32, String): for<'r> FromRow<'r, DB::Row>,
+{
+    /// saves the tasks of every note again, for the notes that were written before the tasks were saved.
+    /// returns the amount of the notes. the notes are locked, so that a note written in the meantime doesn't get its old tasks
+    pub async fn reindex_tasks(&self) -> RepoResult<u64> {
+        let mut last_id = 0;
+        let mut reindexed = 0;
+
+        loop {
+            let mut transaction = self.pool.begin().await?;
+
+            let notes = sqlx::query_as::<_, (i32, String)>(&format!(
+                "SELECT id, text FROM notes WHERE id > $1 ORDER BY id LIMIT $2 {};", DB::DIALECT.for_update(),
+            ))
+                .bind(last_id).bind(REINDEX_BATCH_SIZE)
+                .fetch_all(&mut *transaction)
+                .await?;
+
+            let Some((id, _)) = notes.last() else {
+                return Ok(reindexed);
+            };
+
+            last_id = *id;
+            reindexed += notes.len() as u64;
+
+            for (note_id, text) in notes {
+                save_tasks(&mut transaction, note_id, &text).await?;
+            }
+
+            transaction.commit().await?;
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use std::collections::HashSet;
+
+use sqlx::{database::HasArguments, Encode, Executor, FromRow, IntoArguments, Transaction, Type};
+use tonic::async_trait;
+
+use crate::error::ServiceError;
+use crate::proto::{notes::Note, tags::Tag, templates::Template};
+use crate::repo::{batch, query::SqlDatabase, RepoResult, TemplateRepo};
+use crate::types::{fill_tuple_placeholder, fill_values_placeholder, BindIter, IDWrapper};
+
+use super::{links, tasks, SqlRepo};
+
+/// makes sure that the tags belong to the user, and replaces the tags of the template with them
+async fn set_tags<DB>(transaction: &mut Transaction<'_, DB>, user_id: i32, template_id: i32, tag_ids: &[i32]) -> RepoResult<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    IDWrapper: for<'r> FromRow<'r, DB::Row>,
+{
+    sqlx::query("DELETE FROM template_tags WHERE template_id = $1;")
+        .bind(template_id)
+        .execute(&mut **transaction)
+        .await?;
+
+    if tag_ids.is_empty() {
+        return Ok(());
+    }
+
+    let owned = sqlx::query_as::<_, IDWrapper>(&fill_tuple_placeholder("SELECT id FROM tags WHERE user_id = $1 AND id IN ();", tag_ids, 1))
+        .bind(user_id).bind_iter(tag_ids)
+        .fetch_all(&mut **transaction)
+        .await?;
+
+    batch::check_owned_tags("tag_ids", tag_ids, &owned.into_iter().map(|w| w.id).collect::<HashSet<_>>())?;
+
+    sqlx::query(&fill_values_placeholder("INSERT INTO template_tags (template_id, tag_id) VALUES ();", tag_ids.len(), 2, 0))
+        .bind_iter(tag_ids.iter().flat_map(|&tag_id| [template_id, tag_id]))
+        .execute(&mut **transaction)
+        .await?;
+
+    Ok(())
+}
+
+/// fills in the tag ids of the user's templates
+async fn assign_tags<DB>(transaction: &mut Transaction<'_, DB>, user_id: i32, templates: &mut [Template]) -> RepoResult<()>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    (i32, i32): for<'r> FromRow<'r, DB::Row>,
+{
+    let template_tags = sqlx::query_as::<_, (i32, i32)>(r"
+        SELECT tt.template_id, tt.tag_id FROM template_tags AS tt
+        INNER JOIN note_templates AS t ON t.id = tt.template_id
+        WHERE t.user_id = $1
+        ORDER BY tt.tag_id;
+    ")
+        .bind(user_id)
+        .fetch_all(&mut **transaction)
+        .await?;
+
+    for template in templates {
+        template.tag_ids = template_tags.iter().filter(|(id, _)| *id == template.id).map(|(_, tag_id)| *tag_id).collect();
+    }
+
+    Ok(())
+}
+
+#[async_trait]
+impl<DB> TemplateRepo for SqlRepo<DB>
+where
+    DB: SqlDatabase,
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+    for<'q> <DB as HasArguments<'q>>::Arguments: IntoArguments<'q, DB>,
+    for<'q> i32: Encode<'q, DB> + Type<DB>,
+    for<'q> bool: Encode<'q, DB> + Type<DB>,
+    for<'q> String: Encode<'q, DB> + Type<DB>,
+    for<'q> &'q str: Encode<'q, DB> + Type<DB>,
+    Note: for<'r> FromRow<'r, DB::Row>,
+    Tag: for<'r> FromRow<'r, DB::Row>,
+    Template: for<'r> FromRow<'r, DB::Row>,
+    IDWrapper: for<'r> FromRow<'r, DB::Row>,
+    (i32, i32): for<'r> FromRow<'r, DB::Row>,
+{
+    async fn create(&self, user_id: i32, name: &str, title: &str, text: &str, tag_ids: &[i32]) -> RepoResult<Template> {
+        let mut transaction = self.pool.begin().await?;
+
+        let mut new_template = sqlx::query_as::<_, Template>("INSERT INTO note_templates (user_id, name, title, text) VALUES ($1, $2, $3, $4) RETURNING *;")
+            .bind(user_id).bind(name).bind(title).bind(text)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        set_tags(&mut transaction, user_id, new_template.id, tag_ids).await?;
+        transaction.commit().await?;
+
+        new_template.tag_ids = tag_ids.to_vec();
+        new_template.tag_ids.sort_unstable();
+
+        Ok(new_template)
+    }
+
+    async fn list(&self, user_id: i32) -> RepoResult<Vec<Template>> {
+        let mut transaction = self.pool.begin().await?;
+
+        let mut templates = sqlx::query_as::<_, Template>("SELECT * FROM note_templates WHERE user_id = $1 ORDER BY LOWER(name), id;")
+            .bind(user_id)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        assign_tags(&mut transaction, user_id, &mut templates).await?;
+        transaction.commit().await?;
+
+        Ok(templates)
+    }
+
+    async fn get(&self, id: i32, user_id: i32) -> RepoResult<Template> {
+        let mut transaction = self.pool.begin().await?;
+
+        let mut template = sqlx::query_as::<_, Template>("SELECT * FROM note_templates WHERE id = $1 AND user_id = $2;")
+            .bind(id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        assign_tags(&mut transaction, user_id, std::slice::from_mut(&mut template)).await?;
+        transaction.commit().await?;
+
+        Ok(template)
+    }
+
+    async fn update(&self, id: i32, user_id: i32, name: &str, title: &str, text: &str, tag_ids: &[i32]) -> RepoResult<Template> {
+        let mut transaction = self.pool.begin().await?;
+
+        let mut updated_template = sqlx::query_as::<_, Template>(&format!(r"
+            UPDATE note_templates
+            SET name = $1, title = $2, text = $3, last_edited = {}
+            WHERE id = $4 AND user_id = $5
+            RETURNING *;
+        ", DB::DIALECT.now()))
+            .bind(name).bind(title).bind(text).bind(id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        set_tags(&mut transaction, user_id, id, tag_ids).await?;
+        transaction.commit().await?;
+
+        updated_template.tag_ids = tag_ids.to_vec();
+        updated_template.tag_ids.sort_unstable();
+
+        Ok(updated_template)
+    }
+
+    async fn delete(&self, id: i32, user_id: i32) -> RepoResult<()> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM template_tags WHERE template_id IN (SELECT id FROM note_templates WHERE id = $1 AND user_id = $2);")
+            .bind(id).bind(user_id)
+            .execute(&mut *transaction)
+            .await?;
+
+        sqlx::query_as::<_, IDWrapper>("DELETE FROM note_templates WHERE id = $1 AND user_id = $2 RETURNING id;")
+            .bind(id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await.map_err(ServiceError::from_delete)?;
+
+        transaction.commit().await?;
+
+        Ok(())
+    }
+
+    async fn create_note(&self, id: i32, user_id: i32, title: &str, text: &str) -> RepoResult<Note> {
+        let mut transaction = self.pool.begin().await?;
+
+        sqlx::query_as::<_, IDWrapper>("SELECT id FROM note_templates WHERE id = $1 AND user_id = $2;")
+            .bind(id).bind(user_id)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        let mut new_note = sqlx::query_as::<_, Note>("INSERT INTO notes (user_id, title, text) VALUES ($1, $2, $3) RETURNING *;")
+            .bind(user_id).bind(title).bind(text)
+            .fetch_one(&mut *transaction)
+            .await?;
+
+        links::save_links(&mut transaction, user_id, new_note.id, text).await?;
+        tasks::save_tasks(&mut transaction, new_note.id, text).await?;
+        links::resolve_links(&mut transaction, user_id).await?;
+
+        sqlx::query("INSERT INTO note_tags (note_id, tag_id) SELECT $1, tag_id FROM template_tags WHERE template_id = $2;")
+            .bind(new_note.id).bind(id)
+            .execute(&mut *transaction)
+            .await?;
+
+        new_note.tags = sqlx::query_as::<_, Tag>(r"
+            SELECT t.*, nt.note_id FROM tags AS t
+            INNER JOIN note_tags AS nt ON nt.tag_id = t.id
+            WHERE nt.note_id = $1
+            ORDER BY t.id;
+        ")
+            .bind(new_note.id)
+            .fetch_all(&mut *transaction)
+            .await?;
+
+        transaction.commit().await?;
+
+        Ok(new_note)
+    }
+}
@@ -0,0 +1,99 @@
+//! the markdown tasks of the note texts, shared by the repositories.
+//! a task is a list item with a checkbox, like `- [ ] buy milk`, `* [x] done` or `1. [ ] first`
+
+use crate::error::{ServiceError, Violation};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedTask {
+    pub text: String,
+    pub done: bool,
+    /// the byte offset of the mark between the brackets of the checkbox
+    mark: usize,
+}
+
+/// the tasks of the text in their order, leaving out the ones in the fenced code blocks
+pub fn parse(text: &str) -> Vec<ParsedTask> {
+    let mut tasks = Vec::new();
+    let mut fence: Option<&str> = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let start = offset;
+        offset += line.len();
+
+        let content = line.trim_end_matches(['\n', '\r']);
+        let trimmed = content.trim_start();
+
+        // a fence is closed by the same kind of fence that opened it
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            fence = match fence {
+                None => Some(marker),
+                Some(open) if open == marker => None,
+                open => open,
+            };
+            continue;
+        }
+
+        if fence.is_some() {
+            continue;
+        }
+
+        let Some(rest) = strip_list_marker(trimmed) else {
+            continue;
+        };
+
+        let checkbox = rest.trim_start();
+        let done = match checkbox.as_bytes() {
+            [b'[', b' ', b']', ..] => false,
+            [b'[', b'x' | b'X', b']', ..] => true,
+            _ => continue,
+        };
+
+        // the checkbox has to be followed by a space or the end of the line, like in `- [ ] task`
+        let after = &checkbox[3..];
+        if !after.is_empty() && !after.starts_with(char::is_whitespace) {
+            continue;
+        }
+
+        let mark = start + (content.len() - checkbox.len()) + 1;
+        tasks.push(ParsedTask { text: after.trim().to_owned(), done, mark });
+    }
+
+    tasks
+}
+
+/// the rest of a `-`, `*`, `+` or `1.` list item, without the marker
+fn strip_list_marker(line: &str) -> Option<&str> {
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let rest = match digits {
+        0 => line.strip_prefix(['-', '*', '+'])?,
+        1..=9 => line[digits..].strip_prefix(['.', ')'])?,
+        _ => return None,
+    };
+
+    rest.starts_with([' ', '\t']).then_some(rest)
+}
+
+/// the text with the checkbox of the task at the `position` flipped, or None if there is no such task
+pub fn toggle(text: &str, position: usize) -> Option<String> {
+    let task = parse(text).into_iter().nth(position)?;
+
+    let mut toggled = text.to_owned();
+    toggled.replace_range(task.mark..task.mark + 1, if task.done { " " } else { "x" });
+
+    Some(toggled)
+}
+
+pub fn task_not_found() -> ServiceError {
+    ServiceError::not_found("TASK_NOT_FOUND", "the note doesn't have a task at this position")
+}
+
+/// the note was edited after the client has read its times_edited
+pub fn note_edited() -> ServiceError {
+    ServiceError::FailedPrecondition(Violation {
+        field: Some("times_edited".into()),
+        reason: "NOTE_EDITED",
+        description: "the note was edited in the meantime, read it again and retry".into(),
+    })
+}
@@ -0,0 +1,142 @@
+use axum::body::StreamBody;
+use axum::extract::{multipart::Field, DefaultBodyLimit, Multipart, Path, Query, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use tokio_stream::StreamExt;
+use tonic::Status;
+
+use crate::error::{ServiceError, Violation};
+use crate::idempotency;
+use crate::proto::files::files_server::Files;
+use crate::proto::files::{create_file_metadata::AttachId, CreateFileMetadata, CreateFileReq, DeleteFileReq, DownloadFileReq, File};
+use crate::rate_limit::RequestLimiter;
+use crate::user_auth::VerifiedUser;
+
+use super::{RestResult, RestState, UserQuery};
+
+pub fn routes() -> Router<RestState> {
+    Router::new()
+        // the size of the uploads is limited by the file size in the form and the rate limits
+        .route("/files", post(upload_file).layer(DefaultBodyLimit::disable()))
+        // downloaded by the hash, and deleted by the id
+        .route("/files/:file", get(download_file).delete(delete_file))
+}
+
+fn invalid_multipart(description: String) -> ServiceError {
+    ServiceError::InvalidArgument(Violation { field: None, reason: "INVALID_MULTIPART", description })
+}
+
+/// reads a text field of the form as a number
+async fn number_field<T: std::str::FromStr>(field: Field<'_>, name: &str) -> Result<T, ServiceError> {
+    let value = field.text().await.map_err(|e| invalid_multipart(e.body_text()))?;
+
+    value.trim()
+        .parse()
+        .map_err(|_| ServiceError::invalid_field(name, "INVALID_NUMBER", format!("{name} must be a number")))
+}
+
+/// a multipart/form-data upload. the note_id or the shelf_id field and the size field (in bytes)
+/// have to come before the file field, since the file's data is saved while it is being received
+async fn upload_file(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    mut multipart: Multipart,
+) -> RestResult<(StatusCode, Json<File>)> {
+
+    let request = state.authorize(&headers, "/files.Files/CreateFile", ()).await?;
+    let verified_user = request.extensions().get::<VerifiedUser>().cloned();
+    let request_limiter = request.extensions().get::<RequestLimiter>().cloned();
+    let idempotency_key = idempotency::key(&request)?;
+
+    let mut attach_id = None;
+    let mut file_size = None;
+
+    let file_field = loop {
+        let field = multipart.next_field().await
+            .map_err(|e| invalid_multipart(e.body_text()))?
+            .ok_or(ServiceError::missing_field("file"))?;
+
+        match field.name() {
+            Some("note_id") => attach_id = Some(AttachId::NoteId(number_field(field, "note_id").await?)),
+            Some("shelf_id") => attach_id = Some(AttachId::ShelfId(number_field(field, "shelf_id").await?)),
+            Some("size") => file_size = Some(number_field(field, "size").await?),
+            Some("file") => break field,
+            _ => continue,
+        }
+    };
+
+    let metadata = CreateFileMetadata {
+        user_id: query.user_id,
+        attach_id,
+        name: file_field.file_name().unwrap_or_default().to_owned(),
+        file_size: file_size.ok_or(ServiceError::missing_field("size"))?,
+    };
+
+    let first_part = CreateFileReq { metadata: Some(metadata), data: Vec::new() };
+
+    let data_parts = file_field.map(|chunk| match chunk {
+        Ok(data) => Ok(CreateFileReq { metadata: None, data: data.to_vec() }),
+        Err(e) => Err(Status::invalid_argument(e.body_text())),
+    });
+
+    let stream = tokio_stream::once(Ok(first_part)).chain(data_parts);
+    let file = state.app.receive_file(stream, verified_user.as_ref(), request_limiter.as_ref(), idempotency_key).await?;
+
+    Ok((StatusCode::CREATED, Json(file)))
+}
+
+/// the content-disposition of a download, with the file name percent-encoded as utf-8
+fn content_disposition(file_name: &str) -> HeaderValue {
+    let encoded: String = file_name.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (b as char).to_string(),
+            _ => format!("%{b:02X}"),
+        })
+        .collect();
+
+    HeaderValue::from_str(&format!("attachment; filename*=UTF-8''{encoded}")).unwrap()
+}
+
+async fn download_file(
+    State(state): State<RestState>,
+    Path(file_hash): Path<String>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<Response> {
+
+    let request = state.authorize(&headers, "/files.Files/DownloadFile", DownloadFileReq { file_hash, user_id: query.user_id }).await?;
+    let mut stream = state.app.download_file(request).await?.into_inner();
+
+    // the first part only has the metadata
+
+    let metadata = stream.next().await
+        .transpose()?
+        .and_then(|part| part.metadata)
+        .ok_or(ServiceError::Internal("FILE_METADATA_MISSING"))?;
+
+    let body = StreamBody::new(stream.map(|part| part.map(|part| part.data)));
+
+    let headers = [
+        (header::CONTENT_TYPE, HeaderValue::from_static("application/octet-stream")),
+        (header::CONTENT_LENGTH, HeaderValue::from(metadata.size)),
+        (header::CONTENT_DISPOSITION, content_disposition(&metadata.name)),
+    ];
+
+    Ok((headers, body).into_response())
+}
+
+async fn delete_file(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<StatusCode> {
+
+    let request = state.authorize(&headers, "/files.Files/DeleteFile", DeleteFileReq { id, user_id: query.user_id }).await?;
+    state.app.delete_file(request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,202 @@
+//! the JSON/HTTP gateway. its routes call the same handlers as the gRPC services,
+//! after the same interceptor has checked the headers of the request.
+//! the responses are the proto messages serialized with their field names
+
+use std::future::Future;
+use std::net::SocketAddr;
+
+use axum::http::{header::RETRY_AFTER, HeaderMap, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::{Json, Router, Server};
+use hyper::server::accept;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tonic::{metadata::MetadataMap, transport::Body, Code, Status};
+use tonic_middleware::RequestInterceptor;
+use tonic_types::StatusExt;
+
+use crate::config::WebConfig;
+use crate::rate_limit::RequestLimiter;
+use crate::server::cors_layer;
+use crate::tls::{self, SharedServerConfig};
+use crate::types::{AppState, Interceptor};
+use crate::user_auth::VerifiedUser;
+
+mod files;
+mod notes;
+mod reminders;
+mod shelves;
+mod tags;
+mod templates;
+
+#[derive(Clone)]
+pub struct RestState {
+    app: AppState,
+    interceptor: Interceptor,
+}
+
+pub type RestResult<T> = Result<T, RestError>;
+
+/// a gRPC status, returned as an http status with a json body
+#[derive(Debug)]
+pub struct RestError(Status);
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    /// the UPPER_SNAKE_CASE gRPC code
+    code: &'static str,
+    message: &'a str,
+    reason: Option<String>,
+    field_violations: Vec<FieldViolation>,
+}
+
+#[derive(Serialize)]
+struct FieldViolation {
+    field: String,
+    description: String,
+}
+
+/// the user_id query parameter. it can be left out when the user tokens are verified,
+/// since an unset user id gets replaced with the token's one
+#[derive(Deserialize)]
+struct UserQuery {
+    #[serde(default)]
+    user_id: i32,
+}
+
+impl RestState {
+    /// runs the interceptor of the gRPC services for the `method`, like "/notes.Notes/ReadNotes",
+    /// and returns the `message` as a request with the headers as its metadata, and the verified user
+    /// and the rate limiter of the call
+    async fn authorize<T>(&self, headers: &HeaderMap, method: &'static str, message: T) -> RestResult<tonic::Request<T>> {
+        let mut grpc_request = hyper::Request::builder()
+            .method(Method::POST)
+            .uri(method)
+            .body(Body::empty())
+            .unwrap();
+
+        *grpc_request.headers_mut() = headers.clone();
+
+        let mut extensions = self.interceptor.intercept(grpc_request).await?.into_parts().0.extensions;
+
+        let mut request = tonic::Request::new(message);
+        *request.metadata_mut() = MetadataMap::from_headers(headers.clone());
+
+        if let Some(verified_user) = extensions.remove::<VerifiedUser>() {
+            request.extensions_mut().insert(verified_user);
+        }
+
+        if let Some(request_limiter) = extensions.remove::<RequestLimiter>() {
+            request.extensions_mut().insert(request_limiter);
+        }
+
+        Ok(request)
+    }
+}
+
+impl From<Status> for RestError {
+    fn from(status: Status) -> Self {
+        Self(status)
+    }
+}
+
+impl From<crate::error::ServiceError> for RestError {
+    fn from(error: crate::error::ServiceError) -> Self {
+        Self(error.into())
+    }
+}
+
+/// the usual http status of each gRPC code
+fn http_status(code: Code) -> (StatusCode, &'static str) {
+    match code {
+        Code::Ok => (StatusCode::OK, "OK"),
+        Code::Cancelled => (StatusCode::BAD_REQUEST, "CANCELLED"),
+        Code::Unknown => (StatusCode::INTERNAL_SERVER_ERROR, "UNKNOWN"),
+        Code::InvalidArgument => (StatusCode::BAD_REQUEST, "INVALID_ARGUMENT"),
+        Code::DeadlineExceeded => (StatusCode::GATEWAY_TIMEOUT, "DEADLINE_EXCEEDED"),
+        Code::NotFound => (StatusCode::NOT_FOUND, "NOT_FOUND"),
+        Code::AlreadyExists => (StatusCode::CONFLICT, "ALREADY_EXISTS"),
+        Code::PermissionDenied => (StatusCode::FORBIDDEN, "PERMISSION_DENIED"),
+        Code::ResourceExhausted => (StatusCode::TOO_MANY_REQUESTS, "RESOURCE_EXHAUSTED"),
+        Code::FailedPrecondition => (StatusCode::BAD_REQUEST, "FAILED_PRECONDITION"),
+        Code::Aborted => (StatusCode::CONFLICT, "ABORTED"),
+        Code::OutOfRange => (StatusCode::BAD_REQUEST, "OUT_OF_RANGE"),
+        Code::Unimplemented => (StatusCode::NOT_IMPLEMENTED, "UNIMPLEMENTED"),
+        Code::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+        Code::Unavailable => (StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE"),
+        Code::DataLoss => (StatusCode::INTERNAL_SERVER_ERROR, "DATA_LOSS"),
+        Code::Unauthenticated => (StatusCode::UNAUTHORIZED, "UNAUTHENTICATED"),
+    }
+}
+
+impl IntoResponse for RestError {
+    fn into_response(self) -> Response {
+        let status = self.0;
+        let (http_status, code) = http_status(status.code());
+        let details = status.get_error_details();
+
+        let body = ErrorBody {
+            code,
+            message: status.message(),
+            reason: details.error_info().map(|info| info.reason.clone()),
+            field_violations: details.bad_request()
+                .map(|bad_request| bad_request.field_violations
+                    .iter()
+                    .map(|v| FieldViolation { field: v.field.clone(), description: v.description.clone() })
+                    .collect())
+                .unwrap_or_default(),
+        };
+
+        let mut response = (http_status, Json(body)).into_response();
+
+        let retry_after = status.metadata().get("retry-after").and_then(|v| v.to_str().ok());
+        if let Some(retry_after) = retry_after.and_then(|v| HeaderValue::from_str(v).ok()) {
+            response.headers_mut().insert(RETRY_AFTER, retry_after);
+        }
+
+        response
+    }
+}
+
+/// serves the gateway in a separate task, with tls if it is configured
+pub async fn spawn_server(
+    state: AppState,
+    interceptor: Interceptor,
+    web: &WebConfig,
+    port: u16,
+    tls_config: Option<SharedServerConfig>,
+) -> anyhow::Result<()> {
+    let app = Router::new()
+        .merge(notes::routes())
+        .merge(tags::routes())
+        .merge(files::routes())
+        .merge(shelves::routes())
+        .merge(templates::routes())
+        .merge(reminders::routes())
+        .layer(cors_layer(web))
+        .with_state(RestState { app: state, interceptor });
+
+    let addr = SocketAddr::from(([0, 0, 0, 0, 0, 0, 0, 0], port));
+    let listener = TcpListener::bind(addr).await?;
+
+    match tls_config {
+        None => {
+            spawn(Server::from_tcp(listener.into_std()?)?.serve(app.into_make_service()));
+            log::info!("REST gateway listening on {addr}");
+        },
+        Some(tls_config) => {
+            spawn(Server::builder(accept::from_stream(tls::incoming(listener, tls_config))).serve(app.into_make_service()));
+            log::info!("REST gateway listening on {addr} (TLS)");
+        },
+    }
+
+    Ok(())
+}
+
+fn spawn(server: impl Future<Output = hyper::Result<()>> + Send + 'static) {
+    tokio::spawn(async move {
+        if let Err(e) = server.await {
+            log::error!("REST SERVER ERR: {:?}", e);
+        }
+    });
+}
@@ -0,0 +1,477 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{delete, get, post, put};
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::error::ServiceError;
+use crate::proto::notes::notes_server::Notes;
+use crate::proto::notes::{filter_due, filter_state, sort, AttachTagReq, BatchDeleteNotesReq, BatchMoveNotesReq, BatchResult, BatchSetNoteStateReq, BatchTagsReq, CreateNoteReq, DeleteNoteReq, DetachTagReq, FilterDate, FilterDue, FilterSearch, FilterState, FilterTags, Filters, Note, NoteList, NoteState, Pagination, ReadNotesReq, SetNoteStateReq, Sort, UpdateNoteReq, GetBacklinksReq, GetNoteGraphReq, NoteGraph, NoteRefList, ListTasksReq, TaskList, ToggleTaskReq};
+
+use super::{RestResult, RestState, UserQuery};
+
+pub fn routes() -> Router<RestState> {
+    Router::new()
+        .route("/notes", get(read_notes).post(create_note))
+        .route("/notes/:id", put(update_note).delete(delete_note))
+        .route("/notes/:id/tags", post(attach_tag))
+        .route("/notes/:id/tags/:tag_id", delete(detach_tag))
+        .route("/notes/batch/attach", post(batch_attach_tags))
+        .route("/notes/batch/detach", post(batch_detach_tags))
+        .route("/notes/batch/delete", post(batch_delete_notes))
+        .route("/notes/batch/move", post(batch_move_notes))
+        .route("/notes/batch/state", post(batch_set_note_state))
+        .route("/notes/:id/state", put(set_note_state))
+        .route("/notes/:id/backlinks", get(get_backlinks))
+        .route("/notes/graph", get(get_note_graph))
+        .route("/notes/tasks", get(list_tasks))
+        .route("/notes/:id/tasks/:position/toggle", post(toggle_task))
+}
+
+#[derive(Deserialize)]
+struct NoteBody {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct AttachTagBody {
+    tag_id: i32,
+}
+
+#[derive(Deserialize)]
+struct BatchTagsBody {
+    note_ids: Vec<i32>,
+    tag_ids: Vec<i32>,
+    #[serde(default)]
+    partial: bool,
+}
+
+#[derive(Deserialize)]
+struct BatchDeleteBody {
+    note_ids: Vec<i32>,
+    #[serde(default)]
+    partial: bool,
+}
+
+#[derive(Deserialize)]
+struct BatchMoveBody {
+    note_ids: Vec<i32>,
+    from_tag_id: i32,
+    to_tag_id: i32,
+    #[serde(default)]
+    partial: bool,
+}
+
+#[derive(Deserialize)]
+struct NoteStateBody {
+    pinned: Option<bool>,
+    archived: Option<bool>,
+    favorite: Option<bool>,
+}
+
+impl From<NoteStateBody> for NoteState {
+    fn from(body: NoteStateBody) -> Self {
+        NoteState { pinned: body.pinned, archived: body.archived, favorite: body.favorite }
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchStateBody {
+    note_ids: Vec<i32>,
+    #[serde(flatten)]
+    state: NoteStateBody,
+    #[serde(default)]
+    partial: bool,
+}
+
+/// the ReadNotesReq as query parameters. the dates are unix timestamps,
+/// and a date range that only has one end is open on the other one
+#[derive(Deserialize)]
+#[serde(default)]
+struct ReadNotesQuery {
+    user_id: i32,
+    page: i32,
+    per_page: i32,
+    /// date, date_modif or title
+    sort: String,
+    /// asc or desc
+    order: String,
+    pinned_first: bool,
+    /// comma separated tag ids. an empty value finds the notes without tags
+    tags: Option<String>,
+    created_from: Option<i64>,
+    created_to: Option<i64>,
+    edited_from: Option<i64>,
+    edited_to: Option<i64>,
+    search: Option<String>,
+    /// active, archived or all
+    archive: Option<String>,
+    pinned: Option<bool>,
+    favorite: Option<bool>,
+    /// upcoming or overdue
+    due: Option<String>,
+    /// the end of the upcoming reminders, as a unix timestamp
+    due_until: Option<i64>,
+}
+
+impl Default for ReadNotesQuery {
+    fn default() -> Self {
+        Self {
+            user_id: 0,
+            page: 1,
+            per_page: 20,
+            sort: "date".into(),
+            order: "desc".into(),
+            pinned_first: false,
+            tags: None,
+            created_from: None,
+            created_to: None,
+            edited_from: None,
+            edited_to: None,
+            search: None,
+            archive: None,
+            pinned: None,
+            favorite: None,
+            due: None,
+            due_until: None,
+        }
+    }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct GraphQuery {
+    user_id: i32,
+    include_archived: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct TasksQuery {
+    user_id: i32,
+    page: i32,
+    per_page: i32,
+    done: Option<bool>,
+    tag_id: Option<i32>,
+    include_archived: bool,
+}
+
+impl Default for TasksQuery {
+    fn default() -> Self {
+        Self { user_id: 0, page: 1, per_page: 20, done: None, tag_id: None, include_archived: false }
+    }
+}
+
+#[derive(Deserialize)]
+struct ToggleTaskBody {
+    times_edited: i32,
+}
+
+/// the end of a date range that is only open at its start, 9999-12-31
+const LATEST_DATE: i64 = 253402300799;
+
+fn date_range(start: Option<i64>, end: Option<i64>) -> Option<FilterDate> {
+    match (start, end) {
+        (None, None) => None,
+        (start, end) => Some(FilterDate { start: start.unwrap_or(0), end: end.unwrap_or(LATEST_DATE) }),
+    }
+}
+
+impl TryFrom<ReadNotesQuery> for ReadNotesReq {
+    type Error = ServiceError;
+
+    fn try_from(query: ReadNotesQuery) -> Result<Self, Self::Error> {
+        let sort_field = sort::Field::from_str_name(&query.sort.to_uppercase())
+            .ok_or(ServiceError::invalid_field("sort", "INVALID_ENUM_VALUE", "sort must be one of date, date_modif and title"))?;
+
+        let sort_type = sort::Type::from_str_name(&query.order.to_uppercase())
+            .ok_or(ServiceError::invalid_field("order", "INVALID_ENUM_VALUE", "order must be either asc or desc"))?;
+
+        let filter_tags = match query.tags {
+            Some(tags) => {
+                let tag_ids = tags.split(',')
+                    .filter(|id| !id.trim().is_empty())
+                    .map(|id| id.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| ServiceError::invalid_field("tags", "INVALID_ID", "tags must be comma separated tag ids"))?;
+
+                Some(FilterTags { tag_ids })
+            },
+            None => None,
+        };
+
+        let archive = match &query.archive {
+            Some(archive) => filter_state::Archive::from_str_name(&archive.to_uppercase())
+                .ok_or(ServiceError::invalid_field("archive", "INVALID_ENUM_VALUE", "archive must be one of active, archived and all"))?,
+            None => filter_state::Archive::Active,
+        };
+
+        let filter_state = match (&query.archive, query.pinned, query.favorite) {
+            (None, None, None) => None,
+            _ => Some(FilterState { archive: archive.into(), pinned: query.pinned, favorite: query.favorite }),
+        };
+
+        let filter_due = match &query.due {
+            Some(due) => {
+                let due = filter_due::Due::from_str_name(&due.to_uppercase())
+                    .ok_or(ServiceError::invalid_field("due", "INVALID_ENUM_VALUE", "due must be either upcoming or overdue"))?;
+
+                Some(FilterDue { due: due.into(), until: query.due_until.unwrap_or(0) })
+            },
+            None => None,
+        };
+
+        Ok(ReadNotesReq {
+            user_id: query.user_id,
+            pagination: Some(Pagination { page: query.page, per_page: query.per_page }),
+            sort: Some(Sort { sort_field: sort_field.into(), sort_type: sort_type.into(), pinned_first: query.pinned_first }),
+            filters: Some(Filters {
+                filter_tags,
+                filter_date: date_range(query.created_from, query.created_to),
+                filter_date_modif: date_range(query.edited_from, query.edited_to),
+                filter_search: query.search.map(|query| FilterSearch { query }),
+                filter_state,
+                filter_due,
+            }),
+        })
+    }
+}
+
+async fn create_note(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<NoteBody>,
+) -> RestResult<(StatusCode, Json<Note>)> {
+
+    let message = CreateNoteReq { user_id: query.user_id, title: body.title, text: body.text };
+    let request = state.authorize(&headers, "/notes.Notes/CreateNote", message).await?;
+    let note = state.app.create_note(request).await?.into_inner();
+
+    Ok((StatusCode::CREATED, Json(note)))
+}
+
+async fn read_notes(
+    State(state): State<RestState>,
+    Query(query): Query<ReadNotesQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<NoteList>> {
+
+    let request = state.authorize(&headers, "/notes.Notes/ReadNotes", ReadNotesReq::try_from(query)?).await?;
+    let notes = state.app.read_notes(request).await?.into_inner();
+
+    Ok(Json(notes))
+}
+
+async fn update_note(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<NoteBody>,
+) -> RestResult<Json<Note>> {
+
+    let message = UpdateNoteReq { id, user_id: query.user_id, title: body.title, text: body.text };
+    let request = state.authorize(&headers, "/notes.Notes/UpdateNote", message).await?;
+    let note = state.app.update_note(request).await?.into_inner();
+
+    Ok(Json(note))
+}
+
+async fn delete_note(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<StatusCode> {
+
+    let request = state.authorize(&headers, "/notes.Notes/DeleteNote", DeleteNoteReq { id, user_id: query.user_id }).await?;
+    state.app.delete_note(request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn attach_tag(
+    State(state): State<RestState>,
+    Path(note_id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<AttachTagBody>,
+) -> RestResult<StatusCode> {
+
+    let message = AttachTagReq { user_id: query.user_id, note_id, tag_id: body.tag_id };
+    let request = state.authorize(&headers, "/notes.Notes/AttachTag", message).await?;
+    state.app.attach_tag(request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn detach_tag(
+    State(state): State<RestState>,
+    Path((note_id, tag_id)): Path<(i32, i32)>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<StatusCode> {
+
+    let message = DetachTagReq { user_id: query.user_id, note_id, tag_id };
+    let request = state.authorize(&headers, "/notes.Notes/DetachTag", message).await?;
+    state.app.detach_tag(request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn batch_attach_tags(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<BatchTagsBody>,
+) -> RestResult<Json<BatchResult>> {
+
+    let message = BatchTagsReq { user_id: query.user_id, note_ids: body.note_ids, tag_ids: body.tag_ids, partial: body.partial };
+    let request = state.authorize(&headers, "/notes.Notes/BatchAttachTags", message).await?;
+    let result = state.app.batch_attach_tags(request).await?.into_inner();
+
+    Ok(Json(result))
+}
+
+async fn batch_detach_tags(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<BatchTagsBody>,
+) -> RestResult<Json<BatchResult>> {
+
+    let message = BatchTagsReq { user_id: query.user_id, note_ids: body.note_ids, tag_ids: body.tag_ids, partial: body.partial };
+    let request = state.authorize(&headers, "/notes.Notes/BatchDetachTags", message).await?;
+    let result = state.app.batch_detach_tags(request).await?.into_inner();
+
+    Ok(Json(result))
+}
+
+async fn batch_delete_notes(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<BatchDeleteBody>,
+) -> RestResult<Json<BatchResult>> {
+
+    let message = BatchDeleteNotesReq { user_id: query.user_id, note_ids: body.note_ids, partial: body.partial };
+    let request = state.authorize(&headers, "/notes.Notes/BatchDeleteNotes", message).await?;
+    let result = state.app.batch_delete_notes(request).await?.into_inner();
+
+    Ok(Json(result))
+}
+
+async fn batch_move_notes(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<BatchMoveBody>,
+) -> RestResult<Json<BatchResult>> {
+
+    let message = BatchMoveNotesReq {
+        user_id: query.user_id,
+        note_ids: body.note_ids,
+        from_tag_id: body.from_tag_id,
+        to_tag_id: body.to_tag_id,
+        partial: body.partial,
+    };
+
+    let request = state.authorize(&headers, "/notes.Notes/BatchMoveNotes", message).await?;
+    let result = state.app.batch_move_notes(request).await?.into_inner();
+
+    Ok(Json(result))
+}
+
+async fn set_note_state(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<NoteStateBody>,
+) -> RestResult<Json<Note>> {
+
+    let message = SetNoteStateReq { id, user_id: query.user_id, state: Some(body.into()) };
+    let request = state.authorize(&headers, "/notes.Notes/SetNoteState", message).await?;
+    let note = state.app.set_note_state(request).await?.into_inner();
+
+    Ok(Json(note))
+}
+
+async fn batch_set_note_state(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<BatchStateBody>,
+) -> RestResult<Json<BatchResult>> {
+
+    let message = BatchSetNoteStateReq { user_id: query.user_id, note_ids: body.note_ids, state: Some(body.state.into()), partial: body.partial };
+    let request = state.authorize(&headers, "/notes.Notes/BatchSetNoteState", message).await?;
+    let result = state.app.batch_set_note_state(request).await?.into_inner();
+
+    Ok(Json(result))
+}
+
+async fn get_backlinks(
+    State(state): State<RestState>,
+    Path(note_id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<NoteRefList>> {
+
+    let message = GetBacklinksReq { user_id: query.user_id, note_id };
+    let request = state.authorize(&headers, "/notes.Notes/GetBacklinks", message).await?;
+    let backlinks = state.app.get_backlinks(request).await?.into_inner();
+
+    Ok(Json(backlinks))
+}
+
+async fn get_note_graph(
+    State(state): State<RestState>,
+    Query(query): Query<GraphQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<NoteGraph>> {
+
+    let message = GetNoteGraphReq { user_id: query.user_id, include_archived: query.include_archived };
+    let request = state.authorize(&headers, "/notes.Notes/GetNoteGraph", message).await?;
+    let graph = state.app.get_note_graph(request).await?.into_inner();
+
+    Ok(Json(graph))
+}
+
+async fn list_tasks(
+    State(state): State<RestState>,
+    Query(query): Query<TasksQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<TaskList>> {
+
+    let message = ListTasksReq {
+        user_id: query.user_id,
+        done: query.done,
+        tag_id: query.tag_id,
+        include_archived: query.include_archived,
+        pagination: Some(Pagination { page: query.page, per_page: query.per_page }),
+    };
+    let request = state.authorize(&headers, "/notes.Notes/ListTasks", message).await?;
+    let tasks = state.app.list_tasks(request).await?.into_inner();
+
+    Ok(Json(tasks))
+}
+
+async fn toggle_task(
+    State(state): State<RestState>,
+    Path((note_id, position)): Path<(i32, i32)>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<ToggleTaskBody>,
+) -> RestResult<Json<Note>> {
+
+    let message = ToggleTaskReq { user_id: query.user_id, note_id, position, times_edited: body.times_edited };
+    let request = state.authorize(&headers, "/notes.Notes/ToggleTask", message).await?;
+    let note = state.app.toggle_task(request).await?.into_inner();
+
+    Ok(Json(note))
+}
@@ -0,0 +1,106 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, put};
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::proto::reminders::reminders_server::Reminders;
+use crate::proto::reminders::{CreateReminderReq, DeleteReminderReq, ReadRemindersReq, Reminder, ReminderList, UpdateReminderReq};
+
+use super::{RestResult, RestState, UserQuery};
+
+// the due reminders are only streamed over gRPC, with WatchDueReminders
+
+pub fn routes() -> Router<RestState> {
+    Router::new()
+        .route("/reminders", get(read_reminders).post(create_reminder))
+        .route("/reminders/:id", put(update_reminder).delete(delete_reminder))
+}
+
+#[derive(Deserialize)]
+struct CreateReminderBody {
+    note_id: i32,
+    local_time: String,
+    #[serde(default)]
+    timezone: String,
+    #[serde(default)]
+    recurrence: String,
+}
+
+#[derive(Deserialize)]
+struct UpdateReminderBody {
+    local_time: String,
+    #[serde(default)]
+    timezone: String,
+    #[serde(default)]
+    recurrence: String,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct ReadRemindersQuery {
+    user_id: i32,
+    note_id: Option<i32>,
+}
+
+async fn create_reminder(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<CreateReminderBody>,
+) -> RestResult<(StatusCode, Json<Reminder>)> {
+
+    let message = CreateReminderReq {
+        user_id: query.user_id,
+        note_id: body.note_id,
+        local_time: body.local_time,
+        timezone: body.timezone,
+        recurrence: body.recurrence,
+    };
+
+    let request = state.authorize(&headers, "/reminders.Reminders/CreateReminder", message).await?;
+    let reminder = state.app.create_reminder(request).await?.into_inner();
+
+    Ok((StatusCode::CREATED, Json(reminder)))
+}
+
+async fn read_reminders(
+    State(state): State<RestState>,
+    Query(query): Query<ReadRemindersQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<ReminderList>> {
+
+    let message = ReadRemindersReq { user_id: query.user_id, note_id: query.note_id };
+    let request = state.authorize(&headers, "/reminders.Reminders/ReadReminders", message).await?;
+    let reminders = state.app.read_reminders(request).await?.into_inner();
+
+    Ok(Json(reminders))
+}
+
+async fn update_reminder(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<UpdateReminderBody>,
+) -> RestResult<Json<Reminder>> {
+
+    let message = UpdateReminderReq { id, user_id: query.user_id, local_time: body.local_time, timezone: body.timezone, recurrence: body.recurrence };
+    let request = state.authorize(&headers, "/reminders.Reminders/UpdateReminder", message).await?;
+    let reminder = state.app.update_reminder(request).await?.into_inner();
+
+    Ok(Json(reminder))
+}
+
+async fn delete_reminder(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<StatusCode> {
+
+    let request = state.authorize(&headers, "/reminders.Reminders/DeleteReminder", DeleteReminderReq { id, user_id: query.user_id }).await?;
+    state.app.delete_reminder(request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,81 @@
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::proto::shelves::shelves_server::Shelves;
+use crate::proto::shelves::{ClearShelfReq, ConvertToNoteReq, ReadShelfReq, Shelf, UpdateShelfReq};
+
+use super::{RestResult, RestState, UserQuery};
+
+pub fn routes() -> Router<RestState> {
+    Router::new()
+        .route("/shelf", get(read_shelf).put(update_shelf).delete(clear_shelf))
+        .route("/shelf/convert", post(convert_to_note))
+}
+
+#[derive(Deserialize)]
+struct ShelfBody {
+    #[serde(default)]
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ConvertBody {
+    #[serde(default)]
+    note_title: String,
+    #[serde(default)]
+    note_text: String,
+}
+
+async fn read_shelf(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<Shelf>> {
+
+    let request = state.authorize(&headers, "/shelves.Shelves/ReadShelf", ReadShelfReq { user_id: query.user_id }).await?;
+    let shelf = state.app.read_shelf(request).await?.into_inner();
+
+    Ok(Json(shelf))
+}
+
+async fn update_shelf(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<ShelfBody>,
+) -> RestResult<Json<Shelf>> {
+
+    let request = state.authorize(&headers, "/shelves.Shelves/UpdateShelf", UpdateShelfReq { user_id: query.user_id, text: body.text }).await?;
+    let shelf = state.app.update_shelf(request).await?.into_inner();
+
+    Ok(Json(shelf))
+}
+
+async fn clear_shelf(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<Shelf>> {
+
+    let request = state.authorize(&headers, "/shelves.Shelves/ClearShelf", ClearShelfReq { user_id: query.user_id }).await?;
+    let shelf = state.app.clear_shelf(request).await?.into_inner();
+
+    Ok(Json(shelf))
+}
+
+async fn convert_to_note(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<ConvertBody>,
+) -> RestResult<Json<Shelf>> {
+
+    let message = ConvertToNoteReq { user_id: query.user_id, note_title: body.note_title, note_text: body.note_text };
+    let request = state.authorize(&headers, "/shelves.Shelves/ConvertToNote", message).await?;
+    let shelf = state.app.convert_to_note(request).await?.into_inner();
+
+    Ok(Json(shelf))
+}
@@ -0,0 +1,160 @@
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::proto::tags::tags_server::Tags;
+use crate::error::ServiceError;
+use crate::proto::tags::{tag_sort, CreateTagReq, DeleteTagReq, MergeTagsReq, ReadTagsReq, ReorderTagsReq, Tag, TagList, TagSort, UpdateTagReq};
+
+use super::{RestResult, RestState, UserQuery};
+
+pub fn routes() -> Router<RestState> {
+    Router::new()
+        .route("/tags", get(read_tags).post(create_tag))
+        .route("/tags/:id", put(update_tag).delete(delete_tag))
+        .route("/tags/merge", post(merge_tags))
+        .route("/tags/order", put(reorder_tags))
+}
+
+#[derive(Deserialize)]
+struct TagBody {
+    #[serde(default)]
+    name: String,
+    color: Option<String>,
+    icon: Option<String>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ReorderTagsBody {
+    tag_ids: Vec<i32>,
+}
+
+#[derive(Deserialize)]
+struct MergeTagsBody {
+    source_tag_ids: Vec<i32>,
+    target_tag_id: i32,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+struct ReadTagsQuery {
+    user_id: i32,
+    /// id, name, usage, created or position
+    sort: String,
+    /// asc or desc
+    order: String,
+}
+
+impl Default for ReadTagsQuery {
+    fn default() -> Self {
+        Self { user_id: 0, sort: "id".into(), order: "asc".into() }
+    }
+}
+
+impl TryFrom<ReadTagsQuery> for ReadTagsReq {
+    type Error = ServiceError;
+
+    fn try_from(query: ReadTagsQuery) -> Result<Self, Self::Error> {
+        let sort_field = tag_sort::Field::from_str_name(&query.sort.to_uppercase())
+            .ok_or(ServiceError::invalid_field("sort", "INVALID_ENUM_VALUE", "sort must be one of id, name, usage, created and position"))?;
+
+        let sort_type = tag_sort::Type::from_str_name(&query.order.to_uppercase())
+            .ok_or(ServiceError::invalid_field("order", "INVALID_ENUM_VALUE", "order must be either asc or desc"))?;
+
+        Ok(ReadTagsReq {
+            user_id: query.user_id,
+            sort: Some(TagSort { sort_field: sort_field.into(), sort_type: sort_type.into() }),
+        })
+    }
+}
+
+async fn create_tag(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<TagBody>,
+) -> RestResult<(StatusCode, Json<Tag>)> {
+
+    let message = CreateTagReq {
+        user_id: query.user_id,
+        name: body.name,
+        color: body.color,
+        icon: body.icon,
+        description: body.description.unwrap_or_default(),
+    };
+
+    let request = state.authorize(&headers, "/tags.Tags/CreateTag", message).await?;
+    let tag = state.app.create_tag(request).await?.into_inner();
+
+    Ok((StatusCode::CREATED, Json(tag)))
+}
+
+async fn read_tags(
+    State(state): State<RestState>,
+    Query(query): Query<ReadTagsQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<TagList>> {
+
+    let request = state.authorize(&headers, "/tags.Tags/ReadTags", ReadTagsReq::try_from(query)?).await?;
+    let tags = state.app.read_tags(request).await?.into_inner();
+
+    Ok(Json(tags))
+}
+
+async fn update_tag(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<TagBody>,
+) -> RestResult<Json<Tag>> {
+
+    let message = UpdateTagReq { id, user_id: query.user_id, name: body.name, color: body.color, icon: body.icon, description: body.description };
+    let request = state.authorize(&headers, "/tags.Tags/UpdateTag", message).await?;
+    let tag = state.app.update_tag(request).await?.into_inner();
+
+    Ok(Json(tag))
+}
+
+async fn delete_tag(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<StatusCode> {
+
+    let request = state.authorize(&headers, "/tags.Tags/DeleteTag", DeleteTagReq { id, user_id: query.user_id }).await?;
+    state.app.delete_tag(request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn merge_tags(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<MergeTagsBody>,
+) -> RestResult<Json<Tag>> {
+
+    let message = MergeTagsReq { user_id: query.user_id, source_tag_ids: body.source_tag_ids, target_tag_id: body.target_tag_id };
+    let request = state.authorize(&headers, "/tags.Tags/MergeTags", message).await?;
+    let tag = state.app.merge_tags(request).await?.into_inner();
+
+    Ok(Json(tag))
+}
+
+async fn reorder_tags(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<ReorderTagsBody>,
+) -> RestResult<Json<TagList>> {
+
+    let request = state.authorize(&headers, "/tags.Tags/ReorderTags", ReorderTagsReq { user_id: query.user_id, tag_ids: body.tag_ids }).await?;
+    let tags = state.app.reorder_tags(request).await?.into_inner();
+
+    Ok(Json(tags))
+}
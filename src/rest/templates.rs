@@ -0,0 +1,108 @@
+use std::collections::BTreeMap;
+
+use axum::extract::{Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::routing::{get, post, put};
+use axum::{Json, Router};
+use serde::Deserialize;
+
+use crate::proto::notes::Note;
+use crate::proto::templates::templates_server::Templates;
+use crate::proto::templates::{CreateNoteFromTemplateReq, CreateTemplateReq, DeleteTemplateReq, ReadTemplatesReq, Template, TemplateList, UpdateTemplateReq};
+
+use super::{RestResult, RestState, UserQuery};
+
+pub fn routes() -> Router<RestState> {
+    Router::new()
+        .route("/templates", get(read_templates).post(create_template))
+        .route("/templates/:id", put(update_template).delete(delete_template))
+        .route("/templates/:id/notes", post(create_note_from_template))
+}
+
+#[derive(Deserialize)]
+struct TemplateBody {
+    name: String,
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    tag_ids: Vec<i32>,
+}
+
+#[derive(Deserialize)]
+struct NoteFromTemplateBody {
+    #[serde(default)]
+    variables: BTreeMap<String, String>,
+    #[serde(default)]
+    timezone: String,
+}
+
+async fn create_template(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<TemplateBody>,
+) -> RestResult<(StatusCode, Json<Template>)> {
+
+    let message = CreateTemplateReq { user_id: query.user_id, name: body.name, title: body.title, text: body.text, tag_ids: body.tag_ids };
+    let request = state.authorize(&headers, "/templates.Templates/CreateTemplate", message).await?;
+    let template = state.app.create_template(request).await?.into_inner();
+
+    Ok((StatusCode::CREATED, Json(template)))
+}
+
+async fn read_templates(
+    State(state): State<RestState>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<Json<TemplateList>> {
+
+    let request = state.authorize(&headers, "/templates.Templates/ReadTemplates", ReadTemplatesReq { user_id: query.user_id }).await?;
+    let templates = state.app.read_templates(request).await?.into_inner();
+
+    Ok(Json(templates))
+}
+
+async fn update_template(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<TemplateBody>,
+) -> RestResult<Json<Template>> {
+
+    let message = UpdateTemplateReq { id, user_id: query.user_id, name: body.name, title: body.title, text: body.text, tag_ids: body.tag_ids };
+    let request = state.authorize(&headers, "/templates.Templates/UpdateTemplate", message).await?;
+    let template = state.app.update_template(request).await?.into_inner();
+
+    Ok(Json(template))
+}
+
+async fn delete_template(
+    State(state): State<RestState>,
+    Path(id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+) -> RestResult<StatusCode> {
+
+    let request = state.authorize(&headers, "/templates.Templates/DeleteTemplate", DeleteTemplateReq { id, user_id: query.user_id }).await?;
+    state.app.delete_template(request).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn create_note_from_template(
+    State(state): State<RestState>,
+    Path(template_id): Path<i32>,
+    Query(query): Query<UserQuery>,
+    headers: HeaderMap,
+    Json(body): Json<NoteFromTemplateBody>,
+) -> RestResult<(StatusCode, Json<Note>)> {
+
+    let message = CreateNoteFromTemplateReq { user_id: query.user_id, template_id, variables: body.variables, timezone: body.timezone };
+    let request = state.authorize(&headers, "/templates.Templates/CreateNoteFromTemplate", message).await?;
+    let note = state.app.create_note_from_template(request).await?.into_inner();
+
+    Ok((StatusCode::CREATED, Json(note)))
+}
@@ -1,12 +1,18 @@
-use crate::proto::files::create_file_metadata::AttachId;
 use crate::proto::files::files_server::{Files, FilesServer};
 use crate::proto::files::{CreateFileMetadata, CreateFileReq, DeleteFileReq, DownloadFileMetadata, DownloadFileReq, Empty, File, FileData};
-use crate::types::{AppState, HandleServiceError, ServiceResult};
+use crate::error::ServiceError;
+use crate::idempotency;
+use crate::rate_limit::RequestLimiter;
+use crate::types::{AppState, ServiceResult};
+use crate::user_auth::{check_user_id, IntoVerifiedInner, VerifiedUser};
+use crate::validation::Validate;
+
+use std::path::PathBuf;
 
 use tokio::io::{AsyncWriteExt, AsyncReadExt};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
-use tokio_stream::StreamExt;
+use tokio_stream::{Stream, StreamExt};
 use tonic::{Request, Response, Streaming, Status};
 
 pub fn get_service(state: AppState) -> FilesServer<AppState> {
@@ -17,7 +23,7 @@ pub fn get_service(state: AppState) -> FilesServer<AppState> {
 
 #[derive(Debug)]
 struct FileDefer {
-    file_path: String,
+    file_path: PathBuf,
     delete: bool,
 }
 
@@ -28,62 +34,68 @@ impl Drop for FileDefer {
     fn drop(&mut self) {
         if self.delete {
             if let Err(e) = std::fs::remove_file(&self.file_path) {
-                println!("Could not delete a file: {}; {:?}", self.file_path, e);
+                log::error!("Could not delete a file: {}; {:?}", self.file_path.display(), e);
             }
         }
     }
 }
 
-#[tonic::async_trait]
-impl Files for AppState {
-    async fn create_file(
+fn file_size_mismatch() -> ServiceError {
+    ServiceError::invalid_field("metadata.file_size", "FILE_SIZE_MISMATCH", "the received data does not match the file size")
+}
+
+impl AppState {
+    /// saves an uploaded file. the first part of the `stream` has the metadata, and the rest have the data.
+    /// the rest gateway's uploads are received here too. the idempotency key only covers the metadata
+    pub async fn receive_file<S>(
         &self,
-        request: Request<Streaming<CreateFileReq>>,
-    ) -> ServiceResult<File> {
+        mut stream: S,
+        verified_user: Option<&VerifiedUser>,
+        request_limiter: Option<&RequestLimiter>,
+        idempotency_key: Option<String>,
+    ) -> Result<File, Status>
+    where
+        S: Stream<Item = Result<CreateFileReq, Status>> + Unpin,
+    {
+        // processing the first part
+
+        let first_part = stream.next().await
+            .ok_or(ServiceError::missing_field("metadata"))??;
 
-        println!("\nGOT CREATE FILE REQ");
+        let mut metadata = first_part.metadata.ok_or(ServiceError::missing_field("metadata"))?;
+        check_user_id(&mut metadata, verified_user)?;
 
-        let mut stream = request.into_inner();
+        if let Some(request_limiter) = request_limiter {
+            request_limiter.check_user_call(metadata.user_id)?;
+        }
 
-        // processing the first part
+        metadata.validate()?;
 
-        let first_part = stream.next().await
-            .ok_or(Status::invalid_argument("invalid field"))??;
-
-        let Some(CreateFileMetadata {
-            user_id,
-            attach_id: Some(attach_id),
-            name: file_name,
-            file_size,
-        }) = first_part.metadata else {
-            return Err(Status::invalid_argument("invalid field"));
-        };
+        let request_metadata = metadata.clone();
 
-        println!("metadata: {}, {:?}, {}, {}", user_id, attach_id, file_name, file_size);
+        self.idempotent(idempotency_key, "/files.Files/CreateFile", metadata.user_id, &request_metadata, || {
+            self.store_file(metadata, stream, request_limiter)
+        }).await
+    }
 
-        // making sure the note or the shelf that the file is going to be attached to exists
+    /// writes the data parts of the `stream` into a new blob, and saves the file's info
+    async fn store_file<S>(&self, metadata: CreateFileMetadata, mut stream: S, request_limiter: Option<&RequestLimiter>) -> Result<File, Status>
+    where
+        S: Stream<Item = Result<CreateFileReq, Status>> + Unpin,
+    {
+        let CreateFileMetadata { user_id, attach_id, name: file_name, file_size } = metadata;
+        let attach_id = attach_id.ok_or(ServiceError::missing_field("metadata.attach_id"))?;
 
-        let (query, attach_id_val) = match attach_id {
-            AttachId::NoteId(note_id) => (
-                sqlx::query("SELECT id FROM notes WHERE id = $1 AND user_id = $2;"),
-                note_id,
-            ),
-            AttachId::ShelfId(shelf_id) => (
-                sqlx::query("SELECT id FROM shelves WHERE id = $1 AND user_id = $2;"),
-                shelf_id,
-            ),
-        };
+        log::debug!("metadata: {}, {:?}, {}, {}", user_id, attach_id, file_name, file_size);
+
+        // making sure the note or the shelf that the file is going to be attached to exists
 
-        query
-            .bind(attach_id_val).bind(user_id)
-            .fetch_one(&self.pool)
-            .await
-            .map_to_status()?;
+        self.files.check_attach_target(user_id, &attach_id).await?;
 
         // preparing file stuff
 
         let file_hash = uuid::Uuid::new_v4().to_string();
-        let file_path = format!("./files/{}", file_hash);
+        let file_path = self.file_path(&file_hash);
 
         let mut file = tokio::fs::File::create_new(&file_path).await?;
         file.set_max_buf_size(1024 * 1024 * self.chunk_size);
@@ -93,7 +105,7 @@ impl Files for AppState {
             delete: true,
         };
 
-        println!("file_path: {}", file_path);
+        log::debug!("file_path: {}", file_path.display());
 
         // processing the rest of the parts
 
@@ -103,56 +115,54 @@ impl Files for AppState {
             i += 1;
 
             let file_part = file_part?;
+
+            if let Some(request_limiter) = request_limiter {
+                request_limiter.throttle_upload(user_id, file_part.data.len()).await?;
+            }
+
             let bytes_written = file.write(&file_part.data).await? as u64;
             written_total += bytes_written;
 
             if written_total > file_size {
-                return Err(Status::invalid_argument("got a file with an invalid size"));
+                return Err(file_size_mismatch().into());
             }
 
             if i < 10 || (i < 100 && i % 10 == 0) || (i < 1000 && i % 100 == 0) || i % 1000 == 0 {
-                println!("part {}: {} ({})", i, file_part.data.len(), bytes_written);
+                log::trace!("part {}: {} ({})", i, file_part.data.len(), bytes_written);
             }
         }
 
-        println!("sizes: {written_total}/{file_size}");
+        log::debug!("sizes: {written_total}/{file_size}");
 
         if written_total != file_size {
-            return Err(Status::invalid_argument("got a file with an invalid size"));
+            return Err(file_size_mismatch().into());
         }
 
         // saving the file data
 
-        let mut transaction = self.pool
-            .begin()
-            .await
-            .map_to_status()?;
+        let new_file_info = self.files.create(user_id, &file_hash, &file_name, written_total as i64, attach_id).await?;
 
-        let mut new_file_info = sqlx::query_as::<_, File>("INSERT INTO files (user_id, hash, name, size) VALUES ($1, $2, $3, $4) RETURNING *;")
-            .bind(user_id).bind(file_hash).bind(file_name).bind(written_total as i64)
-            .fetch_one(&mut *transaction)
-            .await
-            .map_to_status()?;
+        file_defer.delete = false;
 
-        let query = match attach_id {
-            AttachId::NoteId(_) => sqlx::query("INSERT INTO note_files (note_id, file_id) VALUES ($1, $2);"),
-            AttachId::ShelfId(_) => sqlx::query("INSERT INTO shelf_files (shelf_id, file_id) VALUES ($1, $2);"),
-        };
+        Ok(new_file_info)
+    }
+}
 
-        query
-            .bind(attach_id_val).bind(new_file_info.id)
-            .execute(&mut *transaction)
-            .await
-            .map_to_status()?;
+#[tonic::async_trait]
+impl Files for AppState {
+    async fn create_file(
+        &self,
+        request: Request<Streaming<CreateFileReq>>,
+    ) -> ServiceResult<File> {
 
-        transaction
-            .commit()
-            .await
-            .map_to_status()?;
+        log::debug!("GOT CREATE FILE REQ");
 
-        file_defer.delete = false;
+        let verified_user = request.extensions().get::<VerifiedUser>().cloned();
+        let request_limiter = request.extensions().get::<RequestLimiter>().cloned();
+        let idempotency_key = idempotency::key(&request)?;
+
+        let new_file_info = self.receive_file(request.into_inner(), verified_user.as_ref(), request_limiter.as_ref(), idempotency_key).await?;
 
-        new_file_info.attach_id = Some(attach_id_val);
         Ok(Response::new(new_file_info))
     }
 
@@ -163,20 +173,29 @@ impl Files for AppState {
         request: Request<DownloadFileReq>,
     ) -> ServiceResult<Self::DownloadFileStream> {
 
-        let req_body = dbg!(request.into_inner());
+        let request_limiter = request.extensions().get::<RequestLimiter>().cloned();
+        let req_body = request.into_verified_inner()?;
+        log::debug!("DOWNLOAD FILE BODY: {:?}", req_body);
+
+        // the guards are moved into the sending task, so that
+        // the download stays counted until the stream ends
+
+        let download_guards = match &request_limiter {
+            Some(request_limiter) => Some(request_limiter.start_download(req_body.user_id)?),
+            None => None,
+        };
 
         // checking the file in the db
 
-        let file_info = sqlx::query_as::<_, File>("SELECT * FROM files WHERE hash = $1 AND user_id = $2;")
-            .bind(&req_body.file_hash).bind(req_body.user_id)
-            .fetch_one(&self.pool)
-            .await
-            .map_to_status()?;
+        let file_info = self.files.find_by_hash(req_body.user_id, &req_body.file_hash).await?;
 
         // preparing the file and size info
 
-        let mut file = tokio::fs::File::open(format!("./files/{}", file_info.hash)).await
-            .map_err(|_| tonic::Status::internal("could not find the file"))?;
+        let mut file = tokio::fs::File::open(self.file_path(&file_info.hash)).await
+            .map_err(|e| {
+                log::error!("Could not open a file: {:?}; {:?}", file_info, e);
+                ServiceError::Internal("FILE_MISSING")
+            })?;
 
         let file_size = file.metadata().await?.len() as usize;
         let chunk_size = 1024 * 1024 * self.chunk_size;
@@ -184,14 +203,15 @@ impl Files for AppState {
         file.set_max_buf_size(chunk_size);
         let mut buffer = vec![0; chunk_size];
 
-        println!("size, chunk: {}, {}", file_size, chunk_size);
+        log::debug!("size, chunk: {}, {}", file_size, chunk_size);
 
         // defining a channel that yields FileData objects with file data
 
-        let (sender, receiver) = mpsc::channel(4);
+        let (sender, receiver) = mpsc::channel(self.download_channel_depth);
 
         tokio::spawn(async move {
-            println!("stream start");
+            let _download_guards = download_guards;
+            log::debug!("stream start");
 
             // send the metadata without any file data first
 
@@ -205,7 +225,7 @@ impl Files for AppState {
 
             match sender.send(Ok(metadata_part)).await {
                 Ok(_) => (),
-                Err(e) => println!("SENDER SEND ERR: {}", e),
+                Err(e) => log::warn!("SENDER SEND ERR: {}", e),
             };
 
             // and then send the actual file data by reading the file
@@ -217,20 +237,20 @@ impl Files for AppState {
                 let bytes_read = match file.read(&mut buffer).await {
                     Ok(l) => l,
                     Err(e) => {
-                        println!("FILE READ ERR: {:#?}", e);
+                        log::error!("FILE READ ERR: {:#?}", e);
                         break;
                     },
                 };
 
                 if bytes_read == 0 {
-                    println!("buf len == 0");
+                    log::debug!("buf len == 0");
                     break;
                 }
 
                 let data = buffer[0..bytes_read].to_vec();
 
                 if i < 10 || (i < 100 && i % 10 == 0) || (i < 1000 && i % 100 == 0) || i % 1000 == 0 {
-                    println!("buf {}: {}", i, data.len());
+                    log::trace!("buf {}: {}", i, data.len());
                 }
 
                 let data_part = FileData {
@@ -240,7 +260,7 @@ impl Files for AppState {
 
                 match sender.send(Ok(data_part)).await {
                     Ok(_) => (),
-                    Err(e) => { println!("SENDER SEND ERR: {}", e); break; },
+                    Err(e) => { log::warn!("SENDER SEND ERR: {}", e); break; },
                 };
             }
         });
@@ -253,40 +273,9 @@ impl Files for AppState {
         request: Request<DeleteFileReq>,
     ) -> ServiceResult<Empty> {
 
-        let req_body = request.into_inner();
-
-        let mut transaction = self.pool
-            .begin()
-            .await
-            .map_to_status()?;
-
-        sqlx::query("DELETE FROM note_files WHERE file_id = $1;")
-            .bind(req_body.id)
-            .execute(&mut *transaction)
-            .await
-            .map_to_status()?;
-
-        sqlx::query("DELETE FROM shelf_files WHERE file_id = $1;")
-            .bind(req_body.id)
-            .execute(&mut *transaction)
-            .await
-            .map_to_status()?;
-
-        let deleted_file = sqlx::query_as::<_, File>("DELETE FROM files WHERE id = $1 AND user_id = $2 RETURNING *;")
-            .bind(req_body.id).bind(req_body.user_id)
-            .fetch_one(&mut *transaction)
-            .await
-            .map_to_status()?;
-
-        transaction
-            .commit()
-            .await
-            .map_to_status()?;
-
-        let file_path = "./files/".to_owned() + &deleted_file.hash;
-        tokio::fs::remove_file(file_path)
-            .await
-            .unwrap_or_else(|e| println!("Could not delete a file: {:?};\nBecause error: {:?};", deleted_file, e));
+        let req_body = request.into_verified_inner()?;
+
+        self.files.delete(req_body.id, req_body.user_id).await?;
 
         Ok(Response::new(Empty {}))
     }
@@ -1,34 +1,116 @@
+use std::{sync::Arc, time::Duration};
+
+use hyper::{header::HeaderName, Method};
+use tokio::net::TcpListener;
 use tonic::transport::Server;
 use tonic_middleware::RequestInterceptorLayer;
+use tonic_web::GrpcWebLayer;
+use tower::ServiceBuilder;
+use tower_http::cors::{AllowOrigin, CorsLayer};
 
-use crate::types::{AppState, Interceptor};
+use crate::{callers, config::{Config, WebConfig}, idempotency::IDEMPOTENCY_KEY, rate_limit::RateLimiter, rest, tls, types::{AppState, Interceptor}, user_auth::{UserTokenVerifier, USER_TOKEN_KEY}};
 
 mod files;
 mod tags;
 mod notes;
+mod reminders;
 mod shelves;
+mod templates;
 
-pub async fn start(state: &AppState, port: u16, service_token: &str) -> anyhow::Result<()> {
-    let interceptor = RequestInterceptorLayer::new(Interceptor {
-        auth_value: format!("Bearer {}", service_token),
-    });
+#[cfg(test)]
+mod tests;
+
+pub async fn start(state: &AppState, config: &Config) -> anyhow::Result<()> {
+    let interceptor = Interceptor {
+        callers: Arc::new(callers::from_config(&config.auth)),
+        client_services: config.tls.as_ref().and_then(|t| t.client_services.clone()).map(Arc::new),
+        user_verifier: UserTokenVerifier::from_config(&config.auth)?.map(Arc::new),
+        rate_limiter: RateLimiter::from_config(&config.rate_limit).map(Arc::new),
+    };
+
+    // the certificates are watched once, and both of the servers use them
+
+    let tls_config = match config.tls.clone() {
+        Some(tls) => Some(tls::watch_server_config(tls).await?),
+        None => None,
+    };
+
+    if let Some(rest_port) = config.web.rest_port {
+        rest::spawn_server(state.clone(), interceptor.clone(), &config.web, rest_port, tls_config.clone()).await?;
+    }
 
     let files_service = files::get_service(state.clone());
     let tags_service = tags::get_service(state.clone());
     let notes_service = notes::get_service(state.clone());
     let shelves_service = shelves::get_service(state.clone());
+    let templates_service = templates::get_service(state.clone());
+    let reminders_service = reminders::get_service(state.clone());
+
+    let addr = format!("[::]:{}", config.server.port).parse()?;
+
+    let mut builder = Server::builder();
+    if let Some(request_timeout) = config.server.request_timeout {
+        builder = builder.timeout(Duration::from_secs(request_timeout));
+    }
+
+    // the gRPC-Web calls are translated before they reach the interceptor,
+    // and the cors preflight requests are answered before they need any auth
 
-    let addr = format!("[::]:{port}").parse()?;
-    println!("Data service listening on {addr}");
+    let grpc_web = config.web.grpc_web.then(|| {
+        ServiceBuilder::new()
+            .layer(cors_layer(&config.web))
+            .layer(GrpcWebLayer::new())
+    });
+
+    let layers = ServiceBuilder::new()
+        .option_layer(grpc_web)
+        .layer(RequestInterceptorLayer::new(interceptor));
 
-    Server::builder()
-        .layer(interceptor)
+    let router = builder
+        .accept_http1(config.web.grpc_web)
+        .layer(layers)
         .add_service(files_service)
         .add_service(tags_service)
         .add_service(notes_service)
         .add_service(shelves_service)
-        .serve(addr)
-        .await?;
+        .add_service(templates_service)
+        .add_service(reminders_service);
+
+    let grpc_web = if config.web.grpc_web { ", gRPC-Web enabled" } else { "" };
+
+    match tls_config {
+        None => {
+            log::info!("Data service listening on {addr}{grpc_web}");
+            router.serve(addr).await?;
+        },
+        Some(tls_config) => {
+            let mtls = config.tls.as_ref().is_some_and(|t| t.client_ca_path.is_some());
+            let mtls = if mtls { " with client certificate verification" } else { "" };
+            let listener = TcpListener::bind(addr).await?;
+
+            log::info!("Data service listening on {addr} (TLS{mtls}){grpc_web}");
+            router.serve_with_incoming(tls::incoming(listener, tls_config)).await?;
+        },
+    }
 
     Ok(())
 }
+
+/// the cors policy of the browser calls, for both the gRPC-Web calls and the rest gateway
+pub fn cors_layer(web: &WebConfig) -> CorsLayer {
+    let allow_origin = match web.cors_origins.is_empty() {
+        true => AllowOrigin::any(),
+        // the origins are checked by the config validation
+        false => AllowOrigin::list(web.cors_origins.iter().map(|o| o.parse().unwrap())),
+    };
+
+    let allow_headers = ["authorization", USER_TOKEN_KEY, IDEMPOTENCY_KEY, "content-type", "x-grpc-web", "x-user-agent", "grpc-timeout"];
+    let expose_headers = ["grpc-status", "grpc-message", "grpc-status-details-bin", "retry-after", "content-disposition"];
+
+    CorsLayer::new()
+        .allow_origin(allow_origin)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
+        .allow_headers(allow_headers.map(HeaderName::from_static))
+        .expose_headers(expose_headers.map(HeaderName::from_static))
+        .max_age(Duration::from_secs(24 * 60 * 60))
+}
@@ -0,0 +1,228 @@
+use crate::proto::notes::notes_server::{Notes, NotesServer};
+use crate::proto::notes::{AttachTagReq, BatchDeleteNotesReq, BatchItemResult, BatchMoveNotesReq, BatchResult, BatchTagsReq, CreateNoteReq, DeleteNoteReq, DetachTagReq, Empty, Note, NoteList, ReadNotesReq, BatchSetNoteStateReq, SetNoteStateReq, UpdateNoteReq, GetBacklinksReq, GetNoteGraphReq, NoteGraph, NoteRefList, ListTasksReq, TaskList, ToggleTaskReq};
+use crate::types::{AppState, ServiceResult};
+use crate::error::ServiceError;
+use crate::idempotency;
+use crate::user_auth::IntoVerifiedInner;
+
+use tonic::{Request, Response};
+
+pub fn get_service(state: AppState) -> NotesServer<AppState> {
+    NotesServer::new(state)
+}
+
+#[tonic::async_trait]
+impl Notes for AppState {
+    async fn create_note(
+        &self,
+        request: Request<CreateNoteReq>,
+    ) -> ServiceResult<Note> {
+
+        let idempotency_key = idempotency::key(&request)?;
+        let req_body = request.into_verified_inner()?;
+
+        let new_note = self.idempotent(idempotency_key, "/notes.Notes/CreateNote", req_body.user_id, &req_body, || async {
+            Ok(self.notes.create(req_body.user_id, &req_body.title, &req_body.text).await?)
+        }).await?;
+
+        Ok(Response::new(new_note))
+    }
+
+    async fn read_notes(
+        &self,
+        request: Request<ReadNotesReq>,
+    ) -> ServiceResult<NoteList> {
+
+        let req_body = request.into_verified_inner()?;
+        log::debug!("READ NOTES BODY: {:#?}", req_body);
+
+        // extracting parameters from the body
+
+        let ReadNotesReq { user_id, pagination, sort, filters } = req_body;
+        let pagination = pagination.ok_or(ServiceError::missing_field("pagination"))?;
+        let sort = sort.ok_or(ServiceError::missing_field("sort"))?;
+        let filters = filters.ok_or(ServiceError::missing_field("filters"))?;
+
+        let (notes, total_count) = self.notes.list(user_id, &pagination, &sort, &filters).await?;
+
+        Ok(Response::new(NoteList { notes, total_count }))
+    }
+
+    async fn update_note(
+        &self,
+        request: Request<UpdateNoteReq>,
+    ) -> ServiceResult<Note> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let updated_note = self.notes.update(req_body.id, req_body.user_id, &req_body.title, &req_body.text).await?;
+
+        Ok(Response::new(updated_note))
+    }
+
+    async fn delete_note(
+        &self,
+        request: Request<DeleteNoteReq>,
+    ) -> ServiceResult<Empty> {
+
+        let req_body = request.into_verified_inner()?;
+
+        self.notes.delete(req_body.id, req_body.user_id).await?;
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn attach_tag(
+        &self,
+        request: Request<AttachTagReq>,
+    ) -> ServiceResult<Empty> {
+
+        let req_body = request.into_verified_inner()?;
+
+        self.notes.attach_tag(req_body.user_id, req_body.note_id, req_body.tag_id).await?;
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn detach_tag(
+        &self,
+        request: Request<DetachTagReq>,
+    ) -> ServiceResult<Empty> {
+
+        let req_body = request.into_verified_inner()?;
+
+        self.notes.detach_tag(req_body.user_id, req_body.note_id, req_body.tag_id).await?;
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn batch_attach_tags(
+        &self,
+        request: Request<BatchTagsReq>,
+    ) -> ServiceResult<BatchResult> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let results = self.notes.batch_attach_tags(req_body.user_id, &req_body.note_ids, &req_body.tag_ids, req_body.partial).await?;
+
+        Ok(Response::new(batch_result(results)))
+    }
+
+    async fn batch_detach_tags(
+        &self,
+        request: Request<BatchTagsReq>,
+    ) -> ServiceResult<BatchResult> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let results = self.notes.batch_detach_tags(req_body.user_id, &req_body.note_ids, &req_body.tag_ids, req_body.partial).await?;
+
+        Ok(Response::new(batch_result(results)))
+    }
+
+    async fn batch_delete_notes(
+        &self,
+        request: Request<BatchDeleteNotesReq>,
+    ) -> ServiceResult<BatchResult> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let results = self.notes.batch_delete(req_body.user_id, &req_body.note_ids, req_body.partial).await?;
+
+        Ok(Response::new(batch_result(results)))
+    }
+
+    async fn batch_move_notes(
+        &self,
+        request: Request<BatchMoveNotesReq>,
+    ) -> ServiceResult<BatchResult> {
+
+        let req_body = request.into_verified_inner()?;
+        let BatchMoveNotesReq { user_id, note_ids, from_tag_id, to_tag_id, partial } = req_body;
+
+        let results = self.notes.batch_move(user_id, &note_ids, from_tag_id, to_tag_id, partial).await?;
+
+        Ok(Response::new(batch_result(results)))
+    }
+
+    async fn set_note_state(
+        &self,
+        request: Request<SetNoteStateReq>,
+    ) -> ServiceResult<Note> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let updated_note = self.notes.set_state(req_body.id, req_body.user_id, &req_body.state.unwrap_or_default()).await?;
+
+        Ok(Response::new(updated_note))
+    }
+
+    async fn batch_set_note_state(
+        &self,
+        request: Request<BatchSetNoteStateReq>,
+    ) -> ServiceResult<BatchResult> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let state = req_body.state.unwrap_or_default();
+        let results = self.notes.batch_set_state(req_body.user_id, &req_body.note_ids, &state, req_body.partial).await?;
+
+        Ok(Response::new(batch_result(results)))
+    }
+
+    async fn get_backlinks(
+        &self,
+        request: Request<GetBacklinksReq>,
+    ) -> ServiceResult<NoteRefList> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let notes = self.notes.backlinks(req_body.user_id, req_body.note_id).await?;
+
+        Ok(Response::new(NoteRefList { notes }))
+    }
+
+    async fn get_note_graph(
+        &self,
+        request: Request<GetNoteGraphReq>,
+    ) -> ServiceResult<NoteGraph> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let graph = self.notes.graph(req_body.user_id, req_body.include_archived).await?;
+
+        Ok(Response::new(graph))
+    }
+
+    async fn list_tasks(
+        &self,
+        request: Request<ListTasksReq>,
+    ) -> ServiceResult<TaskList> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let ListTasksReq { user_id, done, tag_id, include_archived, pagination } = req_body;
+        let pagination = pagination.ok_or(ServiceError::missing_field("pagination"))?;
+
+        let (tasks, total_count) = self.notes.tasks(user_id, &pagination, done, tag_id, include_archived).await?;
+
+        Ok(Response::new(TaskList { tasks, total_count }))
+    }
+
+    async fn toggle_task(
+        &self,
+        request: Request<ToggleTaskReq>,
+    ) -> ServiceResult<Note> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let updated_note = self.notes.toggle_task(req_body.user_id, req_body.note_id, req_body.position, req_body.times_edited).await?;
+
+        Ok(Response::new(updated_note))
+    }
+}
+
+fn batch_result(results: Vec<BatchItemResult>) -> BatchResult {
+    let succeeded = results.iter().filter(|r| r.ok).count() as i32;
+    BatchResult { results, succeeded }
+}
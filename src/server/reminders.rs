@@ -0,0 +1,133 @@
+use crate::proto::reminders::reminders_server::{Reminders, RemindersServer};
+use crate::proto::reminders::{CreateReminderReq, DeleteReminderReq, DueReminder, Empty, ReadRemindersReq, Reminder, ReminderList, UpdateReminderReq, WatchDueRemindersReq};
+use crate::error::ServiceError;
+use crate::idempotency;
+use crate::reminders::Schedule;
+use crate::types::{AppState, ServiceResult};
+use crate::user_auth::IntoVerifiedInner;
+
+use chrono::NaiveDateTime;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+/// how many due reminders can wait for a slow watcher, before it holds back the broadcast
+const WATCH_CHANNEL_DEPTH: usize = 16;
+
+pub fn get_service(state: AppState) -> RemindersServer<AppState> {
+    RemindersServer::new(state)
+}
+
+/// the local time and the first fire time of a reminder, whose values are checked by the validation
+fn first_fire(local_time: &str, timezone: &str, recurrence: &str) -> Result<(NaiveDateTime, NaiveDateTime), ServiceError> {
+    Schedule::parse(local_time, timezone, recurrence)
+        .and_then(|schedule| Some((schedule.local_time, schedule.next_fire(None)?.naive_utc())))
+        .ok_or(ServiceError::Internal("INVALID_SCHEDULE"))
+}
+
+#[tonic::async_trait]
+impl Reminders for AppState {
+    async fn create_reminder(
+        &self,
+        request: Request<CreateReminderReq>,
+    ) -> ServiceResult<Reminder> {
+
+        let idempotency_key = idempotency::key(&request)?;
+        let req_body = request.into_verified_inner()?;
+
+        let new_reminder = self.idempotent(idempotency_key, "/reminders.Reminders/CreateReminder", req_body.user_id, &req_body, || async {
+            let (local_time, fire_at) = first_fire(&req_body.local_time, &req_body.timezone, &req_body.recurrence)?;
+
+            Ok(self.reminders.create(
+                req_body.user_id, req_body.note_id, local_time, &req_body.timezone, &req_body.recurrence, fire_at,
+            ).await?)
+        }).await?;
+
+        Ok(Response::new(new_reminder))
+    }
+
+    async fn read_reminders(
+        &self,
+        request: Request<ReadRemindersReq>,
+    ) -> ServiceResult<ReminderList> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let reminders = self.reminders.list(req_body.user_id, req_body.note_id).await?;
+
+        Ok(Response::new(ReminderList { reminders }))
+    }
+
+    async fn update_reminder(
+        &self,
+        request: Request<UpdateReminderReq>,
+    ) -> ServiceResult<Reminder> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let (local_time, fire_at) = first_fire(&req_body.local_time, &req_body.timezone, &req_body.recurrence)?;
+
+        let updated_reminder = self.reminders.update(
+            req_body.id, req_body.user_id, local_time, &req_body.timezone, &req_body.recurrence, fire_at,
+        ).await?;
+
+        Ok(Response::new(updated_reminder))
+    }
+
+    async fn delete_reminder(
+        &self,
+        request: Request<DeleteReminderReq>,
+    ) -> ServiceResult<Empty> {
+
+        let req_body = request.into_verified_inner()?;
+
+        self.reminders.delete(req_body.id, req_body.user_id).await?;
+
+        Ok(Response::new(Empty {}))
+    }
+
+    type WatchDueRemindersStream = ReceiverStream<Result<DueReminder, Status>>;
+
+    async fn watch_due_reminders(
+        &self,
+        request: Request<WatchDueRemindersReq>,
+    ) -> ServiceResult<Self::WatchDueRemindersStream> {
+
+        let req_body = request.into_verified_inner()?;
+
+        // subscribing before returning, so that the reminders that fire right after the call are not missed
+
+        let mut due_reminders = self.due_reminders.subscribe();
+        let (sender, receiver) = mpsc::channel(WATCH_CHANNEL_DEPTH);
+
+        tokio::spawn(async move {
+            loop {
+                let received = tokio::select! {
+                    received = due_reminders.recv() => received,
+                    // the watcher is gone, even though nothing was fired
+                    _ = sender.closed() => break,
+                };
+
+                let due_reminder = match received {
+                    Ok(due_reminder) => due_reminder,
+                    Err(RecvError::Lagged(missed)) => {
+                        log::warn!("A watcher of the due reminders was too slow and missed {missed} of them");
+                        continue;
+                    },
+                    Err(RecvError::Closed) => break,
+                };
+
+                let user_id = due_reminder.reminder.as_ref().map(|r| r.user_id);
+                if req_body.user_id != 0 && user_id != Some(req_body.user_id) {
+                    continue;
+                }
+
+                if sender.send(Ok(due_reminder)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+}
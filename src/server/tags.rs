@@ -1,6 +1,8 @@
 use crate::proto::tags::tags_server::{Tags, TagsServer};
-use crate::proto::tags::{CreateTagReq, ReadTagsReq, UpdateTagReq, DeleteTagReq, Tag, TagList, Empty};
-use crate::types::{AppState, HandleServiceError, ServiceResult};
+use crate::proto::tags::{CreateTagReq, ReadTagsReq, UpdateTagReq, DeleteTagReq, MergeTagsReq, ReorderTagsReq, Tag, TagList, Empty};
+use crate::idempotency;
+use crate::types::{AppState, ServiceResult};
+use crate::user_auth::IntoVerifiedInner;
 
 use tonic::{Request, Response};
 
@@ -15,13 +17,14 @@ impl Tags for AppState {
         request: Request<CreateTagReq>,
     ) -> ServiceResult<Tag> {
 
-        let req_body = request.into_inner();
+        let idempotency_key = idempotency::key(&request)?;
+        let req_body = request.into_verified_inner()?;
 
-        let new_tag = sqlx::query_as::<_, Tag>("INSERT INTO tags (user_id, name) VALUES ($1, $2) RETURNING *;")
-            .bind(req_body.user_id).bind(req_body.name)
-            .fetch_one(&self.pool)
-            .await
-            .map_to_status()?;
+        let new_tag = self.idempotent(idempotency_key, "/tags.Tags/CreateTag", req_body.user_id, &req_body, || async {
+            Ok(self.tags.create(
+                req_body.user_id, &req_body.name, req_body.color.as_deref(), req_body.icon.as_deref(), &req_body.description,
+            ).await?)
+        }).await?;
 
         Ok(Response::new(new_tag))
     }
@@ -31,13 +34,9 @@ impl Tags for AppState {
         request: Request<ReadTagsReq>,
     ) -> ServiceResult<TagList> {
 
-        let req_body = request.into_inner();
+        let req_body = request.into_verified_inner()?;
 
-        let tags = sqlx::query_as::<_, Tag>("SELECT * FROM tags WHERE user_id = $1 ORDER BY id;")
-            .bind(req_body.user_id)
-            .fetch_all(&self.pool)
-            .await
-            .map_to_status()?;
+        let tags = self.tags.list(req_body.user_id, &req_body.sort.unwrap_or_default()).await?;
 
         Ok(Response::new(TagList { tags }))
     }
@@ -47,13 +46,12 @@ impl Tags for AppState {
         request: Request<UpdateTagReq>,
     ) -> ServiceResult<Tag> {
 
-        let req_body = request.into_inner();
+        let req_body = request.into_verified_inner()?;
 
-        let updated_tag = sqlx::query_as::<_, Tag>("UPDATE tags SET name = $1 WHERE id = $2 AND user_id = $3 RETURNING *;")
-            .bind(req_body.name).bind(req_body.id).bind(req_body.user_id)
-            .fetch_one(&self.pool)
-            .await
-            .map_to_status()?;
+        let updated_tag = self.tags.update(
+            req_body.id, req_body.user_id, &req_body.name,
+            req_body.color.as_deref(), req_body.icon.as_deref(), req_body.description.as_deref(),
+        ).await?;
 
         Ok(Response::new(updated_tag))
     }
@@ -63,35 +61,34 @@ impl Tags for AppState {
         request: Request<DeleteTagReq>,
     ) -> ServiceResult<Empty> {
 
-        let req_body = request.into_inner();
-
-        let mut transaction = self.pool
-            .begin()
-            .await
-            .map_to_status()?;
-
-        sqlx::query("DELETE FROM note_tags WHERE tag_id = $1;")
-            .bind(req_body.id)
-            .execute(&mut *transaction)
-            .await
-            .map_to_status()?;
-
-        sqlx::query("DELETE FROM tags WHERE id = $1 AND user_id = $2;")
-            .bind(req_body.id).bind(req_body.user_id)
-            .execute(&mut *transaction)
-            .await
-            .map_to_status()?
-            .rows_affected()
-            .eq(&1)
-            .then_some(())
-            .ok_or(sqlx::Error::RowNotFound)
-            .map_to_status()?;
-
-        transaction
-            .commit()
-            .await
-            .map_to_status()?;
+        let req_body = request.into_verified_inner()?;
+
+        self.tags.delete(req_body.id, req_body.user_id).await?;
 
         Ok(Response::new(Empty {}))
     }
+
+    async fn merge_tags(
+        &self,
+        request: Request<MergeTagsReq>,
+    ) -> ServiceResult<Tag> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let merged_tag = self.tags.merge(req_body.user_id, &req_body.source_tag_ids, req_body.target_tag_id).await?;
+
+        Ok(Response::new(merged_tag))
+    }
+
+    async fn reorder_tags(
+        &self,
+        request: Request<ReorderTagsReq>,
+    ) -> ServiceResult<TagList> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let tags = self.tags.reorder(req_body.user_id, &req_body.tag_ids).await?;
+
+        Ok(Response::new(TagList { tags }))
+    }
 }
@@ -0,0 +1,99 @@
+use crate::proto::notes::{CreateNoteReq, Note};
+use crate::proto::templates::templates_server::{Templates, TemplatesServer};
+use crate::proto::templates::{CreateNoteFromTemplateReq, CreateTemplateReq, DeleteTemplateReq, Empty, ReadTemplatesReq, Template, TemplateList, UpdateTemplateReq};
+use crate::types::{AppState, ServiceResult};
+use crate::idempotency;
+use crate::templates;
+use crate::user_auth::IntoVerifiedInner;
+use crate::validation::Validate;
+
+use tonic::{Request, Response};
+
+pub fn get_service(state: AppState) -> TemplatesServer<AppState> {
+    TemplatesServer::new(state)
+}
+
+#[tonic::async_trait]
+impl Templates for AppState {
+    async fn create_template(
+        &self,
+        request: Request<CreateTemplateReq>,
+    ) -> ServiceResult<Template> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let new_template = self.templates.create(
+            req_body.user_id, &req_body.name, &req_body.title, &req_body.text, &req_body.tag_ids,
+        ).await?;
+
+        Ok(Response::new(new_template))
+    }
+
+    async fn read_templates(
+        &self,
+        request: Request<ReadTemplatesReq>,
+    ) -> ServiceResult<TemplateList> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let templates = self.templates.list(req_body.user_id).await?;
+
+        Ok(Response::new(TemplateList { templates }))
+    }
+
+    async fn update_template(
+        &self,
+        request: Request<UpdateTemplateReq>,
+    ) -> ServiceResult<Template> {
+
+        let req_body = request.into_verified_inner()?;
+
+        let updated_template = self.templates.update(
+            req_body.id, req_body.user_id, &req_body.name, &req_body.title, &req_body.text, &req_body.tag_ids,
+        ).await?;
+
+        Ok(Response::new(updated_template))
+    }
+
+    async fn delete_template(
+        &self,
+        request: Request<DeleteTemplateReq>,
+    ) -> ServiceResult<Empty> {
+
+        let req_body = request.into_verified_inner()?;
+
+        self.templates.delete(req_body.id, req_body.user_id).await?;
+
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn create_note_from_template(
+        &self,
+        request: Request<CreateNoteFromTemplateReq>,
+    ) -> ServiceResult<Note> {
+
+        let idempotency_key = idempotency::key(&request)?;
+        let req_body = request.into_verified_inner()?;
+
+        let new_note = self.idempotent(idempotency_key, "/templates.Templates/CreateNoteFromTemplate", req_body.user_id, &req_body, || async {
+            let template = self.templates.get(req_body.template_id, req_body.user_id).await?;
+
+            // the timezone is checked by the validation
+            let timezone = templates::parse_timezone(&req_body.timezone).unwrap_or(chrono_tz::Tz::UTC);
+            let now = chrono::Utc::now();
+
+            // the substituted values can make the title or the text too long for a note
+            let mut note = CreateNoteReq {
+                user_id: req_body.user_id,
+                title: templates::render(&template.title, now, timezone, &req_body.variables),
+                text: templates::render(&template.text, now, timezone, &req_body.variables),
+            };
+
+            note.validate()?;
+
+            Ok(self.templates.create_note(template.id, req_body.user_id, &note.title, &note.text).await?)
+        }).await?;
+
+        Ok(Response::new(new_note))
+    }
+}
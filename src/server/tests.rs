@@ -0,0 +1,460 @@
+use std::sync::Arc;
+
+use tonic::{Code, Request};
+
+use crate::idempotency::IDEMPOTENCY_KEY;
+use crate::proto::files::create_file_metadata::AttachId;
+use crate::proto::notes::notes_server::Notes;
+use crate::proto::notes::{filter_state, sort, AttachTagReq, BatchSetNoteStateReq, BatchTagsReq, CreateNoteReq, DeleteNoteReq, FilterSearch, FilterState, FilterTags, Filters, GetBacklinksReq, GetNoteGraphReq, NoteState, Pagination, ReadNotesReq, Sort, UpdateNoteReq};
+use crate::proto::shelves::shelves_server::Shelves;
+use crate::proto::shelves::{ClearShelfReq, ConvertToNoteReq, ReadShelfReq};
+use crate::proto::tags::tags_server::Tags;
+use crate::proto::tags::{CreateTagReq, DeleteTagReq};
+use crate::proto::reminders::reminders_server::Reminders;
+use crate::proto::reminders::CreateReminderReq;
+use crate::proto::templates::templates_server::Templates;
+use crate::proto::templates::{CreateNoteFromTemplateReq, CreateTemplateReq};
+use crate::repo::{memory::MemoryRepo, FileRepo, IdempotencyRepo};
+use crate::types::AppState;
+
+fn state() -> (AppState, Arc<MemoryRepo>) {
+    let repo = Arc::new(MemoryRepo::new());
+
+    let state = AppState {
+        notes: repo.clone(),
+        tags: repo.clone(),
+        files: repo.clone(),
+        shelves: repo.clone(),
+        templates: repo.clone(),
+        reminders: repo.clone(),
+        idempotency: repo.clone(),
+        idempotency_ttl: 60,
+        idempotency_lease: 60,
+        chunk_size: 1,
+        storage_path: std::env::temp_dir(),
+        download_channel_depth: 1,
+        due_reminders: tokio::sync::broadcast::channel(16).0,
+    };
+
+    (state, repo)
+}
+
+async fn create_note(state: &AppState, user_id: i32, title: &str) -> i32 {
+    let req = CreateNoteReq { user_id, title: title.into(), text: String::new() };
+    state.create_note(Request::new(req)).await.unwrap().into_inner().id
+}
+
+fn read_notes_req(user_id: i32, filters: Filters) -> ReadNotesReq {
+    ReadNotesReq {
+        user_id,
+        pagination: Some(Pagination { page: 1, per_page: 10 }),
+        sort: Some(Sort { sort_field: sort::Field::Title.into(), sort_type: sort::Type::Asc.into(), pinned_first: false }),
+        filters: Some(filters),
+    }
+}
+
+#[tokio::test]
+async fn read_notes_filters_sorts_and_attaches_tags() {
+    let (state, _) = state();
+
+    let b = create_note(&state, 1, "b").await;
+    let a = create_note(&state, 1, "a").await;
+    create_note(&state, 1, "c").await;
+    create_note(&state, 2, "a").await;
+
+    let tag = state.create_tag(Request::new(CreateTagReq { user_id: 1, name: "tag".into(), ..Default::default() })).await.unwrap().into_inner();
+
+    for note_id in [a, b] {
+        state.attach_tag(Request::new(AttachTagReq { user_id: 1, note_id, tag_id: tag.id })).await.unwrap();
+    }
+
+    let filters = Filters { filter_tags: Some(FilterTags { tag_ids: vec![tag.id] }), ..Default::default() };
+    let list = state.read_notes(Request::new(read_notes_req(1, filters))).await.unwrap().into_inner();
+
+    assert_eq!(list.total_count, 2);
+    assert_eq!(list.notes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![a, b]);
+    assert!(list.notes.iter().all(|n| n.tags.len() == 1 && n.tags[0].note_id == Some(n.id)));
+
+    // an empty list of tags means the notes without any tags
+    let filters = Filters { filter_tags: Some(FilterTags { tag_ids: vec![] }), ..Default::default() };
+    let list = state.read_notes(Request::new(read_notes_req(1, filters))).await.unwrap().into_inner();
+    assert_eq!(list.notes.iter().map(|n| n.title.as_str()).collect::<Vec<_>>(), vec!["c"]);
+
+    let filters = Filters { filter_search: Some(FilterSearch { query: "B".into() }), ..Default::default() };
+    let list = state.read_notes(Request::new(read_notes_req(1, filters))).await.unwrap().into_inner();
+    assert_eq!(list.notes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![b]);
+}
+
+#[tokio::test]
+async fn notes_of_other_users_are_not_found() {
+    let (state, _) = state();
+    let id = create_note(&state, 1, "note").await;
+
+    let req = UpdateNoteReq { id, user_id: 2, title: "title".into(), text: "text".into() };
+    assert_eq!(state.update_note(Request::new(req)).await.unwrap_err().code(), Code::NotFound);
+
+    let req = DeleteNoteReq { id, user_id: 2 };
+    assert_eq!(state.delete_note(Request::new(req)).await.unwrap_err().code(), Code::NotFound);
+
+    let req = DeleteNoteReq { id, user_id: 1 };
+    assert!(state.delete_note(Request::new(req)).await.is_ok());
+}
+
+#[tokio::test]
+async fn invalid_requests_are_rejected_before_the_repo() {
+    let (state, _) = state();
+
+    let req = CreateNoteReq { user_id: 0, title: "a".repeat(251), text: String::new() };
+    assert_eq!(state.create_note(Request::new(req)).await.unwrap_err().code(), Code::InvalidArgument);
+
+    let list = state.read_notes(Request::new(read_notes_req(1, Filters::default()))).await.unwrap().into_inner();
+    assert_eq!(list.total_count, 0);
+}
+
+#[tokio::test]
+async fn shelf_files_move_to_the_note_or_get_queued_for_deletion() {
+    let (state, repo) = state();
+
+    let shelf = state.read_shelf(Request::new(ReadShelfReq { user_id: 1 })).await.unwrap().into_inner();
+    let shelf_id = AttachId::ShelfId(shelf.id);
+
+    repo.create(1, "moved", "moved.txt", 1, shelf_id.clone()).await.unwrap();
+
+    let req = ConvertToNoteReq { user_id: 1, note_title: "note".into(), note_text: String::new() };
+    state.convert_to_note(Request::new(req)).await.unwrap();
+
+    let list = state.read_notes(Request::new(read_notes_req(1, Filters::default()))).await.unwrap().into_inner();
+    assert_eq!(list.notes[0].files.iter().map(|f| f.hash.as_str()).collect::<Vec<_>>(), vec!["moved"]);
+
+    repo.create(1, "cleared", "cleared.txt", 1, shelf_id).await.unwrap();
+    state.clear_shelf(Request::new(ClearShelfReq { user_id: 1 })).await.unwrap();
+
+    let shelf = state.read_shelf(Request::new(ReadShelfReq { user_id: 1 })).await.unwrap().into_inner();
+    assert!(shelf.files.is_empty());
+    assert_eq!(repo.pending_blob_deletions(), vec!["cleared".to_owned()]);
+}
+
+#[tokio::test]
+async fn idempotent_calls_replay_their_response() {
+    let (state, repo) = state();
+
+    let request = |title: &str| {
+        let mut request = Request::new(CreateNoteReq { user_id: 1, title: title.into(), text: String::new() });
+        request.metadata_mut().insert(IDEMPOTENCY_KEY, "key".parse().unwrap());
+        request
+    };
+
+    let first = state.create_note(request("note")).await.unwrap().into_inner();
+    let replayed = state.create_note(request("note")).await.unwrap().into_inner();
+    assert_eq!(replayed, first);
+
+    let list = state.read_notes(Request::new(read_notes_req(1, Filters::default()))).await.unwrap().into_inner();
+    assert_eq!(list.total_count, 1);
+
+    assert_eq!(state.create_note(request("other")).await.unwrap_err().code(), Code::AlreadyExists);
+
+    // a failed call frees its key, and a completed one keeps it
+    assert!(repo.reserve(1, "failed", "hash", "first", 60, 60).await.unwrap().is_none());
+    repo.release(1, "failed", "first").await.unwrap();
+    assert!(repo.reserve(1, "failed", "other", "second", 60, 60).await.unwrap().is_none());
+
+    repo.complete(1, "failed", "second", b"response").await.unwrap();
+    repo.release(1, "failed", "second").await.unwrap();
+    assert_eq!(repo.reserve(1, "failed", "other", "third", 60, 60).await.unwrap().unwrap().response.as_deref(), Some(&b"response"[..]));
+
+    // a call that never finished, like after a crash, holds its key only until the lease ends
+    assert!(repo.reserve(1, "crashed", "hash", "first", 1, 60).await.unwrap().is_none());
+    assert!(repo.reserve(1, "crashed", "hash", "second", 1, 60).await.unwrap().unwrap().response.is_none());
+
+    tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+
+    assert!(repo.reserve(1, "crashed", "hash", "second", 1, 60).await.unwrap().is_none());
+    assert!(repo.reserve(1, "failed", "other", "third", 1, 60).await.unwrap().unwrap().response.is_some());
+
+    // the call that lost its key can't free it or store its response anymore
+    repo.release(1, "crashed", "first").await.unwrap();
+    repo.complete(1, "crashed", "first", b"stale").await.unwrap();
+    assert!(repo.reserve(1, "crashed", "hash", "third", 60, 60).await.unwrap().unwrap().response.is_none());
+
+    repo.complete(1, "crashed", "second", b"response").await.unwrap();
+    assert_eq!(repo.reserve(1, "crashed", "hash", "third", 60, 60).await.unwrap().unwrap().response.as_deref(), Some(&b"response"[..]));
+}
+
+#[tokio::test]
+async fn idempotent_calls_renew_their_lease() {
+    let (mut state, repo) = state();
+    state.idempotency_lease = 1;
+
+    let req = CreateNoteReq { user_id: 1, title: "slow".into(), text: String::new() };
+
+    let call = state.idempotent(Some("slow".into()), "CreateNote", 1, &req, || async {
+        tokio::time::sleep(std::time::Duration::from_millis(3100)).await;
+        Ok(req.clone())
+    });
+
+    // the call outlives its lease three times over and still holds its key
+    let retry = async {
+        tokio::time::sleep(std::time::Duration::from_millis(2100)).await;
+        repo.reserve(1, "slow", "hash", "retry", 1, 60).await.unwrap()
+    };
+
+    let (result, retried) = tokio::join!(call, retry);
+    assert_eq!(result.unwrap(), req);
+    assert!(retried.unwrap().response.is_none());
+    assert!(repo.reserve(1, "slow", "hash", "retry", 1, 60).await.unwrap().unwrap().response.is_some());
+}
+
+#[tokio::test]
+async fn batches_are_all_or_nothing_unless_partial() {
+    let (state, _) = state();
+
+    let a = create_note(&state, 1, "a").await;
+    let other = create_note(&state, 2, "other").await;
+    let tag = state.create_tag(Request::new(CreateTagReq { user_id: 1, name: "tag".into(), ..Default::default() })).await.unwrap().into_inner();
+
+    let req = |partial| BatchTagsReq { user_id: 1, note_ids: vec![a, other, a], tag_ids: vec![tag.id], partial };
+    assert_eq!(state.batch_attach_tags(Request::new(req(false))).await.unwrap_err().code(), Code::NotFound);
+
+    let list = state.read_notes(Request::new(read_notes_req(1, Filters::default()))).await.unwrap().into_inner();
+    assert!(list.notes[0].tags.is_empty());
+
+    // the repeated note is only attached once
+    let result = state.batch_attach_tags(Request::new(req(true))).await.unwrap().into_inner();
+    assert_eq!((result.results.len(), result.succeeded), (2, 1));
+
+    let req = BatchTagsReq { user_id: 1, note_ids: vec![], tag_ids: vec![0], partial: false };
+    assert_eq!(state.batch_detach_tags(Request::new(req)).await.unwrap_err().code(), Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn archived_notes_are_hidden_and_pinned_ones_go_first() {
+    let (state, _) = state();
+
+    let a = create_note(&state, 1, "a").await;
+    let b = create_note(&state, 1, "b").await;
+    let c = create_note(&state, 1, "c").await;
+
+    let set = |note_ids: Vec<i32>, state: NoteState| Request::new(BatchSetNoteStateReq { user_id: 1, note_ids, state: Some(state), partial: false });
+    state.batch_set_note_state(set(vec![c], NoteState { pinned: Some(true), ..Default::default() })).await.unwrap();
+    state.batch_set_note_state(set(vec![a], NoteState { archived: Some(true), ..Default::default() })).await.unwrap();
+
+    let mut req = read_notes_req(1, Filters::default());
+    req.sort.as_mut().unwrap().pinned_first = true;
+
+    let list = state.read_notes(Request::new(req.clone())).await.unwrap().into_inner();
+    assert_eq!((list.total_count, list.notes.iter().map(|n| n.id).collect::<Vec<_>>()), (2, vec![c, b]));
+
+    req.filters = Some(Filters { filter_state: Some(FilterState { archive: filter_state::Archive::All.into(), ..Default::default() }), ..Default::default() });
+    let list = state.read_notes(Request::new(req)).await.unwrap().into_inner();
+    assert_eq!(list.notes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![c, a, b]);
+
+    let code = state.batch_set_note_state(set(vec![b], NoteState::default())).await.unwrap_err().code();
+    assert_eq!(code, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn renaming_a_note_rewrites_the_links_to_it() {
+    let (state, _) = state();
+
+    let target = create_note(&state, 1, "Target").await;
+    let req = CreateNoteReq { user_id: 1, title: "source".into(), text: "[[target|label]] and [[Later]]".into() };
+    let source = state.create_note(Request::new(req)).await.unwrap().into_inner().id;
+    let later = create_note(&state, 1, "later").await;
+
+    let req = UpdateNoteReq { id: target, user_id: 1, title: "Renamed".into(), text: String::new() };
+    state.update_note(Request::new(req)).await.unwrap();
+
+    let list = state.read_notes(Request::new(read_notes_req(1, Filters::default()))).await.unwrap().into_inner();
+    let source_note = list.notes.iter().find(|n| n.id == source).unwrap();
+    assert_eq!((source_note.text.as_str(), source_note.times_edited), ("[[Renamed|label]] and [[Later]]", 1));
+
+    let backlinks = state.get_backlinks(Request::new(GetBacklinksReq { user_id: 1, note_id: target })).await.unwrap().into_inner();
+    assert_eq!(backlinks.notes.iter().map(|n| n.id).collect::<Vec<_>>(), vec![source]);
+
+    let graph = state.get_note_graph(Request::new(GetNoteGraphReq { user_id: 1, include_archived: false })).await.unwrap().into_inner();
+    assert_eq!(graph.edges.iter().map(|e| (e.source_id, e.target_id)).collect::<Vec<_>>(), vec![(source, target), (source, later)]);
+
+    let code = state.get_backlinks(Request::new(GetBacklinksReq { user_id: 2, note_id: target })).await.unwrap_err().code();
+    assert_eq!(code, Code::NotFound);
+
+    // none of the linking notes is rewritten if one of them would get too long
+    let req = CreateNoteReq { user_id: 1, title: "long".into(), text: format!("[[Renamed]]{}", "a".repeat(50000 - 11)) };
+    state.create_note(Request::new(req)).await.unwrap();
+
+    let req = UpdateNoteReq { id: target, user_id: 1, title: "Renamed again".into(), text: String::new() };
+    assert_eq!(state.update_note(Request::new(req)).await.unwrap_err().code(), Code::FailedPrecondition);
+
+    let list = state.read_notes(Request::new(read_notes_req(1, Filters::default()))).await.unwrap().into_inner();
+    assert_eq!(list.notes.iter().find(|n| n.id == target).unwrap().title, "Renamed");
+    assert_eq!(list.notes.iter().find(|n| n.id == source).unwrap().text, "[[Renamed|label]] and [[Later]]");
+}
+
+#[test]
+fn template_variables_are_substituted_once() {
+    let now = chrono::DateTime::parse_from_rfc3339("2024-10-13T23:30:00Z").unwrap().to_utc();
+    let variables = [("name".to_owned(), "{{date}}".to_owned())].into();
+
+    let rendered = crate::templates::render("{{date}} {{ time }} {{weekday}} {{name}} {{ {{other}} {{date", now, chrono_tz::Tz::Asia__Tokyo, &variables);
+    assert_eq!(rendered, "2024-10-14 08:30 Monday {{date}} {{ {{other}} {{date");
+}
+
+#[tokio::test]
+async fn notes_from_templates_get_the_tags_of_the_template() {
+    let (state, _) = state();
+
+    let create_tag = |name: &str| CreateTagReq { user_id: 1, name: name.into(), ..Default::default() };
+    let kept = state.create_tag(Request::new(create_tag("kept"))).await.unwrap().into_inner();
+    let deleted = state.create_tag(Request::new(create_tag("deleted"))).await.unwrap().into_inner();
+
+    let req = CreateTemplateReq { user_id: 1, name: "template".into(), title: "{{ title }}".into(), text: String::new(), tag_ids: vec![deleted.id, kept.id, kept.id] };
+    let template = state.create_template(Request::new(req)).await.unwrap().into_inner();
+    assert_eq!(template.tag_ids, vec![kept.id, deleted.id]);
+
+    state.delete_tag(Request::new(DeleteTagReq { id: deleted.id, user_id: 1 })).await.unwrap();
+
+    let req = CreateNoteFromTemplateReq { user_id: 1, template_id: template.id, variables: [("title".into(), "note".into())].into(), timezone: String::new() };
+    let note = state.create_note_from_template(Request::new(req.clone())).await.unwrap().into_inner();
+    assert_eq!((note.title.as_str(), note.tags.iter().map(|t| t.id).collect::<Vec<_>>()), ("note", vec![kept.id]));
+
+    let code = state.create_note_from_template(Request::new(CreateNoteFromTemplateReq { user_id: 2, ..req })).await.unwrap_err().code();
+    assert_eq!(code, Code::NotFound);
+}
+
+#[test]
+fn recurring_reminders_keep_their_local_time_across_dst() {
+    let schedule = crate::reminders::Schedule::parse("2024-10-25T09:00", "Europe/Berlin", "FREQ=WEEKLY;BYDAY=MO,FR;COUNT=3").unwrap();
+    let utc = |time: &str| chrono::DateTime::parse_from_rfc3339(time).unwrap().to_utc();
+
+    // the clocks go back on the 27th of october
+    let fires: Vec<_> = std::iter::successors(schedule.next_fire(None), |after| schedule.next_fire(Some(*after))).collect();
+    assert_eq!(fires, [utc("2024-10-25T07:00:00Z"), utc("2024-10-28T08:00:00Z"), utc("2024-11-01T08:00:00Z")]);
+
+    // 2:30 is skipped when the clocks go forward, and moves by the length of the gap
+    let gap = crate::reminders::Schedule::parse("2024-03-31T02:30", "Europe/Berlin", "").unwrap();
+    assert_eq!(gap.next_fire(None), Some(utc("2024-03-31T01:30:00Z")));
+}
+
+#[tokio::test]
+async fn missed_reminders_fire_once_and_move_to_their_next_occurrence() {
+    let (state, repo) = state();
+    let note_id = create_note(&state, 1, "note").await;
+
+    let create = |local_time: &str, recurrence: &str| CreateReminderReq {
+        user_id: 1, note_id, local_time: local_time.into(), timezone: String::new(), recurrence: recurrence.into(),
+    };
+
+    let once = state.create_reminder(Request::new(create("2024-10-01T09:00", ""))).await.unwrap().into_inner();
+    let daily = state.create_reminder(Request::new(create("2024-10-01T09:00", "FREQ=DAILY"))).await.unwrap().into_inner();
+    state.create_reminder(Request::new(create("2024-10-30T09:00", ""))).await.unwrap();
+
+    let mut receiver = state.due_reminders.subscribe();
+    let now = chrono::DateTime::parse_from_rfc3339("2024-10-20T12:00:00Z").unwrap().to_utc();
+
+    assert_eq!(crate::reminders::fire_due(repo.as_ref(), &state.due_reminders, now).await.unwrap(), 2);
+    assert_eq!(crate::reminders::fire_due(repo.as_ref(), &state.due_reminders, now).await.unwrap(), 0);
+
+    let fired = receiver.recv().await.unwrap().reminder.unwrap();
+    assert_eq!((fired.id, fired.fired), (once.id, true));
+
+    let fired = receiver.recv().await.unwrap().reminder.unwrap();
+    let next = chrono::DateTime::parse_from_rfc3339("2024-10-21T09:00:00Z").unwrap().timestamp();
+    assert_eq!((fired.id, fired.fired, fired.fire_at), (daily.id, false, next));
+}
+
+#[test]
+fn task_checkboxes_are_parsed_and_flipped_in_place() {
+    let text = "– ünïcode\r\n\t- [ ]   spaced out  \r\n~~~\n```\n- [ ] fenced\n~~~\n10. [x] ten\n+ [x]\n- [ ]not a task";
+
+    let tasks: Vec<_> = crate::repo::tasks::parse(text).into_iter().map(|t| (t.text, t.done)).collect();
+    assert_eq!(tasks, [("spaced out".to_owned(), false), ("ten".to_owned(), true), (String::new(), true)]);
+
+    let toggled = crate::repo::tasks::toggle(text, 0).unwrap();
+    assert_eq!(toggled, text.replacen("- [ ]   spaced", "- [x]   spaced", 1));
+    assert_eq!(crate::repo::tasks::toggle(&toggled, 1).unwrap(), toggled.replacen("[x] ten", "[ ] ten", 1));
+    assert_eq!(crate::repo::tasks::toggle(text, 3), None);
+}
+
+#[tokio::test]
+async fn uploads_over_the_budget_wait_instead_of_failing() {
+    use crate::rate_limit::{Budget, LimitKey, Limits, RateLimiter};
+
+    let upload_bytes = Some(Budget { rate: 10_000.0, burst: 1_000.0 });
+    let limiter = RateLimiter::new(Limits::default(), Limits { upload_bytes, ..Limits::default() });
+    let user = LimitKey::User(1);
+
+    let start = std::time::Instant::now();
+    for _ in 0..3 {
+        limiter.throttle_upload(&user, 1_000).await.unwrap();
+    }
+
+    // the bucket starts full, and refills a thousand bytes every tenth of a second
+    assert!(start.elapsed() >= std::time::Duration::from_millis(190), "{:?}", start.elapsed());
+
+    let error = limiter.throttle_upload(&user, 1_001).await.unwrap_err();
+    assert_eq!(error.code(), Code::ResourceExhausted);
+    assert!(error.metadata().get("retry-after").is_none());
+}
+
+#[test]
+fn caller_tokens_match_only_whole_and_unexpired() {
+    let callers = crate::callers::parse_callers("gateway:new,old@1000000000:notes.Notes;admin:admin:*").unwrap();
+    let name = |header: &str| crate::callers::find_caller(&callers, header).map(|c| c.name.as_str());
+
+    assert_eq!(name("Bearer new"), Some("gateway"));
+    assert_eq!(name("Bearer admin"), Some("admin"));
+    assert_eq!(name("Bearer old"), None);
+    assert_eq!(name("Bearer ne"), None);
+    assert_eq!(name("Bearer newer"), None);
+    assert_eq!(name("new"), None);
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn foreign_key_violations_are_told_apart_by_the_statement() {
+    use sqlx::Connection;
+
+    use crate::error::ServiceError;
+
+    let mut connection = sqlx::SqliteConnection::connect("sqlite::memory:").await.unwrap();
+    crate::db::SQLITE_MIGRATOR.run(&mut connection).await.unwrap();
+
+    let reason = |error: ServiceError| match error {
+        ServiceError::FailedPrecondition(violation) => violation.reason,
+        other => panic!("{other:?}"),
+    };
+
+    let error = sqlx::query("INSERT INTO note_tags (note_id, tag_id) VALUES (1, 1);").execute(&mut connection).await.unwrap_err();
+    assert_eq!(reason(error.into()), "REFERENCE_NOT_FOUND");
+
+    sqlx::query("INSERT INTO notes (id, user_id, title, text) VALUES (1, 1, 'note', '');").execute(&mut connection).await.unwrap();
+    sqlx::query("INSERT INTO tags (id, user_id, name) VALUES (1, 1, 'tag');").execute(&mut connection).await.unwrap();
+    sqlx::query("INSERT INTO note_tags (note_id, tag_id) VALUES (1, 1);").execute(&mut connection).await.unwrap();
+
+    let error = sqlx::query("DELETE FROM tags WHERE id = 1;").execute(&mut connection).await.unwrap_err();
+    assert_eq!(reason(ServiceError::from_delete(error)), "STILL_REFERENCED");
+}
+
+#[cfg(feature = "sqlite")]
+#[tokio::test]
+async fn reindexing_saves_the_tasks_of_the_older_notes() {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new().max_connections(1).connect("sqlite::memory:").await.unwrap();
+    crate::db::SQLITE_MIGRATOR.run(&pool).await.unwrap();
+
+    // a note written before the tasks were saved
+    sqlx::query("INSERT INTO notes (id, user_id, title, text) VALUES (1, 1, 'old', '- [ ] one\n- [x] two');").execute(&pool).await.unwrap();
+    sqlx::query("INSERT INTO notes (id, user_id, title, text) VALUES (2, 1, 'empty', '');").execute(&pool).await.unwrap();
+
+    let repo = crate::repo::sql::SqliteRepo::new(pool.clone());
+    assert_eq!(repo.reindex_tasks().await.unwrap(), 2);
+
+    let tasks: Vec<(i32, String, bool)> = sqlx::query_as("SELECT position, text, done FROM note_tasks WHERE note_id = 1 ORDER BY position;")
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+
+    assert_eq!(tasks, [(0, "one".to_owned(), false), (1, "two".to_owned(), true)]);
+
+    // running it again changes nothing
+    assert_eq!(repo.reindex_tasks().await.unwrap(), 2);
+    assert_eq!(repo.reindex_tasks().await.unwrap(), 2);
+}
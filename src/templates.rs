@@ -0,0 +1,62 @@
+//! the variables of the note templates, which are written like `{{date}}` or `{{ project }}`
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+
+/// the variables that are filled in from the current time, which the requests can't set
+pub const BUILTIN_VARIABLES: [&str; 3] = ["date", "time", "weekday"];
+
+/// parses an IANA timezone like "Europe/Berlin". the empty one is UTC
+pub fn parse_timezone(timezone: &str) -> Option<Tz> {
+    match timezone {
+        "" => Some(Tz::UTC),
+        _ => timezone.parse().ok(),
+    }
+}
+
+/// a variable name that the requests can set, made of ascii letters, digits and underscores
+pub fn valid_variable(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !BUILTIN_VARIABLES.contains(&name)
+}
+
+/// substitutes the variables of the `template`, with the builtin ones in the `timezone`.
+/// the unknown variables are left as they are, and the substituted values aren't searched for variables again
+pub fn render(template: &str, now: DateTime<Utc>, timezone: Tz, variables: &BTreeMap<String, String>) -> String {
+    let now = now.with_timezone(&timezone);
+
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else {
+            break;
+        };
+
+        let value = match rest[start + 2..start + 2 + len].trim() {
+            "date" => Some(now.format("%Y-%m-%d").to_string()),
+            "time" => Some(now.format("%H:%M").to_string()),
+            "weekday" => Some(now.format("%A").to_string()),
+            name => variables.get(name).cloned(),
+        };
+
+        match value {
+            Some(value) => {
+                rendered += &rest[..start];
+                rendered += &value;
+                rest = &rest[start + len + 4..];
+            },
+            // the braces might still open another variable, like in "{{ {{date}}"
+            None => {
+                rendered += &rest[..start + 2];
+                rest = &rest[start + 2..];
+            },
+        }
+    }
+
+    rendered += rest;
+    rendered
+}
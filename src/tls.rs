@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, Result};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::{server::WebPkiClientVerifier, RootCertStore, ServerConfig};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::transport::{server::{TcpConnectInfo, TlsConnectInfo}, Body};
+
+use crate::config::TlsConfig;
+
+/// h2 alpn in plain format for rustls
+const ALPN_H2: &[u8] = b"h2";
+/// http/1.1 alpn, for the rest gateway and the gRPC-Web clients that don't speak h2
+const ALPN_HTTP1: &[u8] = b"http/1.1";
+
+/// the server config that gets replaced on reloads, and the settings of the handshakes
+#[derive(Clone)]
+pub struct SharedServerConfig {
+    current: Arc<RwLock<Arc<ServerConfig>>>,
+    handshake_timeout: Duration,
+}
+
+/// parses a string like "gateway=notes.Notes,files.Files;admin=*"
+/// into a map of client identities and the services they are allowed to call
+pub fn parse_client_services(value: &str) -> Result<HashMap<String, Vec<String>>> {
+    let mut client_services = HashMap::new();
+
+    for entry in value.split(';').map(str::trim).filter(|e| !e.is_empty()) {
+        let Some((identity, services)) = entry.split_once('=') else {
+            return Err(anyhow!("invalid client services entry, expected name=services, got {entry:?}"));
+        };
+
+        let services = services
+            .split(',')
+            .map(|s| s.trim().to_owned())
+            .filter(|s| !s.is_empty())
+            .collect();
+
+        client_services.insert(identity.trim().to_owned(), services);
+    }
+
+    Ok(client_services)
+}
+
+async fn load_server_config(settings: &TlsConfig) -> Result<Arc<ServerConfig>> {
+    let cert_pem = tokio::fs::read(&settings.cert_path).await?;
+    let key_pem = tokio::fs::read(&settings.key_path).await?;
+
+    let certs = rustls_pemfile::certs(&mut Cursor::new(cert_pem))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let key = rustls_pemfile::private_key(&mut Cursor::new(key_pem))?
+        .ok_or(anyhow!("no private key found in {}", settings.key_path.display()))?;
+
+    let builder = ServerConfig::builder();
+
+    let builder = match &settings.client_ca_path {
+        None => builder.with_no_client_auth(),
+        Some(client_ca_path) => {
+            let ca_pem = tokio::fs::read(client_ca_path).await?;
+            let mut roots = RootCertStore::empty();
+
+            for cert in rustls_pemfile::certs(&mut Cursor::new(ca_pem)) {
+                roots.add(cert?)?;
+            }
+
+            builder.with_client_cert_verifier(WebPkiClientVerifier::builder(roots.into()).build()?)
+        },
+    };
+
+    let mut config = builder.with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![ALPN_H2.into(), ALPN_HTTP1.into()];
+
+    Ok(Arc::new(config))
+}
+
+async fn modified_times(settings: &TlsConfig) -> Vec<Option<SystemTime>> {
+    let paths = [Some(&settings.cert_path), Some(&settings.key_path), settings.client_ca_path.as_ref()];
+    let mut times = Vec::with_capacity(paths.len());
+
+    for path in paths.into_iter().flatten() {
+        let modified = tokio::fs::metadata(path).await.and_then(|m| m.modified()).ok();
+        times.push(modified);
+    }
+
+    times
+}
+
+/// loads the server config and spawns a task that reloads it
+/// whenever any of the certificate files change on disk
+pub async fn watch_server_config(settings: TlsConfig) -> Result<SharedServerConfig> {
+    let current = Arc::new(RwLock::new(load_server_config(&settings).await?));
+    let shared_config = current.clone();
+    let handshake_timeout = Duration::from_secs(settings.handshake_timeout);
+
+    tokio::spawn(async move {
+        let mut last_modified = modified_times(&settings).await;
+
+        loop {
+            tokio::time::sleep(Duration::from_secs(settings.reload_interval)).await;
+
+            let modified = modified_times(&settings).await;
+            if modified == last_modified {
+                continue;
+            }
+
+            // if the reload fails, the old config stays in use and the reload
+            // gets retried on the next tick, since last_modified is not updated
+            match load_server_config(&settings).await {
+                Ok(new_config) => {
+                    *shared_config.write().unwrap() = new_config;
+                    last_modified = modified;
+                    log::info!("Reloaded the TLS certificates");
+                },
+                Err(e) => log::error!("Could not reload the TLS certificates: {:?}", e),
+            }
+        }
+    });
+
+    Ok(SharedServerConfig { current, handshake_timeout })
+}
+
+/// accepts tcp connections from the `listener` and performs tls handshakes
+/// with the current server config. handshakes are done in separate tasks
+/// so that a slow client can't block the others from connecting, and the
+/// connections that don't finish their handshake in time are dropped
+pub fn incoming(
+    listener: TcpListener,
+    config: SharedServerConfig,
+) -> ReceiverStream<Result<TlsStream<TcpStream>, std::io::Error>> {
+    let (sender, receiver) = mpsc::channel(32);
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(v) => v,
+                Err(e) => {
+                    log::error!("TCP ACCEPT ERR: {:?}", e);
+                    continue;
+                },
+            };
+
+            let acceptor = TlsAcceptor::from(config.current.read().unwrap().clone());
+            let handshake_timeout = config.handshake_timeout;
+            let sender = sender.clone();
+
+            tokio::spawn(async move {
+                match tokio::time::timeout(handshake_timeout, acceptor.accept(stream)).await {
+                    Ok(Ok(tls_stream)) => {
+                        let _ = sender.send(Ok(tls_stream)).await;
+                    },
+                    Ok(Err(e)) => log::debug!("TLS handshake with {} failed: {:?}", addr, e),
+                    Err(_) => log::debug!("TLS handshake with {} timed out", addr),
+                }
+            });
+        }
+    });
+
+    ReceiverStream::new(receiver)
+}
+
+/// returns the common name of the client certificate that the request's connection was made with
+pub fn peer_common_name(req: &tonic::codegen::http::Request<Body>) -> Option<String> {
+    let certs = req
+        .extensions()
+        .get::<TlsConnectInfo<TcpConnectInfo>>()?
+        .peer_certs()?;
+
+    let (_, cert) = x509_parser::parse_x509_certificate(certs.first()?.get_ref()).ok()?;
+    let common_name = cert.subject().iter_common_name().next()?.as_str().ok()?;
+
+    Some(common_name.to_owned())
+}
@@ -1,15 +1,39 @@
-use sqlx::{postgres::{PgArguments, PgRow}, prelude::FromRow, PgPool, Postgres, Row};
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use sqlx::{database::HasArguments, postgres::PgRow, prelude::FromRow, Database, Row};
+use tokio::sync::broadcast;
 use tonic::{async_trait, transport::Body, Response, Status};
 use tonic_middleware::RequestInterceptor;
 
-use crate::proto::{files::File, notes::Note, shelves::Shelf, tags::Tag};
+use crate::{callers::{self, Caller}, proto::{files::File, notes::{Note, Task}, reminders::{DueReminder, Reminder}, shelves::Shelf, tags::Tag, templates::Template}, rate_limit::{RateLimiter, RequestLimiter}, reminders::LOCAL_TIME_FORMAT, repo::{FileRepo, IdempotencyRepo, NoteRepo, ReminderRepo, ShelfRepo, TagRepo, TemplateRepo}, tls, user_auth::{UserTokenVerifier, VerifiedUser, USER_TOKEN_KEY}};
 
 pub type ServiceResult<T> = Result<Response<T>, Status>;
 
 #[derive(Clone)]
 pub struct AppState {
-    pub pool: PgPool,
+    pub notes: Arc<dyn NoteRepo>,
+    pub tags: Arc<dyn TagRepo>,
+    pub files: Arc<dyn FileRepo>,
+    pub shelves: Arc<dyn ShelfRepo>,
+    pub templates: Arc<dyn TemplateRepo>,
+    pub reminders: Arc<dyn ReminderRepo>,
+    pub idempotency: Arc<dyn IdempotencyRepo>,
+    /// in seconds
+    pub idempotency_ttl: u64,
+    /// in seconds
+    pub idempotency_lease: u64,
     pub chunk_size: usize,
+    pub storage_path: PathBuf,
+    pub download_channel_depth: usize,
+    /// the reminders that the scheduler fires, for the WatchDueReminders streams
+    pub due_reminders: broadcast::Sender<DueReminder>,
+}
+
+impl AppState {
+    /// the path that the file with the `hash` is stored at
+    pub fn file_path(&self, hash: &str) -> PathBuf {
+        self.storage_path.join(hash)
+    }
 }
 
 #[derive(FromRow)]
@@ -24,46 +48,70 @@ pub struct CountWrapper {
 
 #[derive(Clone)]
 pub struct Interceptor {
-    pub auth_value: String,
+    pub callers: Arc<Vec<Caller>>,
+    pub client_services: Option<Arc<HashMap<String, Vec<String>>>>,
+    pub user_verifier: Option<Arc<UserTokenVerifier>>,
+    pub rate_limiter: Option<Arc<RateLimiter>>,
 }
 
 #[async_trait]
 impl RequestInterceptor for Interceptor {
     async fn intercept(
         &self,
-        req: tonic::codegen::http::Request<Body>
+        mut req: tonic::codegen::http::Request<Body>
     ) -> Result<tonic::codegen::http::Request<Body>, Status> {
+        let caller = match req.headers().get("authorization").map(|v| v.to_str()) {
+            Some(Ok(h)) => callers::find_caller(&self.callers, h),
+            _ => None,
+        };
+
+        let Some(caller) = caller else {
+            return Err(Status::unauthenticated("invalid authorization token"));
+        };
+
         if req.uri().path() != "/" {
-            println!("Request: {} -> {}", req.method(), req.uri().path());
+            log::info!("Request: {} -> {} (caller: {})", req.method(), req.uri().path(), caller.name);
         }
 
-        match req.headers().get("authorization").map(|v| v.to_str()) {
-            Some(Ok(h)) if h == self.auth_value => (),
-            _ => return Err(Status::unauthenticated("invalid authorization token")),
+        if !caller.can_call(req.uri().path()) {
+            return Err(Status::permission_denied(format!("caller {} is not allowed to call {}", caller.name, req.uri().path())));
         }
 
-        Ok(req)
-    }
-}
+        // if there is a mapping of client certificates to services,
+        // making sure that the client is allowed to call the requested service
 
-// convert service errors into tonic::Status
-pub trait HandleServiceError<T> {
-    fn map_to_status(self) -> Result<T, Status>;
-}
-impl<T> HandleServiceError<T> for Result<T, sqlx::Error> {
-    fn map_to_status(self) -> Result<T, Status> {
-        self.map_err(|e| {
-            println!("DB ERR: {:#?}", e);
-            match e {
-                sqlx::Error::Database(e) => match e.kind() {
-                    sqlx::error::ErrorKind::Other => Status::unknown("Unknown"),
-                    _ => Status::invalid_argument("Invalid argument")
-                },
-
-                sqlx::Error::RowNotFound => Status::not_found("Not found"),
-                _ => Status::unknown("Unknown")
+        if let Some(client_services) = &self.client_services {
+            let Some(identity) = tls::peer_common_name(&req) else {
+                return Err(Status::unauthenticated("missing client certificate"));
+            };
+
+            let service = req.uri().path().trim_start_matches('/').split('/').next().unwrap_or_default();
+
+            match client_services.get(&identity) {
+                Some(services) if services.iter().any(|s| s == "*" || s == service) => (),
+                _ => return Err(Status::permission_denied(format!("client {identity} is not allowed to call {service}"))),
             }
-        })
+        }
+
+        // if user tokens are verified, the handlers will only
+        // accept requests for the user that the token belongs to
+
+        if let Some(user_verifier) = &self.user_verifier {
+            let verified_user = match req.headers().get(USER_TOKEN_KEY).map(|v| v.to_str()) {
+                Some(Ok(token)) => user_verifier.verify(token.trim_start_matches("Bearer "))?,
+                _ => return Err(Status::unauthenticated("missing user token")),
+            };
+
+            req.extensions_mut().insert(verified_user);
+        }
+
+        if let Some(rate_limiter) = &self.rate_limiter {
+            let user_id = req.extensions().get::<VerifiedUser>().map(|u| u.id);
+            let request_limiter = RequestLimiter::charge(rate_limiter.clone(), &caller.name, user_id)?;
+            req.extensions_mut().insert(request_limiter);
+        }
+
+        Ok(req)
     }
 }
 
@@ -85,19 +133,57 @@ pub fn fill_tuple_placeholder<V>(query: &str, arr: &[V], index_offset: usize) ->
     query_str
 }
 
+/// finds the first occurence of "()" inside of the `query`, and replaces it
+/// with the `rows` amount of tuples, each with the `columns` amount of placeholders
+pub fn fill_values_placeholder(query: &str, rows: usize, columns: usize, index_offset: usize) -> String {
+    let Some(paren_idx) = query.find("()") else {
+        return query.to_owned();
+    };
+
+    let tuples_str = (0..rows)
+        .map(|row| {
+            let placeholders = (1..=columns)
+                .map(|column| format!("${}", row * columns + column + index_offset))
+                .collect::<Vec<String>>()
+                .join(",");
+
+            format!("({placeholders})")
+        })
+        .collect::<Vec<String>>()
+        .join(",");
+
+    let (s, e) = query.split_at(paren_idx);
+    let query_str = format!("{s}{tuples_str}{}", &e[2..]);
+
+    query_str
+}
+
 // method on sqlx queries to bind values directly from a slice
-pub trait BindIter<'q> {
+pub trait BindIter<'q, DB: Database> {
     fn bind_iter<V>(self, _: V) -> Self
     where
         V: IntoIterator,
-        V::Item: 'q + Send + Sync + sqlx::Encode<'q, Postgres> + sqlx::Type<Postgres>,
+        V::Item: 'q + Send + Sync + sqlx::Encode<'q, DB> + sqlx::Type<DB>,
     ;
 }
-impl<'q, T> BindIter<'q> for sqlx::query::QueryAs<'q, Postgres, T, PgArguments> {
+impl<'q, DB: Database, T> BindIter<'q, DB> for sqlx::query::QueryAs<'q, DB, T, <DB as HasArguments<'q>>::Arguments> {
+    fn bind_iter<V>(mut self, iter: V) -> Self
+    where
+        V: IntoIterator,
+        V::Item: 'q + Send + Sync + sqlx::Encode<'q, DB> + sqlx::Type<DB>,
+    {
+        for item in iter {
+            self = self.bind(item);
+        }
+        self
+    }
+}
+
+impl<'q, DB: Database> BindIter<'q, DB> for sqlx::query::Query<'q, DB, <DB as HasArguments<'q>>::Arguments> {
     fn bind_iter<V>(mut self, iter: V) -> Self
     where
         V: IntoIterator,
-        V::Item: 'q + Send + Sync + sqlx::Encode<'q, Postgres> + sqlx::Type<Postgres>,
+        V::Item: 'q + Send + Sync + sqlx::Encode<'q, DB> + sqlx::Type<DB>,
     {
         for item in iter {
             self = self.bind(item);
@@ -110,69 +196,142 @@ impl<'q, T> BindIter<'q> for sqlx::query::QueryAs<'q, Postgres, T, PgArguments>
 trait FieldToUnix {
     fn try_get_unix(&self, field_name: &str) -> Result<i64, sqlx::Error>;
 }
-impl FieldToUnix for PgRow {
-    fn try_get_unix(&self, field_name: &str) -> Result<i64, sqlx::Error> {
-        Ok(
-            self
-                .try_get::<chrono::NaiveDateTime, &str>(field_name)?
-                .and_utc()
-                .timestamp()
-        )
-    }
-}
 
-impl sqlx::FromRow<'_, PgRow> for File {
-    fn from_row(row: &'_ PgRow) -> Result<Self, sqlx::Error> {
-        Ok(File {
-            id: row.try_get("id")?,
-            user_id: row.try_get("user_id")?,
-            hash: row.try_get("hash")?,
-            name: row.try_get("name")?,
-            size: row.try_get("size")?,
-            created: row.try_get_unix("created")?,
-            attach_id: row.try_get("attach_id").ok(),
-        })
-    }
-}
+// the rows of every supported database are converted the same way
+macro_rules! impl_from_row {
+    ($row:ty) => {
+        impl FieldToUnix for $row {
+            fn try_get_unix(&self, field_name: &str) -> Result<i64, sqlx::Error> {
+                Ok(
+                    self
+                        .try_get::<chrono::NaiveDateTime, &str>(field_name)?
+                        .and_utc()
+                        .timestamp()
+                )
+            }
+        }
 
-impl sqlx::FromRow<'_, PgRow> for Tag {
-    fn from_row(row: &'_ PgRow) -> Result<Self, sqlx::Error> {
-        Ok(Tag {
-            id: row.try_get("id")?,
-            user_id: row.try_get("user_id")?,
-            name: row.try_get("name")?,
-            created: row.try_get_unix("created")?,
-            note_id: row.try_get("note_id").ok(),
-        })
-    }
-}
+        impl sqlx::FromRow<'_, $row> for File {
+            fn from_row(row: &'_ $row) -> Result<Self, sqlx::Error> {
+                Ok(File {
+                    id: row.try_get("id")?,
+                    user_id: row.try_get("user_id")?,
+                    hash: row.try_get("hash")?,
+                    name: row.try_get("name")?,
+                    size: row.try_get("size")?,
+                    created: row.try_get_unix("created")?,
+                    attach_id: row.try_get("attach_id").ok(),
+                })
+            }
+        }
 
-impl sqlx::FromRow<'_, PgRow> for Note {
-    fn from_row(row: &'_ PgRow) -> Result<Self, sqlx::Error> {
-        Ok(Note {
-            id: row.try_get("id")?,
-            user_id: row.try_get("user_id")?,
-            title: row.try_get("title")?,
-            text: row.try_get("text")?,
-            created: row.try_get_unix("created")?,
-            last_edited: row.try_get_unix("last_edited")?,
-            times_edited: row.try_get("times_edited")?,
-            tags: vec![],
-            files: vec![],
-        })
-    }
-}
+        impl sqlx::FromRow<'_, $row> for Tag {
+            fn from_row(row: &'_ $row) -> Result<Self, sqlx::Error> {
+                Ok(Tag {
+                    id: row.try_get("id")?,
+                    user_id: row.try_get("user_id")?,
+                    name: row.try_get("name")?,
+                    created: row.try_get_unix("created")?,
+                    note_id: row.try_get("note_id").ok(),
+                    usage_count: row.try_get("usage_count").unwrap_or(0),
+                    color: row.try_get("color")?,
+                    icon: row.try_get("icon")?,
+                    description: row.try_get("description")?,
+                    position: row.try_get("position")?,
+                })
+            }
+        }
 
-impl sqlx::FromRow<'_, PgRow> for Shelf {
-    fn from_row(row: &'_ PgRow) -> Result<Self, sqlx::Error> {
-        Ok(Shelf {
-            id: row.try_get("id")?,
-            user_id: row.try_get("user_id")?,
-            text: row.try_get("text")?,
-            created: row.try_get_unix("created")?,
-            last_edited: row.try_get_unix("last_edited")?,
-            times_edited: row.try_get("times_edited")?,
-            files: vec![],
-        })
-    }
+        impl sqlx::FromRow<'_, $row> for Note {
+            fn from_row(row: &'_ $row) -> Result<Self, sqlx::Error> {
+                Ok(Note {
+                    id: row.try_get("id")?,
+                    user_id: row.try_get("user_id")?,
+                    title: row.try_get("title")?,
+                    text: row.try_get("text")?,
+                    created: row.try_get_unix("created")?,
+                    last_edited: row.try_get_unix("last_edited")?,
+                    times_edited: row.try_get("times_edited")?,
+                    tags: vec![],
+                    files: vec![],
+                    pinned: row.try_get("pinned")?,
+                    archived: row.try_get("archived")?,
+                    favorite: row.try_get("favorite")?,
+                })
+            }
+        }
+
+        impl sqlx::FromRow<'_, $row> for Task {
+            fn from_row(row: &'_ $row) -> Result<Self, sqlx::Error> {
+                Ok(Task {
+                    note_id: row.try_get("note_id")?,
+                    position: row.try_get("position")?,
+                    text: row.try_get("text")?,
+                    done: row.try_get("done")?,
+                    note_title: row.try_get("note_title")?,
+                    note_times_edited: row.try_get("note_times_edited")?,
+                })
+            }
+        }
+
+        impl sqlx::FromRow<'_, $row> for Shelf {
+            fn from_row(row: &'_ $row) -> Result<Self, sqlx::Error> {
+                Ok(Shelf {
+                    id: row.try_get("id")?,
+                    user_id: row.try_get("user_id")?,
+                    text: row.try_get("text")?,
+                    created: row.try_get_unix("created")?,
+                    last_edited: row.try_get_unix("last_edited")?,
+                    times_edited: row.try_get("times_edited")?,
+                    files: vec![],
+                })
+            }
+        }
+
+        impl sqlx::FromRow<'_, $row> for Template {
+            fn from_row(row: &'_ $row) -> Result<Self, sqlx::Error> {
+                Ok(Template {
+                    id: row.try_get("id")?,
+                    user_id: row.try_get("user_id")?,
+                    name: row.try_get("name")?,
+                    title: row.try_get("title")?,
+                    text: row.try_get("text")?,
+                    tag_ids: vec![],
+                    created: row.try_get_unix("created")?,
+                    last_edited: row.try_get_unix("last_edited")?,
+                })
+            }
+        }
+
+        impl sqlx::FromRow<'_, $row> for Reminder {
+            fn from_row(row: &'_ $row) -> Result<Self, sqlx::Error> {
+                Ok(Reminder {
+                    id: row.try_get("id")?,
+                    user_id: row.try_get("user_id")?,
+                    note_id: row.try_get("note_id")?,
+                    local_time: row.try_get::<chrono::NaiveDateTime, &str>("local_time")?.format(LOCAL_TIME_FORMAT).to_string(),
+                    timezone: row.try_get("timezone")?,
+                    recurrence: row.try_get("recurrence")?,
+                    fire_at: row.try_get_unix("fire_at")?,
+                    fired: row.try_get("fired")?,
+                    created: row.try_get_unix("created")?,
+                    last_edited: row.try_get_unix("last_edited")?,
+                })
+            }
+        }
+
+        impl sqlx::FromRow<'_, $row> for DueReminder {
+            fn from_row(row: &'_ $row) -> Result<Self, sqlx::Error> {
+                Ok(DueReminder {
+                    reminder: Some(Reminder::from_row(row)?),
+                    note_title: row.try_get("note_title")?,
+                    due_at: row.try_get_unix("fire_at")?,
+                })
+            }
+        }
+    };
 }
+
+impl_from_row!(PgRow);
+#[cfg(feature = "sqlite")]
+impl_from_row!(sqlx::sqlite::SqliteRow);
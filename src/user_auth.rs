@@ -0,0 +1,151 @@
+use anyhow::{anyhow, Result};
+use jsonwebtoken::{jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use tonic::{Request, Status};
+
+use crate::{config::AuthConfig, proto::{files, notes, reminders, shelves, tags, templates}, rate_limit::RequestLimiter, validation::Validate};
+
+/// the metadata key that the end user's jwt gets forwarded in
+pub const USER_TOKEN_KEY: &str = "x-user-token";
+
+/// verifies the end users' jwts, either with a shared secret or with keys from a local jwks file
+pub enum UserTokenVerifier {
+    Secret(DecodingKey),
+    Jwks(JwkSet),
+}
+
+#[derive(Deserialize)]
+struct UserClaims {
+    sub: String,
+}
+
+/// the user that the request was made for, inserted into the request's extensions by the interceptor
+#[derive(Clone, Debug)]
+pub struct VerifiedUser {
+    pub id: i32,
+}
+
+impl UserTokenVerifier {
+    /// returns None if neither a jwt secret nor a jwks path is configured, in which case user ids are not verified
+    pub fn from_config(auth: &AuthConfig) -> Result<Option<Self>> {
+        if let Some(secret) = &auth.user_jwt_secret {
+            return Ok(Some(Self::Secret(DecodingKey::from_secret(secret.as_bytes()))));
+        }
+
+        let Some(jwks_path) = &auth.user_jwt_jwks_path else {
+            return Ok(None);
+        };
+
+        let jwks = serde_json::from_str(&std::fs::read_to_string(jwks_path)?)
+            .map_err(|e| anyhow!("could not parse the JWKS file {}: {e}", jwks_path.display()))?;
+
+        Ok(Some(Self::Jwks(jwks)))
+    }
+
+    /// checks the token's signature and expiration, and returns the user that it belongs to
+    pub fn verify(&self, token: &str) -> Result<VerifiedUser, Status> {
+        let invalid_token = |e| {
+            log::debug!("USER TOKEN ERR: {:?}", e);
+            Status::unauthenticated("invalid user token")
+        };
+
+        let header = jsonwebtoken::decode_header(token).map_err(invalid_token)?;
+
+        let (key, algorithm) = match self {
+            Self::Secret(key) => (key.clone(), Algorithm::HS256),
+            Self::Jwks(jwks) => {
+                let jwk = match &header.kid {
+                    Some(kid) => jwks.find(kid),
+                    None if jwks.keys.len() == 1 => jwks.keys.first(),
+                    None => None,
+                }.ok_or(Status::unauthenticated("unknown user token key"))?;
+
+                (DecodingKey::from_jwk(jwk).map_err(invalid_token)?, header.alg)
+            },
+        };
+
+        // decoding fails if the header's algorithm is not the expected one,
+        // or if it doesn't match the type of the jwks key
+        let claims = jsonwebtoken::decode::<UserClaims>(token, &key, &Validation::new(algorithm))
+            .map_err(invalid_token)?
+            .claims;
+
+        let id = claims.sub
+            .parse()
+            .map_err(|_| Status::unauthenticated("invalid user token subject"))?;
+
+        Ok(VerifiedUser { id })
+    }
+}
+
+/// request messages that are executed on behalf of a user
+pub trait UserScoped {
+    fn user_id_mut(&mut self) -> &mut i32;
+}
+
+macro_rules! impl_user_scoped {
+    ($($t:ty),* $(,)?) => {
+        $(impl UserScoped for $t {
+            fn user_id_mut(&mut self) -> &mut i32 {
+                &mut self.user_id
+            }
+        })*
+    };
+}
+
+impl_user_scoped!(
+    notes::CreateNoteReq, notes::ReadNotesReq, notes::UpdateNoteReq, notes::DeleteNoteReq,
+    notes::AttachTagReq, notes::DetachTagReq,
+    notes::BatchTagsReq, notes::BatchDeleteNotesReq, notes::BatchMoveNotesReq,
+    notes::SetNoteStateReq, notes::BatchSetNoteStateReq, notes::GetBacklinksReq, notes::GetNoteGraphReq,
+    notes::ListTasksReq, notes::ToggleTaskReq,
+    tags::CreateTagReq, tags::ReadTagsReq, tags::UpdateTagReq, tags::DeleteTagReq, tags::MergeTagsReq,
+    tags::ReorderTagsReq,
+    files::CreateFileMetadata, files::DownloadFileReq, files::DeleteFileReq,
+    shelves::ReadShelfReq, shelves::UpdateShelfReq, shelves::ClearShelfReq, shelves::ConvertToNoteReq,
+    templates::CreateTemplateReq, templates::ReadTemplatesReq, templates::UpdateTemplateReq, templates::DeleteTemplateReq,
+    templates::CreateNoteFromTemplateReq,
+    reminders::CreateReminderReq, reminders::ReadRemindersReq, reminders::UpdateReminderReq, reminders::DeleteReminderReq,
+    reminders::WatchDueRemindersReq,
+);
+
+/// makes sure that the body's user id matches the verified user, if there is one.
+/// an unset (zero) user id gets replaced with the verified one
+pub fn check_user_id<T: UserScoped>(body: &mut T, verified_user: Option<&VerifiedUser>) -> Result<(), Status> {
+    let Some(verified_user) = verified_user else {
+        return Ok(());
+    };
+
+    let user_id = body.user_id_mut();
+
+    match *user_id {
+        0 => *user_id = verified_user.id,
+        id if id == verified_user.id => (),
+        _ => return Err(Status::permission_denied("user_id does not match the user token")),
+    }
+
+    Ok(())
+}
+
+// method on tonic requests to get the body with an already checked user id.
+// also charges the call to the user's rate limit budget, and validates the body
+pub trait IntoVerifiedInner<T> {
+    fn into_verified_inner(self) -> Result<T, Status>;
+}
+impl<T: UserScoped + Validate> IntoVerifiedInner<T> for Request<T> {
+    fn into_verified_inner(self) -> Result<T, Status> {
+        let verified_user = self.extensions().get::<VerifiedUser>().cloned();
+        let request_limiter = self.extensions().get::<RequestLimiter>().cloned();
+        let mut body = self.into_inner();
+
+        check_user_id(&mut body, verified_user.as_ref())?;
+
+        if let Some(request_limiter) = request_limiter {
+            request_limiter.check_user_call(*body.user_id_mut())?;
+        }
+
+        body.validate()?;
+
+        Ok(body)
+    }
+}
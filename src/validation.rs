@@ -0,0 +1,675 @@
+use crate::{error::{ServiceError, Violation}, proto::{files, notes, reminders, shelves, tags, templates}, reminders::{parse_local_time, Recurrence, Schedule}, templates::{parse_timezone, valid_variable}};
+
+// these match the column sizes in the migrations
+pub const NOTE_TITLE_MAX_LEN: usize = 250;
+pub const NOTE_TEXT_MAX_LEN: usize = 50000;
+pub const TAG_NAME_MAX_LEN: usize = 50;
+pub const TAG_ICON_MAX_LEN: usize = 32;
+pub const TAG_DESCRIPTION_MAX_LEN: usize = 500;
+pub const SHELF_TEXT_MAX_LEN: usize = 2500;
+pub const FILE_NAME_MAX_LEN: usize = 250;
+pub const FILE_HASH_MAX_LEN: usize = 50;
+pub const TEMPLATE_NAME_MAX_LEN: usize = 100;
+pub const RECURRENCE_MAX_LEN: usize = 250;
+
+pub const MAX_PER_PAGE: i32 = 100;
+// a batch of tags makes a pair for each note and tag, so both are limited
+pub const MAX_BATCH_NOTES: usize = 500;
+pub const MAX_BATCH_TAGS: usize = 20;
+pub const MAX_REORDER_TAGS: usize = 500;
+pub const SEARCH_QUERY_MAX_LEN: usize = 250;
+pub const MAX_TEMPLATE_TAGS: usize = 20;
+pub const MAX_TEMPLATE_VARIABLES: usize = 50;
+pub const VARIABLE_NAME_MAX_LEN: usize = 50;
+pub const VARIABLE_VALUE_MAX_LEN: usize = 1000;
+
+/// request messages that get checked before any sql is executed for them
+pub trait Validate {
+    /// checks the values and returns all of the invalid fields at once.
+    /// values that can be fixed, like file names, get normalized instead
+    fn validate(&mut self) -> Result<(), ServiceError>;
+}
+
+/// collects the field violations of a single request
+#[derive(Default)]
+struct Validator {
+    violations: Vec<Violation>,
+}
+
+impl Validator {
+    fn check(mut self, valid: bool, field: &str, reason: &'static str, description: impl FnOnce() -> String) -> Self {
+        if !valid {
+            self.violations.push(Violation { field: Some(field.to_owned()), reason, description: description() });
+        }
+
+        self
+    }
+
+    /// ids of existing rows are always positive
+    fn id(self, field: &str, value: i32) -> Self {
+        self.check(value > 0, field, "INVALID_ID", || format!("{field} must be a positive id"))
+    }
+
+    /// the length is counted in characters, the same way postgres does it for VARCHAR
+    fn max_len(self, field: &str, value: &str, max: usize) -> Self {
+        let len = value.chars().count();
+        self.check(len <= max, field, "VALUE_TOO_LONG", || format!("{field} must be at most {max} characters long, got {len}"))
+    }
+
+    fn not_blank(self, field: &str, value: &str) -> Self {
+        self.check(!value.trim().is_empty(), field, "VALUE_EMPTY", || format!("{field} can't be empty"))
+    }
+
+    /// a non-empty list of at most `max` ids, each of which is checked like `id`
+    fn ids(mut self, field: &str, values: &[i32], max: usize) -> Self {
+        self = self
+            .check(!values.is_empty(), field, "VALUE_EMPTY", || format!("{field} can't be empty"))
+            .check(values.len() <= max, field, "TOO_MANY_ITEMS", || format!("{field} can have at most {max} ids, got {}", values.len()));
+
+        for (i, value) in values.iter().enumerate() {
+            self = self.id(&format!("{field}[{i}]"), *value);
+        }
+
+        self
+    }
+
+    /// a hex color like #1e90ff. the empty color is allowed, as it means no color
+    fn color(self, field: &str, value: &str) -> Self {
+        let valid = value.is_empty()
+            || (value.len() == 7 && value.starts_with('#') && value[1..].chars().all(|c| c.is_ascii_hexdigit()));
+
+        self.check(valid, field, "INVALID_COLOR", || format!("{field} must be a hex color like #1e90ff"))
+    }
+
+    /// a required page, which can't be larger than MAX_PER_PAGE
+    fn pagination(mut self, value: &Option<notes::Pagination>) -> Self {
+        self = self.present("pagination", value);
+
+        if let Some(pagination) = value {
+            self = self
+                .check(pagination.page >= 1, "pagination.page", "INVALID_PAGE", || "pagination.page must be at least 1".into())
+                .check(
+                    (1..=MAX_PER_PAGE).contains(&pagination.per_page), "pagination.per_page", "INVALID_PAGE_SIZE",
+                    || format!("pagination.per_page must be between 1 and {MAX_PER_PAGE}"),
+                );
+        }
+
+        self
+    }
+
+    /// a state that changes at least one of the flags
+    fn note_state(self, field: &str, value: &Option<notes::NoteState>) -> Self {
+        let changes = value.as_ref().is_some_and(|s| s.pinned.is_some() || s.archived.is_some() || s.favorite.is_some());
+        self.check(changes, field, "MISSING_FIELD", || format!("{field} must set at least one of pinned, archived and favorite"))
+    }
+
+    /// an IANA timezone like Europe/Berlin, or the empty one for UTC
+    fn timezone(self, field: &str, value: &str) -> Self {
+        self.check(parse_timezone(value).is_some(), field, "INVALID_TIMEZONE", || format!("{field} must be an IANA timezone like Europe/Berlin"))
+    }
+
+    /// the time, the timezone and the recurrence of a reminder, which has to have at least one occurrence
+    fn schedule(mut self, local_time: &str, timezone: &str, recurrence: &str) -> Self {
+        self = self
+            .check(parse_local_time(local_time).is_some(), "local_time", "INVALID_LOCAL_TIME", || "local_time must be a time like 2024-10-18T09:00".into())
+            .timezone("timezone", timezone);
+
+        if let Err(e) = Recurrence::parse(recurrence) {
+            return self.check(false, "recurrence", "INVALID_RECURRENCE", || format!("recurrence must be an RRULE like FREQ=WEEKLY;BYDAY=MO,FR: {e}"));
+        }
+
+        match Schedule::parse(local_time, timezone, recurrence) {
+            Some(schedule) => self.check(
+                schedule.next_fire(None).is_some(), "recurrence", "NO_OCCURRENCES",
+                || "the recurrence doesn't have any occurrences from local_time".into(),
+            ),
+            None => self,
+        }
+    }
+
+    fn present<T>(self, field: &str, value: &Option<T>) -> Self {
+        self.check(value.is_some(), field, "MISSING_FIELD", || format!("{field} is required"))
+    }
+
+    fn finish(self) -> Result<(), ServiceError> {
+        match self.violations.is_empty() {
+            true => Ok(()),
+            false => Err(ServiceError::InvalidFields(self.violations)),
+        }
+    }
+}
+
+/// removes the repeated ids, keeping the first occurence of each
+fn dedup_ids(ids: &mut Vec<i32>) {
+    let mut seen = std::collections::HashSet::new();
+    ids.retain(|id| seen.insert(*id));
+}
+
+/// keeps only the last component of a path-like name, replaces the control characters and trims the whitespace
+pub fn sanitize_file_name(name: &str) -> String {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default();
+
+    name
+        .chars()
+        .map(|c| if c.is_control() { '_' } else { c })
+        .collect::<String>()
+        .trim()
+        .to_owned()
+}
+
+impl Validate for notes::CreateNoteReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .max_len("title", &self.title, NOTE_TITLE_MAX_LEN)
+            .max_len("text", &self.text, NOTE_TEXT_MAX_LEN)
+            .finish()
+    }
+}
+
+impl Validate for notes::ReadNotesReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        let mut validator = Validator::default()
+            .id("user_id", self.user_id)
+            .pagination(&self.pagination)
+            .present("sort", &self.sort)
+            .present("filters", &self.filters);
+
+        if let Some(sort) = &self.sort {
+            validator = validator
+                .check(notes::sort::Field::try_from(sort.sort_field).is_ok(), "sort.sort_field", "INVALID_ENUM_VALUE", || "sort.sort_field is not a known field".into())
+                .check(notes::sort::Type::try_from(sort.sort_type).is_ok(), "sort.sort_type", "INVALID_ENUM_VALUE", || "sort.sort_type is not a known type".into());
+        }
+
+        if let Some(filters) = &self.filters {
+            if let Some(filter_tags) = &filters.filter_tags {
+                for (i, tag_id) in filter_tags.tag_ids.iter().enumerate() {
+                    validator = validator.id(&format!("filters.filter_tags.tag_ids[{i}]"), *tag_id);
+                }
+            }
+
+            for (field, filter_date) in [("filters.filter_date", &filters.filter_date), ("filters.filter_date_modif", &filters.filter_date_modif)] {
+                if let Some(filter_date) = filter_date {
+                    validator = validator.check(
+                        filter_date.start <= filter_date.end, field, "INVALID_DATE_RANGE",
+                        || format!("{field}.start must not be after {field}.end"),
+                    );
+                }
+            }
+
+            if let Some(filter_search) = &filters.filter_search {
+                validator = validator.max_len("filters.filter_search.query", &filter_search.query, SEARCH_QUERY_MAX_LEN);
+            }
+
+            if let Some(filter_state) = &filters.filter_state {
+                validator = validator.check(
+                    notes::filter_state::Archive::try_from(filter_state.archive).is_ok(), "filters.filter_state.archive", "INVALID_ENUM_VALUE",
+                    || "filters.filter_state.archive is not a known value".into(),
+                );
+            }
+
+            if let Some(filter_due) = &filters.filter_due {
+                validator = validator
+                    .check(
+                        notes::filter_due::Due::try_from(filter_due.due).is_ok(), "filters.filter_due.due", "INVALID_ENUM_VALUE",
+                        || "filters.filter_due.due is not a known value".into(),
+                    )
+                    .check(filter_due.until >= 0, "filters.filter_due.until", "INVALID_TIME", || "filters.filter_due.until can't be negative".into());
+            }
+        }
+
+        validator.finish()
+    }
+}
+
+impl Validate for notes::UpdateNoteReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .max_len("title", &self.title, NOTE_TITLE_MAX_LEN)
+            .max_len("text", &self.text, NOTE_TEXT_MAX_LEN)
+            .finish()
+    }
+}
+
+impl Validate for notes::DeleteNoteReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for notes::AttachTagReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .id("note_id", self.note_id)
+            .id("tag_id", self.tag_id)
+            .finish()
+    }
+}
+
+impl Validate for notes::DetachTagReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .id("note_id", self.note_id)
+            .id("tag_id", self.tag_id)
+            .finish()
+    }
+}
+
+impl Validate for notes::BatchTagsReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        dedup_ids(&mut self.note_ids);
+        dedup_ids(&mut self.tag_ids);
+
+        Validator::default()
+            .id("user_id", self.user_id)
+            .ids("note_ids", &self.note_ids, MAX_BATCH_NOTES)
+            .ids("tag_ids", &self.tag_ids, MAX_BATCH_TAGS)
+            .finish()
+    }
+}
+
+impl Validate for notes::BatchDeleteNotesReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        dedup_ids(&mut self.note_ids);
+
+        Validator::default()
+            .id("user_id", self.user_id)
+            .ids("note_ids", &self.note_ids, MAX_BATCH_NOTES)
+            .finish()
+    }
+}
+
+impl Validate for notes::BatchMoveNotesReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        dedup_ids(&mut self.note_ids);
+
+        Validator::default()
+            .id("user_id", self.user_id)
+            .ids("note_ids", &self.note_ids, MAX_BATCH_NOTES)
+            .id("from_tag_id", self.from_tag_id)
+            .id("to_tag_id", self.to_tag_id)
+            .check(self.from_tag_id != self.to_tag_id, "to_tag_id", "SAME_TAG", || "to_tag_id must be different from from_tag_id".into())
+            .finish()
+    }
+}
+
+impl Validate for notes::SetNoteStateReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .note_state("state", &self.state)
+            .finish()
+    }
+}
+
+impl Validate for notes::BatchSetNoteStateReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        dedup_ids(&mut self.note_ids);
+
+        Validator::default()
+            .id("user_id", self.user_id)
+            .ids("note_ids", &self.note_ids, MAX_BATCH_NOTES)
+            .note_state("state", &self.state)
+            .finish()
+    }
+}
+
+impl Validate for notes::GetBacklinksReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .id("note_id", self.note_id)
+            .finish()
+    }
+}
+
+impl Validate for notes::GetNoteGraphReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for notes::ListTasksReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        let mut validator = Validator::default()
+            .id("user_id", self.user_id)
+            .pagination(&self.pagination);
+
+        if let Some(tag_id) = self.tag_id {
+            validator = validator.id("tag_id", tag_id);
+        }
+
+        validator.finish()
+    }
+}
+
+impl Validate for notes::ToggleTaskReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .id("note_id", self.note_id)
+            .check(self.position >= 0, "position", "INVALID_POSITION", || "position can't be negative".into())
+            .check(self.times_edited >= 0, "times_edited", "INVALID_TIMES_EDITED", || "times_edited can't be negative".into())
+            .finish()
+    }
+}
+
+/// the tags of a template, which unlike the other lists of ids can be empty
+fn template_tag_ids(mut validator: Validator, tag_ids: &[i32]) -> Validator {
+    validator = validator.check(
+        tag_ids.len() <= MAX_TEMPLATE_TAGS, "tag_ids", "TOO_MANY_ITEMS",
+        || format!("tag_ids can have at most {MAX_TEMPLATE_TAGS} ids, got {}", tag_ids.len()),
+    );
+
+    for (i, tag_id) in tag_ids.iter().enumerate() {
+        validator = validator.id(&format!("tag_ids[{i}]"), *tag_id);
+    }
+
+    validator
+}
+
+impl Validate for templates::CreateTemplateReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        dedup_ids(&mut self.tag_ids);
+
+        let validator = Validator::default()
+            .id("user_id", self.user_id)
+            .not_blank("name", &self.name)
+            .max_len("name", &self.name, TEMPLATE_NAME_MAX_LEN)
+            .max_len("title", &self.title, NOTE_TITLE_MAX_LEN)
+            .max_len("text", &self.text, NOTE_TEXT_MAX_LEN);
+
+        template_tag_ids(validator, &self.tag_ids).finish()
+    }
+}
+
+impl Validate for templates::ReadTemplatesReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for templates::UpdateTemplateReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        dedup_ids(&mut self.tag_ids);
+
+        let validator = Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .not_blank("name", &self.name)
+            .max_len("name", &self.name, TEMPLATE_NAME_MAX_LEN)
+            .max_len("title", &self.title, NOTE_TITLE_MAX_LEN)
+            .max_len("text", &self.text, NOTE_TEXT_MAX_LEN);
+
+        template_tag_ids(validator, &self.tag_ids).finish()
+    }
+}
+
+impl Validate for templates::DeleteTemplateReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for templates::CreateNoteFromTemplateReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        let mut validator = Validator::default()
+            .id("user_id", self.user_id)
+            .id("template_id", self.template_id)
+            .timezone("timezone", &self.timezone)
+            .check(
+                self.variables.len() <= MAX_TEMPLATE_VARIABLES, "variables", "TOO_MANY_ITEMS",
+                || format!("variables can have at most {MAX_TEMPLATE_VARIABLES} values, got {}", self.variables.len()),
+            );
+
+        for (name, value) in &self.variables {
+            let field = format!("variables[{name}]");
+
+            validator = validator
+                .check(
+                    valid_variable(name), &field, "INVALID_VARIABLE",
+                    || format!("{field} must be made of letters, digits and underscores, and can't be date, time or weekday"),
+                )
+                .max_len(&field, name, VARIABLE_NAME_MAX_LEN)
+                .max_len(&field, value, VARIABLE_VALUE_MAX_LEN);
+        }
+
+        validator.finish()
+    }
+}
+
+/// the colors are stored in lowercase, and a new tag doesn't need the empty color and icon
+fn normalize_tag_style(color: &mut Option<String>, icon: &mut Option<String>, keep_empty: bool) {
+    if let Some(color) = color {
+        color.make_ascii_lowercase();
+    }
+
+    if !keep_empty {
+        if color.as_deref() == Some("") {
+            *color = None;
+        }
+        if icon.as_deref().is_some_and(|i| i.trim().is_empty()) {
+            *icon = None;
+        }
+    }
+}
+
+impl Validate for reminders::CreateReminderReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .id("note_id", self.note_id)
+            .max_len("recurrence", &self.recurrence, RECURRENCE_MAX_LEN)
+            .schedule(&self.local_time, &self.timezone, &self.recurrence)
+            .finish()
+    }
+}
+
+impl Validate for reminders::ReadRemindersReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        let mut validator = Validator::default()
+            .id("user_id", self.user_id);
+
+        if let Some(note_id) = self.note_id {
+            validator = validator.id("note_id", note_id);
+        }
+
+        validator.finish()
+    }
+}
+
+impl Validate for reminders::UpdateReminderReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .max_len("recurrence", &self.recurrence, RECURRENCE_MAX_LEN)
+            .schedule(&self.local_time, &self.timezone, &self.recurrence)
+            .finish()
+    }
+}
+
+impl Validate for reminders::DeleteReminderReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for reminders::WatchDueRemindersReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        // zero watches the reminders of every user
+        Validator::default()
+            .check(self.user_id >= 0, "user_id", "INVALID_ID", || "user_id must be a positive id or zero".into())
+            .finish()
+    }
+}
+
+impl Validate for tags::CreateTagReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        normalize_tag_style(&mut self.color, &mut self.icon, false);
+
+        Validator::default()
+            .id("user_id", self.user_id)
+            .not_blank("name", &self.name)
+            .max_len("name", &self.name, TAG_NAME_MAX_LEN)
+            .color("color", self.color.as_deref().unwrap_or_default())
+            .max_len("icon", self.icon.as_deref().unwrap_or_default(), TAG_ICON_MAX_LEN)
+            .max_len("description", &self.description, TAG_DESCRIPTION_MAX_LEN)
+            .finish()
+    }
+}
+
+impl Validate for tags::ReadTagsReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        let mut validator = Validator::default()
+            .id("user_id", self.user_id);
+
+        if let Some(sort) = &self.sort {
+            validator = validator
+                .check(tags::tag_sort::Field::try_from(sort.sort_field).is_ok(), "sort.sort_field", "INVALID_ENUM_VALUE", || "sort.sort_field is not a known field".into())
+                .check(tags::tag_sort::Type::try_from(sort.sort_type).is_ok(), "sort.sort_type", "INVALID_ENUM_VALUE", || "sort.sort_type is not a known type".into());
+        }
+
+        validator.finish()
+    }
+}
+
+impl Validate for tags::UpdateTagReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        normalize_tag_style(&mut self.color, &mut self.icon, true);
+
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .not_blank("name", &self.name)
+            .max_len("name", &self.name, TAG_NAME_MAX_LEN)
+            .color("color", self.color.as_deref().unwrap_or_default())
+            .max_len("icon", self.icon.as_deref().unwrap_or_default(), TAG_ICON_MAX_LEN)
+            .max_len("description", self.description.as_deref().unwrap_or_default(), TAG_DESCRIPTION_MAX_LEN)
+            .finish()
+    }
+}
+
+impl Validate for tags::DeleteTagReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for tags::MergeTagsReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        dedup_ids(&mut self.source_tag_ids);
+
+        Validator::default()
+            .id("user_id", self.user_id)
+            .ids("source_tag_ids", &self.source_tag_ids, MAX_BATCH_TAGS)
+            .id("target_tag_id", self.target_tag_id)
+            .check(
+                !self.source_tag_ids.contains(&self.target_tag_id), "target_tag_id", "SAME_TAG",
+                || "target_tag_id can't be one of the source_tag_ids".into(),
+            )
+            .finish()
+    }
+}
+
+impl Validate for tags::ReorderTagsReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        dedup_ids(&mut self.tag_ids);
+
+        Validator::default()
+            .id("user_id", self.user_id)
+            .ids("tag_ids", &self.tag_ids, MAX_REORDER_TAGS)
+            .finish()
+    }
+}
+
+impl Validate for files::CreateFileMetadata {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        self.name = sanitize_file_name(&self.name);
+
+        Validator::default()
+            .id("metadata.user_id", self.user_id)
+            .present("metadata.attach_id", &self.attach_id)
+            .check(
+                !self.name.is_empty() && self.name != "." && self.name != "..", "metadata.name", "INVALID_FILE_NAME",
+                || "metadata.name must be a valid file name".into(),
+            )
+            .max_len("metadata.name", &self.name, FILE_NAME_MAX_LEN)
+            .finish()
+    }
+}
+
+impl Validate for files::DownloadFileReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .not_blank("file_hash", &self.file_hash)
+            .max_len("file_hash", &self.file_hash, FILE_HASH_MAX_LEN)
+            .finish()
+    }
+}
+
+impl Validate for files::DeleteFileReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("id", self.id)
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for shelves::ReadShelfReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for shelves::UpdateShelfReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .max_len("text", &self.text, SHELF_TEXT_MAX_LEN)
+            .finish()
+    }
+}
+
+impl Validate for shelves::ClearShelfReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .finish()
+    }
+}
+
+impl Validate for shelves::ConvertToNoteReq {
+    fn validate(&mut self) -> Result<(), ServiceError> {
+        Validator::default()
+            .id("user_id", self.user_id)
+            .max_len("note_title", &self.note_title, NOTE_TITLE_MAX_LEN)
+            .max_len("note_text", &self.note_text, NOTE_TEXT_MAX_LEN)
+            .finish()
+    }
+}
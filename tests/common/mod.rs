@@ -0,0 +1,367 @@
+//! a harness that runs the service binary against a throwaway database and storage dir.
+//!
+//! the database is picked with the DATABASE_URL env variable:
+//! - a postgres url: a new database is created on that server for each test, and dropped afterwards
+//! - a sqlite url: each test gets its own database file. the tests have to be run with `--features sqlite`
+//! - not set: each test starts its own postgres cluster, so `initdb` and `pg_ctl` have to be in the PATH
+
+// every test file uses a different part of the harness
+#![allow(dead_code)]
+
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use miku_notes_data_client::proto::files::{files_client::FilesClient, DownloadFileMetadata, File};
+use miku_notes_data_client::proto::notes::{notes_client::NotesClient, sort, Filters, Note, NoteList, Pagination, Sort};
+use miku_notes_data_client::proto::shelves::shelves_client::ShelvesClient;
+use miku_notes_data_client::proto::tags::{tags_client::TagsClient, Tag};
+use miku_notes_data_client::proto::templates::templates_client::TemplatesClient;
+use miku_notes_data_client::proto::reminders::reminders_client::RemindersClient;
+use miku_notes_data_client::{AttachId, AuthChannel, Client, Error, RetryPolicy};
+use sqlx::{Connection, PgConnection};
+use tonic::transport::{Channel, Endpoint};
+use tonic::Code;
+
+pub use miku_notes_data_client::proto;
+
+/// the users that the stub users table is created with
+pub const USER: i32 = 1;
+pub const OTHER_USER: i32 = 2;
+
+pub const SERVICE_TOKEN: &str = "integration-test-token";
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+const BLOB_DELETION_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// the size of the chunks that the files get uploaded in
+pub const UPLOAD_CHUNK_SIZE: usize = 256 * 1024;
+
+enum TestDb {
+    /// a database created on the server from DATABASE_URL
+    Postgres { admin_url: String, name: String },
+    /// a cluster started by the harness inside of the test dir
+    Cluster { data_dir: PathBuf },
+    /// a database file inside of the test dir
+    Sqlite,
+}
+
+/// a running service with its own database and storage dir. everything gets cleaned up on drop
+pub struct TestService {
+    pub storage_path: PathBuf,
+    /// the gRPC port, which also accepts the gRPC-Web calls
+    pub port: u16,
+    pub rest_port: u16,
+    dir: PathBuf,
+    channel: Channel,
+    client: Client,
+    server: Option<Child>,
+    db: TestDb,
+}
+
+impl TestService {
+    pub async fn start() -> Self {
+        let dir = std::env::temp_dir().join(format!("miku-notes-test-{}", uuid::Uuid::new_v4()));
+        let storage_path = dir.join("files");
+        std::fs::create_dir_all(&storage_path).unwrap();
+
+        let (db, database_url) = match std::env::var("DATABASE_URL") {
+            Ok(url) if url.starts_with("sqlite:") => (TestDb::Sqlite, format!("sqlite://{}", dir.join("data.db").display())),
+            Ok(url) => create_database(&url).await,
+            Err(_) => start_cluster(&dir),
+        };
+
+        let port = free_port();
+        let rest_port = free_port();
+        let channel = Endpoint::from_shared(format!("http://127.0.0.1:{port}")).unwrap().connect_lazy();
+
+        // a failing call should fail the test right away instead of getting retried
+        let client = Client::new(channel.clone(), SERVICE_TOKEN)
+            .unwrap()
+            .with_retry_policy(RetryPolicy::none())
+            .with_chunk_size(UPLOAD_CHUNK_SIZE);
+
+        // the service is created before anything else can fail, so that its drop cleans up
+
+        let mut service = Self {
+            storage_path,
+            port,
+            rest_port,
+            dir,
+            channel,
+            client,
+            server: None,
+            db,
+        };
+
+        // the migrations reference the users table of the Auth service
+
+        if !matches!(service.db, TestDb::Sqlite) {
+            create_users_stub(&database_url).await;
+        }
+
+        // the cwd is the test dir, so that a .env file of the repo doesn't get picked up
+
+        let server = Command::new(env!("CARGO_BIN_EXE_miku-notes-data"))
+            .current_dir(&service.dir)
+            .env_clear()
+            .env("DATABASE_URL", &database_url)
+            .env("RUN_MIGRATIONS", "true")
+            .env("SERVICE_PORT", port.to_string())
+            .env("SERVICE_TOKEN", SERVICE_TOKEN)
+            .env("STORAGE_PATH", &service.storage_path)
+            .env("MAX_FILE_CHUNK_SIZE", "1")
+            .env("BLOB_DELETION_POLL_INTERVAL", "1")
+            .env("REMINDERS_POLL_INTERVAL", "1")
+            .env("LOG_LEVEL", "warn")
+            .env("GRPC_WEB", "true")
+            .env("REST_PORT", rest_port.to_string())
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("could not start the service");
+
+        service.server = Some(server);
+        service.wait_until_ready(port).await;
+        service
+    }
+
+    /// waits until the server accepts connections, which happens after the migrations are applied
+    async fn wait_until_ready(&mut self, port: u16) {
+        let started = Instant::now();
+        let server = self.server.as_mut().unwrap();
+
+        loop {
+            if let Some(status) = server.try_wait().unwrap() {
+                panic!("the service exited during startup with {status}");
+            }
+
+            if tokio::net::TcpStream::connect(("127.0.0.1", port)).await.is_ok() {
+                return;
+            }
+
+            if started.elapsed() > STARTUP_TIMEOUT {
+                panic!("the service did not start listening on {port} in time");
+            }
+
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel.clone()
+    }
+
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    pub fn notes(&self) -> NotesClient<AuthChannel> {
+        self.client.notes()
+    }
+
+    pub fn tags(&self) -> TagsClient<AuthChannel> {
+        self.client.tags()
+    }
+
+    pub fn files(&self) -> FilesClient<AuthChannel> {
+        self.client.files()
+    }
+
+    pub fn shelves(&self) -> ShelvesClient<AuthChannel> {
+        self.client.shelves()
+    }
+
+    pub fn templates(&self) -> TemplatesClient<AuthChannel> {
+        self.client.templates()
+    }
+
+    pub fn reminders(&self) -> RemindersClient<AuthChannel> {
+        self.client.reminders()
+    }
+
+    pub async fn create_note(&self, user_id: i32, title: &str) -> Note {
+        self.client.create_note(user_id, title, &format!("the text of {title}")).await.unwrap()
+    }
+
+    pub async fn create_tag(&self, user_id: i32, name: &str) -> Tag {
+        self.client.create_tag(user_id, name).await.unwrap()
+    }
+
+    /// reads the first page of up to 100 notes
+    pub async fn read_notes(&self, user_id: i32, sort: Sort, filters: Filters) -> Result<NoteList, Error> {
+        self.client.read_notes(user_id, Pagination { page: 1, per_page: 100 }, sort, filters).await
+    }
+
+    /// uploads the `data` in chunks of UPLOAD_CHUNK_SIZE
+    pub async fn upload(&self, user_id: i32, attach_id: AttachId, name: &str, data: &[u8]) -> Result<File, Error> {
+        self.client.upload_file(user_id, attach_id, name, data.len() as u64, Cursor::new(data.to_vec())).await
+    }
+
+    pub async fn download(&self, user_id: i32, file_hash: &str) -> Result<(DownloadFileMetadata, Vec<u8>), Error> {
+        let mut data = Vec::new();
+        let metadata = self.client.download_file(user_id, file_hash, &mut data).await?;
+        Ok((metadata, data))
+    }
+
+    /// the sorted names of the blobs in the storage dir
+    pub fn blobs(&self) -> Vec<String> {
+        let mut blobs: Vec<_> = std::fs::read_dir(&self.storage_path)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+
+        blobs.sort();
+        blobs
+    }
+
+    /// waits for the blob deletion worker to leave only the `expected` blobs in the storage dir
+    pub async fn wait_for_blobs(&self, expected: &[&str]) {
+        let mut expected: Vec<_> = expected.iter().map(|b| b.to_string()).collect();
+        expected.sort();
+
+        let started = Instant::now();
+        while self.blobs() != expected && started.elapsed() < BLOB_DELETION_TIMEOUT {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        assert_eq!(self.blobs(), expected);
+    }
+}
+
+impl Drop for TestService {
+    fn drop(&mut self) {
+        if let Some(server) = &mut self.server {
+            let _ = server.kill();
+            let _ = server.wait();
+        }
+
+        match &self.db {
+            TestDb::Postgres { admin_url, name } => {
+                // drop can't block on the test's runtime, so the database is dropped on a separate one
+
+                let (admin_url, name) = (admin_url.clone(), name.clone());
+                let _ = std::thread::spawn(move || {
+                    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+                    runtime.block_on(drop_database(&admin_url, &name));
+                }).join();
+            },
+            TestDb::Cluster { data_dir } => {
+                let _ = Command::new("pg_ctl")
+                    .arg("-D").arg(data_dir)
+                    .args(["-m", "immediate", "stop"])
+                    .stdout(Stdio::null())
+                    .status();
+            },
+            TestDb::Sqlite => (),
+        }
+
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// a port that nothing listens on at the moment
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port()
+}
+
+async fn create_database(admin_url: &str) -> (TestDb, String) {
+    let name = format!("miku_notes_test_{}", uuid::Uuid::new_v4().simple());
+
+    let mut conn = PgConnection::connect(admin_url).await.expect("could not connect to DATABASE_URL");
+    sqlx::query(&format!("CREATE DATABASE {name};")).execute(&mut conn).await.unwrap();
+    let _ = conn.close().await;
+
+    let database_url = with_database(admin_url, &name);
+    (TestDb::Postgres { admin_url: admin_url.into(), name }, database_url)
+}
+
+async fn drop_database(admin_url: &str, name: &str) {
+    let Ok(mut conn) = PgConnection::connect(admin_url).await else {
+        return;
+    };
+
+    if let Err(e) = sqlx::query(&format!("DROP DATABASE IF EXISTS {name} WITH (FORCE);")).execute(&mut conn).await {
+        eprintln!("could not drop the test database {name}: {e}");
+    }
+
+    let _ = conn.close().await;
+}
+
+/// replaces the database in a postgres url, keeping the parameters
+fn with_database(url: &str, database: &str) -> String {
+    let (base, params) = match url.split_once('?') {
+        Some((base, params)) => (base, format!("?{params}")),
+        None => (url, String::new()),
+    };
+
+    let authority_start = base.find("://").map(|i| i + 3).unwrap_or(0);
+    let authority = match base[authority_start..].find('/') {
+        Some(i) => &base[..authority_start + i],
+        None => base,
+    };
+
+    format!("{authority}/{database}{params}")
+}
+
+/// initializes and starts a cluster that only listens on localhost and a socket in the `dir`
+fn start_cluster(dir: &Path) -> (TestDb, String) {
+    let data_dir = dir.join("pgdata");
+    let port = free_port();
+
+    run(Command::new("initdb")
+        .arg("-D").arg(&data_dir)
+        .args(["-U", "postgres", "-A", "trust"]));
+
+    run(Command::new("pg_ctl")
+        .arg("-D").arg(&data_dir)
+        .arg("-l").arg(dir.join("postgres.log"))
+        .arg("-o").arg(format!("-p {port} -k {} -c listen_addresses=localhost", dir.display()))
+        .args(["-w", "start"]));
+
+    (TestDb::Cluster { data_dir }, format!("postgres://postgres@localhost:{port}/postgres"))
+}
+
+fn run(command: &mut Command) {
+    let status = command
+        .stdout(Stdio::null())
+        .status()
+        .unwrap_or_else(|e| panic!("could not run {:?}: {e}. set DATABASE_URL to use an existing database instead", command.get_program()));
+
+    assert!(status.success(), "{:?} exited with {status}", command.get_program());
+}
+
+async fn create_users_stub(database_url: &str) {
+    let mut conn = PgConnection::connect(database_url).await.unwrap();
+
+    sqlx::query("CREATE TABLE users (id SERIAL PRIMARY KEY);")
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    sqlx::query("INSERT INTO users (id) VALUES ($1), ($2);")
+        .bind(USER).bind(OTHER_USER)
+        .execute(&mut conn)
+        .await
+        .unwrap();
+
+    let _ = conn.close().await;
+}
+
+pub fn sort(field: sort::Field, sort_type: sort::Type) -> Sort {
+    Sort { sort_field: field.into(), sort_type: sort_type.into(), pinned_first: false }
+}
+
+/// predictable bytes that don't repeat within a chunk
+pub fn test_data(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i % 251) as u8).collect()
+}
+
+/// checks the code of both the generated clients' and the wrappers' errors
+#[track_caller]
+pub fn assert_code<T: std::fmt::Debug, E: Into<Error>>(result: Result<T, E>, code: Code) {
+    match result.map_err(Into::into) {
+        Ok(value) => panic!("expected {code:?}, got {value:?}"),
+        Err(Error::Service(e)) => assert_eq!(e.status.code(), code, "unexpected status: {:?}", e.status),
+        Err(e) => panic!("expected {code:?}, got {e}"),
+    }
+}
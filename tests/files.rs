@@ -0,0 +1,101 @@
+mod common;
+
+use common::proto::files::{create_file_metadata::AttachId, CreateFileMetadata, CreateFileReq, DeleteFileReq};
+use common::proto::notes::{sort, DeleteNoteReq, Filters};
+use common::{assert_code, sort, test_data, TestService, OTHER_USER, UPLOAD_CHUNK_SIZE, USER};
+use tonic::Code;
+
+#[tokio::test]
+async fn upload_and_download() {
+    let service = TestService::start().await;
+    let note = service.create_note(USER, "note").await;
+
+    // more than a single chunk in both directions, the service sends chunks of up to 1mb
+
+    let data = test_data(2 * 1024 * 1024 + UPLOAD_CHUNK_SIZE / 2);
+    let file = service.upload(USER, AttachId::NoteId(note.id), "data.bin", &data).await.unwrap();
+
+    assert_eq!((file.user_id, file.name.as_str(), file.size), (USER, "data.bin", data.len() as i64));
+    assert_eq!(file.attach_id, Some(note.id));
+    assert_eq!(service.blobs(), [file.hash.as_str()]);
+
+    let (metadata, downloaded) = service.download(USER, &file.hash).await.unwrap();
+    assert_eq!((metadata.name.as_str(), metadata.size), ("data.bin", data.len() as i64));
+    assert!(downloaded == data, "the downloaded data is different from the uploaded data");
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(list.notes[0].files.iter().map(|f| f.id).collect::<Vec<_>>(), [file.id]);
+
+    // empty files work too
+
+    let empty = service.upload(USER, AttachId::NoteId(note.id), "empty.txt", &[]).await.unwrap();
+    let (metadata, downloaded) = service.download(USER, &empty.hash).await.unwrap();
+    assert_eq!((metadata.size, downloaded.len()), (0, 0));
+}
+
+#[tokio::test]
+async fn rejected_uploads_leave_no_blobs() {
+    let service = TestService::start().await;
+    let note = service.create_note(USER, "note").await;
+    let data = test_data(1000);
+
+    // the data doesn't match the declared size
+
+    for file_size in [999, 1001] {
+        let metadata = CreateFileMetadata { user_id: USER, attach_id: Some(AttachId::NoteId(note.id)), name: "file".into(), file_size };
+        let parts = vec![
+            CreateFileReq { metadata: Some(metadata), data: Vec::new() },
+            CreateFileReq { metadata: None, data: data.clone() },
+        ];
+
+        assert_code(service.files().create_file(tokio_stream::iter(parts)).await, Code::InvalidArgument);
+    }
+
+    // no metadata, a note of someone else, a note that does not exist, and an empty name
+
+    let parts = vec![CreateFileReq { metadata: None, data: data.clone() }];
+    assert_code(service.files().create_file(tokio_stream::iter(parts)).await, Code::InvalidArgument);
+
+    assert_code(service.upload(OTHER_USER, AttachId::NoteId(note.id), "file", &data).await, Code::NotFound);
+    assert_code(service.upload(USER, AttachId::NoteId(note.id + 100), "file", &data).await, Code::NotFound);
+    assert_code(service.upload(USER, AttachId::NoteId(note.id), "", &data).await, Code::InvalidArgument);
+
+    assert!(service.blobs().is_empty(), "blobs were left behind: {:?}", service.blobs());
+}
+
+#[tokio::test]
+async fn deleting_files() {
+    let service = TestService::start().await;
+    let mut files = service.files();
+    let note = service.create_note(USER, "note").await;
+
+    let first = service.upload(USER, AttachId::NoteId(note.id), "first", &test_data(10)).await.unwrap();
+    let second = service.upload(USER, AttachId::NoteId(note.id), "second", &test_data(20)).await.unwrap();
+
+    files.delete_file(DeleteFileReq { id: first.id, user_id: USER }).await.unwrap();
+
+    assert_code(service.download(USER, &first.hash).await, Code::NotFound);
+    assert_code(files.delete_file(DeleteFileReq { id: first.id, user_id: USER }).await, Code::NotFound);
+    service.wait_for_blobs(&[&second.hash]).await;
+
+    // deleting a note deletes its files
+
+    service.notes().delete_note(DeleteNoteReq { id: note.id, user_id: USER }).await.unwrap();
+
+    assert_code(service.download(USER, &second.hash).await, Code::NotFound);
+    service.wait_for_blobs(&[]).await;
+}
+
+#[tokio::test]
+async fn files_of_other_users() {
+    let service = TestService::start().await;
+    let note = service.create_note(USER, "note").await;
+    let file = service.upload(USER, AttachId::NoteId(note.id), "file", &test_data(100)).await.unwrap();
+
+    assert_code(service.download(OTHER_USER, &file.hash).await, Code::NotFound);
+    assert_code(service.files().delete_file(DeleteFileReq { id: file.id, user_id: OTHER_USER }).await, Code::NotFound);
+
+    let (_, downloaded) = service.download(USER, &file.hash).await.unwrap();
+    assert_eq!(downloaded, test_data(100));
+    assert_eq!(service.blobs(), [file.hash.as_str()]);
+}
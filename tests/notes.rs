@@ -0,0 +1,532 @@
+mod common;
+
+use common::proto::notes::{filter_state, sort, AttachTagReq, DeleteNoteReq, DetachTagReq, FilterDate, FilterState, NoteState, Sort, FilterSearch, FilterTags, Filters, Note, NoteGraph, Pagination, ReadNotesReq, SetNoteStateReq, UpdateNoteReq};
+use common::{assert_code, sort, TestService, OTHER_USER, USER};
+use miku_notes_data_client::{AttachId, Error, ErrorKind};
+use tonic::Code;
+
+fn titles(notes: &[Note]) -> Vec<&str> {
+    notes.iter().map(|n| n.title.as_str()).collect()
+}
+
+fn now() -> i64 {
+    chrono::Utc::now().timestamp()
+}
+
+#[tokio::test]
+async fn note_lifecycle() {
+    let service = TestService::start().await;
+    let mut notes = service.notes();
+
+    let note = service.create_note(USER, "first").await;
+    assert_eq!(note.user_id, USER);
+    assert_eq!(note.title, "first");
+    assert_eq!(note.times_edited, 0);
+    assert!((note.created - now()).abs() < 60);
+
+    let updated = notes.update_note(UpdateNoteReq { id: note.id, user_id: USER, title: "renamed".into(), text: "new text".into() })
+        .await.unwrap().into_inner();
+
+    assert_eq!(updated.id, note.id);
+    assert_eq!((updated.title.as_str(), updated.text.as_str()), ("renamed", "new text"));
+    assert_eq!(updated.times_edited, 1);
+    assert!(updated.last_edited >= note.last_edited);
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(list.total_count, 1);
+    assert_eq!(list.notes[0].title, "renamed");
+
+    notes.delete_note(DeleteNoteReq { id: note.id, user_id: USER }).await.unwrap();
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(list.total_count, 0);
+    assert!(list.notes.is_empty());
+
+    assert_code(notes.delete_note(DeleteNoteReq { id: note.id, user_id: USER }).await, Code::NotFound);
+    assert_code(notes.update_note(UpdateNoteReq { id: note.id, user_id: USER, title: "t".into(), text: String::new() }).await, Code::NotFound);
+}
+
+#[tokio::test]
+async fn read_notes_sorting() {
+    let service = TestService::start().await;
+
+    for title in ["banana", "apple", "cherry"] {
+        service.create_note(USER, title).await;
+    }
+
+    let by_title = |sort_type| service.read_notes(USER, sort(sort::Field::Title, sort_type), Filters::default());
+
+    assert_eq!(titles(&by_title(sort::Type::Asc).await.unwrap().notes), ["apple", "banana", "cherry"]);
+    assert_eq!(titles(&by_title(sort::Type::Desc).await.unwrap().notes), ["cherry", "banana", "apple"]);
+
+    // the timestamps are returned in seconds, while the database might store them more precisely.
+    // either way, the newest note comes first in a descending sort, since the ties are broken by the newest id
+
+    for (field, key) in [(sort::Field::Date, (|n: &Note| n.created) as fn(&Note) -> i64), (sort::Field::DateModif, |n: &Note| n.last_edited)] {
+        let list = service.read_notes(USER, sort(field, sort::Type::Asc), Filters::default()).await.unwrap();
+        assert!(list.notes.windows(2).all(|w| key(&w[0]) <= key(&w[1])), "{field:?} ASC: {:?}", titles(&list.notes));
+
+        let list = service.read_notes(USER, sort(field, sort::Type::Desc), Filters::default()).await.unwrap();
+        assert_eq!(titles(&list.notes), ["cherry", "apple", "banana"], "{field:?} DESC");
+    }
+}
+
+#[tokio::test]
+async fn read_notes_filters() {
+    let service = TestService::start().await;
+    let mut notes = service.notes();
+
+    let apple = service.create_note(USER, "Apple pie").await;
+    let banana = service.create_note(USER, "banana bread").await;
+    service.create_note(USER, "cherry tart").await;
+
+    let fruit = service.create_tag(USER, "fruit").await;
+    let baked = service.create_tag(USER, "baked").await;
+
+    for (note_id, tag_id) in [(apple.id, fruit.id), (apple.id, baked.id), (banana.id, baked.id)] {
+        notes.attach_tag(AttachTagReq { user_id: USER, note_id, tag_id }).await.unwrap();
+    }
+
+    let read = |filters| service.read_notes(USER, sort(sort::Field::Title, sort::Type::Asc), filters);
+    let tags = |tag_ids: &[i32]| Some(FilterTags { tag_ids: tag_ids.to_vec() });
+
+    // tags
+
+    let list = read(Filters { filter_tags: tags(&[fruit.id]), ..Default::default() }).await.unwrap();
+    assert_eq!(titles(&list.notes), ["Apple pie"]);
+    assert_eq!(list.total_count, 1);
+
+    let mut attached: Vec<_> = list.notes[0].tags.iter().map(|t| t.name.as_str()).collect();
+    attached.sort();
+    assert_eq!(attached, ["baked", "fruit"]);
+
+    let list = read(Filters { filter_tags: tags(&[fruit.id, baked.id]), ..Default::default() }).await.unwrap();
+    assert_eq!(titles(&list.notes), ["Apple pie", "banana bread"]);
+
+    // an empty tag list means the untagged notes
+
+    let list = read(Filters { filter_tags: tags(&[]), ..Default::default() }).await.unwrap();
+    assert_eq!(titles(&list.notes), ["cherry tart"]);
+
+    // search ignores the case
+
+    let search = |query: &str| Some(FilterSearch { query: query.into() });
+
+    let list = read(Filters { filter_search: search("APPLE"), ..Default::default() }).await.unwrap();
+    assert_eq!(titles(&list.notes), ["Apple pie"]);
+
+    let list = read(Filters { filter_search: search("b"), ..Default::default() }).await.unwrap();
+    assert_eq!(titles(&list.notes), ["banana bread"]);
+
+    // dates
+
+    let around_now = Some(FilterDate { start: now() - 60, end: now() + 60 });
+    let long_ago = Some(FilterDate { start: 0, end: 1_000_000 });
+
+    let list = read(Filters { filter_date: around_now.clone(), filter_date_modif: around_now.clone(), ..Default::default() }).await.unwrap();
+    assert_eq!(list.total_count, 3);
+
+    let list = read(Filters { filter_date: long_ago.clone(), ..Default::default() }).await.unwrap();
+    assert_eq!(list.total_count, 0);
+
+    let list = read(Filters { filter_date_modif: long_ago, ..Default::default() }).await.unwrap();
+    assert_eq!(list.total_count, 0);
+
+    // everything at once
+
+    let list = read(Filters { filter_tags: tags(&[baked.id]), filter_date: around_now.clone(), filter_date_modif: around_now.clone(), filter_search: search("bread"), filter_state: None, filter_due: None }).await.unwrap();
+    assert_eq!(titles(&list.notes), ["banana bread"]);
+
+    let list = read(Filters { filter_tags: tags(&[fruit.id]), filter_search: search("bread"), ..Default::default() }).await.unwrap();
+    assert_eq!(list.total_count, 0);
+}
+
+#[tokio::test]
+async fn read_notes_pagination() {
+    let service = TestService::start().await;
+
+    for title in ["a", "b", "c", "d", "e"] {
+        service.create_note(USER, title).await;
+    }
+
+    let page = |page| {
+        let mut notes = service.notes();
+        let req = ReadNotesReq {
+            user_id: USER,
+            pagination: Some(Pagination { page, per_page: 2 }),
+            sort: Some(sort(sort::Field::Title, sort::Type::Asc)),
+            filters: Some(Filters::default()),
+        };
+
+        async move { notes.read_notes(req).await }
+    };
+
+    let list = page(1).await.unwrap().into_inner();
+    assert_eq!((titles(&list.notes), list.total_count), (vec!["a", "b"], 5));
+
+    let list = page(3).await.unwrap().into_inner();
+    assert_eq!((titles(&list.notes), list.total_count), (vec!["e"], 5));
+
+    let list = page(4).await.unwrap().into_inner();
+    assert_eq!((list.notes.len(), list.total_count), (0, 5));
+
+    assert_code(page(0).await, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn attaching_tags() {
+    let service = TestService::start().await;
+    let mut notes = service.notes();
+
+    let note = service.create_note(USER, "note").await;
+    let tag = service.create_tag(USER, "tag").await;
+
+    let attach = AttachTagReq { user_id: USER, note_id: note.id, tag_id: tag.id };
+    let detach = DetachTagReq { user_id: USER, note_id: note.id, tag_id: tag.id };
+
+    notes.attach_tag(attach.clone()).await.unwrap();
+
+    let error = service.client().attach_tag(USER, note.id, tag.id).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::AlreadyExists), Some("TAG_ALREADY_ATTACHED")));
+
+    notes.detach_tag(detach.clone()).await.unwrap();
+    assert_code(notes.detach_tag(detach).await, Code::NotFound);
+
+    assert_code(notes.attach_tag(AttachTagReq { tag_id: tag.id + 100, ..attach.clone() }).await, Code::InvalidArgument);
+    assert_code(notes.attach_tag(AttachTagReq { note_id: note.id + 100, ..attach }).await, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn batch_operations() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let (a, b, c) = (service.create_note(USER, "a").await, service.create_note(USER, "b").await, service.create_note(USER, "c").await);
+    let others = service.create_note(OTHER_USER, "others").await;
+    let (work, home) = (service.create_tag(USER, "work").await, service.create_tag(USER, "home").await);
+
+    let tag_ids = |notes: &[Note]| notes.iter().map(|n| n.tags.iter().map(|t| t.id).collect::<Vec<_>>()).collect::<Vec<_>>();
+    let read = || async { service.read_notes(USER, sort(sort::Field::Title, sort::Type::Asc), Filters::default()).await.unwrap().notes };
+
+    let result = client.batch_attach_tags(USER, &[a.id, b.id], &[work.id, home.id], false).await.unwrap();
+    assert_eq!((result.results.len(), result.succeeded), (4, 4));
+
+    // an all-or-nothing batch changes nothing if any of its items fails
+
+    let error = client.batch_attach_tags(USER, &[c.id, a.id], &[work.id], false).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::AlreadyExists), Some("TAG_ALREADY_ATTACHED")));
+    assert!(read().await[2].tags.is_empty());
+
+    // a partial one skips the failed items
+
+    let result = client.batch_attach_tags(USER, &[c.id, a.id, others.id], &[work.id], true).await.unwrap();
+    let reasons: Vec<_> = result.results.iter().map(|r| (r.note_id, r.ok, r.reason.as_str())).collect();
+    assert_eq!(reasons, [(c.id, true, ""), (a.id, false, "TAG_ALREADY_ATTACHED"), (others.id, false, "NOTE_NOT_FOUND")]);
+    assert_eq!(result.succeeded, 1);
+
+    let result = client.batch_detach_tags(USER, &[a.id, b.id, c.id], &[home.id], true).await.unwrap();
+    assert_eq!(result.succeeded, 2);
+    assert_eq!(tag_ids(&read().await), [vec![work.id], vec![work.id], vec![work.id]]);
+
+    // moving replaces the tag, and keeps the other tag of the notes that already have it
+
+    client.batch_attach_tags(USER, &[b.id], &[home.id], false).await.unwrap();
+    client.batch_move_notes(USER, &[a.id, b.id], work.id, home.id, false).await.unwrap();
+    assert_eq!(tag_ids(&read().await), [vec![home.id], vec![home.id], vec![work.id]]);
+
+    let error = client.batch_move_notes(USER, &[a.id], work.id, home.id, false).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::NotFound), Some("TAG_NOT_ATTACHED")));
+
+    let error = client.batch_move_notes(USER, &[a.id], work.id, work.id, false).await.unwrap_err();
+    assert_eq!(error.kind(), Some(ErrorKind::InvalidArgument));
+
+    // deleting takes the files of the notes with them
+
+    let file = service.upload(USER, AttachId::NoteId(a.id), "a.txt", b"data").await.unwrap();
+    service.wait_for_blobs(&[&file.hash]).await;
+
+    let error = client.batch_delete_notes(USER, &[a.id, others.id], false).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::NotFound), Some("NOTE_NOT_FOUND")));
+    assert_eq!(read().await.len(), 3);
+
+    let result = client.batch_delete_notes(USER, &[a.id, b.id, a.id, others.id], true).await.unwrap();
+    assert_eq!((result.results.len(), result.succeeded), (3, 2));
+    assert_eq!(titles(&read().await), ["c"]);
+
+    service.wait_for_blobs(&[]).await;
+    assert_eq!(service.read_notes(OTHER_USER, sort(sort::Field::Title, sort::Type::Asc), Filters::default()).await.unwrap().total_count, 1);
+}
+
+#[tokio::test]
+async fn notes_of_other_users() {
+    let service = TestService::start().await;
+    let mut notes = service.notes();
+
+    let note = service.create_note(USER, "mine").await;
+    let tag = service.create_tag(USER, "mine").await;
+    let other_tag = service.create_tag(OTHER_USER, "theirs").await;
+    notes.attach_tag(AttachTagReq { user_id: USER, note_id: note.id, tag_id: tag.id }).await.unwrap();
+
+    let list = service.read_notes(OTHER_USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(list.total_count, 0);
+
+    let update = UpdateNoteReq { id: note.id, user_id: OTHER_USER, title: "stolen".into(), text: String::new() };
+    assert_code(notes.update_note(update).await, Code::NotFound);
+    assert_code(notes.delete_note(DeleteNoteReq { id: note.id, user_id: OTHER_USER }).await, Code::NotFound);
+
+    // neither someone else's note nor someone else's tag can be used
+
+    assert_code(notes.attach_tag(AttachTagReq { user_id: OTHER_USER, note_id: note.id, tag_id: other_tag.id }).await, Code::InvalidArgument);
+    assert_code(notes.attach_tag(AttachTagReq { user_id: USER, note_id: note.id, tag_id: other_tag.id }).await, Code::InvalidArgument);
+    assert_code(notes.detach_tag(DetachTagReq { user_id: OTHER_USER, note_id: note.id, tag_id: tag.id }).await, Code::NotFound);
+
+    // the note is left as it was
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(titles(&list.notes), ["mine"]);
+    assert_eq!(list.notes[0].tags.len(), 1);
+}
+
+#[tokio::test]
+async fn invalid_requests() {
+    let service = TestService::start().await;
+    let mut notes = service.notes();
+
+    let req = ReadNotesReq { user_id: USER, pagination: None, sort: None, filters: None };
+    assert_code(notes.read_notes(req).await, Code::InvalidArgument);
+
+    let filters = Filters { filter_date: Some(FilterDate { start: 10, end: 0 }), ..Default::default() };
+    assert_code(service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), filters).await, Code::InvalidArgument);
+
+    // every invalid field is reported
+
+    let error = service.client().update_note(USER, 0, &"x".repeat(251), "").await.unwrap_err();
+    let Error::Service(error) = error else { panic!("expected a service error, got {error}") };
+
+    let mut fields: Vec<_> = error.field_violations.iter().map(|v| v.field.as_str()).collect();
+    fields.sort();
+    assert_eq!((error.kind, fields), (ErrorKind::InvalidArgument, vec!["id", "title"]));
+
+    // requests without the service token are rejected
+
+    let mut unauthorized = common::proto::notes::notes_client::NotesClient::new(service.channel());
+    assert_code(unauthorized.delete_note(DeleteNoteReq { id: 1, user_id: USER }).await, Code::Unauthenticated);
+}
+
+#[tokio::test]
+async fn idempotent_note_creation() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let note = client.create_note_with_key("create-1", USER, "note", "text").await.unwrap();
+    assert_eq!(client.create_note_with_key("create-1", USER, "note", "text").await.unwrap(), note);
+
+    // the keys belong to the user of the call
+    let other = client.create_note_with_key("create-1", OTHER_USER, "note", "text").await.unwrap();
+    assert_ne!(other.id, note.id);
+
+    let Error::Service(error) = client.create_note_with_key("create-1", USER, "changed", "text").await.unwrap_err() else { panic!() };
+    assert_eq!((error.kind, error.reason.as_deref()), (ErrorKind::AlreadyExists, Some("IDEMPOTENCY_KEY_REUSED")));
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(titles(&list.notes), ["note"]);
+}
+
+#[tokio::test]
+async fn pinned_archived_and_favorite_notes() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let (a, b, c, d) = (
+        service.create_note(USER, "a").await, service.create_note(USER, "b").await,
+        service.create_note(USER, "c").await, service.create_note(USER, "d").await,
+    );
+
+    let tag = service.create_tag(USER, "tag").await;
+    client.batch_attach_tags(USER, &[a.id, b.id, c.id, d.id], &[tag.id], false).await.unwrap();
+
+    let pinned = client.set_note_state(USER, c.id, NoteState { pinned: Some(true), ..Default::default() }).await.unwrap();
+    assert!(pinned.pinned && !pinned.archived && !pinned.favorite);
+    assert_eq!(pinned.times_edited, 0);
+
+    let favorite = NoteState { favorite: Some(true), ..Default::default() };
+    let result = client.batch_set_note_state(USER, &[b.id, c.id], favorite, false).await.unwrap();
+    assert_eq!(result.succeeded, 2);
+
+    client.set_note_state(USER, d.id, NoteState { archived: Some(true), ..Default::default() }).await.unwrap();
+
+    let service = &service;
+
+    let read = |pinned_first, filter_state| async move {
+        let sort = Sort { pinned_first, ..sort(sort::Field::Title, sort::Type::Asc) };
+        let list = service.read_notes(USER, sort, Filters { filter_state, ..Default::default() }).await.unwrap();
+
+        // every note keeps its own tag, whatever the order
+        assert!(list.notes.iter().all(|n| n.tags.len() == 1 && n.tags[0].note_id == Some(n.id)));
+        list.notes.iter().map(|n| n.title.clone()).collect::<Vec<_>>()
+    };
+
+    let state = |archive: filter_state::Archive, pinned, favorite| Some(FilterState { archive: archive.into(), pinned, favorite });
+
+    // the archived notes are left out by default, and the pinned ones can go first
+
+    assert_eq!(read(false, None).await, ["a", "b", "c"]);
+    assert_eq!(read(true, None).await, ["c", "a", "b"]);
+    assert_eq!(read(false, state(filter_state::Archive::Archived, None, None)).await, ["d"]);
+    assert_eq!(read(true, state(filter_state::Archive::All, None, None)).await, ["c", "a", "b", "d"]);
+    assert_eq!(read(false, state(filter_state::Archive::Active, None, Some(true))).await, ["b", "c"]);
+    assert_eq!(read(false, state(filter_state::Archive::Active, Some(false), Some(true))).await, ["b"]);
+
+    // unpinning keeps the other flags
+
+    let unpinned = client.set_note_state(USER, c.id, NoteState { pinned: Some(false), ..Default::default() }).await.unwrap();
+    assert!(!unpinned.pinned && unpinned.favorite);
+
+    let error = client.batch_set_note_state(USER, &[a.id, 1000], NoteState { archived: Some(true), ..Default::default() }, false).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::NotFound), Some("NOTE_NOT_FOUND")));
+    assert_eq!(read(false, None).await, ["a", "b", "c"]);
+
+    let mut notes = service.notes();
+    assert_code(notes.set_note_state(SetNoteStateReq { id: a.id, user_id: USER, state: Some(NoteState::default()) }).await, Code::InvalidArgument);
+    assert_code(client.set_note_state(OTHER_USER, a.id, NoteState { pinned: Some(true), ..Default::default() }).await, Code::NotFound);
+}
+
+#[tokio::test]
+async fn note_links() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let hub = client.create_note(USER, "Hub", "").await.unwrap();
+    let other = client.create_note(OTHER_USER, "Hub", "").await.unwrap();
+
+    let text = format!("see [[hub]], [[#{}|the hub]], [[#{}]] and [[Missing | later]]", hub.id, other.id);
+    let source = client.create_note(USER, "Source", &text).await.unwrap();
+
+    let backlinks = client.get_backlinks(USER, hub.id).await.unwrap();
+    assert_eq!(backlinks.iter().map(|n| (n.id, n.title.as_str())).collect::<Vec<_>>(), [(source.id, "Source")]);
+
+    // the links to the notes of other users are left out
+    assert!(client.get_backlinks(OTHER_USER, other.id).await.unwrap().is_empty());
+    assert_code(client.get_backlinks(OTHER_USER, hub.id).await, Code::NotFound);
+
+    // an unresolved link points to the note that gets its title
+    let missing = client.create_note(USER, "missing", "back to [[Source]]").await.unwrap();
+    assert_eq!(client.get_backlinks(USER, missing.id).await.unwrap()[0].id, source.id);
+
+    // renaming a note rewrites the title links to it, which counts as an edit of the linking notes
+    client.update_note(USER, hub.id, "Center", "").await.unwrap();
+
+    let notes = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Asc), Filters::default()).await.unwrap().notes;
+    let renamed = notes.iter().find(|n| n.id == source.id).unwrap();
+    assert_eq!(renamed.text, format!("see [[Center]], [[#{}|the hub]], [[#{}]] and [[Missing | later]]", hub.id, other.id));
+    assert_eq!(renamed.times_edited, 1);
+    assert!(renamed.last_edited >= renamed.created);
+    assert_eq!(client.get_backlinks(USER, hub.id).await.unwrap().len(), 1);
+
+    // a rename that would make the text of a linking note too long fails as a whole
+    let long = client.create_note(USER, "Long", &format!("[[Center]]{}", "a".repeat(50000 - 10))).await.unwrap();
+
+    let error = client.update_note(USER, hub.id, "Center of it all", "").await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::FailedPrecondition), Some("LINKING_NOTE_TOO_LONG")));
+
+    let notes = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Asc), Filters::default()).await.unwrap().notes;
+    assert_eq!(notes.iter().find(|n| n.id == hub.id).unwrap().title, "Center");
+    assert_eq!(notes.iter().find(|n| n.id == source.id).unwrap().times_edited, 1);
+
+    client.delete_note(USER, long.id).await.unwrap();
+
+    // the graph leaves out the archived notes and their links unless asked for them
+    client.set_note_state(USER, missing.id, NoteState { archived: Some(true), ..Default::default() }).await.unwrap();
+
+    let edges = |graph: &NoteGraph| graph.edges.iter().map(|e| (e.source_id, e.target_id)).collect::<Vec<_>>();
+
+    let graph = client.get_note_graph(USER, false).await.unwrap();
+    assert_eq!(graph.nodes.iter().map(|n| n.id).collect::<Vec<_>>(), [hub.id, source.id]);
+    assert_eq!(edges(&graph), [(source.id, hub.id)]);
+
+    let graph = client.get_note_graph(USER, true).await.unwrap();
+    assert_eq!(graph.nodes.len(), 3);
+    assert_eq!(edges(&graph), [(source.id, hub.id), (source.id, missing.id), (missing.id, source.id)]);
+
+    // deleting a note moves the title links to another note with its title
+    let second = client.create_note(USER, "MISSING", "").await.unwrap();
+    assert!(client.get_backlinks(USER, second.id).await.unwrap().is_empty());
+
+    client.delete_note(USER, missing.id).await.unwrap();
+    assert_eq!(client.get_backlinks(USER, second.id).await.unwrap()[0].id, source.id);
+
+    client.delete_note(USER, hub.id).await.unwrap();
+    assert_eq!(edges(&client.get_note_graph(USER, true).await.unwrap()), [(source.id, second.id)]);
+}
+
+#[tokio::test]
+async fn note_tasks() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let text = "groceries:\n- [ ] milk\n  * [x] bread\n1. [X] eggs for [[Breakfast]]\n```\n- [ ] not a task\n```\n- [] nope\n-[ ] nope";
+    let groceries = client.create_note(USER, "Groceries", text).await.unwrap();
+    let chores = client.create_note(USER, "Chores", "- [ ] dishes").await.unwrap();
+    client.create_note(OTHER_USER, "Other", "- [ ] theirs").await.unwrap();
+
+    let tasks = |tasks: Vec<common::proto::notes::Task>| tasks.into_iter().map(|t| (t.note_id, t.position, t.text, t.done)).collect::<Vec<_>>();
+    let all = || Pagination { page: 1, per_page: 100 };
+
+    assert_eq!(tasks(client.list_tasks(USER, all(), None, None, false).await.unwrap().tasks), [
+        (groceries.id, 0, "milk".to_owned(), false),
+        (groceries.id, 1, "bread".to_owned(), true),
+        (groceries.id, 2, "eggs for [[Breakfast]]".to_owned(), true),
+        (chores.id, 0, "dishes".to_owned(), false),
+    ]);
+
+    // the pages are counted in tasks, not in notes
+    let page = client.list_tasks(USER, Pagination { page: 2, per_page: 3 }, None, None, false).await.unwrap();
+    assert_eq!((tasks(page.tasks), page.total_count), (vec![(chores.id, 0, "dishes".to_owned(), false)], 4));
+
+    let open = client.list_tasks(USER, all(), Some(false), None, false).await.unwrap().tasks;
+    assert_eq!(open.iter().map(|t| (t.note_title.as_str(), t.position)).collect::<Vec<_>>(), [("Groceries", 0), ("Chores", 0)]);
+
+    let tag = service.create_tag(USER, "home").await;
+    client.attach_tag(USER, chores.id, tag.id).await.unwrap();
+    assert_eq!(tasks(client.list_tasks(USER, all(), None, Some(tag.id), false).await.unwrap().tasks), [(chores.id, 0, "dishes".to_owned(), false)]);
+
+    // a toggle flips the checkbox in the text, as an edit of the note
+    let toggled = client.toggle_task(USER, groceries.id, 1, groceries.times_edited).await.unwrap();
+    assert!(toggled.text.contains("\n  * [ ] bread\n"), "{}", toggled.text);
+    assert_eq!(toggled.times_edited, groceries.times_edited + 1);
+
+    let toggled = client.toggle_task(USER, groceries.id, 0, toggled.times_edited).await.unwrap();
+    assert!(toggled.text.starts_with("groceries:\n- [x] milk\n"), "{}", toggled.text);
+
+    let done = client.list_tasks(USER, all(), Some(true), None, false).await.unwrap().tasks;
+    assert_eq!(done.iter().map(|t| (t.position, t.note_times_edited)).collect::<Vec<_>>(), [(0, toggled.times_edited), (2, toggled.times_edited)]);
+
+    // a toggle with an old times_edited fails instead of flipping the task back
+    let error = client.toggle_task(USER, groceries.id, 0, groceries.times_edited).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::FailedPrecondition), Some("NOTE_EDITED")));
+
+    let error = client.toggle_task(USER, groceries.id, 3, toggled.times_edited).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::NotFound), Some("TASK_NOT_FOUND")));
+
+    assert_code(client.toggle_task(OTHER_USER, groceries.id, 0, toggled.times_edited).await, Code::NotFound);
+    assert_code(client.toggle_task(USER, groceries.id, -1, toggled.times_edited).await, Code::InvalidArgument);
+    assert_code(client.list_tasks(USER, all(), None, Some(0), false).await, Code::InvalidArgument);
+    assert_code(client.list_tasks(USER, Pagination { page: 1, per_page: 101 }, None, None, false).await, Code::InvalidArgument);
+
+    // the tasks follow the updates, the renamed links and the deletes of the notes
+    let breakfast = client.create_note(USER, "Breakfast", "").await.unwrap();
+    client.update_note(USER, breakfast.id, "Brunch", "").await.unwrap();
+    client.update_note(USER, chores.id, "Chores", "- [x] dishes\n- [ ] laundry").await.unwrap();
+
+    let list = client.list_tasks(USER, all(), None, None, false).await.unwrap().tasks;
+    assert_eq!(list[2].text, "eggs for [[Brunch]]");
+    assert_eq!(tasks(list[3..].to_vec()), [(chores.id, 0, "dishes".to_owned(), true), (chores.id, 1, "laundry".to_owned(), false)]);
+
+    // the archived notes are left out unless asked for them
+    client.set_note_state(USER, chores.id, NoteState { archived: Some(true), ..Default::default() }).await.unwrap();
+    assert_eq!(client.list_tasks(USER, all(), None, None, false).await.unwrap().total_count, 3);
+    assert_eq!(client.list_tasks(USER, all(), None, None, true).await.unwrap().total_count, 5);
+
+    client.delete_note(USER, groceries.id).await.unwrap();
+    assert_eq!(client.list_tasks(USER, all(), None, None, true).await.unwrap().total_count, 2);
+}
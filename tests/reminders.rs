@@ -0,0 +1,207 @@
+mod common;
+
+use std::time::Duration;
+
+use common::proto::notes::{filter_due, sort, FilterDue, Filters};
+use common::proto::reminders::{CreateReminderReq, DueReminder};
+use common::{assert_code, sort, TestService, OTHER_USER, USER};
+use miku_notes_data_client::ErrorKind;
+use tonic::{Code, Streaming};
+
+/// how long a due reminder can take to reach the watchers, with the one second poll interval of the harness
+const FIRE_TIMEOUT: Duration = Duration::from_secs(10);
+
+async fn next_due(stream: &mut Streaming<DueReminder>) -> DueReminder {
+    tokio::time::timeout(FIRE_TIMEOUT, stream.message())
+        .await
+        .expect("no reminder was fired in time")
+        .unwrap()
+        .expect("the stream ended")
+}
+
+#[tokio::test]
+async fn reminder_lifecycle() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let note = service.create_note(USER, "groceries").await;
+    let other_note = service.create_note(USER, "other").await;
+
+    // 9:00 in berlin is 7:00 utc in the summer and 8:00 utc in the winter
+    let summer = client.create_reminder(USER, note.id, "2099-07-03T09:00", "Europe/Berlin", "").await.unwrap();
+    assert_eq!((summer.note_id, summer.local_time.as_str(), summer.fired), (note.id, "2099-07-03T09:00", false));
+    assert_eq!(summer.fire_at, chrono::DateTime::parse_from_rfc3339("2099-07-03T07:00:00Z").unwrap().timestamp());
+
+    let weekly = client.create_reminder(USER, note.id, "2099-01-02T09:00", "Europe/Berlin", "RRULE:FREQ=WEEKLY;BYDAY=MO,FR").await.unwrap();
+    // the 2nd of january 2099 is a friday
+    assert_eq!(weekly.fire_at, chrono::DateTime::parse_from_rfc3339("2099-01-02T08:00:00Z").unwrap().timestamp());
+
+    let other = client.create_reminder(USER, other_note.id, "2099-03-01T12:00", "", "").await.unwrap();
+
+    let ids = |reminders: Vec<common::proto::reminders::Reminder>| reminders.into_iter().map(|r| r.id).collect::<Vec<_>>();
+    assert_eq!(ids(client.read_reminders(USER, None).await.unwrap()), [weekly.id, other.id, summer.id]);
+    assert_eq!(ids(client.read_reminders(USER, Some(note.id)).await.unwrap()), [weekly.id, summer.id]);
+    assert!(client.read_reminders(OTHER_USER, None).await.unwrap().is_empty());
+    assert_code(client.read_reminders(OTHER_USER, Some(note.id)).await, Code::NotFound);
+
+    // an update schedules the reminder again
+    let updated = client.update_reminder(USER, summer.id, "2099-12-24T18:30", "America/New_York", "FREQ=YEARLY;COUNT=3").await.unwrap();
+    assert_eq!((updated.local_time.as_str(), updated.timezone.as_str()), ("2099-12-24T18:30", "America/New_York"));
+    assert_eq!(updated.fire_at, chrono::DateTime::parse_from_rfc3339("2099-12-24T23:30:00Z").unwrap().timestamp());
+
+    assert_code(client.update_reminder(OTHER_USER, summer.id, "2099-12-24T18:30", "", "").await, Code::NotFound);
+    assert_code(client.create_reminder(OTHER_USER, note.id, "2099-12-24T18:30", "", "").await, Code::NotFound);
+    assert_code(client.delete_reminder(OTHER_USER, summer.id).await, Code::NotFound);
+
+    client.delete_reminder(USER, summer.id).await.unwrap();
+    assert_code(client.delete_reminder(USER, summer.id).await, Code::NotFound);
+
+    // the reminders are deleted with their notes
+    client.delete_note(USER, note.id).await.unwrap();
+    assert_eq!(ids(client.read_reminders(USER, None).await.unwrap()), [other.id]);
+
+    let keyed = client.create_reminder_with_key("reminder-1", USER, other_note.id, "2099-03-02T12:00", "", "").await.unwrap();
+    assert_eq!(client.create_reminder_with_key("reminder-1", USER, other_note.id, "2099-03-02T12:00", "", "").await.unwrap().id, keyed.id);
+    assert_eq!(client.read_reminders(USER, None).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn invalid_reminders() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let note = service.create_note(USER, "note").await;
+
+    let reason = |result: Result<_, miku_notes_data_client::Error>| {
+        let error = result.unwrap_err();
+        assert_eq!(error.kind(), Some(ErrorKind::InvalidArgument));
+        error.reason().map(str::to_owned)
+    };
+
+    let create = |local_time: &'static str, timezone: &'static str, recurrence: &'static str| {
+        client.create_reminder(USER, note.id, local_time, timezone, recurrence)
+    };
+
+    assert_eq!(reason(create("tomorrow", "", "").await).as_deref(), Some("INVALID_LOCAL_TIME"));
+    assert_eq!(reason(create("2099-01-01 09:00", "", "").await).as_deref(), Some("INVALID_LOCAL_TIME"));
+    assert_eq!(reason(create("2099-01-01T09:00", "Mars/Olympus_Mons", "").await).as_deref(), Some("INVALID_TIMEZONE"));
+
+    for recurrence in ["FREQ=HOURLY", "INTERVAL=2", "FREQ=DAILY;BYDAY=MO", "FREQ=DAILY;COUNT=2;UNTIL=20991231", "FREQ=WEEKLY;BYDAY=XX", "FREQ=DAILY;INTERVAL=0", "FREQ=DAILY;FREQ=WEEKLY", "FREQ"] {
+        assert_eq!(reason(create("2099-01-01T09:00", "", recurrence).await).as_deref(), Some("INVALID_RECURRENCE"), "{recurrence}");
+    }
+
+    // the rules that end before they start, and the days that never happen
+    assert_eq!(reason(create("2099-01-01T09:00", "", "FREQ=DAILY;UNTIL=20981231").await).as_deref(), Some("NO_OCCURRENCES"));
+    assert_eq!(reason(create("2099-02-30T09:00", "", "FREQ=MONTHLY").await).as_deref(), Some("INVALID_LOCAL_TIME"));
+
+    // the 31st is skipped in the months that don't have it
+    let monthly = create("2099-01-31T09:00", "", "FREQ=MONTHLY;COUNT=2").await.unwrap();
+    assert_eq!(monthly.fire_at, chrono::DateTime::parse_from_rfc3339("2099-01-31T09:00:00Z").unwrap().timestamp());
+
+    assert_code(client.create_reminder(USER, note.id + 100, "2099-01-01T09:00", "", "").await, Code::NotFound);
+
+    let mut reminders = service.reminders();
+    let request = CreateReminderReq { user_id: 0, note_id: note.id, local_time: "2099-01-01T09:00".into(), timezone: "".into(), recurrence: "".into() };
+    assert_code(reminders.create_reminder(request).await, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn filtering_notes_by_due_reminders() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let overdue = service.create_note(USER, "overdue").await;
+    let soon = service.create_note(USER, "soon").await;
+    let later = service.create_note(USER, "later").await;
+    service.create_note(USER, "none").await;
+
+    client.create_reminder(USER, overdue.id, "2020-01-01T09:00", "", "").await.unwrap();
+    client.create_reminder(USER, soon.id, "2090-01-01T09:00", "", "").await.unwrap();
+    client.create_reminder(USER, later.id, "2099-01-01T09:00", "Asia/Tokyo", "").await.unwrap();
+
+    let service = &service;
+    let titles = |due: filter_due::Due, until: i64| async move {
+        let filters = Filters { filter_due: Some(FilterDue { due: due.into(), until }), ..Default::default() };
+        let list = service.read_notes(USER, sort(sort::Field::Title, sort::Type::Asc), filters).await.unwrap();
+        assert_eq!(list.total_count as usize, list.notes.len());
+        list.notes.into_iter().map(|n| n.title).collect::<Vec<_>>()
+    };
+
+    assert_eq!(titles(filter_due::Due::Overdue, 0).await, ["overdue"]);
+    assert_eq!(titles(filter_due::Due::Upcoming, 0).await, ["later", "soon"]);
+
+    let until = chrono::DateTime::parse_from_rfc3339("2095-01-01T00:00:00Z").unwrap().timestamp();
+    assert_eq!(titles(filter_due::Due::Upcoming, until).await, ["soon"]);
+
+    assert!(service.read_notes(OTHER_USER, sort(sort::Field::Date, sort::Type::Asc), Filters {
+        filter_due: Some(FilterDue { due: filter_due::Due::Upcoming.into(), until: 0 }),
+        ..Default::default()
+    }).await.unwrap().notes.is_empty());
+
+    let filters = Filters { filter_due: Some(FilterDue { due: 7, until: 0 }), ..Default::default() };
+    assert_code(service.read_notes(USER, sort(sort::Field::Date, sort::Type::Asc), filters).await, Code::InvalidArgument);
+
+    let filters = Filters { filter_due: Some(FilterDue { due: filter_due::Due::Upcoming.into(), until: -1 }), ..Default::default() };
+    assert_code(service.read_notes(USER, sort(sort::Field::Date, sort::Type::Asc), filters).await, Code::InvalidArgument);
+
+    // nothing fires until somebody watches, and a fired reminder is not overdue anymore
+    let mut watcher = client.watch_due_reminders(USER).await.unwrap();
+    assert_eq!(next_due(&mut watcher).await.note_title, "overdue");
+
+    assert!(titles(filter_due::Due::Overdue, 0).await.is_empty());
+    assert_eq!(titles(filter_due::Due::Upcoming, 0).await, ["later", "soon"]);
+}
+
+#[tokio::test]
+async fn watching_due_reminders() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let note = service.create_note(USER, "call mom").await;
+    let other_note = service.create_note(OTHER_USER, "other").await;
+
+    let mut all = client.watch_due_reminders(0).await.unwrap();
+    let mut own = client.watch_due_reminders(OTHER_USER).await.unwrap();
+
+    // the reminders from the past fire right away, and the recurring ones move on to their next occurrence
+    let once = client.create_reminder(USER, note.id, "2020-01-01T09:00", "Europe/Berlin", "").await.unwrap();
+    let daily = client.create_reminder(USER, note.id, "2020-01-02T09:00", "Europe/Berlin", "FREQ=DAILY").await.unwrap();
+    let other = client.create_reminder(OTHER_USER, other_note.id, "2021-01-01T09:00", "", "").await.unwrap();
+
+    let mut fired = Vec::new();
+    for _ in 0..3 {
+        let due = next_due(&mut all).await;
+        let reminder = due.reminder.unwrap();
+        fired.push((reminder.id, due.note_title, due.due_at));
+    }
+
+    fired.sort();
+    assert_eq!(fired, [
+        (once.id, "call mom".to_owned(), once.fire_at),
+        (daily.id, "call mom".to_owned(), daily.fire_at),
+        (other.id, "other".to_owned(), other.fire_at),
+    ]);
+
+    // the watchers of a user only get the user's reminders
+    let due = next_due(&mut own).await;
+    assert_eq!(due.reminder.map(|r| r.id), Some(other.id));
+
+    let now = chrono::Utc::now().timestamp();
+    let reminders = client.read_reminders(USER, None).await.unwrap();
+
+    let once = reminders.iter().find(|r| r.id == once.id).unwrap();
+    assert!(once.fired);
+
+    let daily = reminders.iter().find(|r| r.id == daily.id).unwrap();
+    assert!(!daily.fired);
+    assert!(daily.fire_at > now && daily.fire_at <= now + 2 * 24 * 60 * 60, "{}", daily.fire_at);
+
+    // an update of a fired reminder schedules it again
+    let updated = client.update_reminder(USER, once.id, "2099-01-01T09:00", "", "").await.unwrap();
+    assert!(!updated.fired);
+
+    // nothing else is due
+    assert!(tokio::time::timeout(Duration::from_secs(3), all.message()).await.is_err());
+
+    assert_code(client.watch_due_reminders(-1).await, Code::InvalidArgument);
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::proto::files::create_file_metadata::AttachId;
+use common::proto::notes::{sort, Filters};
+use common::proto::shelves::{ClearShelfReq, ConvertToNoteReq, ReadShelfReq, UpdateShelfReq};
+use common::{assert_code, sort, test_data, TestService, OTHER_USER, USER};
+use tonic::Code;
+
+#[tokio::test]
+async fn shelf_lifecycle() {
+    let service = TestService::start().await;
+    let mut shelves = service.shelves();
+
+    // the shelf is created on the first read
+
+    let shelf = shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap().into_inner();
+    assert_eq!((shelf.user_id, shelf.text.as_str(), shelf.times_edited), (USER, "", 0));
+    assert!(shelf.files.is_empty());
+
+    let again = shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap().into_inner();
+    assert_eq!(again.id, shelf.id);
+
+    let updated = shelves.update_shelf(UpdateShelfReq { user_id: USER, text: "remember this".into() })
+        .await.unwrap().into_inner();
+
+    assert_eq!((updated.id, updated.text.as_str(), updated.times_edited), (shelf.id, "remember this", 1));
+
+    // clearing the shelf deletes its files
+
+    let file = service.upload(USER, AttachId::ShelfId(shelf.id), "file", &test_data(100)).await.unwrap();
+    assert_eq!(file.attach_id, Some(shelf.id));
+
+    let read = shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap().into_inner();
+    assert_eq!(read.files.iter().map(|f| f.id).collect::<Vec<_>>(), [file.id]);
+
+    let cleared = shelves.clear_shelf(ClearShelfReq { user_id: USER }).await.unwrap().into_inner();
+    assert_eq!((cleared.text.as_str(), cleared.times_edited), ("", 2));
+
+    let read = shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap().into_inner();
+    assert!(read.files.is_empty());
+
+    assert_code(service.download(USER, &file.hash).await, Code::NotFound);
+    service.wait_for_blobs(&[]).await;
+}
+
+#[tokio::test]
+async fn converting_to_a_note() {
+    let service = TestService::start().await;
+    let mut shelves = service.shelves();
+
+    let shelf = shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap().into_inner();
+    shelves.update_shelf(UpdateShelfReq { user_id: USER, text: "shelf text".into() }).await.unwrap();
+    let file = service.upload(USER, AttachId::ShelfId(shelf.id), "file", &test_data(100)).await.unwrap();
+
+    let req = ConvertToNoteReq { user_id: USER, note_title: "from the shelf".into(), note_text: "note text".into() };
+    let converted = shelves.convert_to_note(req).await.unwrap().into_inner();
+    assert_eq!(converted.text, "");
+
+    let read = shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap().into_inner();
+    assert!(read.files.is_empty());
+
+    // the file is moved to the note instead of getting deleted
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(list.total_count, 1);
+
+    let note = &list.notes[0];
+    assert_eq!((note.title.as_str(), note.text.as_str()), ("from the shelf", "note text"));
+    assert_eq!(note.files.iter().map(|f| f.id).collect::<Vec<_>>(), [file.id]);
+
+    let (_, downloaded) = service.download(USER, &file.hash).await.unwrap();
+    assert_eq!(downloaded, test_data(100));
+    assert_eq!(service.blobs(), [file.hash.as_str()]);
+
+    // converting an empty shelf still creates a note
+
+    let req = ConvertToNoteReq { user_id: USER, note_title: "empty".into(), note_text: String::new() };
+    shelves.convert_to_note(req).await.unwrap();
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(list.total_count, 2);
+}
+
+#[tokio::test]
+async fn shelves_of_other_users() {
+    let service = TestService::start().await;
+    let mut shelves = service.shelves();
+
+    let shelf = shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap().into_inner();
+    shelves.update_shelf(UpdateShelfReq { user_id: USER, text: "mine".into() }).await.unwrap();
+
+    let other_shelf = shelves.read_shelf(ReadShelfReq { user_id: OTHER_USER }).await.unwrap().into_inner();
+    assert_ne!(other_shelf.id, shelf.id);
+    assert_eq!(other_shelf.text, "");
+
+    assert_code(service.upload(OTHER_USER, AttachId::ShelfId(shelf.id), "file", &test_data(10)).await, Code::NotFound);
+    assert!(service.blobs().is_empty());
+
+    shelves.clear_shelf(ClearShelfReq { user_id: OTHER_USER }).await.unwrap();
+
+    let read = shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap().into_inner();
+    assert_eq!(read.text, "mine");
+}
+
+#[tokio::test]
+async fn invalid_shelf_requests() {
+    let service = TestService::start().await;
+    let mut shelves = service.shelves();
+
+    shelves.read_shelf(ReadShelfReq { user_id: USER }).await.unwrap();
+
+    assert_code(shelves.update_shelf(UpdateShelfReq { user_id: USER, text: "x".repeat(2501) }).await, Code::InvalidArgument);
+    assert_code(shelves.read_shelf(ReadShelfReq { user_id: 0 }).await, Code::InvalidArgument);
+
+    let req = ConvertToNoteReq { user_id: USER, note_title: "x".repeat(251), note_text: String::new() };
+    assert_code(shelves.convert_to_note(req).await, Code::InvalidArgument);
+}
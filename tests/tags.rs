@@ -0,0 +1,220 @@
+mod common;
+
+use common::proto::notes::{sort, AttachTagReq, Filters};
+use common::proto::tags::{tag_sort, CreateTagReq, DeleteTagReq, MergeTagsReq, ReadTagsReq, TagSort, UpdateTagReq};
+use common::{assert_code, sort, TestService, OTHER_USER, USER};
+use miku_notes_data_client::ErrorKind;
+use tonic::Code;
+
+#[tokio::test]
+async fn tag_lifecycle() {
+    let service = TestService::start().await;
+    let mut tags = service.tags();
+
+    let work = service.create_tag(USER, "work").await;
+    let ideas = service.create_tag(USER, "ideas").await;
+    assert_eq!((work.user_id, work.name.as_str()), (USER, "work"));
+
+    let list = tags.read_tags(ReadTagsReq { user_id: USER, sort: None }).await.unwrap().into_inner();
+    let mut ids: Vec<_> = list.tags.iter().map(|t| t.id).collect();
+    ids.sort();
+    assert_eq!(ids, [work.id, ideas.id]);
+
+    let updated = tags.update_tag(UpdateTagReq { id: work.id, user_id: USER, name: "job".into(), ..Default::default() })
+        .await.unwrap().into_inner();
+
+    assert_eq!((updated.id, updated.name.as_str()), (work.id, "job"));
+
+    tags.delete_tag(DeleteTagReq { id: work.id, user_id: USER }).await.unwrap();
+
+    let list = tags.read_tags(ReadTagsReq { user_id: USER, sort: None }).await.unwrap().into_inner();
+    assert_eq!(list.tags.iter().map(|t| t.id).collect::<Vec<_>>(), [ideas.id]);
+
+    assert_code(tags.delete_tag(DeleteTagReq { id: work.id, user_id: USER }).await, Code::NotFound);
+    assert_code(tags.update_tag(UpdateTagReq { id: work.id, user_id: USER, name: "job".into(), ..Default::default() }).await, Code::NotFound);
+}
+
+#[tokio::test]
+async fn deleting_an_attached_tag() {
+    let service = TestService::start().await;
+
+    let note = service.create_note(USER, "note").await;
+    let tag = service.create_tag(USER, "tag").await;
+
+    service.notes().attach_tag(AttachTagReq { user_id: USER, note_id: note.id, tag_id: tag.id }).await.unwrap();
+    service.tags().delete_tag(DeleteTagReq { id: tag.id, user_id: USER }).await.unwrap();
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Desc), Filters::default()).await.unwrap();
+    assert_eq!(list.total_count, 1);
+    assert!(list.notes[0].tags.is_empty());
+}
+
+#[tokio::test]
+async fn tags_of_other_users() {
+    let service = TestService::start().await;
+    let mut tags = service.tags();
+
+    let tag = service.create_tag(USER, "mine").await;
+
+    let list = tags.read_tags(ReadTagsReq { user_id: OTHER_USER, sort: None }).await.unwrap().into_inner();
+    assert!(list.tags.is_empty());
+
+    assert_code(tags.update_tag(UpdateTagReq { id: tag.id, user_id: OTHER_USER, name: "stolen".into(), ..Default::default() }).await, Code::NotFound);
+    assert_code(tags.delete_tag(DeleteTagReq { id: tag.id, user_id: OTHER_USER }).await, Code::NotFound);
+
+    let list = tags.read_tags(ReadTagsReq { user_id: USER, sort: None }).await.unwrap().into_inner();
+    assert_eq!(list.tags.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["mine"]);
+}
+
+#[tokio::test]
+async fn invalid_tag_names() {
+    let service = TestService::start().await;
+    let mut tags = service.tags();
+
+    assert_code(tags.create_tag(CreateTagReq { user_id: USER, name: String::new(), ..Default::default() }).await, Code::InvalidArgument);
+    assert_code(tags.create_tag(CreateTagReq { user_id: USER, name: "x".repeat(51), ..Default::default() }).await, Code::InvalidArgument);
+
+    let tag = service.create_tag(USER, "tag").await;
+    assert_code(tags.update_tag(UpdateTagReq { id: tag.id, user_id: USER, name: String::new(), ..Default::default() }).await, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn tag_names_are_unique_per_user() {
+    let service = TestService::start().await;
+    let mut tags = service.tags();
+
+    let work = service.create_tag(USER, "Work").await;
+    let ideas = service.create_tag(USER, "ideas").await;
+
+    let error = service.client().create_tag(USER, "work").await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::AlreadyExists), Some("TAG_ALREADY_EXISTS")));
+
+    assert_code(tags.update_tag(UpdateTagReq { id: ideas.id, user_id: USER, name: "WORK".into(), ..Default::default() }).await, Code::AlreadyExists);
+
+    // renaming a tag to itself with another case is fine, and so is the same name for another user
+    tags.update_tag(UpdateTagReq { id: work.id, user_id: USER, name: "work".into(), ..Default::default() }).await.unwrap();
+    service.create_tag(OTHER_USER, "work").await;
+}
+
+#[tokio::test]
+async fn usage_counts_and_sorting() {
+    let service = TestService::start().await;
+    let mut tags = service.tags();
+
+    let a = service.create_tag(USER, "a").await;
+    let b = service.create_tag(USER, "b").await;
+    let c = service.create_tag(USER, "c").await;
+
+    for (note, tag_ids) in [("one", vec![b.id, c.id]), ("two", vec![b.id])] {
+        let note = service.create_note(USER, note).await;
+
+        for tag_id in tag_ids {
+            service.notes().attach_tag(AttachTagReq { user_id: USER, note_id: note.id, tag_id }).await.unwrap();
+        }
+    }
+
+    let read = |field: tag_sort::Field, sort_type: tag_sort::Type| {
+        let mut tags = tags.clone();
+        let sort = TagSort { sort_field: field.into(), sort_type: sort_type.into() };
+
+        async move {
+            let list = tags.read_tags(ReadTagsReq { user_id: USER, sort: Some(sort) }).await.unwrap().into_inner();
+            list.tags.into_iter().map(|t| (t.name, t.usage_count)).collect::<Vec<_>>()
+        }
+    };
+
+    let counts = |names: [&str; 3], counts: [i32; 3]| names.into_iter().map(String::from).zip(counts).collect::<Vec<_>>();
+
+    assert_eq!(read(tag_sort::Field::Usage, tag_sort::Type::Desc).await, counts(["b", "c", "a"], [2, 1, 0]));
+    assert_eq!(read(tag_sort::Field::Name, tag_sort::Type::Desc).await, counts(["c", "b", "a"], [1, 2, 0]));
+    assert_eq!(read(tag_sort::Field::Created, tag_sort::Type::Asc).await, counts(["a", "b", "c"], [0, 2, 1]));
+
+    let list = tags.read_tags(ReadTagsReq { user_id: USER, sort: None }).await.unwrap().into_inner();
+    assert_eq!(list.tags.iter().map(|t| t.id).collect::<Vec<_>>(), [a.id, b.id, c.id]);
+}
+
+#[tokio::test]
+async fn merging_tags() {
+    let service = TestService::start().await;
+    let mut tags = service.tags();
+
+    let target = service.create_tag(USER, "target").await;
+    let first = service.create_tag(USER, "first").await;
+    let second = service.create_tag(USER, "second").await;
+    let foreign = service.create_tag(OTHER_USER, "foreign").await;
+
+    let both = service.create_note(USER, "both").await;
+    let source_only = service.create_note(USER, "source only").await;
+
+    for (note_id, tag_id) in [(both.id, target.id), (both.id, first.id), (both.id, second.id), (source_only.id, second.id)] {
+        service.notes().attach_tag(AttachTagReq { user_id: USER, note_id, tag_id }).await.unwrap();
+    }
+
+    let merge = |source_tag_ids: Vec<i32>, target_tag_id| MergeTagsReq { user_id: USER, source_tag_ids, target_tag_id };
+
+    assert_code(tags.merge_tags(merge(vec![first.id, foreign.id], target.id)).await, Code::NotFound);
+    assert_code(tags.merge_tags(merge(vec![target.id], target.id)).await, Code::InvalidArgument);
+
+    let merged = tags.merge_tags(merge(vec![first.id, second.id], target.id)).await.unwrap().into_inner();
+    assert_eq!((merged.id, merged.usage_count), (target.id, 2));
+
+    let list = tags.read_tags(ReadTagsReq { user_id: USER, sort: None }).await.unwrap().into_inner();
+    assert_eq!(list.tags.iter().map(|t| t.id).collect::<Vec<_>>(), [target.id]);
+
+    let list = service.read_notes(USER, sort(sort::Field::Title, sort::Type::Asc), Filters::default()).await.unwrap();
+    assert!(list.notes.iter().all(|n| n.tags.iter().map(|t| t.id).collect::<Vec<_>>() == [target.id]));
+}
+
+#[tokio::test]
+async fn tag_styles() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let tag = client.create_styled_tag(USER, "work", Some("#1E90FF"), Some("💼"), "things to do").await.unwrap();
+    assert_eq!((tag.color.as_deref(), tag.icon.as_deref(), tag.description.as_str()), (Some("#1e90ff"), Some("💼"), "things to do"));
+
+    // the unset fields are kept, and the empty ones are removed
+    let request = UpdateTagReq { id: tag.id, user_id: USER, name: "job".into(), icon: Some(String::new()), ..Default::default() };
+    let updated = client.update_tag_with(request).await.unwrap();
+    assert_eq!((updated.color.as_deref(), updated.icon, updated.description.as_str()), (Some("#1e90ff"), None, "things to do"));
+
+    let list = client.read_tags(USER).await.unwrap();
+    assert_eq!(list[0].color.as_deref(), Some("#1e90ff"));
+
+    let mut tags = service.tags();
+
+    for color in ["1e90ff", "#1e90f", "#1e90fg"] {
+        let request = CreateTagReq { user_id: USER, name: "other".into(), color: Some(color.into()), ..Default::default() };
+        assert_code(tags.create_tag(request).await, Code::InvalidArgument);
+    }
+
+    let request = CreateTagReq { user_id: USER, name: "other".into(), description: "x".repeat(501), ..Default::default() };
+    assert_code(tags.create_tag(request).await, Code::InvalidArgument);
+}
+
+#[tokio::test]
+async fn reordering_tags() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let a = service.create_tag(USER, "a").await;
+    let b = service.create_tag(USER, "b").await;
+    let c = service.create_tag(USER, "c").await;
+    let foreign = service.create_tag(OTHER_USER, "foreign").await;
+    assert_eq!([a.position, b.position, c.position], [0, 1, 2]);
+
+    // the rest of the tags keep their order after the moved ones
+    let list = client.reorder_tags(USER, &[c.id]).await.unwrap();
+    assert_eq!(list.iter().map(|t| (t.id, t.position)).collect::<Vec<_>>(), [(c.id, 0), (a.id, 1), (b.id, 2)]);
+
+    let d = service.create_tag(USER, "d").await;
+    assert_eq!(d.position, 3);
+
+    let sort = TagSort { sort_field: tag_sort::Field::Position.into(), sort_type: tag_sort::Type::Desc.into() };
+    let list = client.read_tags_sorted(USER, sort).await.unwrap();
+    assert_eq!(list.iter().map(|t| t.id).collect::<Vec<_>>(), [d.id, b.id, a.id, c.id]);
+
+    let error = client.reorder_tags(USER, &[a.id, foreign.id]).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::NotFound), Some("TAG_NOT_FOUND")));
+    assert_code(client.reorder_tags(USER, &[]).await, Code::InvalidArgument);
+}
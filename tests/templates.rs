@@ -0,0 +1,107 @@
+mod common;
+
+use chrono_tz::Tz;
+use common::proto::notes::{sort, Filters};
+use common::proto::templates::CreateNoteFromTemplateReq;
+use common::{assert_code, sort, TestService, OTHER_USER, USER};
+use miku_notes_data_client::ErrorKind;
+use tonic::Code;
+
+#[tokio::test]
+async fn template_lifecycle() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let work = service.create_tag(USER, "work").await;
+    let daily = service.create_tag(USER, "daily").await;
+
+    let meeting = client.create_template(USER, "Meeting", "Meeting {{date}}", "- agenda", &[work.id, daily.id]).await.unwrap();
+    assert_eq!((meeting.user_id, meeting.tag_ids.clone()), (USER, vec![work.id, daily.id]));
+
+    let journal = client.create_template(USER, "journal", "", "", &[]).await.unwrap();
+
+    let names = |templates: Vec<common::proto::templates::Template>| templates.into_iter().map(|t| t.name).collect::<Vec<_>>();
+    assert_eq!(names(client.read_templates(USER).await.unwrap()), ["journal", "Meeting"]);
+    assert!(client.read_templates(OTHER_USER).await.unwrap().is_empty());
+
+    // an update replaces the tags too
+    let updated = client.update_template(USER, meeting.id, "Standup", "Standup {{date}}", "", &[daily.id]).await.unwrap();
+    assert_eq!((updated.name.as_str(), updated.tag_ids.clone()), ("Standup", vec![daily.id]));
+
+    let other_tag = service.create_tag(OTHER_USER, "other").await;
+    let error = client.update_template(USER, meeting.id, "Standup", "", "", &[other_tag.id]).await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::NotFound), Some("TAG_NOT_FOUND")));
+    assert_eq!(client.read_templates(USER).await.unwrap()[1].tag_ids, [daily.id]);
+
+    assert_code(client.update_template(OTHER_USER, meeting.id, "mine", "", "", &[]).await, Code::NotFound);
+    assert_code(client.delete_template(OTHER_USER, journal.id).await, Code::NotFound);
+    assert_code(client.create_template(USER, " ", "", "", &[]).await, Code::InvalidArgument);
+
+    client.delete_template(USER, journal.id).await.unwrap();
+    assert_code(client.delete_template(USER, journal.id).await, Code::NotFound);
+    assert_eq!(names(client.read_templates(USER).await.unwrap()), ["Standup"]);
+
+    // deleting and merging tags changes the tags of the templates
+    let both = client.create_template(USER, "both", "", "", &[work.id, daily.id]).await.unwrap();
+    let merged = service.create_tag(USER, "merged").await;
+    client.merge_tags(USER, &[daily.id], merged.id).await.unwrap();
+    client.delete_tag(USER, work.id).await.unwrap();
+
+    let templates = client.read_templates(USER).await.unwrap();
+    assert!(templates.iter().all(|t| t.tag_ids == [merged.id]));
+    assert!(templates.iter().any(|t| t.id == both.id));
+}
+
+#[tokio::test]
+async fn creating_notes_from_templates() {
+    let service = TestService::start().await;
+    let client = service.client();
+
+    let tag = service.create_tag(USER, "meetings").await;
+    let text = "{{weekday}} at {{ time }} with {{ team }}, {{unknown}} stays";
+    let template = client.create_template(USER, "Meeting", "{{project}} meeting {{date}}", text, &[tag.id]).await.unwrap();
+
+    let timezone: Tz = "Pacific/Kiritimati".parse().unwrap();
+    let today = || chrono::Utc::now().with_timezone(&timezone).format("%Y-%m-%d").to_string();
+
+    let before = today();
+    let note = client.create_note_from_template(USER, template.id, &[("project", "Miku"), ("team", "{{date}}")], "Pacific/Kiritimati").await.unwrap();
+    let after = today();
+
+    assert!([before, after].contains(&note.title.trim_start_matches("Miku meeting ").to_owned()), "{}", note.title);
+    assert!(note.text.ends_with("with {{date}}, {{unknown}} stays"), "{}", note.text);
+    assert_eq!(note.tags.iter().map(|t| (t.id, t.note_id)).collect::<Vec<_>>(), [(tag.id, Some(note.id))]);
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Asc), Filters::default()).await.unwrap();
+    assert_eq!(list.notes[0].tags.len(), 1);
+
+    // the missing variables are left as they are, and the time is in UTC without a timezone
+    let note = client.create_note_from_template(USER, template.id, &[], "").await.unwrap();
+    assert_eq!(note.title, format!("{{{{project}}}} meeting {}", chrono::Utc::now().format("%Y-%m-%d")));
+
+    let keyed = client.create_note_from_template_with_key("template-1", USER, template.id, &[], "").await.unwrap();
+    assert_eq!(client.create_note_from_template_with_key("template-1", USER, template.id, &[], "").await.unwrap().id, keyed.id);
+
+    // invalid variables and timezones, and titles that get too long
+
+    let mut templates = service.templates();
+    let request = |variables: &[(&str, &str)], timezone: &str| CreateNoteFromTemplateReq {
+        user_id: USER,
+        template_id: template.id,
+        variables: variables.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+        timezone: timezone.into(),
+    };
+
+    assert_code(templates.create_note_from_template(request(&[], "Mars/Olympus_Mons")).await, Code::InvalidArgument);
+    assert_code(templates.create_note_from_template(request(&[("date", "today")], "")).await, Code::InvalidArgument);
+    assert_code(templates.create_note_from_template(request(&[("my project", "x")], "")).await, Code::InvalidArgument);
+
+    let long = "x".repeat(300);
+    let error = client.create_note_from_template(USER, template.id, &[("project", &long)], "").await.unwrap_err();
+    assert_eq!((error.kind(), error.reason()), (Some(ErrorKind::InvalidArgument), Some("VALUE_TOO_LONG")));
+
+    assert_code(client.create_note_from_template(OTHER_USER, template.id, &[], "").await, Code::NotFound);
+
+    let list = service.read_notes(USER, sort(sort::Field::Date, sort::Type::Asc), Filters::default()).await.unwrap();
+    assert_eq!(list.total_count, 3);
+}
@@ -0,0 +1,291 @@
+mod common;
+
+use common::proto::tags::{ReadTagsReq, TagList};
+use common::{test_data, TestService, OTHER_USER, SERVICE_TOKEN, USER};
+use hyper::{body::to_bytes, Body, Client, HeaderMap, Method, Request, StatusCode};
+use prost::Message;
+use serde_json::{json, Value};
+
+/// makes an authorized http/1.1 request to the rest gateway, and returns the response with its whole body
+async fn rest(service: &TestService, method: Method, path: &str, content_type: &str, body: impl Into<Body>) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let request = Request::builder()
+        .method(method)
+        .uri(format!("http://127.0.0.1:{}{path}", service.rest_port))
+        .header("authorization", format!("Bearer {SERVICE_TOKEN}"))
+        .header("content-type", content_type)
+        .body(body.into())
+        .unwrap();
+
+    send(request).await
+}
+
+async fn send(request: Request<Body>) -> (StatusCode, HeaderMap, Vec<u8>) {
+    let response = Client::new().request(request).await.unwrap();
+    let (parts, body) = response.into_parts();
+
+    (parts.status, parts.headers, to_bytes(body).await.unwrap().to_vec())
+}
+
+async fn rest_json(service: &TestService, method: Method, path: &str, body: Value) -> (StatusCode, Value) {
+    let (status, _, body) = rest(service, method, path, "application/json", body.to_string()).await;
+    let body = if body.is_empty() { Value::Null } else { serde_json::from_slice(&body).unwrap() };
+
+    (status, body)
+}
+
+#[tokio::test]
+async fn notes_over_rest() {
+    let service = TestService::start().await;
+    let tag = service.create_tag(USER, "work").await;
+
+    let (status, note) = rest_json(&service, Method::POST, "/notes?user_id=1", json!({ "title": "plan", "text": "text" })).await;
+    assert_eq!(status, StatusCode::CREATED);
+    assert_eq!((note["user_id"].as_i64(), note["title"].as_str()), (Some(USER.into()), Some("plan")));
+
+    let note_id = note["id"].as_i64().unwrap();
+    rest_json(&service, Method::POST, "/notes?user_id=1", json!({ "title": "other" })).await;
+
+    let (status, _) = rest_json(&service, Method::POST, &format!("/notes/{note_id}/tags?user_id=1"), json!({ "tag_id": tag.id })).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // the tag filter finds only the tagged note
+
+    let (status, list) = rest_json(&service, Method::GET, &format!("/notes?user_id=1&tags={}&sort=title&order=asc", tag.id), Value::Null).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(list["total_count"], 1);
+    assert_eq!(list["notes"][0]["id"].as_i64(), Some(note_id));
+    assert_eq!(list["notes"][0]["tags"][0]["name"], "work");
+
+    let (_, list) = rest_json(&service, Method::GET, "/notes?user_id=1&per_page=1", Value::Null).await;
+    assert_eq!((list["total_count"].as_i64(), list["notes"].as_array().unwrap().len()), (Some(2), 1));
+
+    let (status, job) = rest_json(&service, Method::POST, "/tags?user_id=1", json!({ "name": "job", "color": "#FFA500", "icon": "💼" })).await;
+    assert_eq!((status, job["color"].as_str(), job["position"].as_i64()), (StatusCode::CREATED, Some("#ffa500"), Some(1)));
+
+    let job_id = job["id"].as_i64().unwrap();
+    let (status, list) = rest_json(&service, Method::PUT, "/tags/order?user_id=1", json!({ "tag_ids": [job_id] })).await;
+    assert_eq!((status, list["tags"][0]["id"].as_i64()), (StatusCode::OK, Some(job_id)));
+
+    let (status, merged) = rest_json(&service, Method::POST, "/tags/merge?user_id=1", json!({ "source_tag_ids": [tag.id], "target_tag_id": job_id })).await;
+    assert_eq!((status, merged["usage_count"].as_i64()), (StatusCode::OK, Some(1)));
+
+    let (status, _) = rest_json(&service, Method::GET, "/tags?user_id=1&sort=popularity", Value::Null).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, result) = rest_json(&service, Method::POST, "/notes/batch/detach?user_id=1", json!({ "note_ids": [note_id], "tag_ids": [job_id] })).await;
+    assert_eq!((status, result["succeeded"].as_i64()), (StatusCode::OK, Some(1)));
+
+    let (status, note) = rest_json(&service, Method::PUT, &format!("/notes/{note_id}/state?user_id=1"), json!({ "archived": true })).await;
+    assert_eq!((status, note["archived"].as_bool()), (StatusCode::OK, Some(true)));
+
+    let (_, list) = rest_json(&service, Method::GET, "/notes?user_id=1", Value::Null).await;
+    assert_eq!(list["total_count"], 1);
+
+    let (_, list) = rest_json(&service, Method::GET, "/notes?user_id=1&archive=all&pinned_first=true", Value::Null).await;
+    assert_eq!(list["total_count"], 2);
+
+    let (status, note) = rest_json(&service, Method::PUT, &format!("/notes/{note_id}?user_id=1"), json!({ "title": "new plan", "text": "see [[Other]]" })).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(note["title"], "new plan");
+
+    // the archived note is in the graph only when asked for
+
+    let (_, graph) = rest_json(&service, Method::GET, "/notes/graph?user_id=1", Value::Null).await;
+    assert_eq!((graph["nodes"].as_array().unwrap().len(), graph["edges"].as_array().unwrap().len()), (1, 0));
+
+    let (status, graph) = rest_json(&service, Method::GET, "/notes/graph?user_id=1&include_archived=true", Value::Null).await;
+    assert_eq!((status, graph["edges"][0]["source_id"].as_i64()), (StatusCode::OK, Some(note_id)));
+
+    let target_id = graph["edges"][0]["target_id"].as_i64().unwrap();
+    let (status, backlinks) = rest_json(&service, Method::GET, &format!("/notes/{target_id}/backlinks?user_id=1"), Value::Null).await;
+    assert_eq!((status, backlinks["notes"][0]["title"].as_str()), (StatusCode::OK, Some("new plan")));
+
+    let (_, todo) = rest_json(&service, Method::POST, "/notes?user_id=1", json!({ "title": "todo", "text": "- [ ] one\n- [ ] two" })).await;
+    let todo_id = todo["id"].as_i64().unwrap();
+
+    let path = format!("/notes/{todo_id}/tasks/1/toggle?user_id=1");
+    let (status, todo) = rest_json(&service, Method::POST, &path, json!({ "times_edited": 0 })).await;
+    assert_eq!((status, todo["text"].as_str()), (StatusCode::OK, Some("- [ ] one\n- [x] two")));
+
+    let (status, error) = rest_json(&service, Method::POST, &path, json!({ "times_edited": 0 })).await;
+    assert_eq!((status, error["reason"].as_str()), (StatusCode::BAD_REQUEST, Some("NOTE_EDITED")));
+
+    let (status, list) = rest_json(&service, Method::GET, "/notes/tasks?user_id=1&done=true", Value::Null).await;
+    assert_eq!((status, list["tasks"][0]["text"].as_str(), list["tasks"].as_array().unwrap().len()), (StatusCode::OK, Some("two"), 1));
+
+    let (status, list) = rest_json(&service, Method::GET, "/notes/tasks?user_id=1&page=2&per_page=1", Value::Null).await;
+    assert_eq!((status, list["tasks"][0]["text"].as_str(), list["total_count"].as_i64()), (StatusCode::OK, Some("two"), Some(2)));
+
+    let reminder = json!({ "note_id": note_id, "local_time": "2099-10-18T09:00", "timezone": "Europe/Berlin", "recurrence": "FREQ=WEEKLY" });
+    let (status, reminder) = rest_json(&service, Method::POST, "/reminders?user_id=1", reminder).await;
+    assert_eq!((status, reminder["note_id"].as_i64()), (StatusCode::CREATED, Some(note_id)));
+
+    let reminder_id = reminder["id"].as_i64().unwrap();
+    let (status, reminder) = rest_json(&service, Method::PUT, &format!("/reminders/{reminder_id}?user_id=1"), json!({ "local_time": "2099-10-19T10:00" })).await;
+    assert_eq!((status, reminder["timezone"].as_str(), reminder["recurrence"].as_str()), (StatusCode::OK, Some(""), Some("")));
+
+    let (_, list) = rest_json(&service, Method::GET, &format!("/reminders?user_id=1&note_id={note_id}"), Value::Null).await;
+    assert_eq!(list["reminders"][0]["local_time"], "2099-10-19T10:00");
+
+    let (_, list) = rest_json(&service, Method::GET, "/notes?user_id=1&archive=all&due=upcoming", Value::Null).await;
+    assert_eq!((list["total_count"].as_i64(), list["notes"][0]["id"].as_i64()), (Some(1), Some(note_id)));
+
+    let (_, list) = rest_json(&service, Method::GET, "/notes?user_id=1&archive=all&due=overdue", Value::Null).await;
+    assert_eq!(list["total_count"], 0);
+
+    let (status, _) = rest_json(&service, Method::DELETE, &format!("/reminders/{reminder_id}?user_id=1"), Value::Null).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    let (status, _) = rest_json(&service, Method::DELETE, &format!("/notes/{note_id}?user_id=1"), Value::Null).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    // the errors have the same reasons and field violations as the gRPC ones
+
+    let (status, error) = rest_json(&service, Method::DELETE, &format!("/notes/{note_id}?user_id=1"), Value::Null).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(error["code"], "NOT_FOUND");
+
+    let (status, error) = rest_json(&service, Method::GET, "/notes?user_id=1&page=0&sort=size", Value::Null).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(error["field_violations"][0]["field"], "sort");
+
+    let (status, error) = rest_json(&service, Method::POST, "/notes?user_id=1", json!({ "title": "x".repeat(300) })).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!((error["reason"].as_str(), error["field_violations"][0]["field"].as_str()), (Some("VALUE_TOO_LONG"), Some("title")));
+}
+
+#[tokio::test]
+async fn idempotency_keys_over_rest() {
+    let service = TestService::start().await;
+
+    let create = |title: &str, key: &str| Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://127.0.0.1:{}/notes?user_id=1", service.rest_port))
+        .header("authorization", format!("Bearer {SERVICE_TOKEN}"))
+        .header("content-type", "application/json")
+        .header("idempotency-key", key)
+        .body(Body::from(json!({ "title": title }).to_string()))
+        .unwrap();
+
+    let (status, _, first) = send(create("note", "key")).await;
+    assert_eq!(status, StatusCode::CREATED);
+
+    let (status, _, replayed) = send(create("note", "key")).await;
+    assert_eq!((status, replayed), (StatusCode::CREATED, first));
+
+    let (status, _, body) = send(create("other", "key")).await;
+    assert_eq!(status, StatusCode::CONFLICT);
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["reason"], "IDEMPOTENCY_KEY_REUSED");
+
+    let (_, list) = rest_json(&service, Method::GET, "/notes?user_id=1", Value::Null).await;
+    assert_eq!(list["total_count"], 1);
+}
+
+#[tokio::test]
+async fn rest_calls_go_through_the_interceptor() {
+    let service = TestService::start().await;
+
+    let request = |token: &str| Request::builder()
+        .uri(format!("http://127.0.0.1:{}/tags?user_id=1", service.rest_port))
+        .header("authorization", format!("Bearer {token}"))
+        .body(Body::empty())
+        .unwrap();
+
+    let (status, _, body) = send(request("wrong")).await;
+    assert_eq!(status, StatusCode::UNAUTHORIZED);
+    assert_eq!(serde_json::from_slice::<Value>(&body).unwrap()["code"], "UNAUTHENTICATED");
+
+    let (status, _, _) = send(request(SERVICE_TOKEN)).await;
+    assert_eq!(status, StatusCode::OK);
+}
+
+#[tokio::test]
+async fn files_over_rest() {
+    let service = TestService::start().await;
+    let note = service.create_note(USER, "note").await;
+    let data = test_data(300 * 1024);
+
+    let boundary = "test-boundary";
+    let mut form = Vec::new();
+
+    for (name, value) in [("note_id", note.id.to_string()), ("size", data.len().to_string())] {
+        form.extend(format!("--{boundary}\r\ncontent-disposition: form-data; name=\"{name}\"\r\n\r\n{value}\r\n").bytes());
+    }
+
+    form.extend(format!("--{boundary}\r\ncontent-disposition: form-data; name=\"file\"; filename=\"ノート.bin\"\r\n\r\n").bytes());
+    form.extend(&data);
+    form.extend(format!("\r\n--{boundary}--\r\n").bytes());
+
+    let content_type = format!("multipart/form-data; boundary={boundary}");
+    let (status, _, body) = rest(&service, Method::POST, "/files?user_id=1", &content_type, form).await;
+    assert_eq!(status, StatusCode::CREATED, "{}", String::from_utf8_lossy(&body));
+
+    let file: Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!((file["name"].as_str(), file["size"].as_u64()), (Some("ノート.bin"), Some(data.len() as u64)));
+    assert_eq!(file["attach_id"].as_i64(), Some(note.id.into()));
+
+    let hash = file["hash"].as_str().unwrap();
+
+    let (status, headers, downloaded) = rest(&service, Method::GET, &format!("/files/{hash}?user_id=1"), "", Body::empty()).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["content-disposition"], "attachment; filename*=UTF-8''%E3%83%8E%E3%83%BC%E3%83%88.bin");
+    assert!(downloaded == data, "the downloaded data is different from the uploaded data");
+
+    let (status, _, _) = rest(&service, Method::GET, &format!("/files/{hash}?user_id={OTHER_USER}"), "", Body::empty()).await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+
+    let (status, _, _) = rest(&service, Method::DELETE, &format!("/files/{}?user_id=1", file["id"]), "", Body::empty()).await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+
+    service.wait_for_blobs(&[]).await;
+}
+
+#[tokio::test]
+async fn grpc_web_calls() {
+    let service = TestService::start().await;
+    service.create_tag(USER, "work").await;
+
+    // a length-prefixed message, the same framing as in gRPC
+
+    let message = ReadTagsReq { user_id: USER, sort: None }.encode_to_vec();
+    let mut frame = vec![0];
+    frame.extend((message.len() as u32).to_be_bytes());
+    frame.extend(message);
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(format!("http://127.0.0.1:{}/tags.Tags/ReadTags", service.port))
+        .header("authorization", format!("Bearer {SERVICE_TOKEN}"))
+        .header("content-type", "application/grpc-web+proto")
+        .header("origin", "https://notes.example.com")
+        .body(Body::from(frame))
+        .unwrap();
+
+    let (status, headers, body) = send(request).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(headers["access-control-allow-origin"], "*");
+
+    // the message frame is followed by a trailers frame with the grpc-status
+
+    let len = u32::from_be_bytes(body[1..5].try_into().unwrap()) as usize;
+    let list = TagList::decode(&body[5..5 + len]).unwrap();
+    assert_eq!(list.tags.iter().map(|t| t.name.as_str()).collect::<Vec<_>>(), ["work"]);
+
+    let trailers = String::from_utf8_lossy(&body[5 + len + 5..]).to_lowercase();
+    assert!(trailers.contains("grpc-status:0"), "{trailers}");
+
+    // the cors preflight doesn't need the authorization
+
+    let preflight = Request::builder()
+        .method(Method::OPTIONS)
+        .uri(format!("http://127.0.0.1:{}/tags.Tags/ReadTags", service.port))
+        .header("origin", "https://notes.example.com")
+        .header("access-control-request-method", "POST")
+        .header("access-control-request-headers", "authorization,content-type,x-grpc-web")
+        .body(Body::empty())
+        .unwrap();
+
+    let (status, headers, _) = send(preflight).await;
+    assert_eq!(status, StatusCode::OK);
+    assert!(headers["access-control-allow-headers"].to_str().unwrap().contains("authorization"));
+}